@@ -0,0 +1,46 @@
+use config::ConfigError;
+
+/// Intervals for each scheduled task in seconds, `0` disables the task.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    pub import_secs: u64,
+    pub avatar_sync_secs: u64,
+    pub name_sync_secs: u64,
+}
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            import_secs: 60 * 60,
+            avatar_sync_secs: 60 * 60 * 24,
+            name_sync_secs: 60 * 60 * 24,
+        }
+    }
+}
+
+/// Settings for pushing Steam names/avatars to the webserver.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct SyncConfig {
+    /// The admin the webserver records Steam data updates under, the sync tasks are skipped without one.
+    pub admin_profile_number: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Config {
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    #[serde(default)]
+    pub sync: SyncConfig,
+}
+// Extracts the environment variables from .env
+impl Config {
+    /// Reads the config from the environment (and `./.env`), e.g. `SCHEDULER.IMPORT_SECS=3600`.
+    ///
+    /// Errors if a value is set but can't be parsed, rather than falling back to the default.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        dotenv::dotenv().ok();
+        let mut cfg = config::Config::new();
+        cfg.merge(config::Environment::new())?;
+        cfg.try_into()
+    }
+}
@@ -16,7 +16,13 @@ mod stages;
 use stages::fetching::*;
 mod points;
 use points::*;
+mod config;
+use crate::config::Config;
 mod models;
+mod scheduler;
+use scheduler::*;
+mod shutdown;
+use shutdown::is_shutting_down;
 mod tests;
 // use dotenv::dotenv;
 // use log::{info, trace, warn};
@@ -47,6 +53,7 @@ fn main() {
     //      ssp -> Specific SP map, next arg is map#, will recompute points
     //      scp -> Specific Coop map, next arg is map#, will recompute points
     //      rcp -> Recompute points (useful after a ban/admin confirmation)
+    //      sched -> Runs imports & steam syncs on a schedule, see `Schedule`
     // TODO: Stage point computation??
     // TODO: Handle caching of point information.
     let start = PreciseTime::now();
@@ -62,6 +69,18 @@ fn main() {
             new_args.limit.unwrap_or(500),
         ),
         "all" => fetch_all(new_args.limit.unwrap_or(500)),
+        "sched" => match Config::from_env() {
+            Ok(config) => tokio::runtime::Runtime::new()
+                .expect("Could not start the tokio runtime")
+                .block_on(run_scheduler(Schedule::from_config(
+                    &config,
+                    new_args.limit.unwrap_or(500),
+                ))),
+            Err(e) => {
+                eprintln!("Invalid configuration -> {}", e);
+                std::process::exit(1);
+            }
+        },
         _ => panic!("Incorrect value"),
     }
 
@@ -85,8 +104,10 @@ fn fetch_all(limit: i32) {
     ];
 
     let utc = Utc::now().naive_utc();
+    // Maps that haven't started when a shutdown is requested are skipped, in-progress maps finish.
     let _res_sp: Vec<_> = official_sp
         .into_par_iter()
+        .filter(|_| !is_shutting_down())
         .map(|map_id| {
             // TODO: Pass values like # of results as args to the binary
             fetch_entries(map_id, 0, limit * LIMIT_MULT_SP, utc, false)
@@ -94,6 +115,7 @@ fn fetch_all(limit: i32) {
         .collect();
     let _res_cp: Vec<_> = official_coop
         .into_par_iter()
+        .filter(|_| !is_shutting_down())
         .map(|map_id| fetch_entries(map_id, 0, limit * LIMIT_MULT_COOP, utc, true))
         .collect();
 
@@ -173,6 +173,62 @@ pub struct Users {
     pub discord_id: Option<String>,
 }
 
+/// Fresh Steam data for a user, sent to the webserver to keep names/avatars in sync.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SteamDataUpdate {
+    pub profile_number: String,
+    pub steam_name: Option<String>,
+    pub avatar: Option<String>,
+}
+
+/// A batch of [SteamDataUpdate]s, sent under an admin so the webserver accepts them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SteamDataParams {
+    pub admin_profile_number: String,
+    pub updates: Vec<SteamDataUpdate>,
+}
+
+/// How a score reported by Steam compares to the player's stored best, lower scores are better.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreComparison {
+    Worse,
+    Equal,
+    Better,
+}
+
+impl ScoreComparison {
+    pub fn compare(incoming: i32, current_best: i32) -> Self {
+        match incoming.cmp(&current_best) {
+            std::cmp::Ordering::Less => ScoreComparison::Better,
+            std::cmp::Ordering::Equal => ScoreComparison::Equal,
+            std::cmp::Ordering::Greater => ScoreComparison::Worse,
+        }
+    }
+}
+
+/// A score reported by Steam that is worse than the player's stored best, kept for review rather than inserted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ScoreRegression {
+    pub profile_number: String,
+    pub stored: i32,
+    pub incoming: i32,
+}
+
+/// A player that already had a score on a leaderboard, and moved position between two snapshots of it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RankChange {
+    pub profile_number: String,
+    pub old_rank: i32,
+    pub new_rank: i32,
+}
+
+impl RankChange {
+    /// The # of positions moved, positive when the player moved up the leaderboard.
+    pub fn delta(&self) -> i32 {
+        self.old_rank - self.new_rank
+    }
+}
+
 /// Wrapper for our API call
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GetPlayerSummariesWrapper {
@@ -0,0 +1,156 @@
+use crate::config::Config;
+use crate::fetch_all;
+use crate::points::calc_points;
+use crate::shutdown::{is_shutting_down, listen_for_shutdown};
+use crate::stages::syncing::{sync_avatars, sync_steam_names};
+use log::{error, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::{interval, MissedTickBehavior};
+
+/// The tasks to run and how often, a task set to `None` is disabled.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    pub import: Option<Duration>,
+    pub avatar_sync: Option<Duration>,
+    pub name_sync: Option<Duration>,
+    pub import_limit: i32,
+    /// The admin Steam data updates are sent under, see [crate::config::SyncConfig].
+    pub sync_admin: Option<String>,
+}
+
+impl Schedule {
+    /// Builds the schedule from the configured intervals, see [crate::config::SchedulerConfig].
+    pub fn from_config(config: &Config, import_limit: i32) -> Self {
+        Schedule {
+            import: period(config.scheduler.import_secs),
+            avatar_sync: period(config.scheduler.avatar_sync_secs),
+            name_sync: period(config.scheduler.name_sync_secs),
+            import_limit,
+            sync_admin: config
+                .sync
+                .admin_profile_number
+                .clone()
+                .filter(|admin| !admin.trim().is_empty()),
+        }
+    }
+}
+
+fn period(secs: u64) -> Option<Duration> {
+    match secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
+/// Runs every enabled task on its own interval until the process is stopped.
+///
+/// Each run happens on a blocking thread. If a task is still running when its next tick comes up, that tick is skipped.
+///
+/// On SIGINT/SIGTERM no new runs are started, and in-flight runs stop after their current item before this returns.
+pub async fn run_scheduler(config: Schedule) {
+    let import_limit = config.import_limit;
+    let shutdown = listen_for_shutdown();
+    let mut tasks = Vec::new();
+    if let Some(period) = config.import {
+        tasks.push(tokio::spawn(schedule(
+            "import",
+            period,
+            shutdown.clone(),
+            move || {
+                fetch_all(import_limit);
+                // Points are only recalculated for a complete import.
+                if !is_shutting_down() {
+                    calc_points(None);
+                }
+                Ok(())
+            },
+        )));
+    }
+    if config.sync_admin.is_none() && (config.avatar_sync.is_some() || config.name_sync.is_some()) {
+        warn!("SYNC.ADMIN_PROFILE_NUMBER is not set, skipping the Steam syncs.");
+    }
+    if let (Some(period), Some(admin)) = (config.avatar_sync, config.sync_admin.clone()) {
+        tasks.push(tokio::spawn(schedule(
+            "avatar sync",
+            period,
+            shutdown.clone(),
+            move || {
+                sync_avatars(&admin)
+                    .map(|updated| info!("Updated {} avatars", updated))
+                    .map_err(|e| e.to_string())
+            },
+        )));
+    }
+    if let (Some(period), Some(admin)) = (config.name_sync, config.sync_admin.clone()) {
+        tasks.push(tokio::spawn(schedule(
+            "steam name sync",
+            period,
+            shutdown.clone(),
+            move || {
+                sync_steam_names(&admin)
+                    .map(|updated| info!("Updated {} steam names", updated))
+                    .map_err(|e| e.to_string())
+            },
+        )));
+    }
+    if tasks.is_empty() {
+        warn!("No scheduled tasks are enabled.");
+        return;
+    }
+    for task in tasks {
+        if let Err(e) = task.await {
+            error!("Scheduler task stopped unexpectedly -> {}", e);
+        }
+    }
+}
+
+async fn schedule<F>(
+    name: &'static str,
+    period: Duration,
+    mut shutdown: watch::Receiver<bool>,
+    job: F,
+) where
+    F: Fn() -> Result<(), String> + Send + Sync + Clone + 'static,
+{
+    let running = Arc::new(AtomicBool::new(false));
+    let mut in_flight = None;
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    info!("Scheduled {} every {}s", name, period.as_secs());
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => (),
+            _ = shutdown.changed() => (),
+        }
+        if is_shutting_down() {
+            break;
+        }
+        if running.swap(true, Ordering::SeqCst) {
+            warn!("Skipping {}, the previous run is still going", name);
+            continue;
+        }
+        let running = running.clone();
+        let job = job.clone();
+        // Not awaited here, so ticks keep coming while a long run is in progress.
+        in_flight = Some(tokio::spawn(async move {
+            info!("Starting {}", name);
+            let start = std::time::Instant::now();
+            match tokio::task::spawn_blocking(job).await {
+                Ok(Ok(())) => info!("Finished {} in {:?}", name, start.elapsed()),
+                Ok(Err(e)) => error!("Error running {} -> {}", name, e),
+                Err(e) => error!("{} panicked -> {}", name, e),
+            }
+            running.store(false, Ordering::SeqCst);
+        }));
+    }
+    if let Some(run) = in_flight {
+        if !run.is_finished() {
+            info!("Waiting for {} to finish before shutting down", name);
+        }
+        let _ = run.await;
+    }
+    info!("Stopped {}", name);
+}
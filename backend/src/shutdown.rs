@@ -0,0 +1,45 @@
+use log::info;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::watch;
+
+/// Set once a shutdown has been requested, long-running imports/syncs check this between items.
+static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// Whether a shutdown has been requested.
+///
+/// Loops should finish the item they're working on, then stop before starting the next one.
+pub fn is_shutting_down() -> bool {
+    SHUTDOWN.load(Ordering::SeqCst)
+}
+
+/// Requests a shutdown, see [is_shutting_down].
+pub fn request_shutdown() {
+    SHUTDOWN.store(true, Ordering::SeqCst);
+}
+
+/// Waits for SIGINT (or SIGTERM on unix), then requests a shutdown and notifies the returned receiver.
+pub fn listen_for_shutdown() -> watch::Receiver<bool> {
+    let (tx, rx) = watch::channel(false);
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!("Shutdown requested, finishing in-flight tasks");
+        request_shutdown();
+        let _ = tx.send(true);
+    });
+    rx
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut sigterm = signal(SignalKind::terminate()).expect("Could not listen for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => (),
+        _ = sigterm.recv() => (),
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -1,4 +1,8 @@
-use std::fs::File;
+use crate::models::datamodels::{Entry, RankChange, ScoreRegression};
+use chrono::NaiveDateTime;
+use log::warn;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::path::Path;
@@ -38,3 +42,94 @@ pub fn cache_leaderboard(id: i32, text: String) -> bool {
         true
     }
 }
+
+/// Ranks every player on a leaderboard by score (lower is better), tied scores share the same rank.
+fn ranks(entries: &[(String, i32)]) -> HashMap<&str, i32> {
+    let mut sorted: Vec<&(String, i32)> = entries.iter().collect();
+    sorted.sort_by_key(|(_, score)| *score);
+    let mut ranks = HashMap::with_capacity(sorted.len());
+    let mut rank = 0;
+    let mut last_score = None;
+    for (i, (profile_number, score)) in sorted.into_iter().enumerate() {
+        if last_score != Some(*score) {
+            rank = i as i32 + 1;
+            last_score = Some(*score);
+        }
+        ranks.insert(profile_number.as_str(), rank);
+    }
+    ranks
+}
+
+/// Compares two snapshots of a leaderboard, as `(profile_number, score)` pairs, and returns every player that moved position.
+///
+/// Players only on one of the snapshots (new entries, or players pushed off the board) are not included.
+/// Sorted by the size of the move, biggest first.
+pub fn rank_changes(previous: &[(String, i32)], current: &[(String, i32)]) -> Vec<RankChange> {
+    let old_ranks = ranks(previous);
+    let mut changes: Vec<RankChange> = ranks(current)
+        .into_iter()
+        .filter_map(
+            |(profile_number, new_rank)| match old_ranks.get(profile_number) {
+                Some(old_rank) if *old_rank != new_rank => Some(RankChange {
+                    profile_number: profile_number.to_string(),
+                    old_rank: *old_rank,
+                    new_rank,
+                }),
+                _ => None,
+            },
+        )
+        .collect();
+    changes.sort_by(|a, b| {
+        b.delta()
+            .abs()
+            .cmp(&a.delta().abs())
+            .then_with(|| a.new_rank.cmp(&b.new_rank))
+    });
+    changes
+}
+
+/// Saves the current ranks for a map alongside the leaderboard cache, and returns the rank changes since the last snapshot.
+///
+/// Returns an empty list the first time a map is seen.
+pub fn cache_rank_changes(id: i32, entries: &[Entry]) -> Vec<RankChange> {
+    let path_str = format!("./cache/{}.ranks.json", id);
+    let path = Path::new(&path_str);
+    let current: Vec<(String, i32)> = entries
+        .iter()
+        .map(|entry| (entry.steam_id.value.clone(), entry.score.value))
+        .collect();
+    let previous: Option<Vec<(String, i32)>> = File::open(path)
+        .ok()
+        .and_then(|ifp| serde_json::from_reader(BufReader::new(ifp)).ok());
+    let ofp = File::create(path).expect("Error creating file to write to for rank cache");
+    serde_json::to_writer(ofp, &current).expect("Error writing to rank cache files");
+    match previous {
+        Some(previous) => rank_changes(&previous, &current),
+        None => Vec::new(),
+    }
+}
+
+/// Appends scores that are worse than a player's stored best to `./review/score_regressions.log`, so they can be looked at by hand.
+pub fn log_score_regressions(id: i32, timestamp: NaiveDateTime, regressions: &[ScoreRegression]) {
+    if regressions.is_empty() {
+        return;
+    }
+    std::fs::create_dir_all("./review").expect("Error creating the review directory");
+    let mut ofp = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open("./review/score_regressions.log")
+        .expect("Error opening the score review log");
+    for regression in regressions.iter() {
+        warn!(
+            "Score regression for {} on map {}, stored {} but Steam reports {}",
+            regression.profile_number, id, regression.stored, regression.incoming
+        );
+        writeln!(
+            ofp,
+            "{} map {} {} stored {} steam {}",
+            timestamp, id, regression.profile_number, regression.stored, regression.incoming
+        )
+        .expect("Error writing to the score review log");
+    }
+}
@@ -1,9 +1,10 @@
 use super::exporting::*;
 use super::uploading::*;
 use crate::models::datamodels::{
-    CoopDataUtil, CoopRanked, Entry, GetPlayerSummariesWrapper, Leaderboards, SpBanned, SpRanked,
-    Users, XmlTag,
+    CoopDataUtil, CoopRanked, Entry, GetPlayerSummariesWrapper, Leaderboards, ScoreComparison,
+    ScoreRegression, SpBanned, SpRanked, Users, XmlTag,
 };
+use crate::shutdown::is_shutting_down;
 use crate::LIMIT_MULT_COOP;
 use crate::LIMIT_MULT_SP;
 use chrono::prelude::*;
@@ -38,6 +39,12 @@ pub fn fetch_entries(
         false => trace!("The cache is unchanged for map {}", id),
     }
     let leaderboard: Leaderboards = from_reader(text.as_bytes()).expect("XML Error in parsing");
+    for change in cache_rank_changes(id, &leaderboard.entries.value) {
+        debug!(
+            "{} moved from rank {} to {} on map {}",
+            change.profile_number, change.old_rank, change.new_rank, id
+        );
+    }
     // Get banned players list.
     let banned_users: Vec<String> =
         reqwest::blocking::get("http://localhost:8080/api/v1/banned_users")
@@ -66,6 +73,29 @@ pub fn fetch_entries(
     leaderboard
 }
 
+/// Finds every player whose score on Steam is worse than their stored best, e.g. after a leaderboard reset or from a different account.
+///
+/// Only players with a score on our board can be compared, equal scores are not regressions.
+pub fn find_regressions(
+    data: &XmlTag<Vec<Entry>>,
+    existing_hash: &HashMap<String, (i32, i32)>,
+) -> Vec<ScoreRegression> {
+    data.value
+        .iter()
+        .filter_map(|entry| {
+            let (stored, _) = existing_hash.get(&entry.steam_id.value)?;
+            match ScoreComparison::compare(entry.score.value, *stored) {
+                ScoreComparison::Worse => Some(ScoreRegression {
+                    profile_number: entry.steam_id.value.clone(),
+                    stored: *stored,
+                    incoming: entry.score.value,
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
 /// Breaking apart the modules that filted out the list to times that aren't banned/cheated.
 pub fn validate_entries(
     data: &XmlTag<Vec<Entry>>,
@@ -80,8 +110,8 @@ pub fn validate_entries(
     for entry in data.value.iter() {
         match existing_hash.get(&entry.steam_id.value) {
             Some((score, rank)) => {
-                // The user has a time in top X scores currently
-                if score > &entry.score.value {
+                // The user has a time in top X scores currently, only improvements are new times.
+                if ScoreComparison::compare(entry.score.value, *score) == ScoreComparison::Better {
                     trace!(
                         "New better time for user {} on map_id {}",
                         entry.steam_id.value,
@@ -149,12 +179,17 @@ pub fn filter_entries_sp(
     // TODO: Query to see if the user exists.
 
     // TODO: Implement a per-map threshold???
+    log_score_regressions(id, timestamp, &find_regressions(data, &existing_hash));
     let (current_rank, not_cheated) =
         validate_entries(data, existing_hash, banned_users, id, worst_score);
     // We grab the list of banned times from our API.
     // Filter out any times that are banned from the list of potential runs.
     // The list of new scores is probably relatively low, it would be easier to just send the score information to an endpoint and have it check.
     for entry in not_cheated.iter() {
+        if is_shutting_down() {
+            debug!("Shutting down, skipping the remaining times on map {}", id);
+            break;
+        }
         let ban_url = format!(
             "http://localhost:8080/api/v1/sp/banned/{}?profile_number={}&score={}",
             id, entry.profile_number, entry.score
@@ -230,6 +265,7 @@ pub fn filter_entries_coop(
         );
     }
 
+    log_score_regressions(id, timestamp, &find_regressions(data, &existing_hash));
     let (current_rank, not_banned_players) =
         validate_entries(data, existing_hash, banned_users, id, worst_score);
 
@@ -301,6 +337,10 @@ pub fn filter_entries_coop(
 
     // Push to the database.
     for entry in bundled_entries.iter() {
+        if is_shutting_down() {
+            debug!("Shutting down, skipping the remaining times on map {}", id);
+            break;
+        }
         // TODO: Handle failture to insert.
         match post_coop_pb(
             entry.profile_number1.clone(),
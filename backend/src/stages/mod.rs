@@ -1,3 +1,4 @@
 pub mod exporting;
 pub mod fetching;
+pub mod syncing;
 pub mod uploading;
@@ -0,0 +1,77 @@
+use crate::models::datamodels::{GetPlayerSummariesWrapper, SteamDataParams, SteamDataUpdate};
+use crate::shutdown::is_shutting_down;
+
+/// Max # of steamids the GetPlayerSummaries endpoint accepts per call.
+const SUMMARIES_BATCH: usize = 100;
+
+/// Pulls the current Steam name/avatar for every user, and pushes any changes to the webserver.
+///
+/// `avatars` and `names` select which fields are sent, the webserver only writes values that changed.
+/// Updates are sent under `admin_profile_number`, which must be an admin on the board.
+/// Stops between batches if a shutdown is requested. Returns the # of users that were updated.
+pub fn sync_steam_data(
+    avatars: bool,
+    names: bool,
+    admin_profile_number: &str,
+) -> Result<u64, reqwest::Error> {
+    let api_key = dotenv::var("STEAM_API_KEY").expect("Cannot find STEAM_API_KEY in ./.env");
+    let profile_numbers: Vec<String> =
+        reqwest::blocking::get("http://localhost:8080/api/v1/users")?.json()?;
+
+    let client = reqwest::blocking::Client::new();
+    let mut updated = 0;
+    for batch in profile_numbers.chunks(SUMMARIES_BATCH) {
+        if is_shutting_down() {
+            break;
+        }
+        let steam_api_url = format!(
+            "https://api.steampowered.com/ISteamUser/GetPlayerSummaries/v2/?key={}&steamids={}",
+            api_key,
+            batch.join(",")
+        );
+        let summaries = client
+            .get(&steam_api_url)
+            .send()?
+            .json::<GetPlayerSummariesWrapper>()?;
+        let updates: Vec<SteamDataUpdate> = summaries
+            .response
+            .players
+            .into_iter()
+            .map(|player| SteamDataUpdate {
+                profile_number: player.steamid,
+                steam_name: if names {
+                    Some(player.personaname)
+                } else {
+                    None
+                },
+                avatar: if avatars {
+                    Some(player.avatarfull)
+                } else {
+                    None
+                },
+            })
+            .collect();
+        if updates.is_empty() {
+            continue;
+        }
+        updated += client
+            .put("http://localhost:8080/api/v1/users/steam_data")
+            .json(&SteamDataParams {
+                admin_profile_number: admin_profile_number.to_string(),
+                updates,
+            })
+            .send()?
+            .json::<u64>()?;
+    }
+    Ok(updated)
+}
+
+/// Syncs only avatars, see [sync_steam_data].
+pub fn sync_avatars(admin_profile_number: &str) -> Result<u64, reqwest::Error> {
+    sync_steam_data(true, false, admin_profile_number)
+}
+
+/// Syncs only steam names, see [sync_steam_data].
+pub fn sync_steam_names(admin_profile_number: &str) -> Result<u64, reqwest::Error> {
+    sync_steam_data(false, true, admin_profile_number)
+}
@@ -111,9 +111,18 @@ pub fn post_sp_pb(
         .post(&post_url)
         .json(&new_score)
         .send()
-        .expect("Error querying our local API")
-        .json::<i64>();
-    match res {
+        .expect("Error querying our local API");
+    // Maps that aren't accepting submissions reject the score.
+    if !res.status().is_success() {
+        error!(
+            "Score {} on {} was rejected -> {}",
+            new_score.score,
+            new_score.map_id,
+            res.text().unwrap_or_default()
+        );
+        return false;
+    }
+    match res.json::<i64>() {
         Ok(s) => {
             trace!("{}", s)
         }
@@ -281,37 +290,33 @@ pub fn post_coop_pb(
         debug!("{:#?}", score2);
         // Insert both changelog entries, retrieve their IDs, create bundle
         let client = reqwest::blocking::Client::new();
-        let mut new_id1 = 0;
-        let mut new_id2 = 0;
         let post_url = "http://localhost:8080/api/v1/sp/post_score".to_string();
-        let res = client
-            .post(&post_url)
-            .json(&score1)
-            .send()
-            .expect("Error querying our local API")
-            .json::<i64>();
-        match res {
-            Ok(s) => {
-                new_id1 = s;
-            }
-            Err(e) => {
-                error!("{}", e)
+        let mut new_ids = Vec::with_capacity(2);
+        for score in [&score1, &score2] {
+            let res = client
+                .post(&post_url)
+                .json(score)
+                .send()
+                .expect("Error querying our local API");
+            // Maps that aren't accepting submissions reject the score.
+            if !res.status().is_success() {
+                error!(
+                    "Score {} on {} was rejected -> {}",
+                    score.score,
+                    score.map_id,
+                    res.text().unwrap_or_default()
+                );
+                return false;
             }
-        }
-        let res = client
-            .post(&post_url)
-            .json(&score2)
-            .send()
-            .expect("Error querying our local API")
-            .json::<i64>();
-        match res {
-            Ok(s) => {
-                new_id2 = s;
-            }
-            Err(e) => {
-                error!("{}", e)
+            match res.json::<i64>() {
+                Ok(s) => new_ids.push(s),
+                Err(e) => {
+                    error!("{}", e);
+                    return false;
+                }
             }
         }
+        let (new_id1, new_id2) = (new_ids[0], new_ids[1]);
         let bundle = CoopBundledInsert {
             p_id1: profile_number1,
             p_id2: Some(profile_number2),
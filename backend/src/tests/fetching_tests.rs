@@ -43,6 +43,78 @@ pub fn test_validate_entries() {
     assert_eq!(not_cheated[1].score, 2100);
 }
 
+#[cfg(test)]
+#[test]
+/// Tests that only scores worse than a player's stored best are flagged as regressions.
+pub fn test_find_regressions() {
+    use crate::models::datamodels::{ScoreComparison, ScoreRegression, SpRanked};
+    use crate::stages::fetching::find_regressions;
+    use std::collections::HashMap;
+
+    let data: XmlTag<Vec<Entry>> = XmlTag {
+        value: sp_entries(),
+    };
+    let existing_hash: HashMap<String, (i32, i32)> = example::<SpRanked>("sp_ranked")
+        .into_iter()
+        .map(|rank| {
+            (
+                rank.map_data.profile_number.clone(),
+                (rank.map_data.score, rank.rank),
+            )
+        })
+        .collect();
+    // Xinera's time on Steam is worse than the one on the board, Daniel's is the same.
+    assert_eq!(
+        find_regressions(&data, &existing_hash),
+        vec![ScoreRegression {
+            profile_number: "76561198054297641".to_string(),
+            stored: 1826,
+            incoming: 1858,
+        }]
+    );
+    assert_eq!(
+        ScoreComparison::compare(1720, 1740),
+        ScoreComparison::Better
+    );
+    assert_eq!(ScoreComparison::compare(1816, 1816), ScoreComparison::Equal);
+}
+
+#[cfg(test)]
+#[test]
+/// Tests that rank_changes only reports existing players that moved, biggest move first.
+pub fn test_rank_changes() {
+    use crate::models::datamodels::RankChange;
+    use crate::stages::exporting::rank_changes;
+
+    let previous: Vec<(String, i32)> = sp_entries()
+        .into_iter()
+        .map(|entry| (entry.steam_id.value, entry.score.value))
+        .collect();
+    let mut current = previous.clone();
+    // Xinera improves from 3rd to 1st, and a new player ties with Daniel.
+    current[0].1 = 1700;
+    current.push(("76561198000000000".to_string(), 1816));
+    let changes = rank_changes(&previous, &current);
+    assert_eq!(
+        changes[0],
+        RankChange {
+            profile_number: "76561198054297641".to_string(),
+            old_rank: 3,
+            new_rank: 1,
+        }
+    );
+    assert!(changes
+        .iter()
+        .all(|change| change.old_rank != change.new_rank));
+    assert!(changes
+        .iter()
+        .all(|change| change.profile_number != "76561198000000000"));
+    assert!(changes
+        .windows(2)
+        .all(|w| w[0].delta().abs() >= w[1].delta().abs()));
+    assert!(rank_changes(&previous, &previous).is_empty());
+}
+
 /// Deserializses a generic T into a vec of entries that implement Deserialize.
 /// Used for parsing mock JSON data located in the path referenced below.
 pub fn example<T: for<'de> serde::Deserialize<'de>>(id: &str) -> Vec<T> {
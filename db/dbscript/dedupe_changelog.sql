@@ -0,0 +1,30 @@
+/*
+Removes duplicate changelog entries so the unq_changelog_entry index can be created on an existing database.
+Entries are duplicates when they share (profile_number, map_id, category_id, score, timestamp), NULL timestamps included.
+The oldest entry (lowest id) of each group is kept, and everything that referenced a removed entry is pointed at it.
+*/
+BEGIN;
+
+CREATE TEMP TABLE changelog_dupes ON COMMIT DROP AS
+SELECT id, keep_id FROM (
+    SELECT id, MIN(id) OVER (
+        PARTITION BY profile_number, map_id, category_id, score, COALESCE("timestamp", '-infinity'::timestamp)
+    ) AS keep_id
+    FROM p2boards.changelog
+) AS grouped
+WHERE id <> keep_id;
+
+UPDATE p2boards.demos SET cl_id = d.keep_id FROM changelog_dupes d WHERE demos.cl_id = d.id;
+UPDATE p2boards.coop_bundled SET cl_id1 = d.keep_id FROM changelog_dupes d WHERE coop_bundled.cl_id1 = d.id;
+UPDATE p2boards.coop_bundled SET cl_id2 = d.keep_id FROM changelog_dupes d WHERE coop_bundled.cl_id2 = d.id;
+UPDATE p2boards.changelog SET previous_id = d.keep_id FROM changelog_dupes d WHERE changelog.previous_id = d.id;
+UPDATE p2boards.changelog_history SET cl_id = d.keep_id FROM changelog_dupes d WHERE changelog_history.cl_id = d.id;
+UPDATE p2boards.submission_keys SET cl_id = d.keep_id FROM changelog_dupes d WHERE submission_keys.cl_id = d.id;
+
+DELETE FROM p2boards.changelog USING changelog_dupes d WHERE changelog.id = d.id;
+
+ALTER TABLE p2boards.changelog DROP CONSTRAINT IF EXISTS unq_changelog_entry;
+CREATE UNIQUE INDEX IF NOT EXISTS unq_changelog_entry ON p2boards.changelog USING btree
+    (profile_number, map_id, category_id, score, COALESCE("timestamp", '-infinity'::timestamp without time zone));
+
+COMMIT;
@@ -16,10 +16,50 @@ SET row_security = off;
 CREATE SCHEMA p2boards;
 
 
+--
+-- Name: pg_trgm; Type: EXTENSION; Schema: -; Owner: -
+--
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm WITH SCHEMA public;
+
+
 SET default_tablespace = '';
 
 SET default_table_access_method = heap;
 
+--
+-- Name: admin_audit; Type: TABLE; Schema: p2boards; Owner: -
+--
+
+CREATE TABLE p2boards.admin_audit (
+    id bigint NOT NULL,
+    "timestamp" timestamp without time zone DEFAULT now() NOT NULL,
+    admin_profile_number character varying(50) NOT NULL,
+    action character varying(50) NOT NULL,
+    target character varying(100),
+    details text
+);
+
+
+--
+-- Name: admin_audit_id_seq; Type: SEQUENCE; Schema: p2boards; Owner: -
+--
+
+CREATE SEQUENCE p2boards.admin_audit_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+
+--
+-- Name: admin_audit_id_seq; Type: SEQUENCE OWNED BY; Schema: p2boards; Owner: -
+--
+
+ALTER SEQUENCE p2boards.admin_audit_id_seq OWNED BY p2boards.admin_audit.id;
+
+
 --
 -- Name: categories; Type: TABLE; Schema: p2boards; Owner: -
 --
@@ -74,10 +114,44 @@ CREATE TABLE p2boards.changelog (
     category_id integer DEFAULT 1 NOT NULL,
     score_delta integer,
     verified boolean,
-    admin_note character varying(200)
+    admin_note character varying(200),
+    archived boolean DEFAULT false NOT NULL,
+    banned_by_cascade boolean DEFAULT false NOT NULL
 );
 
 
+--
+-- Name: changelog_history; Type: TABLE; Schema: p2boards; Owner: -
+--
+
+CREATE TABLE p2boards.changelog_history (
+    id bigint NOT NULL,
+    cl_id bigint NOT NULL,
+    "timestamp" timestamp without time zone DEFAULT now() NOT NULL,
+    edited_by character varying(50),
+    previous text NOT NULL
+);
+
+
+--
+-- Name: changelog_history_id_seq; Type: SEQUENCE; Schema: p2boards; Owner: -
+--
+
+CREATE SEQUENCE p2boards.changelog_history_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+
+--
+-- Name: changelog_history_id_seq; Type: SEQUENCE OWNED BY; Schema: p2boards; Owner: -
+--
+
+ALTER SEQUENCE p2boards.changelog_history_id_seq OWNED BY p2boards.changelog_history.id;
+
+
 --
 -- Name: changelog_id_seq; Type: SEQUENCE; Schema: p2boards; Owner: -
 --
@@ -172,7 +246,8 @@ CREATE TABLE p2boards.demos (
     partner_name character varying(50),
     parsed_successfully boolean DEFAULT false NOT NULL,
     sar_version character varying(50),
-    cl_id bigint NOT NULL
+    cl_id bigint NOT NULL,
+    uploaded_at timestamp without time zone DEFAULT now() NOT NULL
 );
 
 
@@ -195,13 +270,80 @@ CREATE SEQUENCE p2boards.demos_id_seq
 ALTER SEQUENCE p2boards.demos_id_seq OWNED BY p2boards.demos.id;
 
 
+--
+-- Name: donations; Type: TABLE; Schema: p2boards; Owner: -
+--
+
+CREATE TABLE p2boards.donations (
+    id bigint NOT NULL,
+    profile_number character varying(50) NOT NULL,
+    "timestamp" timestamp without time zone DEFAULT now() NOT NULL,
+    amount numeric(10,2) NOT NULL,
+    currency character varying(3) DEFAULT 'USD'::character varying NOT NULL,
+    note text,
+    CONSTRAINT chk_donations_amount CHECK ((amount > (0)::numeric))
+);
+
+
+--
+-- Name: donations_id_seq; Type: SEQUENCE; Schema: p2boards; Owner: -
+--
+
+CREATE SEQUENCE p2boards.donations_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+
+--
+-- Name: donations_id_seq; Type: SEQUENCE OWNED BY; Schema: p2boards; Owner: -
+--
+
+ALTER SEQUENCE p2boards.donations_id_seq OWNED BY p2boards.donations.id;
+
+
+--
+-- Name: featured_maps; Type: TABLE; Schema: p2boards; Owner: -
+--
+
+CREATE TABLE p2boards.featured_maps (
+    id bigint NOT NULL,
+    map_id character varying(6) NOT NULL,
+    featured_at timestamp without time zone DEFAULT now() NOT NULL,
+    until timestamp without time zone NOT NULL,
+    featured_by character varying(50) NOT NULL
+);
+
+
+--
+-- Name: featured_maps_id_seq; Type: SEQUENCE; Schema: p2boards; Owner: -
+--
+
+CREATE SEQUENCE p2boards.featured_maps_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+
+--
+-- Name: featured_maps_id_seq; Type: SEQUENCE OWNED BY; Schema: p2boards; Owner: -
+--
+
+ALTER SEQUENCE p2boards.featured_maps_id_seq OWNED BY p2boards.featured_maps.id;
+
+
 --
 -- Name: games; Type: TABLE; Schema: p2boards; Owner: -
 --
 
 CREATE TABLE p2boards.games (
     id integer NOT NULL,
-    game_name character varying(50) DEFAULT 'Portal 2'::character varying NOT NULL
+    game_name character varying(50) DEFAULT 'Portal 2'::character varying NOT NULL,
+    default_category character varying(100)
 );
 
 
@@ -235,7 +377,14 @@ CREATE TABLE p2boards.maps (
     lp_id character varying(6) DEFAULT ''::character varying NOT NULL,
     name character varying(50) NOT NULL,
     chapter_id integer,
-    is_public boolean DEFAULT false NOT NULL
+    default_cat_id integer,
+    is_public boolean DEFAULT false NOT NULL,
+    difficulty_tier integer,
+    submissions_open boolean DEFAULT true NOT NULL,
+    opens_at timestamp without time zone,
+    closes_at timestamp without time zone,
+    CONSTRAINT maps_difficulty_tier_check CHECK (((difficulty_tier >= 1) AND (difficulty_tier <= 10))),
+    CONSTRAINT maps_submission_window_check CHECK (((opens_at IS NULL) OR (closes_at IS NULL) OR (opens_at < closes_at)))
 );
 
 
@@ -259,6 +408,50 @@ CREATE SEQUENCE p2boards.maps_id_seq
 ALTER SEQUENCE p2boards.maps_id_seq OWNED BY p2boards.maps.id;
 
 
+--
+-- Name: name_history; Type: TABLE; Schema: p2boards; Owner: -
+--
+
+CREATE TABLE p2boards.name_history (
+    id bigint NOT NULL,
+    profile_number character varying(50) NOT NULL,
+    "timestamp" timestamp without time zone DEFAULT now() NOT NULL,
+    old_name character varying(50),
+    new_name character varying(50)
+);
+
+
+--
+-- Name: name_history_id_seq; Type: SEQUENCE; Schema: p2boards; Owner: -
+--
+
+CREATE SEQUENCE p2boards.name_history_id_seq
+    START WITH 1
+    INCREMENT BY 1
+    NO MINVALUE
+    NO MAXVALUE
+    CACHE 1;
+
+
+--
+-- Name: name_history_id_seq; Type: SEQUENCE OWNED BY; Schema: p2boards; Owner: -
+--
+
+ALTER SEQUENCE p2boards.name_history_id_seq OWNED BY p2boards.name_history.id;
+
+
+--
+-- Name: submission_keys; Type: TABLE; Schema: p2boards; Owner: -
+--
+
+CREATE TABLE p2boards.submission_keys (
+    profile_number character varying(50) NOT NULL,
+    idempotency_key text NOT NULL,
+    cl_id bigint NOT NULL,
+    created_at timestamp without time zone DEFAULT now() NOT NULL
+);
+
+
 --
 -- Name: users; Type: TABLE; Schema: p2boards; Owner: -
 --
@@ -275,7 +468,9 @@ CREATE TABLE p2boards.users (
     title character varying(200),
     admin integer DEFAULT 0 NOT NULL,
     donation_amount character varying(11),
-    discord_id character varying(40)
+    discord_id character varying(40),
+    merged_into character varying(50),
+    ban_reason character varying(200)
 );
 
 
@@ -288,6 +483,13 @@ CREATE TABLE public.schema_migrations (
 );
 
 
+--
+-- Name: admin_audit id; Type: DEFAULT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.admin_audit ALTER COLUMN id SET DEFAULT nextval('p2boards.admin_audit_id_seq'::regclass);
+
+
 --
 -- Name: categories id; Type: DEFAULT; Schema: p2boards; Owner: -
 --
@@ -302,6 +504,13 @@ ALTER TABLE ONLY p2boards.categories ALTER COLUMN id SET DEFAULT nextval('p2boar
 ALTER TABLE ONLY p2boards.changelog ALTER COLUMN id SET DEFAULT nextval('p2boards.changelog_id_seq'::regclass);
 
 
+--
+-- Name: changelog_history id; Type: DEFAULT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.changelog_history ALTER COLUMN id SET DEFAULT nextval('p2boards.changelog_history_id_seq'::regclass);
+
+
 --
 -- Name: chapters id; Type: DEFAULT; Schema: p2boards; Owner: -
 --
@@ -323,6 +532,20 @@ ALTER TABLE ONLY p2boards.coop_bundled ALTER COLUMN id SET DEFAULT nextval('p2bo
 ALTER TABLE ONLY p2boards.demos ALTER COLUMN id SET DEFAULT nextval('p2boards.demos_id_seq'::regclass);
 
 
+--
+-- Name: donations id; Type: DEFAULT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.donations ALTER COLUMN id SET DEFAULT nextval('p2boards.donations_id_seq'::regclass);
+
+
+--
+-- Name: featured_maps id; Type: DEFAULT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.featured_maps ALTER COLUMN id SET DEFAULT nextval('p2boards.featured_maps_id_seq'::regclass);
+
+
 --
 -- Name: games id; Type: DEFAULT; Schema: p2boards; Owner: -
 --
@@ -337,6 +560,21 @@ ALTER TABLE ONLY p2boards.games ALTER COLUMN id SET DEFAULT nextval('p2boards.ga
 ALTER TABLE ONLY p2boards.maps ALTER COLUMN id SET DEFAULT nextval('p2boards.maps_id_seq'::regclass);
 
 
+--
+-- Name: name_history id; Type: DEFAULT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.name_history ALTER COLUMN id SET DEFAULT nextval('p2boards.name_history_id_seq'::regclass);
+
+
+--
+-- Name: admin_audit pk_admin_audit_id; Type: CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.admin_audit
+    ADD CONSTRAINT pk_admin_audit_id PRIMARY KEY (id);
+
+
 --
 -- Name: categories pk_categories_id; Type: CONSTRAINT; Schema: p2boards; Owner: -
 --
@@ -353,6 +591,14 @@ ALTER TABLE ONLY p2boards.changelog
     ADD CONSTRAINT pk_changelog_id PRIMARY KEY (id);
 
 
+--
+-- Name: changelog_history pk_changelog_history_id; Type: CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.changelog_history
+    ADD CONSTRAINT pk_changelog_history_id PRIMARY KEY (id);
+
+
 --
 -- Name: chapters pk_chapters_id; Type: CONSTRAINT; Schema: p2boards; Owner: -
 --
@@ -369,6 +615,22 @@ ALTER TABLE ONLY p2boards.coop_bundled
     ADD CONSTRAINT pk_coop_bundled_id PRIMARY KEY (id);
 
 
+--
+-- Name: donations pk_donations_id; Type: CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.donations
+    ADD CONSTRAINT pk_donations_id PRIMARY KEY (id);
+
+
+--
+-- Name: featured_maps pk_featured_maps_id; Type: CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.featured_maps
+    ADD CONSTRAINT pk_featured_maps_id PRIMARY KEY (id);
+
+
 --
 -- Name: games pk_game_id; Type: CONSTRAINT; Schema: p2boards; Owner: -
 --
@@ -385,6 +647,22 @@ ALTER TABLE ONLY p2boards.maps
     ADD CONSTRAINT pk_maps_id PRIMARY KEY (id);
 
 
+--
+-- Name: name_history pk_name_history_id; Type: CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.name_history
+    ADD CONSTRAINT pk_name_history_id PRIMARY KEY (id);
+
+
+--
+-- Name: submission_keys pk_submission_keys; Type: CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.submission_keys
+    ADD CONSTRAINT pk_submission_keys PRIMARY KEY (profile_number, idempotency_key);
+
+
 --
 -- Name: users pk_users_profile_number; Type: CONSTRAINT; Schema: p2boards; Owner: -
 --
@@ -417,6 +695,28 @@ ALTER TABLE ONLY public.schema_migrations
     ADD CONSTRAINT schema_migrations_pkey PRIMARY KEY (version);
 
 
+--
+-- Name: idx_changelog_map_category_score; Type: INDEX; Schema: p2boards; Owner: -
+--
+
+CREATE INDEX idx_changelog_map_category_score ON p2boards.changelog USING btree (map_id, category_id, score);
+
+
+--
+-- Name: unq_changelog_entry; Type: INDEX; Schema: p2boards; Owner: -
+--
+
+CREATE UNIQUE INDEX unq_changelog_entry ON p2boards.changelog USING btree (profile_number, map_id, category_id, score, COALESCE("timestamp", '-infinity'::timestamp without time zone));
+
+
+--
+-- Name: admin_audit fk_admin_audit_users; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.admin_audit
+    ADD CONSTRAINT fk_admin_audit_users FOREIGN KEY (admin_profile_number) REFERENCES p2boards.users(profile_number);
+
+
 --
 -- Name: changelog fk_changelog_categories; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
 --
@@ -457,6 +757,22 @@ ALTER TABLE ONLY p2boards.changelog
     ADD CONSTRAINT fk_changelog_users FOREIGN KEY (profile_number) REFERENCES p2boards.users(profile_number);
 
 
+--
+-- Name: changelog_history fk_changelog_history_changelog; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.changelog_history
+    ADD CONSTRAINT fk_changelog_history_changelog FOREIGN KEY (cl_id) REFERENCES p2boards.changelog(id) ON DELETE CASCADE;
+
+
+--
+-- Name: changelog_history fk_changelog_history_users; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.changelog_history
+    ADD CONSTRAINT fk_changelog_history_users FOREIGN KEY (edited_by) REFERENCES p2boards.users(profile_number);
+
+
 --
 -- Name: chapters fk_chapters_game_id; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
 --
@@ -497,6 +813,30 @@ ALTER TABLE ONLY p2boards.coop_bundled
     ADD CONSTRAINT fk_coop_bundled_users_u2 FOREIGN KEY (p_id2) REFERENCES p2boards.users(profile_number);
 
 
+--
+-- Name: donations fk_donations_users; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.donations
+    ADD CONSTRAINT fk_donations_users FOREIGN KEY (profile_number) REFERENCES p2boards.users(profile_number) ON DELETE CASCADE;
+
+
+--
+-- Name: featured_maps fk_featured_maps_maps; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.featured_maps
+    ADD CONSTRAINT fk_featured_maps_maps FOREIGN KEY (map_id) REFERENCES p2boards.maps(steam_id);
+
+
+--
+-- Name: featured_maps fk_featured_maps_users; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.featured_maps
+    ADD CONSTRAINT fk_featured_maps_users FOREIGN KEY (featured_by) REFERENCES p2boards.users(profile_number);
+
+
 --
 -- Name: maps fk_maps_chapters; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
 --
@@ -505,6 +845,30 @@ ALTER TABLE ONLY p2boards.maps
     ADD CONSTRAINT fk_maps_chapters FOREIGN KEY (chapter_id) REFERENCES p2boards.chapters(id);
 
 
+--
+-- Name: name_history fk_name_history_users; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.name_history
+    ADD CONSTRAINT fk_name_history_users FOREIGN KEY (profile_number) REFERENCES p2boards.users(profile_number) ON DELETE CASCADE;
+
+
+--
+-- Name: submission_keys fk_submission_keys_changelog; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.submission_keys
+    ADD CONSTRAINT fk_submission_keys_changelog FOREIGN KEY (cl_id) REFERENCES p2boards.changelog(id) ON DELETE CASCADE;
+
+
+--
+-- Name: submission_keys fk_submission_keys_users; Type: FK CONSTRAINT; Schema: p2boards; Owner: -
+--
+
+ALTER TABLE ONLY p2boards.submission_keys
+    ADD CONSTRAINT fk_submission_keys_users FOREIGN KEY (profile_number) REFERENCES p2boards.users(profile_number) ON DELETE CASCADE;
+
+
 --
 -- PostgreSQL database dump complete
 --
@@ -1,6 +1,20 @@
-use crate::models::models::{Admin, AdminLevel, ChangelogQueryParams, Users};
-use actix_web::{get, web, HttpResponse, Responder};
+use crate::api::v1::handlers::changelog::invalid_params_response;
+use crate::models::models::{
+    Admin, AdminLevel, AdminNoteSearchParams, AuditLogParams, BackfillParams, BanUserParams,
+    BulkBanParams, Changelog, ChangelogField, ChangelogFieldParams, ChangelogQueryParams,
+    CompactHistoryParams, Demos, DonationParams, DryRun, DuplicateParams, FeaturedMapParams,
+    LargeJumpParams, MapTierParams, Maps, MergeAccountsParams, PendingReparseParams,
+    RecentDemosParams, SubmissionWindowParams, Users, VerifyPendingParams,
+};
+use crate::tools::backblaze::BackBlazeClient;
+use crate::tools::cache::CacheState;
+use crate::tools::config::Config;
+use crate::tools::errors::BoardsError;
+use crate::tools::helpers::normalize_youtube_id;
+use crate::tools::maintenance::{enforce_retention, run_demo_gc};
+use actix_web::{get, post, put, web, HttpResponse, Responder, ResponseError};
 use sqlx::PgPool;
+use std::collections::HashMap;
 
 /// **GET** method for admin-relevant entiries. Utilizes `ChangelogQueryParrams` as an optional addition to the query
 ///
@@ -28,6 +42,10 @@ use sqlx::PgPool;
 ///         - Will only return scores with an ID higher than the given amount
 ///    - **last**            
 ///         - Will only return scores with an ID lower than the given amount
+///    - **proof_level**
+///         - One of `none`, `demo_only`, `video_only`, `both` or `any`, filters on the combination of demo & video
+///    - **pbs_only**
+///         - Boolean that, if true, will only return scores that were a PB for the player when they were submitted
 /// ## Example endpoints:
 ///  - **Default**           
 ///     - `/api/v1/admin/changelog`
@@ -42,9 +60,13 @@ use sqlx::PgPool;
 #[get("/admin/changelog")]
 async fn get_admin_changelog(
     pool: web::Data<PgPool>,
-    query_params: web::Query<ChangelogQueryParams>,
+    query_params: web::Query<HashMap<String, String>>,
 ) -> impl Responder {
-    let res = Admin::get_admin_page(pool.get_ref(), query_params.into_inner()).await;
+    let params = match ChangelogQueryParams::from_query_map(&query_params) {
+        Ok(params) => params,
+        Err(e) => return invalid_params_response(e),
+    };
+    let res = Admin::get_admin_page(pool.get_ref(), params).await;
     match res {
         Ok(changelog_entries) => HttpResponse::Ok().json(changelog_entries),
         _ => HttpResponse::NotFound().body("No changelog entries found."),
@@ -58,10 +80,7 @@ async fn get_admin_changelog(
 async fn get_banned_stats(pool: web::Data<PgPool>) -> impl Responder {
     match Admin::get_user_banned_time_stats(pool.get_ref()).await {
         Ok(Some(res)) => HttpResponse::Ok().json(res),
-        Err(e) => {
-            eprintln!("Error getting banned time stats -> {}", e);
-            HttpResponse::NotFound().body("Could not find banned stats.")
-        }
+        Err(e) => BoardsError::from(e.context("Error getting banned time stats")).error_response(),
         _ => HttpResponse::NotFound().body("Could not find banned stats."),
     }
 }
@@ -89,13 +108,762 @@ async fn get_banned_stats(pool: web::Data<PgPool>) -> impl Responder {
 ///     - `/api/v1/admins?admin-level=2`
 ///
 #[get("/admins")]
-async fn get_admin_list(pool: web::Data<PgPool>, query: web::Query<AdminLevel>) -> impl Responder {
-    match Users::get_all_admins(pool.get_ref(), query.into_inner().admin_level.unwrap_or(1)).await {
+async fn get_admin_list(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    query: web::Query<AdminLevel>,
+) -> impl Responder {
+    let res = Users::get_all_admins(
+        pool.get_ref(),
+        query.into_inner().admin_level.unwrap_or(1),
+        &config.users.default_avatar,
+    )
+    .await;
+    match res {
         Ok(Some(res)) => HttpResponse::Ok().json(res),
+        Err(e) => BoardsError::from(e.context("Error getting Admins")).error_response(),
+        _ => HttpResponse::NotFound().body("Could not find admins."),
+    }
+}
+
+/// **POST** method to garbage collect orphaned demos (demos no longer referenced by any changelog entry).
+///
+/// **Required Parameters**: admin_profile_number
+///
+/// **Optional Parameters**:
+///    - **dry_run**
+///         - **DEFAULT** `true` - Reports what would be removed without deleting anything.
+///
+/// ## Example endpoints:
+///  - **Preview**
+///     - `/api/v1/admin/demos/gc?admin_profile_number=76561198040982247`
+///  - **Delete orphaned demos**
+///     - `/api/v1/admin/demos/gc?dry_run=false&admin_profile_number=76561198040982247`
+#[post("/admin/demos/gc")]
+async fn post_demo_gc(
+    pool: web::Data<PgPool>,
+    b2: web::Data<BackBlazeClient>,
+    query: web::Query<DryRun>,
+) -> Result<HttpResponse, BoardsError> {
+    let query = query.into_inner();
+    let dry_run = query.dry_run.unwrap_or(true);
+    let report = run_demo_gc(
+        pool.get_ref(),
+        b2.get_ref(),
+        dry_run,
+        &query.admin_profile_number,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// **GET** method to find demos referenced by the changelog whose file is missing from BackBlaze.
+///
+/// Returns the ids of the missing demos. Checks every demo, so this can take a while on a large board.
+/// Failures are a JSON [BoardsError] body, `503` if demo storage can't be reached.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/demos/audit`
+#[get("/admin/demos/audit")]
+async fn get_demo_audit(
+    pool: web::Data<PgPool>,
+    b2: web::Data<BackBlazeClient>,
+) -> Result<HttpResponse, BoardsError> {
+    let missing = Demos::audit_storage(pool.get_ref(), b2.get_ref()).await?;
+    Ok(HttpResponse::Ok().json(missing))
+}
+
+/// **GET** method for the ids of every demo that should be reparsed after the demo parser moved to a newer SAR version.
+///
+/// Versions are compared numerically (`1.12.10` is newer than `1.12.9`), demos without a SAR version are always included.
+/// This only lists the ids, the server has no reparse job, so reparsing them is up to the caller.
+///
+/// **Required Parameters**: min_sar_version
+///
+/// ## Example endpoints:
+///  - `/api/v1/admin/demos/pending_reparse?min_sar_version=1.12.7`
+#[get("/admin/demos/pending_reparse")]
+async fn get_pending_reparse(
+    pool: web::Data<PgPool>,
+    query: web::Query<PendingReparseParams>,
+) -> impl Responder {
+    match Demos::get_older_than_version(pool.get_ref(), &query.min_sar_version).await {
+        Ok(ids) => HttpResponse::Ok().json(ids),
         Err(e) => {
-            eprintln!("Error getting Admins -> {}", e);
-            HttpResponse::NotFound().body("Could not find admins.")
+            BoardsError::from(e.context("Error finding demos pending reparse")).error_response()
         }
-        _ => HttpResponse::NotFound().body("Could not find admins."),
+    }
+}
+
+/// **POST** method to remove demos past the configured retention period (`demo.retention`), and null out their changelog references.
+///
+/// Records, runs that needed a demo under the proof standards, and runs still in the top `keep_top_n` keep their demos.
+///
+/// **Required Parameters**: admin_profile_number
+///
+/// **Optional Parameters**:
+///    - **dry_run**
+///         - **DEFAULT** `true` - Reports what would be removed without deleting anything.
+///
+/// ## Example endpoints:
+///  - **Preview**
+///     - `/api/v1/admin/demos/retention?admin_profile_number=76561198040982247`
+///  - **Delete expired demos**
+///     - `/api/v1/admin/demos/retention?dry_run=false&admin_profile_number=76561198040982247`
+#[post("/admin/demos/retention")]
+async fn post_demo_retention(
+    pool: web::Data<PgPool>,
+    b2: web::Data<BackBlazeClient>,
+    config: web::Data<Config>,
+    query: web::Query<DryRun>,
+) -> Result<HttpResponse, BoardsError> {
+    let query = query.into_inner();
+    let dry_run = query.dry_run.unwrap_or(true);
+    let report = enforce_retention(
+        pool.get_ref(),
+        b2.get_ref(),
+        config.get_ref(),
+        dry_run,
+        &query.admin_profile_number,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// **POST** method to fill in `pre_rank`/`post_rank` on changelog entries that are missing them.
+///
+/// Ranks are recomputed as of when each run was set. Returns the # of entries updated, run it again until it returns `0`.
+///
+/// **Required Parameters**: admin_profile_number
+///
+/// **Optional Parameters**:
+///    - **limit**
+///         - **DEFAULT** `1000` - The max # of entries updated in this run, at most `10000`.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/changelog/backfill_ranks?admin_profile_number=76561198040982247`
+///  - **Larger batch**
+///     - `/api/v1/admin/changelog/backfill_ranks?limit=5000&admin_profile_number=76561198040982247`
+#[post("/admin/changelog/backfill_ranks")]
+async fn post_backfill_ranks(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+    query: web::Query<BackfillParams>,
+) -> Result<HttpResponse, BoardsError> {
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(1000).clamp(1, 10000);
+    let updated =
+        Changelog::backfill_ranks(pool.get_ref(), limit, &query.admin_profile_number).await?;
+    if updated > 0 {
+        cache.invalidate_previews().await;
+    }
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// **POST** method to rewrite `score_delta` on changelog entries that don't store it as `score - previous score`.
+///
+/// Improvements are stored as negative deltas. Returns the # of entries updated, run it again until it returns `0`.
+///
+/// **Required Parameters**: admin_profile_number
+///
+/// **Optional Parameters**:
+///    - **limit**
+///         - **DEFAULT** `1000` - The max # of entries updated in this run, at most `10000`.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/changelog/backfill_score_deltas?admin_profile_number=76561198040982247`
+///  - **Larger batch**
+///     - `/api/v1/admin/changelog/backfill_score_deltas?limit=5000&admin_profile_number=76561198040982247`
+#[post("/admin/changelog/backfill_score_deltas")]
+async fn post_backfill_score_deltas(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+    query: web::Query<BackfillParams>,
+) -> Result<HttpResponse, BoardsError> {
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(1000).clamp(1, 10000);
+    let updated =
+        Changelog::backfill_score_deltas(pool.get_ref(), limit, &query.admin_profile_number)
+            .await?;
+    if updated > 0 {
+        cache.invalidate_previews().await;
+    }
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// **PUT** method to ban or unban several changelog entries at once.
+///
+/// Expects a JSON body with the changelog ids, whether they should be banned, and the profile_number of the admin making the change.
+///
+/// Returns the map/category pairs affected by the change.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/changelog/ban`
+///     - `{"ids": [157804, 157805], "banned": true, "admin_profile_number": "76561198040982247"}`
+#[put("/admin/changelog/ban")]
+async fn put_banned_bulk(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+    params: web::Json<BulkBanParams>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let res = Changelog::set_banned_bulk(
+        pool.get_ref(),
+        &params.ids,
+        params.banned,
+        params.admin_profile_number,
+    )
+    .await;
+    match res {
+        Ok(affected) => {
+            // The previews include the top 7 for every map, ranks need to be recomputed for affected maps.
+            if !affected.is_empty() {
+                cache.invalidate_previews().await;
+            }
+            HttpResponse::Ok().json(affected)
+        }
+        Err(e) => {
+            eprintln!("Error banning changelog entries -> {}", e);
+            HttpResponse::BadRequest().body("Error updating banned status for changelog entries.")
+        }
+    }
+}
+
+/// **PUT** method to update a single field on a changelog entry, without touching any of its other columns.
+///
+/// Expects a JSON body with the field and its new value, and the profile_number of the admin making the change.
+/// Youtube links are normalized to the video id. Returns whether the entry changed.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/changelog/157804/field`
+///     - `{"field": "note", "value": "Segmented?", "admin_profile_number": "76561198040982247"}`
+///     - `{"field": "banned", "value": true, "admin_profile_number": "76561198040982247"}`
+#[put("/admin/changelog/{cl_id}/field")]
+async fn put_changelog_field(
+    cl_id: web::Path<i64>,
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+    params: web::Json<ChangelogFieldParams>,
+) -> impl Responder {
+    let cl_id = cl_id.into_inner();
+    let params = params.into_inner();
+    let admin = &params.admin_profile_number;
+    let res = match params.field {
+        ChangelogField::Note(note) => Changelog::set_note(pool.get_ref(), cl_id, note, admin).await,
+        ChangelogField::YoutubeId(Some(link)) => match normalize_youtube_id(&link) {
+            Some(youtube_id) => {
+                Changelog::set_youtube_id(pool.get_ref(), cl_id, Some(youtube_id), admin).await
+            }
+            None => return HttpResponse::BadRequest().body("Not a valid youtube link."),
+        },
+        ChangelogField::YoutubeId(None) => {
+            Changelog::set_youtube_id(pool.get_ref(), cl_id, None, admin).await
+        }
+        ChangelogField::Banned(banned) => {
+            let res = Changelog::set_banned(pool.get_ref(), cl_id, banned, admin).await;
+            if let Ok(true) = res {
+                cache.invalidate_previews().await;
+            }
+            res
+        }
+    };
+    match res {
+        Ok(changed) => HttpResponse::Ok().json(changed),
+        Err(e) => {
+            eprintln!("Error updating changelog field -> {}", e);
+            HttpResponse::BadRequest().body("Error updating the changelog entry.")
+        }
+    }
+}
+
+/// **PUT** method to verify all pending (unverified, non-banned) scores on a map at once.
+///
+/// Expects a JSON body with the profile_number of the admin making the change, and optionally a category.
+/// Uses the map's default category if `cat_id` is not provided.
+///
+/// Returns the ids of all verified changelog entries.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/changelog/verify/47763`
+///     - `{"cat_id": 19, "admin_profile_number": "76561198040982247"}`
+#[put("/admin/changelog/verify/{map_id}")]
+async fn put_verify_pending(
+    map_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+    params: web::Json<VerifyPendingParams>,
+) -> impl Responder {
+    let map_id = map_id.into_inner();
+    let params = params.into_inner();
+    let cat_id = match params.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    let res = Changelog::verify_pending_for_map(
+        pool.get_ref(),
+        map_id,
+        cat_id,
+        params.admin_profile_number,
+    )
+    .await;
+    match res {
+        Ok(ids) => {
+            // Ranks only need to be recomputed once, after every entry has been verified.
+            if !ids.is_empty() {
+                cache.invalidate_previews().await;
+            }
+            HttpResponse::Ok().json(ids)
+        }
+        Err(e) => {
+            eprintln!("Error verifying pending scores -> {}", e);
+            HttpResponse::BadRequest().body("Error verifying pending scores.")
+        }
+    }
+}
+
+/// **PUT** method to archive the intermediate non-PB runs in a player's history on a map.
+///
+/// Expects a JSON body with the player's profile_number, the profile_number of the admin making the change,
+/// and optionally a category and `keep_pbs_only` (defaults to `true`).
+/// Uses the map's default category if `cat_id` is not provided.
+///
+/// PBs, the first and last entries, and any run that ever held the WR are always kept.
+/// Returns the number of entries archived.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/changelog/compact/47763`
+///     - `{"profile_number": "76561198040982247", "keep_pbs_only": false, "admin_profile_number": "76561198040982247"}`
+#[put("/admin/changelog/compact/{map_id}")]
+async fn put_compact_history(
+    map_id: web::Path<String>,
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+    params: web::Json<CompactHistoryParams>,
+) -> impl Responder {
+    let map_id = map_id.into_inner();
+    let params = params.into_inner();
+    let cat_id = match params.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    let res = Changelog::compact_history(
+        pool.get_ref(),
+        params.profile_number,
+        map_id,
+        cat_id,
+        params.keep_pbs_only.unwrap_or(true),
+        params.admin_profile_number,
+    )
+    .await;
+    match res {
+        Ok(archived) => HttpResponse::Ok().json(archived),
+        Err(e) => {
+            eprintln!("Error compacting score history -> {}", e);
+            HttpResponse::BadRequest().body("Error compacting score history.")
+        }
+    }
+}
+
+/// **GET** method for the admin audit log, most recent actions first.
+///
+/// **Optional Parameters**:
+///    - **limit**
+///         - **DEFAULT** `100` - The # of max returned results.
+///    - **offset**
+///         - **DEFAULT** `0` - The # of entries to skip.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/audit`
+///  - **With parameters**
+///     - `/api/v1/admin/audit?limit=50&offset=100`
+#[get("/admin/audit")]
+async fn get_audit_log(
+    pool: web::Data<PgPool>,
+    query: web::Query<AuditLogParams>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let res = Admin::get_audit_log(
+        pool.get_ref(),
+        query.limit.unwrap_or(100),
+        query.offset.unwrap_or(0),
+    )
+    .await;
+    match res {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => BoardsError::from(e.context("Error getting audit log")).error_response(),
+    }
+}
+
+/// **GET** method that returns all changelog entries with a category that is not valid for their map.
+///
+/// Intended as a data-integrity audit for entries left behind after maps were recategorized.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/changelog/invalid_categories`
+#[get("/admin/changelog/invalid_categories")]
+async fn get_invalid_category_entries(pool: web::Data<PgPool>) -> impl Responder {
+    match Changelog::get_invalid_category_entries(pool.get_ref()).await {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => BoardsError::from(e.context("Error finding entries with invalid categories"))
+            .error_response(),
+    }
+}
+
+/// **GET** method for coop bundles with inconsistent halves, e.g. a player partnered with themselves or halves on different maps.
+///
+/// Each problem is reported separately, see [crate::models::models::CoopAnomaly] for the possible reasons.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/coop/invalid`
+#[get("/admin/coop/invalid")]
+async fn get_invalid_coop(pool: web::Data<PgPool>) -> impl Responder {
+    match Changelog::find_invalid_coop(pool.get_ref()).await {
+        Ok(anomalies) => HttpResponse::Ok().json(anomalies),
+        Err(e) => {
+            BoardsError::from(e.context("Error finding invalid coop entries")).error_response()
+        }
+    }
+}
+
+/// **GET** method to search changelog entries by their admin note, case-insensitive.
+///
+/// **Required Parameters**: pattern, matched literally anywhere in the note, and admin_profile_number.
+///
+/// **Optional Parameters**:
+///    - **limit**
+///         - **DEFAULT** `100` - The max # of entries returned, up to 500.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/changelog/notes?pattern=segmented?&admin_profile_number=76561198040982247`
+///  - **With parameters**
+///     - `/api/v1/admin/changelog/notes?pattern=segmented?&limit=20&admin_profile_number=76561198040982247`
+#[get("/admin/changelog/notes")]
+async fn get_admin_note_search(
+    pool: web::Data<PgPool>,
+    query: web::Query<AdminNoteSearchParams>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+    match Changelog::search_admin_notes(
+        pool.get_ref(),
+        query.pattern,
+        limit,
+        &query.admin_profile_number,
+    )
+    .await
+    {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => BoardsError::from(e.context("Error searching admin notes")).error_response(),
+    }
+}
+
+/// **GET** method that lists groups of users that might be alt accounts of the same person.
+///
+/// Users are grouped by similar board/steam names, or shared twitch/youtube/discord_id.
+///
+/// **Optional Parameters**:
+///    - **threshold**
+///         - **DEFAULT** `0.6` - The minimum name similarity (0-1) for two users to be linked.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/users/duplicates`
+///  - **With parameters**
+///     - `/api/v1/admin/users/duplicates?threshold=0.8`
+#[get("/admin/users/duplicates")]
+async fn get_possible_duplicates(
+    pool: web::Data<PgPool>,
+    query: web::Query<DuplicateParams>,
+) -> impl Responder {
+    let threshold = query.into_inner().threshold.unwrap_or(0.6);
+    match Users::find_possible_duplicates(pool.get_ref(), threshold).await {
+        Ok(candidates) => HttpResponse::Ok().json(candidates),
+        Err(e) => {
+            BoardsError::from(e.context("Error finding possible duplicate users")).error_response()
+        }
+    }
+}
+
+/// **GET** method for the review queue of runs that improved on the player's previous score by an implausibly large amount.
+///
+/// **Optional Parameters**:
+///    - **min_ratio**
+///         - **DEFAULT** `0.2` - The minimum improvement, as a fraction (0-1) of the previous score.
+///    - **limit**
+///         - **DEFAULT** `200` - The # of max returned results.
+///    - **before**
+///         - Will only return scores with an ID lower than the given amount
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/changelog/large_jumps`
+///  - **With parameters**
+///     - `/api/v1/admin/changelog/large_jumps?min_ratio=0.5&limit=50`
+#[get("/admin/changelog/large_jumps")]
+async fn get_large_jumps(
+    pool: web::Data<PgPool>,
+    query: web::Query<LargeJumpParams>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let min_ratio = query.min_ratio.unwrap_or(0.2).clamp(0.0, 1.0);
+    match Changelog::get_large_jumps(pool.get_ref(), min_ratio, query.limit, query.before).await {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => BoardsError::from(e.context("Error finding large score jumps")).error_response(),
+    }
+}
+
+/// **PUT** method to merge a duplicate account into another.
+///
+/// Moves all scores, demos and coop runs to the kept account, and fills in any socials/title it is missing.
+/// Where both accounts have a score on the same board, the worse account's runs are archived.
+/// The removed account is deactivated rather than deleted. Returns a summary of what was moved.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/users/merge`
+///     - `{"keep_profile_number": "76561198040982247", "remove_profile_number": "76561198040982248", "admin_profile_number": "76561198040982247"}`
+#[put("/admin/users/merge")]
+async fn put_merge_accounts(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+    params: web::Json<MergeAccountsParams>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let res = Users::merge_accounts(
+        pool.get_ref(),
+        params.keep_profile_number,
+        params.remove_profile_number,
+        params.admin_profile_number,
+    )
+    .await;
+    match res {
+        Ok(summary) => {
+            cache.invalidate_previews().await;
+            HttpResponse::Ok().json(summary)
+        }
+        Err(e) => {
+            eprintln!("Error merging accounts -> {}", e);
+            HttpResponse::BadRequest().body("Error merging accounts.")
+        }
+    }
+}
+
+/// **PUT** method to ban or unban a user.
+///
+/// `reason` is a short public reason shown on the wall of shame, `internal_note` is only kept in the admin audit log.
+/// With `cascade_scores`, all of the user's runs are banned too, unbanning this way only restores the runs a cascading ban changed.
+/// Returns the # of runs whose banned status changed.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/users/ban`
+///     - `{"profile_number": "76561198040982248", "banned": true, "reason": "Cheated runs", "internal_note": "Spliced demos on 47763, see #reports", "cascade_scores": true, "admin_profile_number": "76561198040982247"}`
+#[put("/admin/users/ban")]
+async fn put_user_ban(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+    params: web::Json<BanUserParams>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let res = Users::set_ban(
+        pool.get_ref(),
+        params.profile_number,
+        params.banned,
+        params.reason,
+        params.internal_note,
+        params.cascade_scores.unwrap_or(false),
+        params.admin_profile_number,
+    )
+    .await;
+    match res {
+        Ok(Some(scores_affected)) => {
+            // Also drops every cached leaderboard, so ranks on the affected maps are recomputed.
+            cache.invalidate_previews().await;
+            HttpResponse::Ok().json(scores_affected)
+        }
+        Ok(None) => HttpResponse::NotFound().body("User does not exist."),
+        Err(e) => {
+            eprintln!("Error setting user ban -> {}", e);
+            HttpResponse::BadRequest().body("Error setting user ban.")
+        }
+    }
+}
+
+/// **POST** method to record a donation, the user's cumulative `donation_amount` is recomputed from their donations.
+///
+/// Returns the user's new `donation_amount`.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/users/donations`
+///     - `{"profile_number": "76561198040982247", "amount": 5.0, "currency": "USD", "note": "Ko-fi", "admin_profile_number": "76561198040982247"}`
+#[post("/admin/users/donations")]
+async fn post_donation(
+    pool: web::Data<PgPool>,
+    params: web::Json<DonationParams>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let res = Users::add_donation(
+        pool.get_ref(),
+        params.profile_number,
+        params.amount,
+        params.currency.unwrap_or_else(|| "USD".to_string()),
+        params.note,
+        params.admin_profile_number,
+    )
+    .await;
+    match res {
+        Ok(Some(total)) => HttpResponse::Ok().json(total),
+        Ok(None) => HttpResponse::NotFound().body("User does not exist."),
+        Err(e) => {
+            eprintln!("Error adding donation -> {}", e);
+            HttpResponse::BadRequest().body("Error adding donation.")
+        }
+    }
+}
+
+/// **GET** method for every donation recorded for a user, most recent first.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/users/76561198040982247/donations`
+#[get("/admin/users/{profile_number}/donations")]
+async fn get_donation_history(
+    pool: web::Data<PgPool>,
+    profile_number: web::Path<String>,
+) -> impl Responder {
+    match Users::get_donation_history(pool.get_ref(), profile_number.into_inner()).await {
+        Ok(history) => HttpResponse::Ok().json(history),
+        Err(e) => BoardsError::from(e.context("Error fetching donation history")).error_response(),
+    }
+}
+
+/// **PUT** method to feature a map on the homepage until a given time.
+///
+/// Only one map is featured at a time, featuring a new map ends the current one early. Returns the id of the featured entry.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/maps/featured`
+///     - `{"map_id": "47763", "until": "2022-08-25T00:00:00", "admin_profile_number": "76561198040982247"}`
+#[put("/admin/maps/featured")]
+async fn put_featured_map(
+    pool: web::Data<PgPool>,
+    params: web::Json<FeaturedMapParams>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let res = Maps::set_featured(
+        pool.get_ref(),
+        params.map_id,
+        params.until,
+        params.admin_profile_number,
+    )
+    .await;
+    match res {
+        Ok(id) => HttpResponse::Ok().json(id),
+        Err(e) => {
+            eprintln!("Error setting featured map -> {}", e);
+            HttpResponse::BadRequest().body("Error setting featured map.")
+        }
+    }
+}
+
+/// **PUT** method to set or clear a map's difficulty tier.
+///
+/// Returns `true` on success, tiers outside of 1-10 are rejected.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/maps/tier`
+///     - `{"map_id": "47763", "tier": 3, "admin_profile_number": "76561198040982247"}`
+#[put("/admin/maps/tier")]
+async fn put_map_tier(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+    params: web::Json<MapTierParams>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let res = Maps::set_tier(
+        pool.get_ref(),
+        params.map_id,
+        params.tier,
+        params.admin_profile_number,
+    )
+    .await;
+    match res {
+        Ok(true) => {
+            cache.invalidate_previews().await;
+            HttpResponse::Ok().json(true)
+        }
+        Ok(false) => HttpResponse::NotFound().body("Map does not exist."),
+        Err(e) => {
+            eprintln!("Error setting map tier -> {}", e);
+            HttpResponse::BadRequest().body("Error setting map tier.")
+        }
+    }
+}
+
+/// **PUT** method to open or close submissions on a map, optionally only within a time window.
+///
+/// Returns `true` on success, windows that open after they close are rejected.
+/// Submissions to a closed map are rejected with a `SubmissionsClosed` error.
+///
+/// ## Example endpoint:
+///  - `/api/v1/admin/maps/submission_window`
+///     - `{"map_id": "47763", "submissions_open": true, "opens_at": "2026-11-01T18:00:00", "closes_at": "2026-11-02T18:00:00", "admin_profile_number": "76561198040982247"}`
+#[put("/admin/maps/submission_window")]
+async fn put_submission_window(
+    pool: web::Data<PgPool>,
+    params: web::Json<SubmissionWindowParams>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let res = Maps::set_submission_window(
+        pool.get_ref(),
+        params.map_id,
+        params.submissions_open,
+        params.opens_at,
+        params.closes_at,
+        params.admin_profile_number,
+    )
+    .await;
+    match res {
+        Ok(true) => HttpResponse::Ok().json(true),
+        Ok(false) => HttpResponse::NotFound().body("Map does not exist."),
+        Err(e) => {
+            eprintln!("Error setting submission window -> {}", e);
+            HttpResponse::BadRequest().body("Error setting submission window.")
+        }
+    }
+}
+
+/// **GET** method for the most recently uploaded demos, regardless of verification status.
+///
+/// The landing page for demo review, each demo includes the run, player and map it belongs to.
+///
+/// **Optional Parameters**:
+///    - **limit**
+///         - **DEFAULT** `50` - The # of max returned results, at most `200`.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/admin/demos/recent`
+///  - **With parameters**
+///     - `/api/v1/admin/demos/recent?limit=100`
+#[get("/admin/demos/recent")]
+async fn get_recent_demos(
+    pool: web::Data<PgPool>,
+    query: web::Query<RecentDemosParams>,
+) -> impl Responder {
+    let limit = query.into_inner().limit.unwrap_or(50).clamp(1, 200);
+    match Demos::get_recent(pool.get_ref(), limit).await {
+        Ok(demos) => HttpResponse::Ok().json(demos),
+        Err(e) => BoardsError::from(e.context("Error getting recent demos")).error_response(),
     }
 }
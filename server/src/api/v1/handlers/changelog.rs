@@ -1,11 +1,16 @@
 use crate::models::models::{
-    Changelog, ChangelogInsert, ChangelogPage, ChangelogQueryParams, SubmissionChangelog,
+    ActivityParams, CategoryFeedParams, Changelog, ChangelogPage, ChangelogQueryParams,
+    ChangelogSinceParams, DailySummaryParams, HourlyActivityParams, ScoreContextParams,
+    SubmissionChangelog, TopImproversParams, ValidationErrors, WrCountParams,
 };
 use crate::tools::cache::CacheState;
 use crate::tools::config::Config;
-use crate::tools::helpers::check_for_valid_score;
-use actix_web::{get, post, web, HttpResponse, Responder};
+use crate::tools::errors::BoardsError;
+use crate::tools::submissions::validate;
+use actix_web::{get, post, web, HttpResponse, Responder, ResponseError};
+use chrono::{Duration, Utc};
 use sqlx::PgPool;
+use std::collections::HashMap;
 
 /// **GET** method for changelog entiries. Utilizes `ChangelogQueryParrams` as an optional addition to the query
 ///
@@ -33,11 +38,17 @@ use sqlx::PgPool;
 ///         - Will only return scores with an ID higher than the given amount
 ///    - **last**            
 ///         - Will only return scores with an ID lower than the given amount
+///    - **proof_level**
+///         - One of `none`, `demo_only`, `video_only`, `both` or `any`, filters on the combination of demo & video
+///    - **pbs_only**
+///         - Boolean that, if true, will only return scores that were a PB for the player when they were submitted
 /// ## Example endpoints:
 ///  - **Default**           
 ///     - `/api/v1/changelog`
 ///  - **With parameters**   
 ///     - `/api/v1/changelog?limit=200&nick_name=Zypeh&chamber=47759&sp=true&coop=false&wr_gain=true&has_demo=true&yt=true`
+///  - **Proof type**
+///     - `/api/v1/changelog?proof_level=demo_only`
 ///  - **A refresh call**    
 ///     - `/api/v1/changelog?limit=200&first=157804`
 ///  - **A scroll call**     
@@ -47,17 +58,354 @@ use sqlx::PgPool;
 #[get("/changelog")]
 async fn get_changelog(
     pool: web::Data<PgPool>,
-    query_params: web::Query<ChangelogQueryParams>,
+    query_params: web::Query<HashMap<String, String>>,
 ) -> impl Responder {
-    let res = ChangelogPage::get_changelog_page(pool.get_ref(), query_params.into_inner()).await;
+    let params = match ChangelogQueryParams::from_query_map(&query_params) {
+        Ok(params) => params,
+        Err(e) => return invalid_params_response(e),
+    };
+    let res = ChangelogPage::get_changelog_page(pool.get_ref(), params).await;
     match res {
         Ok(changelog_entries) => HttpResponse::Ok().json(changelog_entries),
         _ => HttpResponse::NotFound().body("No changelog entries found."),
     }
 }
 
+/// **GET** method for the total number of changelog entries matching a set of filters.
+///
+/// Accepts the same parameters as [get_changelog], `first`, `last` and `limit` are ignored so the total is stable while paging.
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/count`
+///  - **With parameters**
+///     - `/api/v1/changelog/count?nick_name=Zypeh&sp=true&coop=false&wr_gain=true`
+///
+/// Makes a call to the underlying [ChangelogPage::count_filtered_changelog]
+#[get("/changelog/count")]
+async fn get_changelog_count(
+    pool: web::Data<PgPool>,
+    query_params: web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let params = match ChangelogQueryParams::from_query_map(&query_params) {
+        Ok(params) => params,
+        Err(e) => return invalid_params_response(e),
+    };
+    let res = ChangelogPage::count_filtered_changelog(pool.get_ref(), params).await;
+    match res {
+        Ok(count) => HttpResponse::Ok().json(count),
+        Err(e) => BoardsError::from(e.context("Error counting changelog entries")).error_response(),
+    }
+}
+
+/// **GET** method for the most recent changelog entries in a specific category, across all maps.
+///
+/// **Optional Parameters**: [crate::controllers::models::CategoryFeedParams]
+/// ## Parameters:
+///    - **limit**
+///         - The # of max returned results, defaults to 200.
+///    - **before**
+///         - Will only return scores with an ID lower than the given amount
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/category/19`
+///  - **A scroll call**
+///     - `/api/v1/changelog/category/19?limit=50&before=157604`
+///
+/// Makes a call to the underlying [Changelog::get_category_feed]
+#[get("/changelog/category/{cat_id}")]
+async fn get_category_feed(
+    params: web::Path<i32>,
+    pool: web::Data<PgPool>,
+    query: web::Query<CategoryFeedParams>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let res = Changelog::get_category_feed(
+        pool.get_ref(),
+        params.into_inner(),
+        query.limit,
+        query.before,
+    )
+    .await;
+    match res {
+        Ok(changelog_entries) => HttpResponse::Ok().json(changelog_entries),
+        Err(e) => BoardsError::from(e.context("Error getting category feed")).error_response(),
+    }
+}
+
+/// **GET** method for incremental sync, the changelog entries after a given id, oldest first.
+///
+/// Call again with the id of the last entry received until an empty list is returned.
+///
+/// **Optional Parameters**: [crate::controllers::models::ChangelogSinceParams]
+/// ## Parameters:
+///    - **limit**
+///         - The # of max returned results, defaults to 200, max 1000.
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/since/157804`
+///  - **With parameters**
+///     - `/api/v1/changelog/since/157804?limit=1000`
+///
+/// Makes a call to the underlying [Changelog::get_since_id]
+#[get("/changelog/since/{last_id}")]
+async fn get_changelog_since(
+    params: web::Path<i64>,
+    pool: web::Data<PgPool>,
+    query: web::Query<ChangelogSinceParams>,
+) -> impl Responder {
+    let limit = query.into_inner().limit.unwrap_or(200).clamp(1, 1000);
+    match Changelog::get_since_id(pool.get_ref(), params.into_inner(), limit).await {
+        Ok(changelog_entries) => HttpResponse::Ok().json(changelog_entries),
+        Err(e) => BoardsError::from(e.context("Error getting changelog entries since id"))
+            .error_response(),
+    }
+}
+
+/// **GET** method for the "most improved" leaderboard, players ranked by total time saved on new scores.
+///
+/// **Optional Parameters**: [crate::controllers::models::TopImproversParams]
+/// ## Parameters:
+///    - **since**
+///         - Only scores set after this time are counted, defaults to the last 30 days.
+///    - **limit**
+///         - The # of max returned results, defaults to 50.
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/top_improvers`
+///  - **With parameters**
+///     - `/api/v1/changelog/top_improvers?since=2022-02-01T00:00:00&limit=10`
+///
+/// Makes a call to the underlying [Changelog::get_top_improvers]
+#[get("/changelog/top_improvers")]
+async fn get_top_improvers(
+    pool: web::Data<PgPool>,
+    query: web::Query<TopImproversParams>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let since = query
+        .since
+        .unwrap_or_else(|| Utc::now().naive_utc() - Duration::days(30));
+    let res = Changelog::get_top_improvers(pool.get_ref(), since, query.limit.unwrap_or(50)).await;
+    match res {
+        Ok(improvers) => HttpResponse::Ok().json(improvers),
+        Err(e) => BoardsError::from(e.context("Error getting top improvers")).error_response(),
+    }
+}
+
+/// **GET** method for the # of current WRs held by every player, as a `{profile_number: count}` object.
+///
+/// Players without any WRs are omitted. Tied WRs count for every player in the tie.
+///
+/// **Optional Parameters**: [crate::controllers::models::WrCountParams]
+/// ## Parameters:
+///    - **scope**
+///         - `default` (only each map's default category) or `all`, defaults to `default`.
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/wr_counts`
+///  - **With parameters**
+///     - `/api/v1/changelog/wr_counts?scope=all`
+///
+/// Makes a call to the underlying [Changelog::get_wr_counts_for_all]
+#[get("/changelog/wr_counts")]
+async fn get_wr_counts(
+    pool: web::Data<PgPool>,
+    query: web::Query<WrCountParams>,
+) -> impl Responder {
+    let scope = query.into_inner().scope.unwrap_or_default();
+    match Changelog::get_wr_counts_for_all(pool.get_ref(), scope).await {
+        Ok(counts) => HttpResponse::Ok().json(counts),
+        Err(e) => BoardsError::from(e.context("Error getting WR counts")).error_response(),
+    }
+}
+
+/// **GET** method for a map's daily submission counts, used for activity sparklines.
+///
+/// **Optional Parameters**: [crate::controllers::models::ActivityParams]
+/// ## Parameters:
+///    - **days**
+///         - The # of days to return (including today), defaults to 30, max 365.
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/activity/47759`
+///  - **With parameters**
+///     - `/api/v1/changelog/activity/47759?days=7`
+///
+/// Makes a call to the underlying [Changelog::get_daily_counts]
+#[get("/changelog/activity/{map_id}")]
+async fn get_map_activity(
+    params: web::Path<String>,
+    pool: web::Data<PgPool>,
+    query: web::Query<ActivityParams>,
+) -> impl Responder {
+    let days = query.into_inner().days.unwrap_or(30).clamp(1, 365);
+    let res = Changelog::get_daily_counts(pool.get_ref(), params.into_inner(), days).await;
+    match res {
+        Ok(counts) => HttpResponse::Ok().json(counts),
+        Err(e) => BoardsError::from(e.context("Error getting map activity")).error_response(),
+    }
+}
+
+/// **GET** method for the # of submissions during each hour of the day (UTC), for an activity heatmap.
+///
+/// **Optional Parameters**: [crate::controllers::models::HourlyActivityParams]
+/// ## Parameters:
+///    - **profile_number**
+///         - Only counts submissions from a specific player.
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/hourly_activity`
+///  - **With parameters**
+///     - `/api/v1/changelog/hourly_activity?profile_number=76561198040982247`
+///
+/// Makes a call to the underlying [Changelog::get_hourly_distribution]
+#[get("/changelog/hourly_activity")]
+async fn get_hourly_activity(
+    pool: web::Data<PgPool>,
+    query: web::Query<HourlyActivityParams>,
+) -> impl Responder {
+    let res =
+        Changelog::get_hourly_distribution(pool.get_ref(), query.into_inner().profile_number).await;
+    match res {
+        Ok(counts) => HttpResponse::Ok().json(counts),
+        Err(e) => BoardsError::from(e.context("Error getting hourly activity")).error_response(),
+    }
+}
+
+/// **GET** method for a summary of a single day on the boards, new submissions, new WRs, active players and the top improvement.
+///
+/// **Optional Parameters**: [crate::controllers::models::DailySummaryParams]
+/// ## Parameters:
+///    - **date**
+///         - The UTC day to summarize as `%Y-%m-%d`, defaults to today.
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/daily_summary`
+///  - **With parameters**
+///     - `/api/v1/changelog/daily_summary?date=2022-02-20`
+///
+/// Makes a call to the underlying [Changelog::get_daily_summary]
+#[get("/changelog/daily_summary")]
+async fn get_daily_summary(
+    pool: web::Data<PgPool>,
+    query: web::Query<DailySummaryParams>,
+) -> impl Responder {
+    let date = query
+        .into_inner()
+        .date
+        .unwrap_or_else(|| Utc::now().naive_utc().date());
+    match Changelog::get_daily_summary(pool.get_ref(), date).await {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(e) => BoardsError::from(e.context("Error getting daily summary")).error_response(),
+    }
+}
+
+/// **GET** method for the "run of the day", a random verified run weighted towards records and runs with a demo or video.
+///
+/// The same run is returned for the whole (UTC) day.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/random_notable`
+///
+/// Makes a call to the underlying [Changelog::get_random_notable]
+#[get("/changelog/random_notable")]
+async fn get_random_notable(pool: web::Data<PgPool>) -> impl Responder {
+    let today = Utc::now().naive_utc().date();
+    match Changelog::get_random_notable(pool.get_ref(), today).await {
+        Ok(run) => HttpResponse::Ok().json(run),
+        Err(e) => BoardsError::from(e.context("Error picking a notable run")).error_response(),
+    }
+}
+
+/// **GET** method for the edit history of a changelog entry, each snapshot is the entry as it was before an edit.
+///
+/// Most recent edit first, an entry that was never edited has an empty history.
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/127825/history`
+///
+/// Makes a call to the underlying [Changelog::get_edit_history]
+#[get("/changelog/{cl_id}/history")]
+async fn get_edit_history(params: web::Path<i64>, pool: web::Data<PgPool>) -> impl Responder {
+    match Changelog::get_edit_history(pool.get_ref(), params.into_inner()).await {
+        Ok(history) => HttpResponse::Ok().json(history),
+        Err(e) => BoardsError::from(e.context("Error getting edit history")).error_response(),
+    }
+}
+
+/// **GET** method for a changelog entry in the context of its board, with the entries ranked around it.
+///
+/// If the entry is near the top or bottom of the board, extra entries from the other side fill out the window.
+///
+/// **Optional Parameters**: [crate::controllers::models::ScoreContextParams]
+/// ## Parameters:
+///    - **window**
+///         - The # of entries above and below the score, defaults to 5, max 50.
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/127825/context`
+///  - **With parameters**
+///     - `/api/v1/changelog/127825/context?window=2`
+///
+/// Makes a call to the underlying [Changelog::get_score_with_neighbors]
+#[get("/changelog/{cl_id}/context")]
+async fn get_score_context(
+    params: web::Path<i64>,
+    pool: web::Data<PgPool>,
+    query: web::Query<ScoreContextParams>,
+) -> impl Responder {
+    let window = query.into_inner().window.unwrap_or(5).clamp(0, 50);
+    match Changelog::get_score_with_neighbors(pool.get_ref(), params.into_inner(), window).await {
+        Ok(entries) if !entries.is_empty() => HttpResponse::Ok().json(entries),
+        Ok(_) => HttpResponse::NotFound().body("No changelog entry found with the given id."),
+        Err(e) => BoardsError::from(e.context("Error getting score context")).error_response(),
+    }
+}
+
+/// **GET** method to export changelog entries as an [Arrow IPC](https://arrow.apache.org/docs/format/Columnar.html#ipc-file-format) file.
+///
+/// Intended for bulk analytics (pandas/polars), accepts the same filters as `/changelog`.
+/// `limit` isn't tied to the page size, it defaults to [crate::controllers::changelog::EXPORT_LIMIT] entries.
+///
+/// **Optional Parameters**: [crate::controllers::models::ChangelogQueryParams]
+///
+/// ## Example endpoints:
+///  - **Default**
+///     - `/api/v1/changelog/export`
+///  - **With parameters**
+///     - `/api/v1/changelog/export?limit=100000&chamber=47759&wr_gain=true`
+///
+/// Makes a call to the underlying [Changelog::export_arrow]
+#[get("/changelog/export")]
+async fn get_changelog_export(
+    pool: web::Data<PgPool>,
+    query_params: web::Query<HashMap<String, String>>,
+) -> impl Responder {
+    let params = match ChangelogQueryParams::from_query_map(&query_params) {
+        Ok(params) => params,
+        Err(e) => return invalid_params_response(e),
+    };
+    match Changelog::export_arrow(pool.get_ref(), params).await {
+        Ok(bytes) => HttpResponse::Ok()
+            .content_type("application/vnd.apache.arrow.file")
+            .insert_header((
+                "Content-Disposition",
+                "attachment; filename=\"changelog.arrow\"",
+            ))
+            .body(bytes),
+        Err(e) => {
+            eprintln!("Error exporting changelog -> {}", e);
+            HttpResponse::InternalServerError().body("Could not export changelog entries.")
+        }
+    }
+}
+
 /// **POST** endpoint for submitting a new changelog entry.
 /// Accepts field values for a new changelog insert
+///
+/// Not routed yet, it needs authentication before it can be exposed.
 /// ## Expects the following fields:
 ///
 /// **Required Parameters**: timestamp, profile_number, score, map_id
@@ -67,7 +415,7 @@ async fn get_changelog(
 /// ## Parameters (expects valid JSON Object):
 ///
 /// - **timestamp**    
-///     - `String`: `%Y-%m-%d %H:%M:%S` (use `%20` to denote a space)
+///     - `String`: `%Y-%m-%d %H:%M:%S` (use `%20` to denote a space), RFC3339 or epoch seconds. Timezones are converted to UTC.
 /// - **profile_number**
 ///     - `String`: Steam ID Number
 /// - **score**         
@@ -80,6 +428,8 @@ async fn get_changelog(
 ///     - `String`: Note for the run
 /// - **category_id**   
 ///     - `i32`: ID for the category being played  
+/// - **idempotency_key**
+///     - `String`: Optional client-generated key, resubmitting with the same key within 24 hours returns the original id instead of inserting again.
 ///
 /// ## Example endpoints:       
 /// - `/api/v1/changelog`
@@ -94,52 +444,65 @@ async fn get_changelog(
 ///     "youtube_id" : null,
 ///     "note" : null,
 ///     "category_id" : 19,
+///     "idempotency_key" : "0b7f3c9e-6a1d-4e57-9a55-2f0c6b1d8e42",
 /// }
 /// ```
 ///
 #[post("/changelog")]
 async fn post_changelog(
     pool: web::Data<PgPool>,
-    cache: web::Data<CacheState>,
     config: web::Data<Config>,
+    cache: web::Data<CacheState>,
     params: web::Json<SubmissionChangelog>,
 ) -> impl Responder {
-    let mut cl_insert = ChangelogInsert::new_from_submission(
-        params.into_inner(),
-        cache.into_inner().default_cat_ids.clone(),
-    )
-    .await;
-    match check_for_valid_score(
-        pool.get_ref(),
-        cl_insert.profile_number.clone(),
-        cl_insert.score,
-        cl_insert.map_id.clone(),
-        config.proof.results,
-        cl_insert.category_id,
-    )
-    .await
-    {
-        Ok(details) => {
-            if !details.banned {
-                cl_insert.previous_id = details.previous_id;
-                cl_insert.post_rank = details.post_rank;
-                cl_insert.pre_rank = details.pre_rank;
-                cl_insert.score_delta = details.score_delta;
-            } else {
-                eprintln!("USER IS BANNED, DO NOT ADD A TIME FOR THEM");
-                return HttpResponse::NotFound().body("User is banned");
+    let params = params.into_inner();
+    // A retry of a submission we already accepted gets the original id back, rather than failing validation or inserting again.
+    if let Some(key) = &params.idempotency_key {
+        match Changelog::get_idempotent_submission(pool.get_ref(), &params.profile_number, key)
+            .await
+        {
+            Ok(Some(id)) => return HttpResponse::Ok().json(id),
+            Ok(None) => (),
+            Err(e) => {
+                eprintln!("Error checking idempotency key -> {}", e);
+                return HttpResponse::InternalServerError().body("Error checking idempotency key.");
             }
         }
+    }
+    let cl_insert = match validate(pool.get_ref(), &params, &config, &cache).await {
+        Ok(validated) => validated.insert,
         Err(e) => {
-            eprintln!("Error finding newscore details -> {:#?}", e);
-            return HttpResponse::NotFound().body("User not found, or better time exists.");
+            eprintln!("Submission failed validation -> {}", e);
+            return match e.downcast_ref::<ValidationErrors>() {
+                Some(errors) => HttpResponse::BadRequest().json(errors),
+                None => HttpResponse::InternalServerError().body("Error validating submission."),
+            };
         }
     };
-    match Changelog::insert_changelog(pool.get_ref(), cl_insert).await {
-        Ok(id) => HttpResponse::Ok().json(id),
+    let profile_number = cl_insert.profile_number.clone();
+    let map_id = cl_insert.map_id.clone();
+    let res = match &params.idempotency_key {
+        Some(key) => Changelog::insert_changelog_with_key(pool.get_ref(), cl_insert, key).await,
+        None => Changelog::insert_changelog(pool.get_ref(), cl_insert).await,
+    };
+    match res {
+        Ok(id) => {
+            cache.invalidate_profile(&profile_number).await;
+            cache.invalidate_leaderboard(&map_id).await;
+            HttpResponse::Ok().json(id)
+        }
         Err(e) => {
             eprintln!("Error with adding changelog entry to database -> {}", e);
             HttpResponse::InternalServerError().body("Could not add user to databse")
         }
     }
 }
+
+/// The `400` response for changelog filters that failed [ChangelogQueryParams::from_query_map], listing every invalid parameter.
+pub(crate) fn invalid_params_response(e: anyhow::Error) -> HttpResponse {
+    eprintln!("Invalid changelog parameters -> {}", e);
+    match e.downcast_ref::<ValidationErrors>() {
+        Some(errors) => HttpResponse::BadRequest().json(errors),
+        None => HttpResponse::BadRequest().body("Invalid changelog parameters."),
+    }
+}
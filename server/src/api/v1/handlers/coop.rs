@@ -1,6 +1,6 @@
 use crate::models::models::{
-    Changelog, CoopBanned, CoopBundled, CoopBundledInsert, CoopMap, CoopPreviews, Opti32,
-    ScoreParams,
+    Changelog, CoopBanned, CoopBundled, CoopBundledInsert, CoopMap, CoopPreviews, Maps, Opti32,
+    ScoreParams, ValidationError, ValidationErrors,
 };
 use crate::tools::cache::{read_from_file, write_to_file, CacheState};
 use crate::tools::{config::Config, helpers::filter_coop_entries};
@@ -63,15 +63,16 @@ async fn get_cooperative_maps(
     cache: web::Data<CacheState>,
     pool: web::Data<PgPool>,
 ) -> impl Responder {
-    let res = CoopMap::get_coop_map_page(
-        pool.get_ref(),
-        map_id.clone(),
-        config.proof.results,
-        cat_id
-            .cat_id
-            .unwrap_or(cache.into_inner().default_cat_ids[&map_id.into_inner()]),
-    )
-    .await;
+    let cat_id = match cat_id.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    let res =
+        CoopMap::get_coop_map_page(pool.get_ref(), map_id.clone(), config.proof.results, cat_id)
+            .await;
     match res {
         Ok(coop_entries) => {
             let coop_entries_filtered =
@@ -99,14 +100,14 @@ async fn get_banned_scores_coop(
     cache: web::Data<CacheState>,
     params: web::Query<Opti32>,
 ) -> impl Responder {
-    let res = CoopBanned::get_coop_banned(
-        pool.get_ref(),
-        map_id.clone(),
-        params
-            .cat_id
-            .unwrap_or(cache.into_inner().default_cat_ids[&map_id.into_inner()]),
-    )
-    .await;
+    let cat_id = match params.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    let res = CoopBanned::get_coop_banned(pool.get_ref(), map_id.clone(), cat_id).await;
     match res {
         Ok(banned_entries) => HttpResponse::Ok().json(banned_entries),
         _ => HttpResponse::NotFound().body("Error fetching Coop banned information"),
@@ -138,14 +139,19 @@ async fn post_banned_scores_coop(
     cache: web::Data<CacheState>,
     pool: web::Data<PgPool>,
 ) -> impl Responder {
+    let cat_id = match params.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
     let res = Changelog::check_banned_scores(
         pool.get_ref(),
         map_id.clone(),
         params.score,
         params.profile_number.clone(),
-        params
-            .cat_id
-            .unwrap_or(cache.into_inner().default_cat_ids[&map_id.into_inner()]),
+        cat_id,
     )
     .await;
     match res {
@@ -154,6 +160,9 @@ async fn post_banned_scores_coop(
     }
 }
 
+/// Receives a new coop bundle for two (or one) changelog entries already added to the DB.
+///
+/// Bundles on maps that aren't accepting submissions are rejected with a `SubmissionsClosed` error, see [Maps::is_submission_open].
 #[allow(unused_variables)]
 #[post("/coop/post_score")]
 async fn post_score_coop(
@@ -161,9 +170,34 @@ async fn post_score_coop(
     pool: web::Data<PgPool>,
     cache: web::Data<CacheState>,
 ) -> impl Responder {
+    let map_id = match Changelog::get_changelog(pool.get_ref(), params.cl_id1).await {
+        Ok(Some(cl)) => cl.map_id,
+        Ok(None) => return HttpResponse::NotFound().body("No changelog entry found for cl_id1."),
+        Err(e) => {
+            eprintln!("Error finding the bundled changelog entry -> {}", e);
+            return HttpResponse::InternalServerError().body("Error adding new score to database.");
+        }
+    };
+    match Maps::is_submission_open(pool.get_ref(), map_id.clone()).await {
+        Ok(true) => (),
+        Ok(false) => {
+            return HttpResponse::BadRequest().json(ValidationErrors(vec![
+                ValidationError::SubmissionsClosed(map_id),
+            ]))
+        }
+        Err(e) => {
+            eprintln!("Error checking the submission window -> {}", e);
+            return HttpResponse::InternalServerError()
+                .body("Error checking the submission window.");
+        }
+    }
+    let profile_numbers = [Some(params.p_id1.clone()), params.p_id2.clone()];
     let res = CoopBundled::insert_coop_bundled(pool.get_ref(), params.0).await;
     match res {
         Ok(id) => {
+            for profile_number in profile_numbers.iter().flatten() {
+                cache.invalidate_profile(profile_number).await;
+            }
             // Invalidate cache if this new score impacts the top 7 preview times.
             let state_data = &mut cache.current_state.lock().await;
             let is_cached = state_data.get_mut("coop_previews").unwrap();
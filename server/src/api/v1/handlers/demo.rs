@@ -1,20 +1,42 @@
 use crate::models::models::{
-    Changelog, ChangelogInsert, DemoInsert, DemoOptions, Demos, Maps, SubmissionChangelog,
+    Changelog, ChangelogInsert, DemoInsert, DemoOptions, Demos, Maps, ReplaceDemoParams,
+    ReplacedDemo, SubmissionChangelog, ValidationErrors,
 };
+use crate::tools::backblaze::{BackBlazeClient, StorageUnavailable};
 use crate::tools::cache::CacheState;
 use crate::tools::config::Config;
-use crate::tools::helpers::check_for_valid_score;
+use crate::tools::errors::BoardsError;
+use crate::tools::submissions::validate;
 use actix_multipart::Multipart;
-use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use actix_web::{delete, get, post, put, web, HttpResponse, Responder, ResponseError};
 use anyhow::{bail, Result};
 use futures::{StreamExt, TryStreamExt};
-use raze::api::*;
-use raze::utils::*;
 use sqlx::PgPool;
+use std::fmt;
 use std::fs::remove_file;
-use std::fs::OpenOptions;
-use std::io::Write;
 use std::str;
+use tokio::io::AsyncWriteExt;
+
+/// Every Source engine demo starts with this header, anything else is rejected before reaching storage.
+const DEMO_MAGIC: &[u8] = b"HL2DEMO\0";
+
+/// Why an uploaded file was rejected, check for this with `downcast_ref::<RejectedUpload>()`.
+#[derive(Debug)]
+enum RejectedUpload {
+    TooLarge(u64),
+    NotADemo,
+}
+
+impl fmt::Display for RejectedUpload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectedUpload::TooLarge(max) => write!(f, "Demo is larger than {} bytes", max),
+            RejectedUpload::NotADemo => write!(f, "File is not a Source engine demo"),
+        }
+    }
+}
+
+impl std::error::Error for RejectedUpload {}
 
 /// GET endpoint to return demo information.
 /// ## Expects **one** of following fields:
@@ -39,19 +61,13 @@ pub async fn get_demo(pool: web::Data<PgPool>, query: web::Query<DemoOptions>) -
     if query.demo_id.is_none() & !query.cl_id.is_none() {
         match Demos::get_demo(pool.get_ref(), query.demo_id.unwrap()).await {
             Ok(Some(demo)) => HttpResponse::Ok().json(demo),
-            Err(e) => {
-                eprintln!("{}", e);
-                HttpResponse::NotFound().body(res_str)
-            }
+            Err(e) => BoardsError::from(e).error_response(),
             _ => HttpResponse::NotFound().body(res_str),
         }
     } else if !query.demo_id.is_none() & query.cl_id.is_none() {
         match Changelog::get_demo_id_from_changelog(pool.get_ref(), query.cl_id.unwrap()).await {
             Ok(Some(demo)) => HttpResponse::Ok().json(demo),
-            Err(e) => {
-                eprintln!("{}", e);
-                HttpResponse::NotFound().body(res_str)
-            }
+            Err(e) => BoardsError::from(e).error_response(),
             _ => HttpResponse::NotFound().body(res_str),
         }
     } else {
@@ -60,6 +76,53 @@ pub async fn get_demo(pool: web::Data<PgPool>, query: web::Query<DemoOptions>) -
     }
 }
 
+/// GET endpoint to download a demo file, named so it can be played back in-game (`{map_name}_{player}_{score}.dem`).
+/// ## Expects **one** of following fields:
+///
+/// **Required Parameters**: cl_id, demo_id
+///
+/// ## Parameters:
+///
+/// - **cl_id**    
+///     - `i64`: ID for a changelog entry, will download the most updated demo assocaited with that changelog entry.
+/// - **demo_id**
+///     - `i64`: ID for a specific demo.
+///
+/// ## Example endpoints:       
+/// - `/api/v1/demos/download?cl_id=15625`
+/// - `/api/v1/demos/download?demo_id=12651`
+///
+#[get("/demos/download")]
+pub async fn download_demo(
+    pool: web::Data<PgPool>,
+    b2: web::Data<BackBlazeClient>,
+    query: web::Query<DemoOptions>,
+) -> Result<HttpResponse, BoardsError> {
+    let query = query.into_inner();
+    let demo_id = match (query.demo_id, query.cl_id) {
+        (Some(demo_id), None) => Some(demo_id),
+        (None, Some(cl_id)) => Changelog::get_demo_id_from_changelog(pool.get_ref(), cl_id).await?,
+        _ => {
+            return Ok(HttpResponse::BadRequest()
+                .body("Exactly one of `cl_id` or `demo_id` must be provided."))
+        }
+    };
+    let demo_id = match demo_id {
+        Some(demo_id) => demo_id,
+        None => return Ok(HttpResponse::NotFound().body("Could not find demo.")),
+    };
+    match Demos::download_demo(pool.get_ref(), b2.get_ref(), demo_id).await? {
+        Some((file_name, resp)) => Ok(HttpResponse::Ok()
+            .content_type("application/octet-stream")
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", file_name),
+            ))
+            .streaming(resp.bytes_stream())),
+        None => Ok(HttpResponse::NotFound().body("Could not find demo.")),
+    }
+}
+
 //  a. Handle renaming/db interactions (update demo table/specific time that is being uploaded)
 //  b. Pass to backblaze
 //  c. Look to see if there is anything special needed for auto-submit
@@ -76,7 +139,7 @@ pub async fn get_demo(pool: web::Data<PgPool>, query: web::Query<DemoOptions>) -
 /// ## Parameters:
 ///
 /// - **timestamp**    
-///     - `String`: `%Y-%m-%d %H:%M:%S` (use `%20` to denote a space)
+///     - `String`: `%Y-%m-%d %H:%M:%S` (use `%20` to denote a space), RFC3339 or epoch seconds. Timezones are converted to UTC.
 /// - **profile_number**
 ///     - `String`: Steam ID Number
 /// - **score**         
@@ -97,53 +160,62 @@ pub async fn get_demo(pool: web::Data<PgPool>, query: web::Query<DemoOptions>) -
 pub async fn changelog_with_demo(
     mut payload: Multipart,
     config: web::Data<Config>,
+    b2: web::Data<BackBlazeClient>,
     query: web::Query<SubmissionChangelog>,
-    cache: web::Data<CacheState>,
     pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
 ) -> impl Responder {
     // This function heavily utilizes helper functions to make error propagation easier, and reduce the # of match arms
+    // Reject early if we know the demo can't be stored, submissions without a demo go through `/changelog` instead.
+    if !b2.is_available().await {
+        return HttpResponse::ServiceUnavailable().body(
+            "Demo storage is currently unavailable, try again later or submit without a demo.",
+        );
+    }
     let mut file_name = String::default();
     let query = query.into_inner();
-    let mut changelog_insert =
-        ChangelogInsert::new_from_submission(query, cache.into_inner().default_cat_ids.clone())
-            .await;
-    let res = check_for_valid_score(
-        pool.get_ref(),
-        changelog_insert.profile_number.clone(),
-        changelog_insert.score,
-        changelog_insert.map_id.clone(),
-        config.proof.results,
-        changelog_insert.category_id,
-    )
-    .await;
-
-    match res {
-        Ok(details) => {
-            if !details.banned {
-                changelog_insert.previous_id = details.previous_id;
-                changelog_insert.post_rank = details.post_rank;
-                changelog_insert.pre_rank = details.pre_rank;
-                changelog_insert.score_delta = details.score_delta;
-            } else {
-                eprintln!("USER IS BANNED, DO NOT ADD A TIME FOR THEM");
-                return HttpResponse::NotFound().body("User is banned");
+    let changelog_insert = match validate(pool.get_ref(), &query, &config, &cache).await {
+        Ok(validated) => {
+            let verified = validated.verified_with_demo();
+            ChangelogInsert {
+                verified: Some(verified),
+                ..validated.insert
             }
         }
         Err(e) => {
-            eprintln!("Error finding newscore details -> {:#?}", e);
-            return HttpResponse::NotFound().body("User not found, or better time exists.");
-        }
-    }
-    match parse_and_write_multipart(&mut payload, &mut file_name).await {
-        Ok(_) => (),
-        Err(e) => {
-            eprintln!("Error parsing or writing the file. -> {}", e);
-            return HttpResponse::BadRequest().body("Error parsing or write the file.");
+            eprintln!("Submission failed validation -> {}", e);
+            return match e.downcast_ref::<ValidationErrors>() {
+                Some(errors) => HttpResponse::BadRequest().json(errors),
+                None => HttpResponse::InternalServerError().body("Error validating submission."),
+            };
         }
+    };
+    if let Err(e) =
+        parse_and_write_multipart(&mut payload, &mut file_name, config.demo.max_upload_bytes).await
+    {
+        return upload_error_response(e);
     }
     // Add Changelog/Demo entries to database.
-    match add_to_database(pool.get_ref(), changelog_insert, &config, &file_name, true).await {
-        Ok((cl_id, demo_id)) => HttpResponse::Ok().json((cl_id, demo_id)),
+    match add_to_database(
+        pool.get_ref(),
+        changelog_insert,
+        b2.get_ref(),
+        &file_name,
+        true,
+    )
+    .await
+    {
+        Ok((cl_id, demo_id)) => {
+            cache.invalidate_profile(&query.profile_number).await;
+            cache.invalidate_leaderboard(&query.map_id).await;
+            HttpResponse::Ok().json((cl_id, demo_id))
+        }
+        Err(e) if e.downcast_ref::<StorageUnavailable>().is_some() => {
+            eprintln!("Error with adding changelog/demo insert -> {:#}", e);
+            HttpResponse::ServiceUnavailable().body(
+                "Demo storage is currently unavailable, try again later or submit without a demo.",
+            )
+        }
         Err(e) => {
             eprintln!("Error with adding changelog/demo insert -> {}", e);
             HttpResponse::InternalServerError()
@@ -152,6 +224,68 @@ pub async fn changelog_with_demo(
     }
 }
 
+/// PUT endpoint to replace the file for an existing demo, such as a wrong or corrupt upload.
+///
+/// The demo keeps its id and changelog entry, only the file is swapped. Expects the new demo as a multipart upload.
+/// `stale_file_id` in the response is set if the old file couldn't be removed from BackBlaze.
+///
+/// **Required Parameters**: profile_number, the run's owner or an admin.
+///
+/// ## Example endpoint:
+/// - `/api/v1/demos/12651/replace?profile_number=76561198040982247`
+#[put("/demos/{demo_id}/replace")]
+pub async fn put_replace_demo(
+    demo_id: web::Path<i64>,
+    query: web::Query<ReplaceDemoParams>,
+    mut payload: Multipart,
+    config: web::Data<Config>,
+    b2: web::Data<BackBlazeClient>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    if !b2.is_available().await {
+        return HttpResponse::ServiceUnavailable()
+            .body("Demo storage is currently unavailable, try again later.");
+    }
+    let mut file_name = String::default();
+    if let Err(e) =
+        parse_and_write_multipart(&mut payload, &mut file_name, config.demo.max_upload_bytes).await
+    {
+        return upload_error_response(e);
+    }
+    if file_name.is_empty() {
+        return HttpResponse::BadRequest().body("No demo file was uploaded.");
+    }
+    match replace_demo_file(
+        pool.get_ref(),
+        b2.get_ref(),
+        demo_id.into_inner(),
+        &query.profile_number,
+        &file_name,
+    )
+    .await
+    {
+        Ok(Some(replaced)) => HttpResponse::Ok().json(replaced),
+        Ok(None) => HttpResponse::NotFound().body("No demo found for the given demo_id."),
+        Err(e) => BoardsError::from(e.context("Error replacing demo file")).error_response(),
+    }
+}
+
+/// Streams the uploaded file from disk to replace the demo's file, the local copy is always removed.
+async fn replace_demo_file(
+    pool: &PgPool,
+    b2: &BackBlazeClient,
+    demo_id: i64,
+    profile_number: &str,
+    file_name: &str,
+) -> Result<Option<ReplacedDemo>> {
+    let path = format!("./demos/{}", file_name);
+    let file = tokio::fs::File::open(&path).await?;
+    let size = file.metadata().await?.len();
+    let res = Demos::replace_file(pool, b2, demo_id, profile_number, file, size, file_name).await;
+    remove_file(&path)?;
+    res
+}
+
 // Different demo entries can have the same changelog ID, but a changelog entry should only have the most recent, valid demo_id.
 /// DELETE endpoint to remove a demo from both backbalze and the database.
 /// ## Expects **one** of the two parametes
@@ -174,7 +308,7 @@ pub async fn changelog_with_demo(
 #[delete("/demos")]
 pub async fn delete_demo(
     query: web::Query<DemoOptions>,
-    config: web::Data<Config>,
+    b2: web::Data<BackBlazeClient>,
     pool: web::Data<PgPool>,
 ) -> impl Responder {
     let query = query.into_inner();
@@ -186,7 +320,7 @@ pub async fn delete_demo(
                 .body("Cannot find changelog and demo associated with provided information");
         }
     };
-    match delete_demo_file(pool.get_ref(), &config.into_inner(), cl, demo_id).await {
+    match delete_demo_file(pool.get_ref(), b2.get_ref(), cl, demo_id).await {
         Ok(_) => match delete_demo_db(pool.get_ref(), demo_id).await {
             Ok(_) => HttpResponse::Ok().body("Demo file and entry succesfully removed."),
             Err(e) => {
@@ -195,6 +329,10 @@ pub async fn delete_demo(
                     .body("Error deleting demo entry from database");
             }
         },
+        Err(e) if e.downcast_ref::<StorageUnavailable>().is_some() => {
+            eprintln!("{:#}", e);
+            HttpResponse::ServiceUnavailable().body("Demo storage is currently unavailable.")
+        }
         Err(e) => {
             eprintln!("{}", e);
             return HttpResponse::InternalServerError().body("Error deleting file from backblaze.");
@@ -203,113 +341,118 @@ pub async fn delete_demo(
 }
 
 /// Adds a demo and changelog insert to the database
-/// The debug value passed will remove the added changelog/demo entries inserted, and skip uploading the file for quicker debugging.
+/// The debug value passed will roll back the added changelog/demo entries, and skip uploading the file for quicker debugging.
+///
+/// The demo is uploaded before anything is inserted, so a failed upload never leaves a changelog entry without its demo.
 async fn add_to_database(
     pool: &PgPool,
     changelog_insert: ChangelogInsert,
-    config: &Config,
+    b2: &BackBlazeClient,
     file_name: &str,
     debug: bool,
 ) -> Result<(i64, i64)> {
     let mut demo_insert = DemoInsert::default();
-    let cl_id = Changelog::insert_changelog(pool, changelog_insert).await?;
-    demo_insert.cl_id = cl_id;
     // TODO: How do we want demo files named?
     let file_id = if !debug {
-        upload_demo(config, &file_name).await?
+        upload_demo(b2, file_name).await
     } else {
-        Some(format!("{}.dem", file_name))
+        Ok(Some(format!("{}.dem", file_name)))
     };
-    // Delete Demo
+    // Delete Demo, whether or not the upload succeeded.
     remove_file(format!("./demos/{}", file_name))?;
+    let file_id = file_id?;
     if let Some(file_id) = file_id {
         demo_insert.file_id = file_id;
     }
-    // Add demo entry to database.
-    let demo_id = Demos::insert_demo(pool, demo_insert).await?;
-    // Update changelog to have the new demo_id
-    Changelog::update_demo_id_in_changelog(pool, cl_id, demo_id).await?;
+    // The changelog entry and its demo are added together, a failure part way through leaves neither behind.
+    let mut tx = pool.begin().await?;
+    let cl_id = Changelog::insert_changelog_tx(&mut tx, changelog_insert).await?;
+    demo_insert.cl_id = cl_id;
+    let demo_id = Demos::insert_demo_tx(&mut tx, demo_insert).await?;
+    Changelog::update_demo_id_in_changelog_tx(&mut tx, cl_id, demo_id).await?;
     if debug {
-        Changelog::delete_changelog(pool, cl_id).await?;
-        Demos::delete_demo(pool, demo_id).await?;
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
     }
     Ok((cl_id, demo_id))
 }
 
 /// Helper function that handles parsing the multipart and writing the file out locally
-async fn parse_and_write_multipart(payload: &mut Multipart, file_name: &mut String) -> Result<()> {
+///
+/// Chunks are written to disk as they arrive, so large demos are never held in memory.
+/// Files over `max_bytes`, or without the demo header, are deleted and rejected with [RejectedUpload].
+async fn parse_and_write_multipart(
+    payload: &mut Multipart,
+    file_name: &mut String,
+    max_bytes: u64,
+) -> Result<()> {
     while let Ok(Some(mut field)) = payload.try_next().await {
-        let mut content_data = Vec::new();
-        while let Some(Ok(chunk)) = field.next().await {
-            content_data.extend(chunk);
-        }
-        let fname = field.content_disposition().get_filename();
+        let fname = field
+            .content_disposition()
+            .get_filename()
+            .map(|fname| fname.to_string());
 
         if let Some(fname) = fname {
-            use std::fs;
-            fs::create_dir_all("./demos")?;
-            let mut file = OpenOptions::new()
-                .create(true)
-                .write(true)
-                .open(format!("./demos/{}", fname))?;
-            file.write_all(&content_data)?;
-            *file_name = fname.to_string();
+            tokio::fs::create_dir_all("./demos").await?;
+            let path = format!("./demos/{}", fname);
+            let mut file = tokio::fs::File::create(&path).await?;
+            let mut written: u64 = 0;
+            let mut header: Vec<u8> = Vec::with_capacity(DEMO_MAGIC.len());
+            while let Some(chunk) = field.next().await {
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => bail!("Error reading multipart chunk -> {}", e),
+                };
+                written += chunk.len() as u64;
+                if written > max_bytes {
+                    drop(file);
+                    remove_file(&path)?;
+                    return Err(RejectedUpload::TooLarge(max_bytes).into());
+                }
+                if header.len() < DEMO_MAGIC.len() {
+                    let needed = (DEMO_MAGIC.len() - header.len()).min(chunk.len());
+                    header.extend_from_slice(&chunk[..needed]);
+                    if !DEMO_MAGIC.starts_with(&header) {
+                        drop(file);
+                        remove_file(&path)?;
+                        return Err(RejectedUpload::NotADemo.into());
+                    }
+                }
+                file.write_all(&chunk).await?;
+            }
+            file.flush().await?;
+            if header.len() < DEMO_MAGIC.len() {
+                drop(file);
+                remove_file(&path)?;
+                return Err(RejectedUpload::NotADemo.into());
+            }
+            *file_name = fname;
             // TODO: Parse Demo
         }
     }
     Ok(())
 }
 
-/// Returns a client, and an authenticated session for use with backblaze.
-async fn b2_client_and_auth(config: &Config) -> Result<(reqwest::Client, B2Auth)> {
-    let client = reqwest::ClientBuilder::new().build()?;
-    let auth = b2_authorize_account(
-        &client,
-        format!("{}:{}", config.backblaze.keyid, config.backblaze.key),
-    )
-    .await
-    .unwrap();
-    Ok((client, auth))
+/// Maps an error from [parse_and_write_multipart] to a response, rejected uploads get a specific status.
+fn upload_error_response(e: anyhow::Error) -> HttpResponse {
+    eprintln!("Error parsing or writing the file. -> {}", e);
+    match e.downcast_ref::<RejectedUpload>() {
+        Some(RejectedUpload::TooLarge(max)) => {
+            HttpResponse::PayloadTooLarge().body(format!("Demos can be at most {} bytes.", max))
+        }
+        Some(RejectedUpload::NotADemo) => {
+            HttpResponse::BadRequest().body("The uploaded file is not a valid demo.")
+        }
+        None => HttpResponse::BadRequest().body("Error parsing or write the file."),
+    }
 }
 
-/// Handles uploading the demo file
-async fn upload_demo(config: &Config, file_name: &str) -> Result<Option<String>> {
-    // Ref: https://docs.rs/raze/0.4.1/raze/api/fn.b2_authorize_account.html
-    let (client, auth) = b2_client_and_auth(&config).await.unwrap();
-
-    let upload_auth = b2_get_upload_url(&client, &auth, config.backblaze.bucket.clone())
-        .await
-        .unwrap();
-    let file = tokio::fs::File::open(format!("./demos/{}", file_name))
-        .await
-        .unwrap();
-    let metadata = file.metadata().await.unwrap();
-    let size = metadata.len();
-    let modf = metadata
-        .modified()
-        .unwrap()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs()
-        * 1000;
-
-    let param = FileParameters {
-        file_path: file_name,
-        file_size: size,
-        content_type: None,
-        content_sha1: Sha1Variant::HexAtEnd,
-        last_modified_millis: modf,
-    };
-
-    let stream = reader_to_stream(file);
-    let stream = BytesStreamHashAtEnd::wrap(stream);
-    let stream = BytesStreamThrottled::wrap(stream, 500000000);
-
-    let body = reqwest::Body::wrap_stream(stream);
-    let resp1 = b2_upload_file(&client, &upload_auth, body, param)
-        .await
-        .unwrap();
-    Ok(resp1.file_id)
+/// Handles uploading the demo file, streaming it from disk.
+async fn upload_demo(b2: &BackBlazeClient, file_name: &str) -> Result<Option<String>> {
+    let file = tokio::fs::File::open(format!("./demos/{}", file_name)).await?;
+    let size = file.metadata().await?.len();
+    Demos::upload_stream(b2, file, size, file_name).await
 }
 
 /// Takes in either a demo_id or a changelog_id, and returns a changelog entry and a demno_id
@@ -347,14 +490,13 @@ async fn get_changelog_and_demo_id(query: DemoOptions, pool: &PgPool) -> Result<
 /// Deletes the demo from backblaze.
 async fn delete_demo_file(
     pool: &PgPool,
-    config: &Config,
+    b2: &BackBlazeClient,
     cl: Changelog,
     demo_id: i64,
 ) -> Result<()> {
-    let (client, auth) = b2_client_and_auth(&config).await.unwrap();
     let demo = Demos::get_demo(pool, demo_id).await.unwrap().unwrap();
     let file_name = generate_file_name(pool, cl).await?;
-    match b2_delete_file_version(&client, &auth, file_name, demo.file_id).await {
+    match b2.delete_file_version(&file_name, &demo.file_id).await {
         Ok(_) => Ok(()),
         Err(e) => {
             eprintln!("Failed to delete file -> {:#?}", e);
@@ -373,7 +515,10 @@ async fn delete_demo_db(pool: &PgPool, demo_id: i64) -> Result<bool> {
 
 /// Create file_name
 async fn generate_file_name(pool: &PgPool, cl: Changelog) -> Result<String> {
-    let mut map_name = Maps::get_map_name(pool, cl.map_id).await?.unwrap();
+    let mut map_name = match Maps::get_map_name(pool, cl.map_id.clone()).await? {
+        Some(map_name) => map_name,
+        None => bail!("Map {} does not exist", cl.map_id),
+    };
     map_name.retain(|c| !c.is_whitespace());
     Ok(format!("{}_{}_{}", map_name, cl.score, cl.profile_number))
 }
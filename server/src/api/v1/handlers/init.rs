@@ -17,6 +17,7 @@ pub fn init(cfg: &mut web::ServiceConfig) {
             .service(get_singleplayer_maps) // SP
             .service(get_singleplayer_preview)
             .service(get_banned_scores_sp)
+            .service(get_sp_compare)
             .service(post_banned_scores_sp)
             .service(get_banned_scores_coop)
             .service(post_banned_scores_coop)
@@ -27,17 +28,61 @@ pub fn init(cfg: &mut web::ServiceConfig) {
             .service(get_cooperative_maps) // Coop
             .service(get_cooperative_preview)
             .service(get_changelog)
+            .service(get_changelog_count)
+            .service(get_changelog_export)
+            .service(get_category_feed)
+            .service(get_changelog_since)
+            .service(get_top_improvers)
+            .service(get_map_activity)
+            .service(get_hourly_activity)
+            .service(get_wr_counts)
+            .service(get_daily_summary)
+            .service(get_random_notable)
+            .service(get_score_context)
+            .service(get_edit_history)
             .service(post_score_coop)
             .service(check_ban_status) // Users
             .service(get_user)
+            .service(get_all_users)
+            .service(get_name_history)
+            .service(put_steam_data)
             .service(get_banned_users)
             .service(post_new_user)
             .service(get_donators)
             .service(get_wall_of_shame)
+            .service(get_active_players)
+            .service(get_active_now)
             .service(get_profile)
+            .service(get_chapter_completion)
+            .service(get_game_completion)
+            .service(get_coop_summary)
+            .service(get_coop_partners)
+            .service(get_percentiles)
+            .service(get_bests)
+            .service(get_most_played_maps)
+            .service(get_profile_changelog)
+            .service(get_wr_streak)
             .service(get_map_ids_by_chapter) // Chapters
             .service(get_chapter_by_name)
             .service(get_default_cat) // Maps
+            .service(get_completion_extremes) // Before get_map, so the path isn't parsed as a map_id
+            .service(get_top_n_all_maps) // Before get_map, so the path isn't parsed as a map_id
+            .service(get_softest_wrs) // Before get_map, so the path isn't parsed as a map_id
+            .service(get_map_search) // Before get_map, so the path isn't parsed as a map_id
+            .service(get_featured_map) // Before get_map, so the path isn't parsed as a map_id
+            .service(get_maps_by_tier)
+            .service(get_map)
+            .service(get_games)
+            .service(get_longest_standing_wr)
+            .service(get_wr_reign_history)
+            .service(get_tied_scores)
+            .service(get_wr_at_time)
+            .service(get_map_page)
+            .service(get_map_leaderboard)
+            .service(get_avg_times)
+            .service(get_categories_with_counts)
+            .service(get_current_wrs)
+            .service(get_records_grid)
             .service(get_points_sp) // Points
             .service(post_points_sp)
             .service(get_points_coop)
@@ -47,8 +92,34 @@ pub fn init(cfg: &mut web::ServiceConfig) {
             .service(get_points_overall)
             .service(post_points_overall)
             .service(changelog_with_demo)
+            .service(download_demo)
+            .service(put_replace_demo)
             .service(get_admin_changelog) // Admin
             .service(get_banned_stats)
-            .service(get_admin_list),
+            .service(get_admin_list)
+            .service(post_demo_gc)
+            .service(get_demo_audit)
+            .service(post_demo_retention)
+            .service(get_pending_reparse)
+            .service(post_backfill_ranks)
+            .service(post_backfill_score_deltas)
+            .service(put_banned_bulk)
+            .service(put_changelog_field)
+            .service(put_verify_pending)
+            .service(get_audit_log)
+            .service(get_invalid_category_entries)
+            .service(get_invalid_coop)
+            .service(get_admin_note_search)
+            .service(put_compact_history)
+            .service(get_possible_duplicates)
+            .service(get_large_jumps)
+            .service(put_merge_accounts)
+            .service(put_user_ban)
+            .service(post_donation)
+            .service(get_donation_history)
+            .service(put_featured_map)
+            .service(put_map_tier)
+            .service(put_submission_window)
+            .service(get_recent_demos),
     );
 }
@@ -1,13 +1,500 @@
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
 use sqlx::PgPool;
 
-use crate::models::models::Maps;
+use crate::models::models::{
+    Changelog, CompletionExtremesParams, Games, MapLeaderboardParams, MapPageParams,
+    MapSearchParams, Maps, Opti32, RecordsGridParams, RecordsParams, SoftestWrsParams, TopNParams,
+    WrAtParams,
+};
+use crate::tools::cache::CacheState;
+use crate::tools::errors::BoardsError;
 
 #[get("/category/default_category/{map}")]
-async fn get_default_cat(params: web::Path<u64>, pool: web::Data<PgPool>) -> impl Responder {
-    let res = Maps::get_default_cat(pool.get_ref(), params.to_string()).await;
+async fn get_default_cat(
+    params: web::Path<u64>,
+    cache: web::Data<CacheState>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let res = cache
+        .get_default_cat(pool.get_ref(), &params.to_string())
+        .await;
     match res {
-        Ok(id) => HttpResponse::Ok().json(id),
-        _ => HttpResponse::NotFound().body("Error finding deafult cat_id"),
+        Ok(Some(id)) => HttpResponse::Ok().json(id),
+        Ok(None) => HttpResponse::NotFound().body("No map found for the given map_id."),
+        Err(e) => BoardsError::from(e.context("Error finding default cat_id")).error_response(),
+    }
+}
+
+/// **GET** method to return the full map information for a given map_id.
+///
+/// **Required Parameters**: map_id
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/47802`
+#[get("/maps/{map_id}")]
+async fn get_map(params: web::Path<u64>, pool: web::Data<PgPool>) -> impl Responder {
+    match Maps::get_map(pool.get_ref(), params.to_string()).await {
+        Ok(Some(map)) => HttpResponse::Ok().json(map),
+        Ok(None) => HttpResponse::NotFound().body("No map found for the given map_id."),
+        Err(e) => BoardsError::from(e.context("Error finding map")).error_response(),
+    }
+}
+
+/// **GET** method to return all games supported by the board.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/games`
+#[get("/games")]
+async fn get_games(pool: web::Data<PgPool>) -> impl Responder {
+    match Games::get_games(pool.get_ref()).await {
+        Ok(games) => HttpResponse::Ok().json(games),
+        Err(e) => BoardsError::from(e.context("Error finding games")).error_response(),
+    }
+}
+
+/// **GET** method to return the world record that held the #1 spot for the longest time on a map.
+///
+/// Banned scores are excluded from the history, the current world record is measured up to now.
+///
+/// **Required Parameters**: map_id
+///
+/// **Optional Parameters**: cat_id, will assume the default category if not provided.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/47802/longest_wr`
+/// - **With a category**
+///     - `/api/v1/maps/47802/longest_wr?cat_id=40`
+#[get("/maps/{map_id}/longest_wr")]
+async fn get_longest_standing_wr(
+    params: web::Path<u64>,
+    cat_id: web::Query<Opti32>,
+    cache: web::Data<CacheState>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let map_id = params.to_string();
+    let cat_id = match cat_id.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    match Changelog::get_longest_standing_wr(pool.get_ref(), map_id, cat_id).await {
+        Ok(Some(wr)) => HttpResponse::Ok().json(wr),
+        Ok(None) => HttpResponse::NotFound().body("No world records found for the given map."),
+        Err(e) => BoardsError::from(e.context("Error finding longest standing world record"))
+            .error_response(),
+    }
+}
+
+/// **GET** method to return every player's best score on a map, ranked.
+///
+/// Served from an in-memory cache, which is refreshed after new runs or once the configured TTL expires.
+///
+/// **Required Parameters**: map_id
+///
+/// **Optional Parameters**:
+///    - **cat_id**
+///         - Will assume the default category if not provided.
+///    - **include_history**
+///         - **DEFAULT** `false` - Returns every run, not just each player's best. Runs share their player's rank, and aren't cached.
+///    - **at**
+///         - Reconstructs the board as it stood at the given time, from each player's best run submitted by then. Not cached.
+///    - **include_banned**
+///         - **DEFAULT** `false` - With `at`, keeps runs that were banned since, showing the board as it appeared at the time.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/47802/leaderboard`
+/// - **With a category**
+///     - `/api/v1/maps/47802/leaderboard?cat_id=40`
+/// - **With every run**
+///     - `/api/v1/maps/47802/leaderboard?include_history=true`
+/// - **As it stood on a date**
+///     - `/api/v1/maps/47802/leaderboard?at=2021-06-01T00:00:00&include_banned=true`
+#[get("/maps/{map_id}/leaderboard")]
+async fn get_map_leaderboard(
+    params: web::Path<u64>,
+    query: web::Query<MapLeaderboardParams>,
+    cache: web::Data<CacheState>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let map_id = params.to_string();
+    let query = query.into_inner();
+    let include_history = query.include_history.unwrap_or(false);
+    if include_history && query.at.is_some() {
+        return HttpResponse::BadRequest().body("include_history can't be combined with at.");
+    }
+    let cat_id = match query.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    let res = if let Some(at) = query.at {
+        let include_banned = query.include_banned.unwrap_or(false);
+        Maps::get_leaderboard_at(pool.get_ref(), map_id, cat_id, at, include_banned).await
+    } else if include_history {
+        Maps::get_map_board_history(pool.get_ref(), map_id, cat_id).await
+    } else {
+        cache
+            .get_map_leaderboard(pool.get_ref(), &map_id, cat_id)
+            .await
+    };
+    match res {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => BoardsError::from(e.context("Error finding map leaderboard")).error_response(),
+    }
+}
+
+/// **GET** method for everything on a map's page in a single call, see [Maps::get_map_page].
+///
+/// Returns the map, its categories with player counts, the top of the leaderboard, per-category stats and the 10 most recent changelog entries.
+///
+/// **Required Parameters**: map_id
+///
+/// **Optional Parameters**:
+///    - **cat_id**
+///         - Will assume the default category if not provided.
+///    - **top_n**
+///         - **DEFAULT** `10` - The # of leaderboard entries returned, at most `100`.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/47802/page`
+/// - **With parameters**
+///     - `/api/v1/maps/47802/page?cat_id=40&top_n=25`
+#[get("/maps/{map_id}/page")]
+async fn get_map_page(
+    params: web::Path<u64>,
+    query: web::Query<MapPageParams>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let top_n = query.top_n.unwrap_or(10).clamp(1, 100);
+    match Maps::get_map_page(pool.get_ref(), params.to_string(), query.cat_id, top_n).await {
+        Ok(Some(page)) => HttpResponse::Ok().json(page),
+        Ok(None) => HttpResponse::NotFound().body("No map found for the given map_id."),
+        Err(e) => BoardsError::from(e.context("Error building map page")).error_response(),
+    }
+}
+
+/// **GET** method to return every reign as world record holder on a map, oldest first.
+///
+/// Consecutive records by the same player count as a single reign, the current holder's reign runs up to now.
+///
+/// **Required Parameters**: map_id
+///
+/// **Optional Parameters**: cat_id, will assume the default category if not provided.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/47802/wr_reigns`
+/// - **With a category**
+///     - `/api/v1/maps/47802/wr_reigns?cat_id=40`
+#[get("/maps/{map_id}/wr_reigns")]
+async fn get_wr_reign_history(
+    params: web::Path<u64>,
+    cat_id: web::Query<Opti32>,
+    cache: web::Data<CacheState>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let map_id = params.to_string();
+    let cat_id = match cat_id.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    match Maps::get_wr_reign_history(pool.get_ref(), map_id, cat_id).await {
+        Ok(reigns) => HttpResponse::Ok().json(reigns),
+        Err(e) => {
+            BoardsError::from(e.context("Error finding world record reigns")).error_response()
+        }
+    }
+}
+
+/// **GET** method for the current ties on a map, groups of players sharing the same best score.
+///
+/// Scores held by a single player are omitted, an empty list means the map has no ties.
+///
+/// **Required Parameters**: map_id
+///
+/// **Optional Parameters**: cat_id, will assume the default category if not provided.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/47802/ties`
+/// - **With a category**
+///     - `/api/v1/maps/47802/ties?cat_id=40`
+#[get("/maps/{map_id}/ties")]
+async fn get_tied_scores(
+    params: web::Path<u64>,
+    cat_id: web::Query<Opti32>,
+    cache: web::Data<CacheState>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let map_id = params.to_string();
+    let cat_id = match cat_id.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    match Changelog::get_tied_scores(pool.get_ref(), map_id, cat_id).await {
+        Ok(ties) => HttpResponse::Ok().json(ties),
+        Err(e) => BoardsError::from(e.context("Error finding tied scores")).error_response(),
+    }
+}
+
+/// **GET** method for the world record score on a map as it stood at a given time.
+///
+/// Used to show how far behind the world record a historical run was when it was set. Returns `null` if the map had no scores yet.
+///
+/// **Required Parameters**: map_id, at
+///
+/// **Optional Parameters**: cat_id, will assume the default category if not provided.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/47802/wr_at?at=2021-06-01T00:00:00`
+/// - **With a category**
+///     - `/api/v1/maps/47802/wr_at?at=2021-06-01T00:00:00&cat_id=40`
+#[get("/maps/{map_id}/wr_at")]
+async fn get_wr_at_time(
+    params: web::Path<u64>,
+    query: web::Query<WrAtParams>,
+    cache: web::Data<CacheState>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let map_id = params.to_string();
+    let query = query.into_inner();
+    let cat_id = match query.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    match Changelog::get_wr_at_time(pool.get_ref(), map_id, cat_id, query.at).await {
+        Ok(wr) => HttpResponse::Ok().json(wr),
+        Err(e) => {
+            BoardsError::from(e.context("Error finding world record at time")).error_response()
+        }
+    }
+}
+
+/// **GET** method for every category on a map, with the # of players with a score in each.
+///
+/// Intended for the map page's category tabs, categories without scores have a count of 0.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/47802/categories`
+#[get("/maps/{map_id}/categories")]
+async fn get_categories_with_counts(
+    params: web::Path<u64>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    match Maps::get_categories_with_counts(pool.get_ref(), params.to_string()).await {
+        Ok(categories) => HttpResponse::Ok().json(categories),
+        Err(e) => BoardsError::from(e.context("Error finding categories")).error_response(),
+    }
+}
+
+/// **GET** method for the average & median of players' best scores on each category of a map.
+///
+/// Intended to help gauge how difficult each category is relative to the record.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/47802/avg_times`
+#[get("/maps/{map_id}/avg_times")]
+async fn get_avg_times(params: web::Path<u64>, pool: web::Data<PgPool>) -> impl Responder {
+    match Maps::get_avg_times(pool.get_ref(), params.to_string()).await {
+        Ok(avgs) => HttpResponse::Ok().json(avgs),
+        Err(e) => BoardsError::from(e.context("Error finding average times")).error_response(),
+    }
+}
+
+/// **GET** method for the public maps with the fewest (hardest) and most (easiest) players with a valid score.
+///
+/// **Optional Parameters**: limit, the # of maps in each list, defaults to `5` (max `50`).
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/completion_extremes`
+/// - **Top 10**
+///     - `/api/v1/maps/completion_extremes?limit=10`
+#[get("/maps/completion_extremes")]
+async fn get_completion_extremes(
+    params: web::Query<CompletionExtremesParams>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let limit = params.limit.unwrap_or(5).clamp(1, 50) as usize;
+    match Maps::get_completion_extremes(pool.get_ref(), limit).await {
+        Ok(extremes) => HttpResponse::Ok().json(extremes),
+        Err(e) => {
+            BoardsError::from(e.context("Error finding completion extremes")).error_response()
+        }
+    }
+}
+
+/// **GET** method to return the currently featured map, with its board stats.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/featured`
+#[get("/maps/featured")]
+async fn get_featured_map(pool: web::Data<PgPool>) -> impl Responder {
+    match Maps::get_featured(pool.get_ref()).await {
+        Ok(Some(featured)) => HttpResponse::Ok().json(featured),
+        Ok(None) => HttpResponse::NotFound().body("No map is currently featured."),
+        Err(e) => BoardsError::from(e.context("Error finding featured map")).error_response(),
+    }
+}
+
+/// **GET** method to return every map in a difficulty tier, in map order.
+///
+/// Example Endpoints:
+/// - **Tier 3**
+///     - `/api/v1/maps/tier/3`
+#[get("/maps/tier/{tier}")]
+async fn get_maps_by_tier(tier: web::Path<i32>, pool: web::Data<PgPool>) -> impl Responder {
+    match Maps::get_by_tier(pool.get_ref(), tier.into_inner()).await {
+        Ok(maps) => HttpResponse::Ok().json(maps),
+        Err(e) => BoardsError::from(e.context("Error finding maps by tier")).error_response(),
+    }
+}
+
+/// **GET** method to return the top `n` players on every SP or coop map, on each map's default category, keyed by map_id.
+///
+/// **Optional Parameters**: [TopNParams]
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/top`
+/// - **Top 5 on coop maps**
+///     - `/api/v1/maps/top?n=5&is_mp=true`
+#[get("/maps/top")]
+async fn get_top_n_all_maps(
+    params: web::Query<TopNParams>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let n = params.n.unwrap_or(3).clamp(1, 20) as i64;
+    let is_mp = params.is_mp.unwrap_or(false);
+    match Maps::get_top_n_all_maps(pool.get_ref(), n, is_mp).await {
+        Ok(top) => HttpResponse::Ok().json(top),
+        Err(e) => {
+            BoardsError::from(e.context("Error finding top scores for all maps")).error_response()
+        }
+    }
+}
+
+/// **GET** method to return the WRs closest to being beaten, by the gap to the #2 run on each map's default category.
+///
+/// **Optional Parameters**:
+///    - **limit**
+///         - **DEFAULT** `10` - The max # of records returned, at most `100`.
+///    - **is_mp**
+///         - **DEFAULT** `false` - Coop maps instead of SP maps.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/softest_wrs`
+/// - **Coop**
+///     - `/api/v1/maps/softest_wrs?is_mp=true&limit=5`
+#[get("/maps/softest_wrs")]
+async fn get_softest_wrs(
+    params: web::Query<SoftestWrsParams>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let limit = params.limit.unwrap_or(10).clamp(1, 100);
+    let is_mp = params.is_mp.unwrap_or(false);
+    match Maps::get_softest_wrs(pool.get_ref(), is_mp, limit).await {
+        Ok(wrs) => HttpResponse::Ok().json(wrs),
+        Err(e) => {
+            BoardsError::from(e.context("Error finding the softest world records")).error_response()
+        }
+    }
+}
+
+/// **GET** method for the global search box, finds public maps by name, chapter name or category name.
+///
+/// Each map is returned once, exact name matches first, then name, chapter and category matches.
+///
+/// **Required Parameters**: q, the search string.
+///
+/// **Optional Parameters**:
+///    - **limit**
+///         - **DEFAULT** `10` - The max # of maps returned, at most `50`.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/maps/search?q=portal%20gun`
+/// - **With a limit**
+///     - `/api/v1/maps/search?q=inbounds&limit=25`
+#[get("/maps/search")]
+async fn get_map_search(
+    params: web::Query<MapSearchParams>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let params = params.into_inner();
+    let limit = params.limit.unwrap_or(10).clamp(1, 50);
+    match Maps::global_search(pool.get_ref(), params.q, limit).await {
+        Ok(maps) => HttpResponse::Ok().json(maps),
+        Err(e) => BoardsError::from(e.context("Error searching maps")).error_response(),
+    }
+}
+
+/// **GET** method to return the current world record on every SP or coop map, on each map's default category.
+///
+/// **Optional Parameters**: is_mp, defaults to `false` (SP maps).
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/records`
+/// - **Coop**
+///     - `/api/v1/records?is_mp=true`
+#[get("/records")]
+async fn get_current_wrs(
+    query: web::Query<RecordsParams>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let is_mp = query.into_inner().is_mp.unwrap_or(false);
+    match Maps::get_all_current_wrs(pool.get_ref(), is_mp).await {
+        Ok(wrs) => HttpResponse::Ok().json(wrs),
+        Err(e) => {
+            BoardsError::from(e.context("Error finding current world records")).error_response()
+        }
+    }
+}
+
+/// **GET** method to return the current world record on every map & category for a records grid, with each map's chapter.
+///
+/// **Optional Parameters**: scope, `default` (each map's default category, the default) or `all` (every category).
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/records/grid`
+/// - **Every category**
+///     - `/api/v1/records/grid?scope=all`
+#[get("/records/grid")]
+async fn get_records_grid(
+    query: web::Query<RecordsGridParams>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let scope = query.into_inner().scope.unwrap_or_default();
+    match Maps::get_all_wrs(pool.get_ref(), scope).await {
+        Ok(wrs) => HttpResponse::Ok().json(wrs),
+        Err(e) => BoardsError::from(e.context("Error finding the records grid")).error_response(),
     }
 }
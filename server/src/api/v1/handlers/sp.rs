@@ -1,11 +1,13 @@
 use crate::models::models::{
-    Changelog, ChangelogInsert, Opti32, ScoreParams, SpBanned, SpMap, SpPbHistory, SpPreviews,
-    SpRanked, Users, UsersPage,
+    Changelog, ChangelogInsert, CompareParams, Maps, Opti32, PbHistoryParams, ScoreParams,
+    SpBanned, SpMap, SpPbHistory, SpPreviews, SpRanked, UpdateChangelogParams, Users, UsersPage,
+    ValidationError, ValidationErrors,
 };
 use crate::tools::cache::{read_from_file, write_to_file, CacheState};
+use crate::tools::errors::BoardsError;
 use crate::tools::helpers::check_for_valid_score;
 use crate::tools::{config::Config, helpers::score};
-use actix_web::{get, post, put, web, HttpResponse, Responder};
+use actix_web::{get, post, put, web, HttpResponse, Responder, ResponseError};
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
@@ -54,15 +56,15 @@ pub async fn get_singleplayer_maps(
     cache: web::Data<CacheState>,
     pool: web::Data<PgPool>,
 ) -> impl Responder {
-    let res = SpMap::get_sp_map_page(
-        pool.get_ref(),
-        map_id.clone(),
-        config.proof.results,
-        cat_id
-            .cat_id
-            .unwrap_or(cache.into_inner().default_cat_ids[&map_id.into_inner()]),
-    )
-    .await;
+    let cat_id = match cat_id.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
+    let res =
+        SpMap::get_sp_map_page(pool.get_ref(), map_id.clone(), config.proof.results, cat_id).await;
     match res {
         Ok(sp_map) => {
             let mut ranked_vec = Vec::with_capacity(config.proof.results as usize);
@@ -78,6 +80,39 @@ pub async fn get_singleplayer_maps(
         _ => HttpResponse::NotFound().body("Error fetching SP Map Page"),
     }
 }
+/// Compares the best scores of a set of players on a given SP map, with ranks relative to the full board.
+/// REQUIRED PARAMETER profile_numbers (comma separated), OPTIONAL PARAMETER cat_id
+///   Example endpoint  -> /maps/sp/compare/47802?profile_numbers=76561198040982247,76561198039230536
+///                     -> /maps/sp/compare/47802?profile_numbers=76561198040982247&cat_id=40
+#[get("/maps/sp/compare/{map_id}")]
+async fn get_sp_compare(
+    map_id: web::Path<String>,
+    params: web::Query<CompareParams>,
+    cache: web::Data<CacheState>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let map_id = map_id.into_inner();
+    let params = params.into_inner();
+    let cat_id = match params.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("Map not found."),
+        },
+    };
+    let profile_numbers: Vec<String> = params
+        .profile_numbers
+        .split(',')
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty())
+        .collect();
+    let res = Changelog::get_best_scores(pool.get_ref(), map_id, cat_id, &profile_numbers).await;
+    match res {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => BoardsError::from(e.context("Error comparing scores")).error_response(),
+    }
+}
+
 /// Gives the profile number and score for all banned times on a given SP map
 #[get("/maps/sp/banned/{map_id}")]
 async fn get_banned_scores_sp(map_id: web::Path<u64>, pool: web::Data<PgPool>) -> impl Responder {
@@ -96,14 +131,19 @@ async fn post_banned_scores_sp(
     cache: web::Data<CacheState>,
     pool: web::Data<PgPool>,
 ) -> impl Responder {
+    let cat_id = match params.cat_id {
+        Some(cat_id) => cat_id,
+        None => match cache.get_default_cat(pool.get_ref(), &map_id).await {
+            Ok(Some(cat_id)) => cat_id,
+            _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+        },
+    };
     let res = Changelog::check_banned_scores(
         pool.get_ref(),
         map_id.clone(),
         params.score,
         params.profile_number.clone(),
-        params
-            .cat_id
-            .unwrap_or(cache.into_inner().default_cat_ids[&map_id.into_inner()]),
+        cat_id,
     )
     .await;
     match res {
@@ -112,14 +152,39 @@ async fn post_banned_scores_sp(
     }
 }
 
-/// Returns a players PB history on an SP map
+/// Returns a page of a players PB history on an SP map, most recent first.
+///
+/// **Optional Parameters**: [crate::controllers::models::PbHistoryParams]
+///    - **limit**
+///         - **DEFAULT** `50` - The # of max returned results, at most 500.
+///    - **offset**
+///         - **DEFAULT** `0` - The # of entries to skip.
+///
+/// Example Endpoints:
+/// - **Default**
+///     - `/api/v1/map/sp/47763/76561198040982247`
+/// - **Second page**
+///     - `/api/v1/map/sp/47763/76561198040982247?limit=50&offset=50`
 #[get("/map/sp/{map_id}/{profile_number}")]
-async fn get_sp_pbs(info: web::Path<(String, String)>, pool: web::Data<PgPool>) -> impl Responder {
+async fn get_sp_pbs(
+    info: web::Path<(String, String)>,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    query: web::Query<PbHistoryParams>,
+) -> impl Responder {
     let map_id = info.0.clone();
     let profile_number = info.1.clone();
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(50).clamp(1, 500);
+    let offset = query.offset.unwrap_or(0).max(0);
     let user_data: UsersPage;
     // Get information for the player (user_name and avatar).
-    let res = Users::get_user_data(pool.get_ref(), profile_number.clone()).await;
+    let res = Users::get_user_data(
+        pool.get_ref(),
+        profile_number.clone(),
+        &config.users.default_avatar,
+    )
+    .await;
     // TODO: Handle the case where the is no user in the db
     match res {
         Ok(Some(res)) => user_data = res,
@@ -128,18 +193,28 @@ async fn get_sp_pbs(info: web::Path<(String, String)>, pool: web::Data<PgPool>)
                 user_name: None,
                 avatar: None,
                 pb_history: None,
+                total: None,
             })
         }
         _ => return HttpResponse::NotFound().body("Error fetching User Data on given user."),
     }
-    // Get Changelog data for all previous times.
-    let res =
-        Changelog::get_sp_pb_history(pool.get_ref(), profile_number.clone(), map_id.clone()).await;
+    // Get Changelog data for a page of previous times, and the total to page through.
+    let res = tokio::try_join!(
+        Changelog::get_sp_pb_history(
+            pool.get_ref(),
+            profile_number.clone(),
+            map_id.clone(),
+            limit,
+            offset
+        ),
+        Changelog::get_sp_pb_history_count(pool.get_ref(), profile_number, map_id),
+    );
     match res {
-        Ok(changelog_data) => HttpResponse::Ok().json(SpPbHistory {
+        Ok((changelog_data, total)) => HttpResponse::Ok().json(SpPbHistory {
             user_name: Some(user_data.user_name),
             avatar: Some(user_data.avatar),
             pb_history: Some(changelog_data),
+            total: Some(total),
         }),
         Err(e) => {
             eprintln!("Could not find SP PB History -> {}", e);
@@ -147,12 +222,15 @@ async fn get_sp_pbs(info: web::Path<(String, String)>, pool: web::Data<PgPool>)
                 user_name: None,
                 avatar: None,
                 pb_history: None,
+                total: None,
             })
         }
     }
 }
 
 /// Receives a new score to add to the DB.
+///
+/// Scores on maps that aren't accepting submissions are rejected with a `SubmissionsClosed` error, see [Maps::is_submission_open].
 #[allow(unused_variables)]
 #[post("/sp/post_score")]
 async fn post_score_sp(
@@ -163,31 +241,54 @@ async fn post_score_sp(
     // TODO: Handle demo uploads.
     // TODO: Working with sequence re-sync. Need to implement role-back.
 
+    let profile_number = params.profile_number.clone();
+    let map_id = params.map_id.clone();
+    match Maps::is_submission_open(pool.get_ref(), map_id.clone()).await {
+        Ok(true) => (),
+        Ok(false) => {
+            return HttpResponse::BadRequest().json(ValidationErrors(vec![
+                ValidationError::SubmissionsClosed(map_id),
+            ]))
+        }
+        Err(e) => {
+            eprintln!("Error checking the submission window -> {}", e);
+            return HttpResponse::InternalServerError()
+                .body("Error checking the submission window.");
+        }
+    }
     let res = Changelog::insert_changelog(pool.get_ref(), params.0).await;
     match res {
         Ok(id) => {
+            cache.invalidate_profile(&profile_number).await;
+            cache.invalidate_leaderboard(&map_id).await;
             // Invalide our sp_previews cache with the new score.
             let state_data = &mut cache.current_state.lock().await;
             let is_cached = state_data.get_mut("sp_previews").unwrap();
             *is_cached = false;
             HttpResponse::Ok().json(id)
         }
-        Err(e) => {
-            eprintln!("{}", e);
-            HttpResponse::NotFound().body("Error adding new score to database.")
-        }
+        Err(e) => BoardsError::from(e).error_response(),
     }
 }
 
 /// Receives new data to update an existing score.
+///
+/// **Required Parameters**: admin, the profile_number of the admin making the edit, recorded in the entry's edit history.
 #[put("/maps/sp/update")]
-async fn put_score_sp(params: web::Json<Changelog>, pool: web::Data<PgPool>) -> impl Responder {
+async fn put_score_sp(
+    params: web::Json<Changelog>,
+    query: web::Query<UpdateChangelogParams>,
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheState>,
+) -> Result<HttpResponse, BoardsError> {
     // TODO: Handle demo uploads.
-    let res = Changelog::update_changelog(pool.get_ref(), params.0).await;
-    match res {
-        Ok(changelog_entry) => HttpResponse::Ok().json(changelog_entry),
-        _ => HttpResponse::NotFound().body("Error updating score."),
-    }
+    let profile_number = params.profile_number.clone();
+    let map_id = params.map_id.clone();
+    let changelog_entry =
+        Changelog::update_changelog(pool.get_ref(), params.0, &query.admin).await?;
+    cache.invalidate_profile(&profile_number).await;
+    cache.invalidate_leaderboard(&map_id).await;
+    Ok(HttpResponse::Ok().json(changelog_entry))
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -204,20 +305,21 @@ pub async fn get_newscore_details(
     cache: web::Data<CacheState>,
     config: web::Data<Config>,
 ) -> impl Responder {
+    let cat_id = match cache.get_default_cat(pool.get_ref(), &data.map_id).await {
+        Ok(Some(cat_id)) => cat_id,
+        _ => return HttpResponse::NotFound().body("No map found for the given map_id."),
+    };
     let res = check_for_valid_score(
         pool.get_ref(),
         data.profile_number.clone(),
         data.score,
         data.map_id.clone(),
         config.proof.results,
-        cache.into_inner().default_cat_ids[&data.map_id],
+        cat_id,
     )
     .await;
     match res {
         Ok(details) => HttpResponse::Ok().json(details),
-        Err(e) => {
-            eprintln!("Error finding newscore details -> {:#?}", e);
-            HttpResponse::NotFound().body("Error finding user")
-        }
+        Err(e) => BoardsError::from(e.context("Error finding newscore details")).error_response(),
     }
 }
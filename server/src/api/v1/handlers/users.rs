@@ -1,6 +1,12 @@
-use crate::models::models::{PointsProfileWrapper, ProfileData, ProfilePage, Users, UsersDisplay};
+use crate::models::models::{
+    ActiveNowParams, Changelog, Completion, MostPlayedParams, PercentileParams,
+    PointsProfileWrapper, ProfileChangelogParams, ProfileData, ProfilePage, ProfileParams,
+    RecentlyActiveParams, SteamDataParams, Users, UsersDisplay, ValidationErrors,
+};
 use crate::tools::cache::CacheState;
-use actix_web::{get, post, web, HttpResponse, Responder};
+use crate::tools::config::Config;
+use crate::tools::errors::BoardsError;
+use actix_web::{get, post, put, web, HttpResponse, Responder, ResponseError};
 use sqlx::PgPool;
 
 /// GET the user information for a given profile_number.
@@ -14,6 +20,54 @@ async fn get_user(pool: web::Data<PgPool>, profile_number: web::Path<String>) ->
     }
 }
 
+/// GET every board_name a user has gone by, most recent change first.
+///
+/// Used to resolve who older runs belong to when a player has since renamed.
+#[get("/users/{profile_number}/name_history")]
+async fn get_name_history(
+    pool: web::Data<PgPool>,
+    profile_number: web::Path<String>,
+) -> impl Responder {
+    match Users::get_name_history(pool.get_ref(), profile_number.into_inner()).await {
+        Ok(history) => HttpResponse::Ok().json(history),
+        Err(e) => BoardsError::from(e.context("Error fetching name history")).error_response(),
+    }
+}
+
+/// GET method for the steamIDs of every user on the board, used by the backend to sync Steam data.
+#[get("/users")]
+async fn get_all_users(pool: web::Data<PgPool>) -> impl Responder {
+    match Users::get_all_profile_numbers(pool.get_ref()).await {
+        Ok(profile_numbers) => HttpResponse::Ok().json(profile_numbers),
+        Err(e) => BoardsError::from(e.context("Error fetching all users")).error_response(),
+    }
+}
+
+/// PUT method to update the Steam name and/or avatar for several users, returns the # of users that changed.
+///
+/// Expects `{"admin_profile_number", "updates"}`, where `updates` is a list of `{"profile_number", "steam_name", "avatar"}`.
+/// `null` fields are left unchanged. Rejected unless `admin_profile_number` belongs to an admin.
+#[put("/users/steam_data")]
+async fn put_steam_data(
+    pool: web::Data<PgPool>,
+    params: web::Json<SteamDataParams>,
+) -> impl Responder {
+    let params = params.into_inner();
+    match Users::update_steam_data(
+        pool.get_ref(),
+        &params.updates,
+        &params.admin_profile_number,
+    )
+    .await
+    {
+        Ok(updated) => HttpResponse::Ok().json(updated),
+        Err(e) => {
+            eprintln!("Error updating steam data -> {}", e);
+            HttpResponse::BadRequest().body("Could not update steam data for users.")
+        }
+    }
+}
+
 /// GET method for the steamIDs of all banned users on the board.
 #[get("/banned_users")]
 async fn get_banned_users(pool: web::Data<PgPool>) -> impl Responder {
@@ -39,8 +93,17 @@ async fn check_ban_status(
 
 /// POST method for adding a new user to the db.
 #[post("/users")]
-async fn post_new_user(pool: web::Data<PgPool>, new_user: web::Json<Users>) -> impl Responder {
-    let res = Users::insert_new_users(pool.get_ref(), new_user.0.clone()).await;
+async fn post_new_user(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    new_user: web::Json<Users>,
+) -> impl Responder {
+    let res = Users::insert_new_users(
+        pool.get_ref(),
+        new_user.0.clone(),
+        config.notifications.new_user_webhook(),
+    )
+    .await;
     match res {
         Ok(true) => HttpResponse::Ok().json(new_user.0),
         Ok(false) => HttpResponse::InternalServerError().body("Could not add user to database"),
@@ -49,7 +112,10 @@ async fn post_new_user(pool: web::Data<PgPool>, new_user: web::Json<Users>) -> i
                 "Adding user {:#?} to DB failed with error -> {}",
                 new_user.0, e
             );
-            HttpResponse::InternalServerError().body("Could not add user to database.")
+            match e.downcast_ref::<ValidationErrors>() {
+                Some(errors) => HttpResponse::BadRequest().json(errors),
+                None => HttpResponse::InternalServerError().body("Could not add user to database."),
+            }
         }
     }
 }
@@ -58,32 +124,101 @@ async fn post_new_user(pool: web::Data<PgPool>, new_user: web::Json<Users>) -> i
 async fn get_donators(pool: web::Data<PgPool>) -> impl Responder {
     match Users::get_donators(pool.get_ref()).await {
         Ok(Some(res)) => HttpResponse::Ok().json(res),
-        Err(e) => {
-            eprintln!("Error getting donation stats -> {}", e);
-            HttpResponse::NotFound().body("Could not find donation stats.")
-        }
+        Err(e) => BoardsError::from(e.context("Error getting donation stats")).error_response(),
         _ => HttpResponse::NotFound().body("Could not find donation stats."),
     }
 }
 
-/// GET method for the UsersDisplay of all banned users on the board.
+/// GET method for the display info of all banned users on the board, with their public ban reason.
 #[get("/wall_of_shame")]
-async fn get_wall_of_shame(pool: web::Data<PgPool>) -> impl Responder {
-    let res = Users::get_banned_display(pool.get_ref()).await;
+async fn get_wall_of_shame(pool: web::Data<PgPool>, config: web::Data<Config>) -> impl Responder {
+    let res = Users::get_banned_with_reasons(pool.get_ref(), &config.users.default_avatar).await;
     match res {
-        Ok(Some(users)) => HttpResponse::Ok().json(users),
-        _ => HttpResponse::NotFound().body("Error fetching previews"),
+        Ok(users) => HttpResponse::Ok().json(users),
+        Err(e) => BoardsError::from(e.context("Error fetching banned users")).error_response(),
     }
 }
 
+/// GET method for players who submitted a run recently, most recently active first.
+///
+/// **Optional Parameters**:
+///    - **hours**
+///         - **DEFAULT** `24` - How far back to look for submissions, at most `720`.
+///    - **limit**
+///         - **DEFAULT** `50` - The max # of players returned, at most `100`.
+///
+///   Example endpoint  -> /active_players
+///                     -> /active_players?hours=6&limit=10
+#[get("/active_players")]
+async fn get_active_players(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    query: web::Query<RecentlyActiveParams>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let hours = query.hours.unwrap_or(24).clamp(1, 720);
+    let limit = query.limit.unwrap_or(50).clamp(1, 100);
+    let res =
+        Users::get_recently_active(pool.get_ref(), hours, limit, &config.users.default_avatar)
+            .await;
+    match res {
+        Ok(users) => HttpResponse::Ok().json(users),
+        Err(e) => {
+            BoardsError::from(e.context("Error fetching recently active players")).error_response()
+        }
+    }
+}
+
+/// GET method for players submitting right now, with their latest run, most recent first.
+///
+/// **Optional Parameters**:
+///    - **minutes**
+///         - **DEFAULT** `30` - How far back to look for submissions, at most `1440`.
+///    - **limit**
+///         - **DEFAULT** `20` - The max # of players returned, at most `100`.
+///
+///   Example endpoint  -> /active_now
+///                     -> /active_now?minutes=10&limit=5
+#[get("/active_now")]
+async fn get_active_now(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    query: web::Query<ActiveNowParams>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let minutes = query.minutes.unwrap_or(30).clamp(1, 1440);
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let res =
+        Users::get_active_now(pool.get_ref(), minutes, limit, &config.users.default_avatar).await;
+    match res {
+        Ok(players) => HttpResponse::Ok().json(players),
+        Err(e) => {
+            BoardsError::from(e.context("Error fetching players active now")).error_response()
+        }
+    }
+}
+
+/// GET the profile page for a given profile_number.
+///
+/// OPTIONAL PARAMETER pb_only
+///   - `true` (default): "oldest" is the first run that was a PB when it was set, "newest" is the most recent improvement.
+///   - `false`: "oldest" and "newest" only consider the user's current best score on each map.
+///
+///   Example endpoint  -> /profile/76561198040982247
+///                     -> /profile/76561198040982247?pb_only=false
 #[get("/profile/{profile_number}")]
 async fn get_profile(
     pool: web::Data<PgPool>,
     profile_number: web::Path<String>,
+    query: web::Query<ProfileParams>,
     cache: web::Data<CacheState>,
 ) -> impl Responder {
     let profile_number = profile_number.into_inner();
-    match Users::get_profile(pool.get_ref(), &profile_number).await {
+    let pb_only = query.into_inner().pb_only.unwrap_or(true);
+    match cache
+        .get_profile(pool.get_ref(), &profile_number, pb_only)
+        .await
+    {
         Ok(Some(data)) => {
             let mut points: Vec<PointsProfileWrapper> = Vec::new();
             let points_hm = cache.points.lock().await;
@@ -113,14 +248,170 @@ async fn get_profile(
             let profile_page = ProfilePage { points, data };
             HttpResponse::Ok().json(profile_page)
         }
-        Err(e) => {
-            eprintln!("Error creating profile page -> {:?}", e);
-            HttpResponse::NotFound().body("Could not create profile page for user.")
-        }
+        Err(e) => BoardsError::from(e.context("Error creating profile page")).error_response(),
         _ => HttpResponse::NotFound().body("Could not create profile page for user."),
     }
 }
 
+/// GET a user's completion (maps with a verified score out of all maps) for a chapter.
+///
+///   Example endpoint  -> /profile/76561198040982247/completion/chapter/7
+#[get("/profile/{profile_number}/completion/chapter/{chapter_id}")]
+async fn get_chapter_completion(
+    pool: web::Data<PgPool>,
+    info: web::Path<(String, i32)>,
+) -> impl Responder {
+    let (profile_number, chapter_id) = info.into_inner();
+    match Users::get_chapter_completion(pool.get_ref(), profile_number, chapter_id).await {
+        Ok((completed, total)) => HttpResponse::Ok().json(Completion { completed, total }),
+        Err(e) => BoardsError::from(e.context("Error getting chapter completion")).error_response(),
+    }
+}
+
+/// GET a user's completion (maps with a verified score out of all maps) for a game.
+///
+///   Example endpoint  -> /profile/76561198040982247/completion/game/1
+#[get("/profile/{profile_number}/completion/game/{game_id}")]
+async fn get_game_completion(
+    pool: web::Data<PgPool>,
+    info: web::Path<(String, i32)>,
+) -> impl Responder {
+    let (profile_number, game_id) = info.into_inner();
+    match Users::get_game_completion(pool.get_ref(), profile_number, game_id).await {
+        Ok((completed, total)) => HttpResponse::Ok().json(Completion { completed, total }),
+        Err(e) => BoardsError::from(e.context("Error getting game completion")).error_response(),
+    }
+}
+
+/// GET a summary of a user's coop activity: maps completed, WRs held, total submissions and most frequent partner.
+///
+///   Example endpoint  -> /profile/76561198040982247/coop
+#[get("/profile/{profile_number}/coop")]
+async fn get_coop_summary(
+    pool: web::Data<PgPool>,
+    profile_number: web::Path<String>,
+) -> impl Responder {
+    match Users::get_coop_summary(pool.get_ref(), profile_number.into_inner()).await {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(e) => BoardsError::from(e.context("Error getting coop summary")).error_response(),
+    }
+}
+
+/// GET every partner a user has run coop with, and how many runs they share, most frequent first.
+///
+///   Example endpoint  -> /profile/76561198040982247/coop/partners
+#[get("/profile/{profile_number}/coop/partners")]
+async fn get_coop_partners(
+    pool: web::Data<PgPool>,
+    profile_number: web::Path<String>,
+) -> impl Responder {
+    match Users::get_coop_partners(pool.get_ref(), profile_number.into_inner()).await {
+        Ok(partners) => HttpResponse::Ok().json(partners),
+        Err(e) => BoardsError::from(e.context("Error getting coop partners")).error_response(),
+    }
+}
+
+/// GET a user's rank and percentile on every SP or coop map they have a score on.
+///
+/// OPTIONAL PARAMETER is_mp, defaults to `false` (SP maps).
+///
+///   Example endpoint  -> /profile/76561198040982247/percentiles
+///                     -> /profile/76561198040982247/percentiles?is_mp=true
+#[get("/profile/{profile_number}/percentiles")]
+async fn get_percentiles(
+    pool: web::Data<PgPool>,
+    profile_number: web::Path<String>,
+    query: web::Query<PercentileParams>,
+) -> impl Responder {
+    let is_mp = query.into_inner().is_mp.unwrap_or(false);
+    match Users::get_percentiles(pool.get_ref(), profile_number.into_inner(), is_mp).await {
+        Ok(percentiles) => HttpResponse::Ok().json(percentiles),
+        Err(e) => BoardsError::from(e.context("Error getting percentiles")).error_response(),
+    }
+}
+
+/// GET a user's best run on every SP or coop map they have a score on, keyed by map_id, for the profile's score grid.
+///
+/// OPTIONAL PARAMETER is_mp, defaults to `false` (SP maps).
+///
+///   Example endpoint  -> /profile/76561198040982247/bests
+///                     -> /profile/76561198040982247/bests?is_mp=true
+#[get("/profile/{profile_number}/bests")]
+async fn get_bests(
+    pool: web::Data<PgPool>,
+    profile_number: web::Path<String>,
+    query: web::Query<PercentileParams>,
+) -> impl Responder {
+    let is_mp = query.into_inner().is_mp.unwrap_or(false);
+    match Users::get_bests(pool.get_ref(), profile_number.into_inner(), is_mp).await {
+        Ok(bests) => HttpResponse::Ok().json(bests),
+        Err(e) => BoardsError::from(e.context("Error getting bests")).error_response(),
+    }
+}
+
+/// GET a user's longest streak of consecutive WR-setting submissions, and the streak they're currently on.
+///
+///   Example endpoint  -> /profile/76561198040982247/wr_streak
+#[get("/profile/{profile_number}/wr_streak")]
+async fn get_wr_streak(
+    pool: web::Data<PgPool>,
+    profile_number: web::Path<String>,
+) -> impl Responder {
+    match Users::get_wr_streak(pool.get_ref(), profile_number.into_inner()).await {
+        Ok(streak) => HttpResponse::Ok().json(streak),
+        Err(e) => BoardsError::from(e.context("Error getting wr streak")).error_response(),
+    }
+}
+
+/// GET the maps a user has submitted the most runs on, most submissions first.
+///
+/// OPTIONAL PARAMETER limit, defaults to 10 (max 100).
+///
+///   Example endpoint  -> /profile/76561198040982247/most_played
+///                     -> /profile/76561198040982247/most_played?limit=5
+#[get("/profile/{profile_number}/most_played")]
+async fn get_most_played_maps(
+    pool: web::Data<PgPool>,
+    profile_number: web::Path<String>,
+    query: web::Query<MostPlayedParams>,
+) -> impl Responder {
+    let limit = query.into_inner().limit.unwrap_or(10).clamp(1, 100);
+    match Users::get_most_played_maps(pool.get_ref(), profile_number.into_inner(), limit).await {
+        Ok(maps) => HttpResponse::Ok().json(maps),
+        Err(e) => BoardsError::from(e.context("Error getting most played maps")).error_response(),
+    }
+}
+
+/// GET every changelog entry a user has submitted across all maps, most recent first.
+///
+/// OPTIONAL PARAMETERS limit, defaults to 200 (max 1000), before (cursor, only entries with a lower id are returned)
+/// and hide_banned, defaults to `false`.
+///
+///   Example endpoint  -> /profile/76561198040982247/changelog
+///                     -> /profile/76561198040982247/changelog?limit=50&before=157604&hide_banned=true
+#[get("/profile/{profile_number}/changelog")]
+async fn get_profile_changelog(
+    pool: web::Data<PgPool>,
+    profile_number: web::Path<String>,
+    query: web::Query<ProfileChangelogParams>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let limit = query.limit.unwrap_or(200).clamp(1, 1000);
+    let hide_banned = query.hide_banned.unwrap_or(false);
+    match Changelog::get_all_for_profile(
+        pool.get_ref(),
+        profile_number.into_inner(),
+        Some(limit),
+        query.before,
+        hide_banned,
+    )
+    .await
+    {
+        Ok(entries) => HttpResponse::Ok().json(entries),
+        Err(e) => BoardsError::from(e.context("Error getting profile changelog")).error_response(),
+    }
+}
+
 // let mut points: Vec<&PointsProfileWrapper> = Vec::new();
 // let points_hm = cache.points.lock().await;
 // for i in 1..16 {
@@ -1,7 +1,9 @@
 use crate::controllers::changelog::build_filtered_changelog;
 use crate::models::models::*;
+use crate::tools::helpers::QueryFilters;
 use anyhow::{bail, Result};
-use sqlx::PgPool;
+use sqlx::postgres::PgRow;
+use sqlx::{Executor, PgPool, Postgres, Row};
 
 impl Admin {
     pub async fn get_admin_page(
@@ -9,14 +11,15 @@ impl Admin {
         params: ChangelogQueryParams,
     ) -> Result<Option<Vec<ChangelogPage>>> {
         // TODO: Add a ratio of verified/non-verified scores, # banned per-user.
-        let mut additional_filters: Vec<String> =
-            vec!["cl.banned = 'true' OR cl.verified = 'false' OR u.banned = 'true'".to_string()];
-        let query_string =
-            match build_filtered_changelog(pool, params, Some(&mut additional_filters)).await {
+        let mut additional_filters = QueryFilters::new();
+        additional_filters.push("cl.banned = True OR cl.verified = False OR u.banned = True");
+        let (query_string, filters) =
+            match build_filtered_changelog(pool, params, Some(additional_filters)).await {
                 Ok(s) => s,
                 Err(e) => bail!(e),
             };
-        let res = sqlx::query_as::<_, ChangelogPage>(&query_string)
+        let res = filters
+            .bind_query_as(sqlx::query_as::<_, ChangelogPage>(&query_string))
             .fetch_all(pool)
             .await;
         match res {
@@ -78,4 +81,66 @@ impl Admin {
 
         Ok(Some(res))
     }
+    /// Returns the admin level of a user, errors if the user doesn't exist or isn't an admin.
+    ///
+    /// Accepts either a pool or a transaction, so it can guard changes made within a transaction.
+    pub async fn check_admin<'e, E>(executor: E, profile_number: &str) -> Result<i32>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let admin: Option<i32> =
+            sqlx::query(r#"SELECT admin FROM "p2boards".users WHERE profile_number = $1"#)
+                .bind(profile_number)
+                .map(|row: PgRow| row.get(0))
+                .fetch_optional(executor)
+                .await?;
+        match admin {
+            Some(level) if level > 0 => Ok(level),
+            _ => bail!("User {} is not an admin", profile_number),
+        }
+    }
+    /// Records an action taken by an admin in the audit log, returns the id of the audit entry.
+    ///
+    /// Accepts either a pool or a transaction, so the audit entry can be committed alongside the change it describes.
+    pub async fn log_admin_action<'e, E>(
+        executor: E,
+        admin_profile_number: &str,
+        action: &str,
+        target: Option<String>,
+        details: Option<String>,
+    ) -> Result<i64>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let res = sqlx::query(
+            r#"
+                INSERT INTO "p2boards".admin_audit 
+                (admin_profile_number, action, target, details)
+                VALUES ($1, $2, $3, $4)
+                RETURNING id"#,
+        )
+        .bind(admin_profile_number)
+        .bind(action)
+        .bind(target)
+        .bind(details)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(executor)
+        .await?;
+        Ok(res)
+    }
+    /// Returns entries from the admin audit log, most recent first.
+    pub async fn get_audit_log(pool: &PgPool, limit: i64, offset: i64) -> Result<Vec<AdminAudit>> {
+        let res = sqlx::query_as::<_, AdminAudit>(
+            r#"
+                SELECT * FROM "p2boards".admin_audit
+                ORDER BY id DESC
+                LIMIT $1
+                OFFSET $2"#,
+        )
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
 }
@@ -0,0 +1,17 @@
+use crate::models::models::*;
+use anyhow::Result;
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+
+impl Categories {
+    /// Returns the ids of all valid categories for a given map.
+    pub async fn get_category_ids(pool: &PgPool, map_id: String) -> Result<Vec<i32>> {
+        let res =
+            sqlx::query(r#"SELECT id FROM "p2boards".categories WHERE map_id = $1 ORDER BY id"#)
+                .bind(map_id)
+                .map(|row: PgRow| row.get(0))
+                .fetch_all(pool)
+                .await?;
+        Ok(res)
+    }
+}
@@ -1,11 +1,25 @@
 use anyhow::{Result, bail};
+use std::sync::Arc;
 use std::collections::HashMap;
+use std::str::FromStr;
 use sqlx::postgres::PgRow;
-use sqlx::{Row, PgPool};
-use chrono::NaiveDateTime;
+use sqlx::{Encode, Executor, Postgres, Row, PgPool, Transaction, Type};
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use arrow::array::{ArrayRef, BooleanArray, Int32Array, Int64Array, StringArray, TimestampMillisecondArray};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use crate::controllers::maps::{DEFAULT_CAT, DEFAULT_CAT_FROM};
 use crate::models::models::*;
+use crate::tools::helpers::{escape_like, QueryFilters};
 
 // Implementations of associated functions for Changelog
+/// Hours a submission's idempotency key is remembered for, retries after this insert a new entry.
+#[allow(dead_code)]
+pub const IDEMPOTENCY_WINDOW_HOURS: i32 = 24;
+/// The most entries a single changelog export returns, used when no `limit` is given.
+pub const EXPORT_LIMIT: u32 = 1_000_000;
+
 impl Changelog {
     pub async fn get_changelog(pool: &PgPool, cl_id: i64) -> Result<Option<Changelog>> {
         let res = sqlx::query_as::<_, Changelog>(r#"SELECT * FROM "p2boards".changelog WHERE id = $1"#)
@@ -14,7 +28,6 @@ impl Changelog {
             .await?;
         Ok(Some(res))
     }
-    #[allow(dead_code)]
     pub async fn get_demo_id_from_changelog(pool: &PgPool, cl_id: i64) -> Result<Option<i64>> {
         let res = sqlx::query(r#"SELECT demo_id FROM "p2boards".changelog WHERE id = $1"#)
             .bind(cl_id)
@@ -47,16 +60,21 @@ impl Changelog {
             None => Ok(false),
         }
     }
-    /// Returns a vec of changelog for a user's PB history on a given SP map.
-    pub async fn get_sp_pb_history(pool: &PgPool, profile_number: String, map_id: String) -> Result<Vec<Changelog>> {
+    /// Returns a page of a user's PB history on a given SP map, most recent first.
+    pub async fn get_sp_pb_history(pool: &PgPool, profile_number: String, map_id: String, limit: i64, offset: i64) -> Result<Vec<Changelog>> {
         let res = sqlx::query_as::<_, Changelog>(r#" 
                 SELECT * 
                 FROM "p2boards".changelog
                 WHERE changelog.profile_number = $1
                 AND changelog.map_id = $2
-                ORDER BY changelog.timestamp DESC NULLS LAST"#)
+                AND changelog.archived = False
+                ORDER BY changelog.timestamp DESC NULLS LAST, changelog.id DESC
+                LIMIT $3
+                OFFSET $4"#)
             .bind(profile_number)
             .bind(map_id)
+            .bind(limit)
+            .bind(offset)
             .fetch_all(pool)
             .await;
         match res{
@@ -64,12 +82,71 @@ impl Changelog {
             Err(e) => Err(anyhow::Error::new(e).context("Could not find SP PB History")),
         }
     }
+    /// Returns the total # of entries in a user's PB history on a given SP map, for paging through [Changelog::get_sp_pb_history].
+    pub async fn get_sp_pb_history_count(pool: &PgPool, profile_number: String, map_id: String) -> Result<i64> {
+        let res = sqlx::query(r#"
+                SELECT COUNT(*)
+                FROM "p2boards".changelog
+                WHERE changelog.profile_number = $1
+                AND changelog.map_id = $2
+                AND changelog.archived = False"#)
+            .bind(profile_number)
+            .bind(map_id)
+            .map(|row: PgRow| row.get(0))
+            .fetch_one(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns a user's current best verified, non-banned entry on a map & category, `None` if they have no such entry.
+    #[allow(dead_code)]
+    pub async fn get_current_pb(
+        pool: &PgPool,
+        profile_number: String,
+        map_id: String,
+        category_id: i32,
+    ) -> Result<Option<Changelog>> {
+        Changelog::get_best_entry(pool, profile_number, map_id, category_id, true).await
+    }
+    /// [Changelog::get_current_pb], with `verified_only` unset runs still pending verification count as well.
+    pub async fn get_best_entry(
+        pool: &PgPool,
+        profile_number: String,
+        map_id: String,
+        category_id: i32,
+        verified_only: bool,
+    ) -> Result<Option<Changelog>> {
+        let res = sqlx::query_as::<_, Changelog>(
+            r#"
+                SELECT *
+                FROM "p2boards".changelog
+                WHERE changelog.profile_number = $1
+                AND changelog.map_id = $2
+                AND changelog.category_id = $3
+                AND changelog.banned = False
+                AND (changelog.verified = True OR NOT $4)
+                AND changelog.archived = False
+                ORDER BY changelog.score ASC, changelog.timestamp ASC NULLS LAST, changelog.id ASC
+                LIMIT 1"#,
+        )
+        .bind(profile_number)
+        .bind(map_id)
+        .bind(category_id)
+        .bind(verified_only)
+        .fetch_optional(pool)
+        .await?;
+        Ok(res)
+    }
     /// Deletes all references to a demo_id in `changelog`
-    pub async fn delete_references_to_demo(pool: &PgPool, demo_id: i64) -> Result<Vec<i64>> {
+    ///
+    /// Accepts either a pool or a transaction, so the references can be removed alongside the demo.
+    pub async fn delete_references_to_demo<'e, E>(executor: E, demo_id: i64) -> Result<Vec<i64>>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         let res: Vec<i64> = sqlx::query(r#"UPDATE "p2boards".changelog SET demo_id = NULL WHERE demo_id = $1 RETURNING id;"#)
             .bind(demo_id)
             .map(|row: PgRow| {row.get(0)})
-            .fetch_all(pool)
+            .fetch_all(executor)
             .await?;
         // eprintln!("{:#?}", res);
         Ok(res)
@@ -85,64 +162,1078 @@ impl Changelog {
         Ok(res)
     }
     /// Insert a new changelog entry.
+    ///
+    /// Entries are unique on (profile_number, map_id, category_id, score, timestamp), if the entry already exists the existing id is returned instead.
+    /// Entries without a timestamp are compared as equal, so they're deduplicated too.
+    /// The lookup is retried if a concurrent insert of the same entry isn't visible yet, so re-imports are idempotent.
     pub async fn insert_changelog(pool: &PgPool, cl: ChangelogInsert) -> Result<i64> {
-        // TODO: https://stackoverflow.com/questions/4448340/postgresql-duplicate-key-violates-unique-constraint
-        let mut res: i64 = 0; 
-        let _ = sqlx::query(r#"
-                INSERT INTO "p2boards".changelog 
-                (timestamp, profile_number, score, map_id, demo_id, banned, 
-                youtube_id, coop_id, post_rank, pre_rank, submission, note,
-                category_id, score_delta, verified, admin_note) VALUES 
-                ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
-                RETURNING id"#)
-            .bind(cl.timestamp).bind(cl.profile_number).bind(cl.score).bind(cl.map_id) // TODO: There has GOT to be a better way to do this... https://crates.io/crates/sqlxinsert ?
-            .bind(cl.demo_id).bind(cl.banned).bind(cl.youtube_id).bind(cl.coop_id).bind(cl.post_rank)
-            .bind(cl.pre_rank).bind(cl.submission).bind(cl.note).bind(cl.category_id)
-            .bind(cl.score_delta).bind(cl.verified).bind(cl.admin_note)
-            .map(|row: PgRow|{res = row.get(0)})
-            .fetch_one(pool)
-            .await?;
+        let mut tx = pool.begin().await?;
+        let id = Changelog::insert_changelog_tx(&mut tx, cl).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+    /// [Changelog::insert_changelog] as part of a larger transaction, see [crate::tools::helpers::with_transaction].
+    pub async fn insert_changelog_tx(tx: &mut Transaction<'_, Postgres>, cl: ChangelogInsert) -> Result<i64> {
+        const ATTEMPTS: usize = 3;
+        for _ in 0..ATTEMPTS {
+            let res: Option<i64> = sqlx::query(r#"
+                    WITH inserted AS (
+                        INSERT INTO "p2boards".changelog 
+                        (timestamp, profile_number, score, map_id, demo_id, banned, 
+                        youtube_id, coop_id, post_rank, pre_rank, submission, note,
+                        category_id, score_delta, verified, admin_note) VALUES 
+                        ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+                        ON CONFLICT (profile_number, map_id, category_id, score, COALESCE("timestamp", '-infinity'::timestamp)) DO NOTHING
+                        RETURNING id)
+                    SELECT id FROM inserted
+                    UNION ALL
+                    SELECT id FROM "p2boards".changelog
+                        WHERE timestamp IS NOT DISTINCT FROM $1 AND profile_number = $2 AND score = $3
+                        AND map_id = $4 AND category_id = $13
+                    LIMIT 1"#)
+                .bind(cl.timestamp).bind(&cl.profile_number).bind(cl.score).bind(&cl.map_id) // TODO: There has GOT to be a better way to do this... https://crates.io/crates/sqlxinsert ?
+                .bind(cl.demo_id).bind(cl.banned).bind(&cl.youtube_id).bind(cl.coop_id).bind(cl.post_rank)
+                .bind(cl.pre_rank).bind(cl.submission).bind(&cl.note).bind(cl.category_id)
+                .bind(cl.score_delta).bind(cl.verified).bind(&cl.admin_note)
+                .map(|row: PgRow| {row.get(0)})
+                .fetch_optional(&mut *tx)
+                .await?;
+            if let Some(id) = res {
+                return Ok(id);
+            }
+        }
+        bail!("Could not insert or find changelog entry for {} on {}", cl.profile_number, cl.map_id)
+    }
+    /// Returns the changelog entry created by an earlier submission with the same idempotency key for this user.
+    /// `None` if the key hasn't been used, or was used more than [IDEMPOTENCY_WINDOW_HOURS] ago.
+    #[allow(dead_code)]
+    pub async fn get_idempotent_submission(
+        pool: &PgPool,
+        profile_number: &str,
+        key: &str,
+    ) -> Result<Option<i64>> {
+        let res = sqlx::query(
+            r#"
+                SELECT cl_id FROM "p2boards".submission_keys
+                WHERE profile_number = $1 AND idempotency_key = $2
+                AND created_at > now() - ($3 * INTERVAL '1 hour')"#,
+        )
+        .bind(profile_number)
+        .bind(key)
+        .bind(IDEMPOTENCY_WINDOW_HOURS)
+        .map(|row: PgRow| row.get(0))
+        .fetch_optional(pool)
+        .await?;
         Ok(res)
     }
-    /// Updates all fields (except ID) for a given changelog entry. Returns the updated Changelog struct.
-    pub async fn update_changelog(pool: &PgPool, update: Changelog) -> Result<bool> {
+    /// Inserts a changelog entry and claims its idempotency key in the same transaction, so retries return it instead of inserting again.
+    ///
+    /// The user's expired keys are cleared first, so an expired key can be reused. A concurrent submission with the same key
+    /// blocks on the key until this one commits, then rolls back its own insert and returns the id recorded for the key.
+    #[allow(dead_code)]
+    pub async fn insert_changelog_with_key(
+        pool: &PgPool,
+        cl: ChangelogInsert,
+        key: &str,
+    ) -> Result<i64> {
+        let profile_number = cl.profile_number.clone();
+        let mut tx = pool.begin().await?;
+        sqlx::query(
+            r#"
+                DELETE FROM "p2boards".submission_keys
+                WHERE profile_number = $1
+                AND created_at <= now() - ($2 * INTERVAL '1 hour')"#,
+        )
+        .bind(&profile_number)
+        .bind(IDEMPOTENCY_WINDOW_HOURS)
+        .execute(&mut tx)
+        .await?;
+        let id = Changelog::insert_changelog_tx(&mut tx, cl).await?;
+        let claimed: Option<i64> = sqlx::query(
+            r#"
+                INSERT INTO "p2boards".submission_keys (profile_number, idempotency_key, cl_id)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (profile_number, idempotency_key) DO NOTHING
+                RETURNING cl_id"#,
+        )
+        .bind(&profile_number)
+        .bind(key)
+        .bind(id)
+        .map(|row: PgRow| row.get(0))
+        .fetch_optional(&mut tx)
+        .await?;
+        if claimed.is_some() {
+            tx.commit().await?;
+            return Ok(id);
+        }
+        // Another submission claimed the key first, it has committed by the time the conflict is reported.
+        tx.rollback().await?;
+        match Changelog::get_idempotent_submission(pool, &profile_number, key).await? {
+            Some(id) => Ok(id),
+            None => bail!(
+                "Idempotency key {} was claimed, but no entry is recorded for it",
+                key
+            ),
+        }
+    }
+    /// Updates all fields (except ID) for a given changelog entry.
+    ///
+    /// The row as it was before the update is saved to the entry's edit history (see [Changelog::get_edit_history]).
+    /// `edited_by` must be an admin, the edit is recorded in the audit log.
+    pub async fn update_changelog(pool: &PgPool, update: Changelog, edited_by: &str) -> Result<bool> {
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, edited_by).await?;
+        let _ = sqlx::query(r#"
+                INSERT INTO "p2boards".changelog_history (cl_id, edited_by, previous)
+                SELECT id, $2, to_jsonb(changelog)::text FROM "p2boards".changelog
+                WHERE id = $1"#)
+            .bind(update.id)
+            .bind(edited_by)
+            .execute(&mut tx)
+            .await?;
         let _ = sqlx::query(r#"UPDATE "p2boards".changelog 
                 SET timestamp = $1, profile_number = $2, score = $3, map_id = $4, demo_id = $5, banned = $6, 
                 youtube_id = $7, coop_id = $8, post_rank = $9, pre_rank = $10, submission = $11, note = $12,
-                category_id = $13, score_delta = $14, verified = $15, admin_note = $16
-                WHERE id = $17"#)
+                category_id = $13, score_delta = $14, verified = $15, admin_note = $16, archived = $17
+                WHERE id = $18"#)
             .bind(update.timestamp).bind(update.profile_number).bind(update.score).bind(update.map_id) 
             .bind(update.demo_id).bind(update.banned).bind(update.youtube_id).bind(update.coop_id)
             .bind(update.post_rank).bind(update.pre_rank).bind(update.submission).bind(update.note)
             .bind(update.category_id).bind(update.score_delta).bind(update.verified).bind(update.admin_note)
-            .bind(update.id)
-            .fetch_optional(pool)
+            .bind(update.archived).bind(update.id)
+            .fetch_optional(&mut tx)
             .await?;
+        Admin::log_admin_action(&mut tx, edited_by, "update_changelog", Some(update.id.to_string()), None).await?;
+        tx.commit().await?;
         Ok(true)
     }
+    /// Sets the note for a changelog entry, returns whether the note changed. See [Changelog::set_field].
+    pub async fn set_note(pool: &PgPool, cl_id: i64, note: Option<String>, admin_profile_number: &str) -> Result<bool> {
+        Changelog::set_field(pool, cl_id, "note", note, admin_profile_number, "set_note").await
+    }
+    /// Sets the youtube_id for a changelog entry, returns whether the youtube_id changed. See [Changelog::set_field].
+    pub async fn set_youtube_id(pool: &PgPool, cl_id: i64, youtube_id: Option<String>, admin_profile_number: &str) -> Result<bool> {
+        Changelog::set_field(pool, cl_id, "youtube_id", youtube_id, admin_profile_number, "set_youtube_id").await
+    }
+    /// Bans or unbans a single changelog entry, returns whether the banned status changed. See [Changelog::set_field].
+    pub async fn set_banned(pool: &PgPool, cl_id: i64, banned: bool, admin_profile_number: &str) -> Result<bool> {
+        let action = if banned { "ban" } else { "unban" };
+        Changelog::set_field(pool, cl_id, "banned", banned, admin_profile_number, action).await
+    }
+    /// Updates exactly one column of a changelog entry, leaving every other column untouched.
+    ///
+    /// Only admins can make the change. Nothing is written if the value is unchanged, otherwise the previous row
+    /// is saved to the edit history and the change is recorded in the audit log, all within a single transaction.
+    async fn set_field<T>(pool: &PgPool, cl_id: i64, column: &'static str, value: T, admin_profile_number: &str, action: &str) -> Result<bool>
+    where
+        T: for<'q> Encode<'q, Postgres> + Type<Postgres> + Send + Clone + 'static,
+    {
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, admin_profile_number).await?;
+        let _ = sqlx::query(&format!(r#"
+                INSERT INTO "p2boards".changelog_history (cl_id, edited_by, previous)
+                SELECT id, $2, to_jsonb(changelog)::text FROM "p2boards".changelog
+                WHERE id = $1 AND {column} IS DISTINCT FROM $3"#, column = column))
+            .bind(cl_id)
+            .bind(admin_profile_number)
+            .bind(value.clone())
+            .execute(&mut tx)
+            .await?;
+        let changed = sqlx::query(&format!(r#"
+                UPDATE "p2boards".changelog SET {column} = $1
+                WHERE id = $2 AND {column} IS DISTINCT FROM $1"#, column = column))
+            .bind(value)
+            .bind(cl_id)
+            .execute(&mut tx)
+            .await?
+            .rows_affected() > 0;
+        if changed {
+            Admin::log_admin_action(&mut tx, admin_profile_number, action, Some(cl_id.to_string()), None).await?;
+        }
+        tx.commit().await?;
+        Ok(changed)
+    }
+    /// Returns every previous version of a changelog entry, most recent edit first.
+    pub async fn get_edit_history(pool: &PgPool, cl_id: i64) -> Result<Vec<ChangelogHistory>> {
+        let rows: Vec<(i64, i64, NaiveDateTime, Option<String>, String)> = sqlx::query_as(r#"
+                SELECT id, cl_id, timestamp, edited_by, previous
+                FROM "p2boards".changelog_history
+                WHERE cl_id = $1
+                ORDER BY timestamp DESC, id DESC"#)
+            .bind(cl_id)
+            .fetch_all(pool)
+            .await?;
+        rows.into_iter()
+            .map(|(id, cl_id, timestamp, edited_by, previous)| {
+                Ok(ChangelogHistory { id, cl_id, timestamp, edited_by, previous: serde_json::from_str(&previous)? })
+            })
+            .collect()
+    }
     /// Updates demo_id
+    #[allow(dead_code)]
     pub async fn update_demo_id_in_changelog(pool: &PgPool, cl_id: i64, demo_id: i64) -> Result<bool> {
+        let mut tx = pool.begin().await?;
+        let res = Changelog::update_demo_id_in_changelog_tx(&mut tx, cl_id, demo_id).await?;
+        tx.commit().await?;
+        Ok(res)
+    }
+    /// [Changelog::update_demo_id_in_changelog] as part of a larger transaction, see [crate::tools::helpers::with_transaction].
+    pub async fn update_demo_id_in_changelog_tx(tx: &mut Transaction<'_, Postgres>, cl_id: i64, demo_id: i64) -> Result<bool> {
         let _ = sqlx::query(r#"UPDATE "p2boards".changelog 
                 SET demo_id = $1 WHERE id = $2;"#)
             .bind(demo_id)
             .bind(cl_id)
-            .fetch_optional(pool)
+            .fetch_optional(tx)
             .await?;
         Ok(true)
     }
-    pub async fn delete_changelog(pool: &PgPool, cl_id: i64) -> Result<bool> {
+    /// Deletes a changelog entry, returns whether it existed.
+    ///
+    /// `deleted_by` must be an admin, the deletion is recorded in the audit log.
+    #[allow(dead_code)]
+    pub async fn delete_changelog(pool: &PgPool, cl_id: i64, deleted_by: &str) -> Result<bool> {
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, deleted_by).await?;
         let res = sqlx::query_as::<_, Changelog>(r#"DELETE FROM "p2boards".changelog WHERE id = $1 RETURNING *"#)
             .bind(cl_id)
-            .fetch_one(pool)
+            .fetch_one(&mut tx)
             .await;
-        match res {
-            Ok(_) => Ok(true),
+        let deleted = match res {
+            Ok(cl) => {
+                let details = format!("{} on {} by {}", cl.score, cl.map_id, cl.profile_number);
+                Admin::log_admin_action(&mut tx, deleted_by, "delete_changelog", Some(cl_id.to_string()), Some(details)).await?;
+                true
+            },
             Err(e) => {
                 eprintln!("Error deleting changelog -> {}", e);
-                Ok(false)
+                false
             },
-        }
+        };
+        tx.commit().await?;
+        Ok(deleted)
     }  
+    /// Exports the filtered changelog as an Arrow IPC file for use in analytics tooling (pandas/polars).
+    ///
+    /// Uses the same filters as [ChangelogPage::get_changelog_page], returns the bytes of the file.
+    /// The export isn't held to the page size, `limit` defaults to (and is capped at) [EXPORT_LIMIT].
+    pub async fn export_arrow(pool: &PgPool, mut params: ChangelogQueryParams) -> Result<Vec<u8>> {
+        params.limit = Some(params.limit.map_or(EXPORT_LIMIT, |limit| limit.min(EXPORT_LIMIT)));
+        let changelog: Vec<ChangelogPage> = ChangelogPage::get_changelog_page(pool, params).await?.unwrap_or_default();
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("timestamp", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+            Field::new("profile_number", DataType::Utf8, false),
+            Field::new("score", DataType::Int32, false),
+            Field::new("map_id", DataType::Utf8, false),
+            Field::new("demo_id", DataType::Int64, true),
+            Field::new("banned", DataType::Boolean, false),
+            Field::new("youtube_id", DataType::Utf8, true),
+            Field::new("previous_id", DataType::Int64, true),
+            Field::new("coop_id", DataType::Int64, true),
+            Field::new("post_rank", DataType::Int32, true),
+            Field::new("pre_rank", DataType::Int32, true),
+            Field::new("submission", DataType::Boolean, false),
+            Field::new("note", DataType::Utf8, true),
+            Field::new("category_id", DataType::Int32, false),
+            Field::new("score_delta", DataType::Int32, true),
+            Field::new("verified", DataType::Boolean, true),
+            Field::new("admin_note", DataType::Utf8, true),
+            Field::new("map_name", DataType::Utf8, false),
+            Field::new("user_name", DataType::Utf8, false),
+            Field::new("avatar", DataType::Utf8, false),
+        ]));
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(Int64Array::from(changelog.iter().map(|cl| cl.id).collect::<Vec<i64>>())),
+            Arc::new(TimestampMillisecondArray::from(changelog.iter().map(|cl| cl.timestamp.map(|t| t.and_utc().timestamp_millis())).collect::<Vec<Option<i64>>>())),
+            Arc::new(StringArray::from(changelog.iter().map(|cl| cl.profile_number.as_str()).collect::<Vec<&str>>())),
+            Arc::new(Int32Array::from(changelog.iter().map(|cl| cl.score).collect::<Vec<i32>>())),
+            Arc::new(StringArray::from(changelog.iter().map(|cl| cl.map_id.as_str()).collect::<Vec<&str>>())),
+            Arc::new(Int64Array::from(changelog.iter().map(|cl| cl.demo_id).collect::<Vec<Option<i64>>>())),
+            Arc::new(BooleanArray::from(changelog.iter().map(|cl| cl.banned).collect::<Vec<bool>>())),
+            Arc::new(StringArray::from(changelog.iter().map(|cl| cl.youtube_id.as_deref()).collect::<Vec<Option<&str>>>())),
+            Arc::new(Int64Array::from(changelog.iter().map(|cl| cl.previous_id).collect::<Vec<Option<i64>>>())),
+            Arc::new(Int64Array::from(changelog.iter().map(|cl| cl.coop_id).collect::<Vec<Option<i64>>>())),
+            Arc::new(Int32Array::from(changelog.iter().map(|cl| cl.post_rank).collect::<Vec<Option<i32>>>())),
+            Arc::new(Int32Array::from(changelog.iter().map(|cl| cl.pre_rank).collect::<Vec<Option<i32>>>())),
+            Arc::new(BooleanArray::from(changelog.iter().map(|cl| cl.submission).collect::<Vec<bool>>())),
+            Arc::new(StringArray::from(changelog.iter().map(|cl| cl.note.as_deref()).collect::<Vec<Option<&str>>>())),
+            Arc::new(Int32Array::from(changelog.iter().map(|cl| cl.category_id).collect::<Vec<i32>>())),
+            Arc::new(Int32Array::from(changelog.iter().map(|cl| cl.score_delta).collect::<Vec<Option<i32>>>())),
+            Arc::new(BooleanArray::from(changelog.iter().map(|cl| cl.verified).collect::<Vec<Option<bool>>>())),
+            Arc::new(StringArray::from(changelog.iter().map(|cl| cl.admin_note.as_deref()).collect::<Vec<Option<&str>>>())),
+            Arc::new(StringArray::from(changelog.iter().map(|cl| cl.map_name.as_str()).collect::<Vec<&str>>())),
+            Arc::new(StringArray::from(changelog.iter().map(|cl| cl.user_name.as_str()).collect::<Vec<&str>>())),
+            Arc::new(StringArray::from(changelog.iter().map(|cl| cl.avatar.as_str()).collect::<Vec<&str>>())),
+        ];
+        let batch = RecordBatch::try_new(schema.clone(), columns)?;
+        let mut buf: Vec<u8> = Vec::new();
+        {
+            let mut writer = FileWriter::try_new(&mut buf, &schema)?;
+            writer.write(&batch)?;
+            writer.finish()?;
+        }
+        Ok(buf)
+    }
+    /// Returns every score that was a world record on a map when it was set, for a given category, oldest first.
+    ///
+    /// Banned and unverified entries are excluded, so durations reflect the corrected history.
+    /// Each record ends when the next one is set, the current world record is measured up to now.
+    pub async fn get_wr_history(pool: &PgPool, map_id: String, cat_id: i32) -> Result<Vec<StandingWr>> {
+        // Every entry that beat all earlier entries on the map was a world record at the time it was set.
+        let wrs = sqlx::query(r#"
+                SELECT wr.id, wr.profile_number, wr.user_name, wr.score, wr.timestamp FROM (
+                    SELECT cl.id, cl.profile_number, cl.score, cl.timestamp,
+                        CASE
+                        WHEN u.board_name IS NULL
+                            THEN u.steam_name
+                        WHEN u.board_name IS NOT NULL
+                            THEN u.board_name
+                        END user_name,
+                        MIN(cl.score) OVER (
+                            ORDER BY cl.timestamp, cl.id
+                            ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING) AS previous_best
+                    FROM "p2boards".changelog AS cl
+                    INNER JOIN "p2boards".users AS u ON (u.profile_number = cl.profile_number)
+                        WHERE cl.map_id = $1
+                        AND cl.category_id = $2
+                        AND cl.banned = False
+                        AND cl.verified = True
+                        AND u.banned = False
+                        AND cl.timestamp IS NOT NULL
+                ) wr
+                WHERE wr.previous_best IS NULL OR wr.score < wr.previous_best
+                ORDER BY wr.timestamp, wr.id"#)
+            .bind(map_id)
+            .bind(cat_id)
+            .map(|row: PgRow| StandingWr {
+                cl_id: row.get(0),
+                profile_number: row.get(1),
+                user_name: row.get(2),
+                score: row.get(3),
+                timestamp: row.get(4),
+                ended: None,
+                duration_secs: 0,
+            })
+            .fetch_all(pool)
+            .await?;
+        let now = Utc::now().naive_utc();
+        let ends: Vec<Option<NaiveDateTime>> = wrs.iter().skip(1).map(|wr| Some(wr.timestamp)).chain(std::iter::once(None)).collect();
+        let history = wrs.into_iter().zip(ends)
+            .map(|(mut wr, ended)| {
+                wr.duration_secs = (ended.unwrap_or(now) - wr.timestamp).num_seconds();
+                wr.ended = ended;
+                wr
+            })
+            .collect();
+        Ok(history)
+    }
+    /// Finds the world record that held the #1 spot for the longest time on a map, for a given category.
+    ///
+    /// Built on [Changelog::get_wr_history]. Returns `None` if the map has no valid scores.
+    pub async fn get_longest_standing_wr(pool: &PgPool, map_id: String, cat_id: i32) -> Result<Option<StandingWr>> {
+        let history = Changelog::get_wr_history(pool, map_id, cat_id).await?;
+        Ok(history.into_iter().max_by_key(|wr| wr.duration_secs))
+    }
+    /// Returns the world record score on a map as it stood at a given time, the best valid score set at or before `at`.
+    ///
+    /// Uses the same rules as [Changelog::get_wr_history], banned and unverified entries are excluded. Returns `None` if no score existed yet.
+    pub async fn get_wr_at_time(pool: &PgPool, map_id: String, category_id: i32, at: NaiveDateTime) -> Result<Option<i32>> {
+        let res = sqlx::query(r#"
+                SELECT MIN(cl.score)
+                FROM "p2boards".changelog AS cl
+                INNER JOIN "p2boards".users AS u ON (u.profile_number = cl.profile_number)
+                    WHERE cl.map_id = $1
+                    AND cl.category_id = $2
+                    AND cl.timestamp <= $3
+                    AND cl.banned = False
+                    AND cl.verified = True
+                    AND u.banned = False"#)
+            .bind(map_id)
+            .bind(category_id)
+            .bind(at)
+            .map(|row: PgRow| row.get(0))
+            .fetch_one(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Bans (or unbans) all the given changelog entries in a single statement, within a transaction.
+    ///
+    /// `admin_profile_number` must belong to an admin, otherwise nothing is changed. Each entry is recorded in the admin audit log.
+    /// Ranks on every affected board are recomputed in the same transaction, see [Changelog::recompute_ranks].
+    /// Returns the distinct map/category pairs that were affected.
+    pub async fn set_banned_bulk(pool: &PgPool, ids: &[i64], banned: bool, admin_profile_number: String) -> Result<Vec<MapCategory>> {
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, &admin_profile_number).await?;
+        let res = sqlx::query_as::<_, MapCategory>(r#"
+                WITH updated AS (
+                    UPDATE "p2boards".changelog
+                    SET banned = $1
+                    WHERE id = ANY($2)
+                    RETURNING map_id, category_id)
+                SELECT DISTINCT map_id, category_id FROM updated
+                ORDER BY map_id, category_id"#)
+            .bind(banned)
+            .bind(ids)
+            .fetch_all(&mut tx)
+            .await?;
+        for board in res.iter() {
+            Changelog::recompute_ranks(&mut tx, &board.map_id, board.category_id, None).await?;
+        }
+        let action = if banned { "ban" } else { "unban" };
+        for id in ids.iter() {
+            Admin::log_admin_action(&mut tx, &admin_profile_number, action, Some(id.to_string()), None).await?;
+        }
+        tx.commit().await?;
+        Ok(res)
+    }
+    /// Verifies every pending (unverified, non-banned) entry on a map for a given category, within a single transaction.
+    ///
+    /// Each verified entry is recorded in the admin audit log. Returns the ids of all entries that were verified.
+    pub async fn verify_pending_for_map(pool: &PgPool, map_id: String, cat_id: i32, admin_profile_number: String) -> Result<Vec<i64>> {
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, &admin_profile_number).await?;
+        let ids: Vec<i64> = sqlx::query(r#"
+                UPDATE "p2boards".changelog
+                SET verified = True
+                WHERE map_id = $1
+                AND category_id = $2
+                AND verified = False
+                AND banned = False
+                RETURNING id"#)
+            .bind(map_id.clone())
+            .bind(cat_id)
+            .map(|row: PgRow| {row.get(0)})
+            .fetch_all(&mut tx)
+            .await?;
+        let details = format!("Bulk verified pending scores on map {} (category {})", map_id, cat_id);
+        for id in ids.iter() {
+            Admin::log_admin_action(&mut tx, &admin_profile_number, "verify", Some(id.to_string()), Some(details.clone())).await?;
+        }
+        tx.commit().await?;
+        Ok(ids)
+    }
+    /// Archives the intermediate non-PB runs in a player's history on a map, within a single transaction.
+    ///
+    /// PBs, the first and last entries, banned entries and any run that ever held the WR are never archived.
+    /// If `keep_pbs_only` is `false`, non-PB runs with a demo or video attached are also kept.
+    /// Archived entries stay in the database, they are only hidden from the player's PB history.
+    ///
+    /// The compaction is recorded in the admin audit log. Returns the number of entries archived.
+    pub async fn compact_history(pool: &PgPool, profile_number: String, map_id: String, cat_id: i32, keep_pbs_only: bool, admin_profile_number: String) -> Result<u64> {
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, &admin_profile_number).await?;
+        // A run held the WR if no other valid run on the board was faster at the time it was set.
+        let history: Vec<(i64, i32, bool, bool, bool)> = sqlx::query(r#"
+                SELECT cl.id, cl.score, cl.banned,
+                    (cl.demo_id IS NOT NULL OR cl.youtube_id IS NOT NULL) AS has_proof,
+                    (cl.post_rank = 1 OR NOT EXISTS (
+                        SELECT 1 FROM "p2boards".changelog other
+                        WHERE other.map_id = cl.map_id
+                        AND other.category_id = cl.category_id
+                        AND other.banned = False
+                        AND other.score < cl.score
+                        AND other.timestamp <= cl.timestamp)) AS held_wr
+                FROM "p2boards".changelog cl
+                WHERE cl.profile_number = $1
+                AND cl.map_id = $2
+                AND cl.category_id = $3
+                AND cl.archived = False
+                ORDER BY cl.timestamp ASC NULLS FIRST, cl.id ASC"#)
+            .bind(profile_number.clone())
+            .bind(map_id.clone())
+            .bind(cat_id)
+            .map(|row: PgRow| (row.get(0), row.get(1), row.get(2), row.get(3), row.get(4)))
+            .fetch_all(&mut tx)
+            .await?;
+        let mut best: Option<i32> = None;
+        let mut to_archive: Vec<i64> = Vec::new();
+        let last = history.len().saturating_sub(1);
+        for (i, (id, score, banned, has_proof, held_wr)) in history.into_iter().enumerate() {
+            if banned {
+                continue;
+            }
+            let is_pb = match best { Some(best) => score < best, None => true };
+            if is_pb {
+                best = Some(score);
+            }
+            if i == 0 || i == last || is_pb || held_wr || (!keep_pbs_only && has_proof) {
+                continue;
+            }
+            to_archive.push(id);
+        }
+        if to_archive.is_empty() {
+            tx.commit().await?;
+            return Ok(0);
+        }
+        let res = sqlx::query(r#"UPDATE "p2boards".changelog SET archived = True WHERE id = ANY($1)"#)
+            .bind(&to_archive)
+            .execute(&mut tx)
+            .await?;
+        let details = format!("Archived {} entries on map {} (category {})", res.rows_affected(), map_id, cat_id);
+        Admin::log_admin_action(&mut tx, &admin_profile_number, "compact_history", Some(profile_number), Some(details)).await?;
+        tx.commit().await?;
+        Ok(res.rows_affected())
+    }
+    /// Returns up to `limit` unbanned entries that are missing rank data, grouped by map & category, oldest first.
+    ///
+    /// `pre_rank` is only expected on entries that improved on a previous run, so a first run with no `pre_rank` isn't missing anything.
+    pub async fn get_entries_missing_ranks(pool: &PgPool, limit: i64) -> Result<Vec<Changelog>> {
+        let res = sqlx::query_as::<_, Changelog>(r#"
+                SELECT *
+                FROM "p2boards".changelog
+                WHERE changelog.banned = False
+                AND (changelog.post_rank IS NULL
+                    OR (changelog.pre_rank IS NULL AND changelog.previous_id IS NOT NULL))
+                ORDER BY changelog.map_id, changelog.category_id, changelog.timestamp ASC NULLS FIRST, changelog.id ASC
+                LIMIT $1"#)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Fills in `pre_rank` & `post_rank` for up to `limit` entries from [Changelog::get_entries_missing_ranks], one map & category at a time.
+    /// Only the missing rank is computed, a rank that's already stored is left as is.
+    ///
+    /// Ranks are calculated as they would have been when the run was set, against other players' verified, unbanned runs up to that point.
+    /// `pre_rank` is the rank of the run the entry improved on. Returns the number of entries updated.
+    /// Errors if `admin_profile_number` isn't an admin, the backfill is recorded in the admin audit log.
+    pub async fn backfill_ranks(pool: &PgPool, limit: i64, admin_profile_number: &str) -> Result<u64> {
+        Admin::check_admin(pool, admin_profile_number).await?;
+        let entries = Changelog::get_entries_missing_ranks(pool, limit).await?;
+        let mut boards: Vec<((String, i32), Vec<i64>)> = Vec::new();
+        for entry in entries.into_iter() {
+            match boards.last_mut() {
+                Some((board, ids)) if *board == (entry.map_id.clone(), entry.category_id) => ids.push(entry.id),
+                _ => boards.push(((entry.map_id, entry.category_id), vec![entry.id])),
+            }
+        }
+        let mut updated = 0;
+        for ((map_id, cat_id), ids) in boards.into_iter() {
+            updated += Changelog::update_ranks(pool, &map_id, cat_id, Some(&ids), true).await?;
+        }
+        if updated > 0 {
+            Admin::log_admin_action(pool, admin_profile_number, "backfill_ranks", None, Some(format!("Updated {} entries", updated))).await?;
+        }
+        Ok(updated)
+    }
+    /// Recomputes `pre_rank` & `post_rank` on a map & category, for the given `ids` or every entry on the board if `None`.
+    ///
+    /// See [Changelog::backfill_ranks] for how ranks are calculated. Returns the number of entries updated.
+    pub async fn recompute_ranks<'e, E>(executor: E, map_id: &str, cat_id: i32, ids: Option<&[i64]>) -> Result<u64>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        Changelog::update_ranks(executor, map_id, cat_id, ids, false).await
+    }
+    /// [Changelog::recompute_ranks], with `fill_only` set ranks that are already stored are kept and only missing ones are computed.
+    async fn update_ranks<'e, E>(executor: E, map_id: &str, cat_id: i32, ids: Option<&[i64]>, fill_only: bool) -> Result<u64>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
+        let res = sqlx::query(r#"
+                UPDATE "p2boards".changelog AS cl
+                SET post_rank = COALESCE(CASE WHEN $4 THEN cl.post_rank END, (
+                        SELECT 1 + COUNT(DISTINCT other.profile_number)
+                        FROM "p2boards".changelog AS other
+                        INNER JOIN "p2boards".users ON (users.profile_number = other.profile_number)
+                        WHERE other.map_id = cl.map_id
+                        AND other.category_id = cl.category_id
+                        AND other.profile_number <> cl.profile_number
+                        AND other.score < cl.score
+                        AND other.banned = False
+                        AND other.verified = True
+                        AND users.banned = False
+                        AND (cl.timestamp IS NULL OR other.timestamp <= cl.timestamp))::int),
+                    pre_rank = CASE
+                        WHEN $4 AND cl.pre_rank IS NOT NULL
+                            THEN cl.pre_rank
+                        WHEN cl.previous_id IS NULL
+                            THEN NULL
+                        ELSE (
+                            SELECT 1 + COUNT(DISTINCT other.profile_number)
+                            FROM "p2boards".changelog AS other
+                            INNER JOIN "p2boards".users ON (users.profile_number = other.profile_number)
+                            WHERE other.map_id = cl.map_id
+                            AND other.category_id = cl.category_id
+                            AND other.profile_number <> cl.profile_number
+                            AND other.score < (SELECT previous.score FROM "p2boards".changelog AS previous WHERE previous.id = cl.previous_id)
+                            AND other.banned = False
+                            AND other.verified = True
+                            AND users.banned = False
+                            AND (cl.timestamp IS NULL OR other.timestamp < cl.timestamp))::int
+                    END
+                WHERE ($1::bigint[] IS NULL OR cl.id = ANY($1))
+                AND cl.map_id = $2
+                AND cl.category_id = $3"#)
+            .bind(ids)
+            .bind(map_id)
+            .bind(cat_id)
+            .bind(fill_only)
+            .execute(executor)
+            .await?;
+        Ok(res.rows_affected())
+    }
+    /// Rewrites `score_delta` as `score - previous.score` for up to `limit` entries with a `previous_id` where it doesn't match.
+    ///
+    /// Improvements are stored as negative deltas, older web submissions stored them as positive. Returns the number of entries updated.
+    /// Errors if `admin_profile_number` isn't an admin, the backfill is recorded in the admin audit log.
+    pub async fn backfill_score_deltas(pool: &PgPool, limit: i64, admin_profile_number: &str) -> Result<u64> {
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, admin_profile_number).await?;
+        let res = sqlx::query(r#"
+                UPDATE "p2boards".changelog AS cl
+                SET score_delta = cl.score - previous.score
+                FROM "p2boards".changelog AS previous
+                WHERE previous.id = cl.previous_id
+                AND cl.id IN (
+                    SELECT changelog.id
+                    FROM "p2boards".changelog
+                    INNER JOIN "p2boards".changelog AS prev ON (prev.id = changelog.previous_id)
+                    WHERE changelog.score_delta IS DISTINCT FROM changelog.score - prev.score
+                    ORDER BY changelog.id ASC
+                    LIMIT $1)"#)
+            .bind(limit)
+            .execute(&mut tx)
+            .await?;
+        let updated = res.rows_affected();
+        if updated > 0 {
+            Admin::log_admin_action(&mut tx, admin_profile_number, "backfill_score_deltas", None, Some(format!("Updated {} entries", updated))).await?;
+        }
+        tx.commit().await?;
+        Ok(updated)
+    }
+    /// Returns the current best score for each of the given players on a map, with their rank relative to the full board.
+    ///
+    /// Players without a valid score on the map are omitted from the result.
+    pub async fn get_best_scores(pool: &PgPool, map_id: String, category_id: i32, profile_numbers: &[String]) -> Result<Vec<MapBoardEntry>> {
+        let res = sqlx::query_as::<_, MapBoardEntry>(r#"
+                SELECT * FROM (
+                    SELECT t.timestamp, t.CL_profile_number, t.score, t.demo_id, t.youtube_id,
+                        t.submission, t.note, t.category_id,
+                        CASE
+                        WHEN t.board_name IS NULL
+                            THEN t.steam_name
+                        WHEN t.board_name IS NOT NULL
+                            THEN t.board_name
+                        END user_name,
+                        t.avatar,
+                        RANK() OVER (ORDER BY t.score) AS rank
+                    FROM (
+                        SELECT DISTINCT ON (changelog.profile_number) 
+                            changelog.profile_number as CL_profile_number,
+                            users.profile_number as U_profile_number, *
+                        FROM "p2boards".changelog
+                        INNER JOIN "p2boards".users ON (users.profile_number = changelog.profile_number)
+                            WHERE map_id = $1
+                            AND users.banned = False
+                            AND changelog.verified = True
+                            AND changelog.banned = False
+                            AND changelog.category_id = $2
+                        ORDER BY changelog.profile_number, changelog.score ASC
+                    ) t
+                ) board
+                WHERE board.CL_profile_number = ANY($3)
+                ORDER BY board.rank"#)
+            .bind(map_id)
+            .bind(category_id)
+            .bind(profile_numbers)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns the current ties on a map, every group of players whose best scores are identical.
+    ///
+    /// Each group is ordered by who set the score first, and groups are ordered by rank. Scores held by a single player are omitted.
+    pub async fn get_tied_scores(pool: &PgPool, map_id: String, category_id: i32) -> Result<Vec<Vec<MapBoardEntry>>> {
+        let entries = sqlx::query_as::<_, MapBoardEntry>(r#"
+                SELECT * FROM (
+                    SELECT t.timestamp, t.CL_profile_number, t.score, t.demo_id, t.youtube_id,
+                        t.submission, t.note, t.category_id,
+                        CASE
+                        WHEN t.board_name IS NULL
+                            THEN t.steam_name
+                        WHEN t.board_name IS NOT NULL
+                            THEN t.board_name
+                        END user_name,
+                        t.avatar,
+                        RANK() OVER (ORDER BY t.score) AS rank,
+                        COUNT(*) OVER (PARTITION BY t.score) AS tied
+                    FROM (
+                        SELECT DISTINCT ON (changelog.profile_number) 
+                            changelog.profile_number as CL_profile_number,
+                            users.profile_number as U_profile_number, *
+                        FROM "p2boards".changelog
+                        INNER JOIN "p2boards".users ON (users.profile_number = changelog.profile_number)
+                            WHERE map_id = $1
+                            AND users.banned = False
+                            AND changelog.verified = True
+                            AND changelog.banned = False
+                            AND changelog.category_id = $2
+                        ORDER BY changelog.profile_number, changelog.score ASC, changelog.timestamp ASC
+                    ) t
+                ) board
+                WHERE board.tied > 1
+                ORDER BY board.rank, board.timestamp ASC NULLS LAST"#)
+            .bind(map_id)
+            .bind(category_id)
+            .fetch_all(pool)
+            .await?;
+        let mut ties: Vec<Vec<MapBoardEntry>> = Vec::new();
+        for entry in entries {
+            match ties.last_mut() {
+                Some(group) if group[0].score == entry.score => group.push(entry),
+                _ => ties.push(vec![entry]),
+            }
+        }
+        Ok(ties)
+    }
+    /// Returns a summary of a single (UTC) day on the boards, counting only verified, unbanned entries.
+    ///
+    /// `new_wrs` counts entries that were the best score on their map & category at the time they were set.
+    pub async fn get_daily_summary(pool: &PgPool, date: NaiveDate) -> Result<DailySummary> {
+        let (submissions, new_wrs, active_players): (i64, i64, i64) = sqlx::query(r#"
+                SELECT COUNT(*),
+                    COUNT(*) FILTER (WHERE cl.post_rank = 1 OR NOT EXISTS (
+                        SELECT 1 FROM "p2boards".changelog other
+                        WHERE other.map_id = cl.map_id
+                        AND other.category_id = cl.category_id
+                        AND other.banned = False
+                        AND other.verified = True
+                        AND other.score <= cl.score
+                        AND other.timestamp < cl.timestamp)),
+                    COUNT(DISTINCT cl.profile_number)
+                FROM "p2boards".changelog cl
+                WHERE cl.timestamp >= $1
+                AND cl.timestamp < $1 + interval '1 day'
+                AND cl.banned = False
+                AND cl.verified = True"#)
+            .bind(date)
+            .map(|row: PgRow| (row.get(0), row.get(1), row.get(2)))
+            .fetch_one(pool)
+            .await?;
+        let top_improvement = sqlx::query_as::<_, TopImprovement>(r#"
+                SELECT cl.id, cl.profile_number, COALESCE(users.board_name, users.steam_name) AS user_name,
+                    cl.map_id, maps.name AS map_name, cl.score, -cl.score_delta AS improvement
+                FROM "p2boards".changelog cl
+                INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                INNER JOIN "p2boards".maps ON (maps.steam_id = cl.map_id)
+                WHERE cl.timestamp >= $1
+                AND cl.timestamp < $1 + interval '1 day'
+                AND cl.banned = False
+                AND cl.verified = True
+                AND cl.score_delta < 0
+                ORDER BY cl.score_delta ASC, cl.id ASC
+                LIMIT 1"#)
+            .bind(date)
+            .fetch_optional(pool)
+            .await?;
+        Ok(DailySummary { date, submissions, new_wrs, active_players, top_improvement })
+    }
+    /// Picks a random verified, unbanned run to highlight, weighted towards records and runs with proof.
+    ///
+    /// Every run has a weight of 1, +4 if it was a WR when set (`post_rank = 1`), +2 for a demo and +2 for a video.
+    /// The randomness is seeded from the run's id and `date`, so the same run is picked for the whole day.
+    pub async fn get_random_notable(pool: &PgPool, date: NaiveDate) -> Result<ChangelogPage> {
+        // Weighted sampling (Efraimidis-Spirakis), the run with the smallest -ln(u) / weight wins, u is derived from an md5 of the id & date.
+        let query_string = format!(r#"
+                SELECT cl.id, cl.timestamp, cl.profile_number, cl.score, cl.map_id, cl.demo_id, cl.banned,
+                cl.youtube_id, cl.previous_id, cl.coop_id, cl.post_rank, cl.pre_rank, cl.submission, cl.note,
+                cl.category_id, cl.score_delta, cl.verified, cl.admin_note, map.name AS map_name,
+                COALESCE(u.board_name, u.steam_name) AS user_name, u.avatar
+                {}
+                WHERE cl.banned = False
+                AND cl.verified = True
+                AND u.banned = False
+                ORDER BY -ln((('x' || substr(md5(cl.id::text || $1::text), 1, 8))::bit(32)::bigint + 1) / 4294967297.0)
+                    / (1 + CASE WHEN cl.post_rank = 1 THEN 4 ELSE 0 END
+                        + CASE WHEN cl.demo_id IS NOT NULL THEN 2 ELSE 0 END
+                        + CASE WHEN cl.youtube_id IS NOT NULL THEN 2 ELSE 0 END) ASC,
+                    cl.id ASC
+                LIMIT 1"#, FILTERED_CHANGELOG_FROM);
+        let res = sqlx::query_as::<_, ChangelogPage>(&query_string)
+            .bind(date)
+            .fetch_one(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns a changelog entry in the context of its board, with up to `window` entries above and below it.
+    ///
+    /// The board is every other player's best score on the entry's map & category, plus the entry itself.
+    /// Entries near the top or bottom of the board are shifted so a full `2 * window + 1` entries are returned where possible.
+    /// Returns an empty vec if the entry does not exist.
+    pub async fn get_score_with_neighbors(pool: &PgPool, cl_id: i64, window: i64) -> Result<Vec<MapBoardEntry>> {
+        let res = sqlx::query_as::<_, MapBoardEntry>(r#"
+                WITH entry AS (
+                    SELECT * FROM "p2boards".changelog WHERE changelog.id = $1
+                ), board AS (
+                    SELECT cl.id, cl.timestamp, cl.profile_number AS cl_profile_number, cl.score, cl.demo_id,
+                        cl.youtube_id, cl.submission, cl.note, cl.category_id,
+                        COALESCE(users.board_name, users.steam_name) AS user_name, users.avatar
+                    FROM (
+                        SELECT DISTINCT ON (changelog.profile_number) changelog.*
+                        FROM "p2boards".changelog, entry
+                        WHERE changelog.map_id = entry.map_id
+                        AND changelog.category_id = entry.category_id
+                        AND changelog.profile_number <> entry.profile_number
+                        AND changelog.verified = True
+                        AND changelog.banned = False
+                        ORDER BY changelog.profile_number, changelog.score ASC
+                    ) cl
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    WHERE users.banned = False
+                    UNION ALL
+                    SELECT entry.id, entry.timestamp, entry.profile_number, entry.score, entry.demo_id,
+                        entry.youtube_id, entry.submission, entry.note, entry.category_id,
+                        COALESCE(users.board_name, users.steam_name), users.avatar
+                    FROM entry
+                    INNER JOIN "p2boards".users ON (users.profile_number = entry.profile_number)
+                ), ranked AS (
+                    SELECT board.*,
+                        RANK() OVER (ORDER BY board.score) AS rank,
+                        ROW_NUMBER() OVER (ORDER BY board.score, board.timestamp NULLS LAST, board.id) AS position,
+                        COUNT(*) OVER () AS total
+                    FROM board
+                ), target AS (
+                    SELECT GREATEST(1, LEAST(ranked.position - $2, ranked.total - 2 * $2)) AS first
+                    FROM ranked
+                    WHERE ranked.id = $1
+                )
+                SELECT ranked.*
+                FROM ranked, target
+                WHERE ranked.position BETWEEN target.first AND target.first + 2 * $2
+                ORDER BY ranked.position"#)
+            .bind(cl_id)
+            .bind(window)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns the most recent changelog entries for a specific category across all maps.
+    ///
+    /// Uses `before` as a cursor, only entries with an id lower than it are returned. Banned entries are excluded.
+    pub async fn get_category_feed(pool: &PgPool, cat_id: i32, limit: Option<u32>, before: Option<i64>) -> Result<Vec<ChangelogPage>> {
+        let params = ChangelogQueryParams { limit: Some(limit.unwrap_or(200)), last: before, ..Default::default() };
+        let mut additional_filters = QueryFilters::new();
+        additional_filters.push_bind("cl.category_id = {}", cat_id).push("cl.banned = False");
+        let (query_string, filters) = build_filtered_changelog(pool, params, Some(additional_filters)).await?;
+        let res = filters.bind_query_as(sqlx::query_as::<_, ChangelogPage>(&query_string))
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns every changelog entry a player has submitted across all maps, most recent first.
+    ///
+    /// Banned entries are included unless `hide_banned` is set, so players can see their own banned runs.
+    /// Uses `before` as a cursor like [Changelog::get_category_feed].
+    pub async fn get_all_for_profile(pool: &PgPool, profile_number: String, limit: Option<u32>, before: Option<i64>, hide_banned: bool) -> Result<Vec<ChangelogPage>> {
+        let params = ChangelogQueryParams { limit: Some(limit.unwrap_or(200)), last: before, profile_number: Some(profile_number), ..Default::default() };
+        let mut additional_filters = QueryFilters::new();
+        if hide_banned {
+            additional_filters.push("cl.banned = False");
+        }
+        let (query_string, filters) = build_filtered_changelog(pool, params, Some(additional_filters)).await?;
+        let res = filters.bind_query_as(sqlx::query_as::<_, ChangelogPage>(&query_string))
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns up to `limit` entries with an id greater than `last_id`, oldest first, for clients that keep a local copy of the changelog.
+    ///
+    /// Unlike paging with `first`, which returns the newest entries above the cursor, the entries directly after `last_id` come first,
+    /// so a client can call this repeatedly with the last id it received until nothing is returned. Banned entries are included, as in the changelog.
+    pub async fn get_since_id(pool: &PgPool, last_id: i64, limit: i64) -> Result<Vec<ChangelogPage>> {
+        let query_string = format!(r#"
+                SELECT cl.id, cl.timestamp, cl.profile_number, cl.score, cl.map_id, cl.demo_id, cl.banned,
+                cl.youtube_id, cl.previous_id, cl.coop_id, cl.post_rank, cl.pre_rank, cl.submission, cl.note,
+                cl.category_id, cl.score_delta, cl.verified, cl.admin_note, map.name AS map_name,
+                COALESCE(u.board_name, u.steam_name) AS user_name, u.avatar
+                {}
+                WHERE cl.id > $1
+                ORDER BY cl.id ASC
+                LIMIT $2"#, FILTERED_CHANGELOG_FROM);
+        let res = sqlx::query_as::<_, ChangelogPage>(&query_string)
+            .bind(last_id)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns entries that improved on the player's previous score by at least `min_delta_ratio` of it in a single submission, most recent first.
+    ///
+    /// The ratio is `-score_delta` over the score of the `previous_id` entry, e.g. `0.2` flags runs at least 20% faster than the run they replaced.
+    /// Entries without a previous run, and banned entries, are excluded. Uses `before` as a cursor like [Changelog::get_category_feed].
+    pub async fn get_large_jumps(pool: &PgPool, min_delta_ratio: f64, limit: Option<u32>, before: Option<i64>) -> Result<Vec<ChangelogPage>> {
+        let params = ChangelogQueryParams { limit: Some(limit.unwrap_or(200)), last: before, ..Default::default() };
+        let mut additional_filters = QueryFilters::new();
+        additional_filters
+            .push("cl.banned = False")
+            .push("cl.score_delta < 0")
+            .push_bind(r#"EXISTS (SELECT 1 FROM "p2boards".changelog AS prev
+                WHERE prev.id = cl.previous_id
+                AND prev.score > 0
+                AND -cl.score_delta::float8 / prev.score >= {})"#, min_delta_ratio);
+        let (query_string, filters) = build_filtered_changelog(pool, params, Some(additional_filters)).await?;
+        let res = filters.bind_query_as(sqlx::query_as::<_, ChangelogPage>(&query_string))
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns the players with the largest total improvement since a given time, most improved first.
+    ///
+    /// Improvement is the sum of all negative `score_delta` values (time saved) on valid scores in the window.
+    pub async fn get_top_improvers(pool: &PgPool, since: NaiveDateTime, limit: i64) -> Result<Vec<TopImprover>> {
+        let res = sqlx::query_as::<_, TopImprover>(r#"
+                SELECT changelog.profile_number,
+                    CASE
+                    WHEN users.board_name IS NULL
+                        THEN users.steam_name
+                    WHEN users.board_name IS NOT NULL
+                        THEN users.board_name
+                    END user_name,
+                    users.avatar,
+                    SUM(-changelog.score_delta) AS improvement,
+                    COUNT(*) AS improvements
+                FROM "p2boards".changelog
+                INNER JOIN "p2boards".users ON (users.profile_number = changelog.profile_number)
+                WHERE changelog.timestamp >= $1
+                AND changelog.score_delta < 0
+                AND changelog.banned = False
+                AND changelog.verified = True
+                AND users.banned = False
+                GROUP BY changelog.profile_number, users.board_name, users.steam_name, users.avatar
+                ORDER BY improvement DESC, improvements DESC
+                LIMIT $2"#)
+            .bind(since)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns the # of current WRs held by every player with at least one, keyed by profile_number.
+    ///
+    /// Computed for all players in a single grouped query. Tied WRs count for every player in the tie.
+    pub async fn get_wr_counts_for_all(pool: &PgPool, category_scope: CategoryScope) -> Result<HashMap<String, i64>> {
+        let res = sqlx::query(&format!(r#"
+                WITH valid AS (
+                    SELECT cl.profile_number, cl.map_id, cl.category_id, cl.score
+                    FROM "p2boards".changelog cl
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    INNER JOIN (SELECT maps.steam_id, {} {}) AS defaults ON (defaults.steam_id = cl.map_id)
+                    WHERE cl.banned = False
+                    AND cl.verified = True
+                    AND users.banned = False
+                    AND ($1 = False OR cl.category_id = defaults.default_cat_id)
+                ), bests AS (
+                    SELECT map_id, category_id, MIN(score) AS best
+                    FROM valid
+                    GROUP BY map_id, category_id
+                )
+                SELECT valid.profile_number, COUNT(DISTINCT (valid.map_id, valid.category_id))
+                FROM valid
+                INNER JOIN bests ON (bests.map_id = valid.map_id
+                    AND bests.category_id = valid.category_id
+                    AND bests.best = valid.score)
+                GROUP BY valid.profile_number"#, DEFAULT_CAT, DEFAULT_CAT_FROM))
+            .bind(category_scope == CategoryScope::Default)
+            .map(|row: PgRow| (row.get(0), row.get(1)))
+            .fetch_all(pool)
+            .await?;
+        Ok(res.into_iter().collect())
+    }
+    /// Returns the # of submissions on a map for each of the last `days` days (including today), oldest first.
+    ///
+    /// Days without submissions are included with a count of 0, so the result always has `days` entries.
+    pub async fn get_daily_counts(pool: &PgPool, map_id: String, days: i32) -> Result<Vec<DailyCount>> {
+        let res = sqlx::query_as::<_, DailyCount>(r#"
+                SELECT days.day::date AS date, COUNT(changelog.id) AS count
+                FROM generate_series(CURRENT_DATE - ($2 - 1), CURRENT_DATE, interval '1 day') AS days(day)
+                LEFT JOIN "p2boards".changelog ON (changelog.timestamp::date = days.day::date
+                    AND changelog.map_id = $1
+                    AND changelog.banned = False)
+                GROUP BY days.day
+                ORDER BY days.day"#)
+            .bind(map_id)
+            .bind(days)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns the # of submissions made during each hour of the day (0-23, UTC), optionally only for a single player.
+    ///
+    /// Hours without submissions are included with a count of 0, so the result always has 24 entries.
+    /// Banned runs and entries without a timestamp are excluded.
+    pub async fn get_hourly_distribution(pool: &PgPool, profile_number: Option<String>) -> Result<Vec<HourlyCount>> {
+        let res = sqlx::query_as::<_, HourlyCount>(r#"
+                SELECT hours.hour, COUNT(changelog.id) AS count
+                FROM generate_series(0, 23) AS hours(hour)
+                LEFT JOIN "p2boards".changelog ON (EXTRACT(hour FROM changelog.timestamp) = hours.hour
+                    AND changelog.timestamp IS NOT NULL
+                    AND changelog.banned = False
+                    AND ($1::text IS NULL OR changelog.profile_number = $1))
+                GROUP BY hours.hour
+                ORDER BY hours.hour"#)
+            .bind(profile_number)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns all changelog entries whose `category_id` is not one of the valid categories for their map.
+    ///
+    /// Used to audit legacy entries left behind by map recategorization.
+    pub async fn get_invalid_category_entries(pool: &PgPool) -> Result<Vec<Changelog>> {
+        let res = sqlx::query_as::<_, Changelog>(r#"
+                SELECT changelog.*
+                FROM "p2boards".changelog
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM "p2boards".categories
+                    WHERE categories.id = changelog.category_id
+                    AND categories.map_id = changelog.map_id)
+                ORDER BY changelog.map_id, changelog.id"#)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns every coop bundle with inconsistent halves, ordered by coop_id.
+    ///
+    /// A bundle is invalid when a player is their own partner, when a half is missing (the first half always, the second whenever
+    /// a partner is set), or when the two halves disagree on the map, category or score. Used by moderators to clean up coop data.
+    pub async fn find_invalid_coop(pool: &PgPool) -> Result<Vec<CoopAnomaly>> {
+        let res = sqlx::query_as::<_, CoopAnomaly>(r#"
+                SELECT cb.id AS coop_id, cb.p_id1, cb.p_id2, cb.cl_id1, cb.cl_id2, checks.reason
+                FROM "p2boards".coop_bundled AS cb
+                LEFT JOIN "p2boards".changelog AS c1 ON (c1.id = cb.cl_id1)
+                LEFT JOIN "p2boards".changelog AS c2 ON (c2.id = cb.cl_id2)
+                CROSS JOIN LATERAL (VALUES
+                    ('self_partner', cb.p_id1 = cb.p_id2 OR c1.profile_number = c2.profile_number),
+                    ('missing_half', c1.id IS NULL OR (cb.p_id2 IS NOT NULL AND c2.id IS NULL)),
+                    ('mismatched_map', c1.map_id <> c2.map_id),
+                    ('mismatched_category', c1.category_id <> c2.category_id),
+                    ('mismatched_score', c1.score <> c2.score)
+                ) AS checks(reason, failed)
+                WHERE checks.failed
+                ORDER BY cb.id, checks.reason"#)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Searches for changelog entries whose `admin_note` contains the pattern (case-insensitive), up to `limit` of the most recent first.
+    ///
+    /// Lets moderators find runs they've tagged for follow-up, e.g. `segmented?`. The pattern is matched literally, `%` and `_` aren't wildcards.
+    /// Errors if `admin_profile_number` isn't an admin.
+    pub async fn search_admin_notes(pool: &PgPool, pattern: String, limit: i64, admin_profile_number: &str) -> Result<Vec<Changelog>> {
+        Admin::check_admin(pool, admin_profile_number).await?;
+        let query_pattern = format!("%{}%", escape_like(&pattern));
+        let res = sqlx::query_as::<_, Changelog>(r#"
+                SELECT * FROM "p2boards".changelog
+                WHERE LOWER(admin_note) LIKE LOWER($1) ESCAPE '\'
+                ORDER BY timestamp DESC NULLS LAST, id DESC
+                LIMIT $2"#)
+            .bind(query_pattern)
+            .bind(limit)
+            .fetch_all(pool)
+            .await?;
+        Ok(res)
+    }
 }
 
 impl ChangelogPage {
@@ -157,11 +1248,11 @@ impl ChangelogPage {
     ) -> Result<Option<Vec<ChangelogPage>>> {
         // TODO: Add additonal filters
         
-        let query_string = match build_filtered_changelog(pool, params, None).await {
+        let (query_string, filters) = match build_filtered_changelog(pool, params, None).await {
             Ok(s) => s,
             Err(e) => bail!(e),
         };
-        let res = sqlx::query_as::<_, ChangelogPage>(&query_string)
+        let res = filters.bind_query_as(sqlx::query_as::<_, ChangelogPage>(&query_string))
             .fetch_all(pool)
             .await;
         match res {
@@ -173,111 +1264,131 @@ impl ChangelogPage {
             }
         }
     }
+    /// Counts every changelog entry matching the filters of [ChangelogPage::get_changelog_page], for pagination.
+    ///
+    /// The `first`/`last` cursor and `limit` are ignored, so the total is the same for every page.
+    pub async fn count_filtered_changelog(pool: &PgPool, params: ChangelogQueryParams) -> Result<i64> {
+        let filters = changelog_filters(pool, &params).await?;
+        let query_string = format!("SELECT COUNT(*) {}{}", FILTERED_CHANGELOG_FROM, filters.where_clause());
+        let res = filters.bind_query(sqlx::query(&query_string))
+            .map(|row: PgRow| {row.get(0)})
+            .fetch_one(pool)
+            .await?;
+        Ok(res)
+    }
 }
 
-pub async fn build_filtered_changelog(pool: &PgPool, params: ChangelogQueryParams, additional_filters: Option<&mut Vec<String>>) -> Result<String> {
-    let mut query_string: String = String::from(
-        r#" 
-        SELECT cl.id, cl.timestamp, cl.profile_number, cl.score, cl.map_id, cl.demo_id, cl.banned, 
-        cl.youtube_id, cl.previous_id, cl.coop_id, cl.post_rank, cl.pre_rank, cl.submission, cl.note,
-        cl.category_id, cl.score_delta, cl.verified, cl.admin_note, map.name AS map_name,  
-        CASE
-            WHEN u.board_name IS NULL
-                THEN u.steam_name
-            WHEN u.board_name IS NOT NULL
-                THEN u.board_name
-        END user_name, u.avatar
+/// The tables (and joins) every filtered changelog query runs over, shared so the page and the count always agree.
+const FILTERED_CHANGELOG_FROM: &str = r#"
         FROM "p2boards".changelog AS cl
         INNER JOIN "p2boards".users AS u ON (u.profile_number = cl.profile_number)
         INNER JOIN "p2boards".maps AS map ON (map.steam_id = cl.map_id)
         INNER JOIN "p2boards".chapters AS chapter on (map.chapter_id = chapter.id)
-    "#,
-    );
-    let mut filters: Vec<String> = Vec::new();
+"#;
+
+/// Builds the conditions for the filters in [ChangelogQueryParams], excluding the `first`/`last` cursor and the limit.
+///
+/// Used by both [build_filtered_changelog] and [ChangelogPage::count_filtered_changelog].
+async fn changelog_filters(pool: &PgPool, params: &ChangelogQueryParams) -> Result<QueryFilters> {
+    let mut filters = QueryFilters::new();
     if let Some(coop) = params.coop {
         if !coop {
-            filters.push("chapter.is_multiplayer = False\n".to_string());
+            filters.push("chapter.is_multiplayer = False");
         } else if let Some(sp) = params.sp {
             if !sp {
-                filters.push("chapter.is_multiplayer = True\n".to_string());
+                filters.push("chapter.is_multiplayer = True");
             }
         }
     }
     if let Some(has_demo) = params.has_demo {
         if has_demo {
-            filters.push("cl.demo_id IS NOT NULL\n".to_string());
+            filters.push("cl.demo_id IS NOT NULL");
         } else {
-            filters.push("cl.demo_id IS NULL\n".to_string());
+            filters.push("cl.demo_id IS NULL");
         }
     }
     if let Some(yt) = params.yt {
         if yt {
-            filters.push("cl.youtube_id IS NOT NULL\n".to_string());
+            filters.push("cl.youtube_id IS NOT NULL");
         } else {
-            filters.push("cl.youtube_id IS NULL\n".to_string());
+            filters.push("cl.youtube_id IS NULL");
         }
     }
+    if let Some(proof_level) = &params.proof_level {
+        filters.push(proof_level.filter());
+    }
     if let Some(wr_gain) = params.wr_gain {
         if wr_gain {
-            filters.push("cl.post_rank = 1\n".to_string());
+            filters.push("cl.post_rank = 1");
         }
     }
-    if let Some(chamber) = params.chamber {
-        filters.push(format!("cl.map_id = '{}'\n", &chamber));
+    if let Some(pbs_only) = params.pbs_only {
+        if pbs_only {
+            // A PB if none of the player's earlier, unbanned runs on the same board were as fast.
+            filters.push(r#"NOT EXISTS (
+                SELECT 1 FROM "p2boards".changelog AS prior
+                WHERE prior.profile_number = cl.profile_number
+                AND prior.map_id = cl.map_id
+                AND prior.category_id = cl.category_id
+                AND prior.banned = False
+                AND prior.score <= cl.score
+                AND (prior.timestamp < cl.timestamp OR (prior.timestamp = cl.timestamp AND prior.id < cl.id)))"#);
+        }
     }
-    if let Some(profile_number) = params.profile_number {
-        filters.push(format!("cl.profile_number = {}\n", &profile_number));
-    } else if let Some(nick_name) = params.nick_name {
-        if let Some(profile_numbers) = Users::check_board_name(pool, nick_name.clone())
-            .await?
-            .as_mut()
-        {
-            if profile_numbers.len() == 1 {
-                filters.push(format!(
-                    "cl.profile_number = '{}'\n",
-                    &profile_numbers[0].to_string()
-                ));
-            } else {
-                let mut profile_str = format!(
-                    "(cl.profile_number = '{}'\n",
-                    &profile_numbers[0].to_string()
-                );
-                profile_numbers.remove(0);
-                for num in profile_numbers.iter() {
-                    profile_str.push_str(&format!(" OR cl.profile_number = '{}'\n", num));
-                }
-                profile_str.push(')');
-                filters.push(profile_str);
+    if let Some(chamber) = &params.chamber {
+        filters.push_bind("cl.map_id = {}", chamber.as_str());
+    }
+    if let Some(profile_number) = &params.profile_number {
+        filters.push_bind("cl.profile_number = {}", profile_number.as_str());
+    } else if let Some(nick_name) = &params.nick_name {
+        match Users::check_board_name(pool, nick_name.clone()).await? {
+            Some(profile_numbers) => {
+                filters.push_bind("cl.profile_number = ANY({})", profile_numbers);
             }
-        } else {
-            bail!("No users found with specified username pattern.");
+            None => bail!("No users found with specified username pattern."),
         }
     }
+    Ok(filters)
+}
+
+/// Builds the query for a page of the filtered changelog, returns the query alongside the filters that need to be bound to it.
+///
+/// `additional_filters` are added after the [ChangelogQueryParams] filters.
+pub async fn build_filtered_changelog(pool: &PgPool, params: ChangelogQueryParams, additional_filters: Option<QueryFilters>) -> Result<(String, QueryFilters)> {
+    let mut query_string: String = format!(
+        r#" 
+        SELECT cl.id, cl.timestamp, cl.profile_number, cl.score, cl.map_id, cl.demo_id, cl.banned, 
+        cl.youtube_id, cl.previous_id, cl.coop_id, cl.post_rank, cl.pre_rank, cl.submission, cl.note,
+        cl.category_id, cl.score_delta, cl.verified, cl.admin_note, map.name AS map_name,  
+        CASE
+            WHEN u.board_name IS NULL
+                THEN u.steam_name
+            WHEN u.board_name IS NOT NULL
+                THEN u.board_name
+        END user_name, u.avatar
+        {}"#,
+        FILTERED_CHANGELOG_FROM
+    );
+    let mut filters = changelog_filters(pool, &params).await?;
     if let Some(first) = params.first {
-        filters.push(format!("cl.id > {}\n", &first));
+        filters.push_bind("cl.id > {}", first);
     } else if let Some(last) = params.last {
-        filters.push(format!("cl.id < {}\n", &last));
+        filters.push_bind("cl.id < {}", last);
     }
     if let Some(additional_filters) = additional_filters {
         filters.append(additional_filters);
     }
-    // Build the statement based off the elements we added to our vector (used to make sure only first statement is WHERE, and additional are OR)
-    for (i, entry) in filters.iter().enumerate() {
-        if i == 0 {
-            query_string = format!("{} WHERE {}", query_string, entry);
-        } else {
-            query_string = format!("{} AND {}", query_string, entry);
-        }
-    }
+    query_string = format!("{}{}", query_string, filters.where_clause());
     //TODO: Maybe allow for custom order params????
-    query_string = format!("{} ORDER BY cl.timestamp DESC NULLS LAST\n", query_string);
+    // Entries can share a timestamp (bulk imports), so we tie-break on the id to keep paging with `first`/`last` stable.
+    query_string = format!("{} ORDER BY cl.timestamp DESC NULLS LAST, cl.id DESC\n", query_string);
     if let Some(limit) = params.limit {
         query_string = format!("{} LIMIT {}\n", query_string, limit);
     } else {
         // Default limit
         query_string = format!("{} LIMIT 200\n", query_string);
     }
-    Ok(query_string)
+    Ok((query_string, filters))
 }
 
 impl Default for ChangelogQueryParams {
@@ -294,28 +1405,77 @@ impl Default for ChangelogQueryParams {
             yt: None,
             first: None,
             last: None,
+            proof_level: None,
+            pbs_only: None,
         }
     }
 }
 
-impl ChangelogInsert {
-    pub async fn new_from_submission(
-        params: SubmissionChangelog,
-        cache: HashMap<String, i32>,
-    ) -> ChangelogInsert {
-        ChangelogInsert {
-            timestamp: match NaiveDateTime::parse_from_str(&params.timestamp, "%Y-%m-%d %H:%M:%S") {
-                Ok(val) => Some(val),
-                Err(_) => None,
-            },
-            profile_number: params.profile_number.clone(),
-            score: params.score,
-            map_id: params.map_id.clone(),
-            youtube_id: params.youtube_id,
-            note: params.note,
-            category_id: params.category_id.unwrap_or(cache[&params.map_id]),
-            submission: true,
-            ..Default::default()
+impl ChangelogQueryParams {
+    /// Builds the changelog filters from raw query string pairs, the single entry point for parsing them in handlers.
+    ///
+    /// Missing parameters are `None`, unknown parameters are ignored. On failure, the error is a [ValidationErrors]
+    /// with an [ValidationError::InvalidParameter] for every malformed value, it can be recovered with `downcast_ref`.
+    pub fn from_query_map(map: &HashMap<String, String>) -> Result<Self> {
+        let mut errors = Vec::new();
+        let params = ChangelogQueryParams {
+            limit: parse_param(map, "limit", &mut errors),
+            nick_name: map.get("nick_name").cloned(),
+            profile_number: map.get("profile_number").cloned(),
+            chamber: map.get("chamber").cloned(),
+            sp: parse_param(map, "sp", &mut errors),
+            coop: parse_param(map, "coop", &mut errors),
+            wr_gain: parse_param(map, "wr_gain", &mut errors),
+            has_demo: parse_param(map, "has_demo", &mut errors),
+            yt: parse_param(map, "yt", &mut errors),
+            first: parse_param(map, "first", &mut errors),
+            last: parse_param(map, "last", &mut errors),
+            proof_level: parse_param(map, "proof_level", &mut errors),
+            pbs_only: parse_param(map, "pbs_only", &mut errors),
+        };
+        if !errors.is_empty() {
+            return Err(ValidationErrors(errors).into());
         }
+        Ok(params)
     }
-}
\ No newline at end of file
+}
+
+/// Parses a single query parameter, recording an [ValidationError::InvalidParameter] if the value is malformed.
+fn parse_param<T: FromStr>(map: &HashMap<String, String>, param: &str, errors: &mut Vec<ValidationError>) -> Option<T> {
+    let value = map.get(param)?;
+    match value.parse() {
+        Ok(parsed) => Some(parsed),
+        Err(_) => {
+            errors.push(ValidationError::InvalidParameter(param.to_string(), value.clone()));
+            None
+        }
+    }
+}
+
+impl FromStr for ProofLevel {
+    type Err = anyhow::Error;
+    /// Accepts the same snake_case names as the query string, e.g. `demo_only`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(ProofLevel::None),
+            "demo_only" => Ok(ProofLevel::DemoOnly),
+            "video_only" => Ok(ProofLevel::VideoOnly),
+            "both" => Ok(ProofLevel::Both),
+            "any" => Ok(ProofLevel::Any),
+            _ => bail!("{} is not a valid proof level", s),
+        }
+    }
+}
+
+impl ProofLevel {
+    /// The SQL clause for the proof level, on the `cl` changelog alias used by [build_filtered_changelog].
+    pub fn filter(&self) -> &'static str {
+        match self {
+            ProofLevel::None => "cl.demo_id IS NULL AND cl.youtube_id IS NULL",
+            ProofLevel::DemoOnly => "cl.demo_id IS NOT NULL AND cl.youtube_id IS NULL",
+            ProofLevel::VideoOnly => "cl.demo_id IS NULL AND cl.youtube_id IS NOT NULL",
+            ProofLevel::Both => "cl.demo_id IS NOT NULL AND cl.youtube_id IS NOT NULL",
+            ProofLevel::Any => "(cl.demo_id IS NOT NULL OR cl.youtube_id IS NOT NULL)",
+        }
+    }
+}
@@ -55,7 +55,7 @@ impl Chapters {
     #[allow(dead_code)]
     pub async fn get_chapter_game(pool: &PgPool, chapter_id: i32) -> Result<Option<Games>> {
         let res = sqlx::query_as::<_, Games>(
-            r#"SELECT games.id, games.game_name 
+            r#"SELECT games.id, games.game_name, games.default_category
                 FROM "p2boards".games
                 INNER JOIN "p2boards".chapters ON (games.id = chapters.game_id)
                 WHERE chapters.id = $1"#,
@@ -1,7 +1,20 @@
 use crate::models::models::*;
-use anyhow::Result;
+use crate::tools::backblaze::{BackBlazeClient, RateLimited};
+use crate::tools::helpers::{compare_sar_versions, demo_file_name};
+use anyhow::{bail, Context, Result};
+use futures::future::join_all;
 use sqlx::postgres::PgRow;
-use sqlx::{PgPool, Row};
+use sqlx::{Executor, PgPool, Postgres, Row, Transaction};
+use std::cmp::Ordering;
+use std::time::Duration;
+use tokio::io::AsyncRead;
+
+/// The # of demos checked against BackBlaze concurrently during a storage audit.
+const AUDIT_BATCH_SIZE: usize = 25;
+/// How many times a rate limited check is retried, the wait doubles after every attempt.
+const AUDIT_MAX_RETRIES: u32 = 5;
+/// The wait before the first retry of a rate limited check.
+const AUDIT_BACKOFF: Duration = Duration::from_secs(1);
 
 impl Demos {
     /// Gets Demo information for a given demo_id
@@ -53,8 +66,306 @@ impl Demos {
                 .await?;
         Ok(res)
     }
+    /// Returns the ids of every demo that needs to be reparsed to be up to date with `min_sar_version`, ordered by id.
+    ///
+    /// Versions are compared with [compare_sar_versions] rather than lexically. Demos without a `sar_version` were never
+    /// parsed successfully, and demos with a version that isn't recognised can't be shown to be up to date, so both are included.
+    /// Nothing is reparsed or queued here, see `GET /admin/demos/pending_reparse`.
+    pub async fn get_older_than_version(pool: &PgPool, min_sar_version: &str) -> Result<Vec<i64>> {
+        if compare_sar_versions(min_sar_version, min_sar_version).is_none() {
+            return Err(ValidationErrors(vec![ValidationError::InvalidParameter(
+                "min_sar_version".to_string(),
+                min_sar_version.to_string(),
+            )])
+            .into());
+        }
+        let demos: Vec<(i64, Option<String>)> =
+            sqlx::query(r#"SELECT id, sar_version FROM "p2boards".demos ORDER BY id"#)
+                .map(|row: PgRow| (row.get(0), row.get(1)))
+                .fetch_all(pool)
+                .await?;
+        Ok(demos
+            .into_iter()
+            .filter(|(_, sar_version)| match sar_version {
+                Some(version) => !matches!(
+                    compare_sar_versions(version, min_sar_version),
+                    Some(Ordering::Equal | Ordering::Greater)
+                ),
+                None => true,
+            })
+            .map(|(id, _)| id)
+            .collect())
+    }
+    /// Returns all demos that are no longer referenced by the changelog.
+    ///
+    /// A demo is orphaned when its changelog entry no longer exists, or when the changelog entry
+    /// has since been pointed at a different (newer) demo, and no other changelog entry references it.
+    pub async fn get_orphaned(pool: &PgPool) -> Result<Vec<Demos>> {
+        let res = sqlx::query_as::<_, Demos>(
+            r#"
+                SELECT demos.* FROM "p2boards".demos
+                LEFT JOIN "p2boards".changelog ON (changelog.id = demos.cl_id)
+                WHERE (changelog.id IS NULL OR changelog.demo_id IS DISTINCT FROM demos.id)
+                AND NOT EXISTS 
+                    (SELECT 1 FROM "p2boards".changelog AS cl 
+                    WHERE cl.demo_id = demos.id)
+                ORDER BY demos.id"#,
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns the ids of demos referenced by the changelog whose file is missing from BackBlaze.
+    ///
+    /// Files are checked in batches of [AUDIT_BATCH_SIZE], checks that are rate limited are retried with an exponential backoff.
+    /// The audit is aborted if BackBlaze is unavailable, rather than reporting every remaining demo as missing.
+    pub async fn audit_storage(pool: &PgPool, b2: &BackBlazeClient) -> Result<Vec<i64>> {
+        let demos: Vec<(i64, String)> = sqlx::query(
+            r#"
+                SELECT demos.id, demos.file_id FROM "p2boards".demos
+                WHERE EXISTS
+                    (SELECT 1 FROM "p2boards".changelog
+                    WHERE changelog.demo_id = demos.id)
+                ORDER BY demos.id"#,
+        )
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_all(pool)
+        .await?;
+        let mut missing = Vec::new();
+        for batch in demos.chunks(AUDIT_BATCH_SIZE) {
+            let checks = batch.iter().map(|(id, file_id)| async move {
+                (*id, file_exists_with_backoff(b2, file_id).await)
+            });
+            for (id, exists) in join_all(checks).await {
+                if !exists? {
+                    missing.push(id);
+                }
+            }
+        }
+        Ok(missing)
+    }
+    /// Returns the demos that can be removed under the retention policy, see [crate::tools::config::RetentionConfig].
+    ///
+    /// A demo expires once it was uploaded more than `expire_after_days` ago, unless a run referencing it is exempt.
+    /// Runs are exempt when they placed within `proof_demo` when set (the proof standards required a demo, this includes records),
+    /// or are still within the top `keep_top_n` of their board. Runs without rank data are kept, and orphaned demos are left to the garbage collector.
+    #[allow(dead_code)]
+    pub async fn get_expired(
+        pool: &PgPool,
+        expire_after_days: i32,
+        keep_top_n: i32,
+        proof_demo: i32,
+    ) -> Result<Vec<Demos>> {
+        Demos::get_expired_after(pool, Some(expire_after_days), None, keep_top_n, proof_demo).await
+    }
+    /// [Demos::get_expired], with the retention period in days and/or calendar months.
+    ///
+    /// When both are set, demos expire after whichever period is shorter. Nothing expires when neither is set.
+    pub async fn get_expired_after(
+        pool: &PgPool,
+        expire_after_days: Option<i32>,
+        expire_after_months: Option<i32>,
+        keep_top_n: i32,
+        proof_demo: i32,
+    ) -> Result<Vec<Demos>> {
+        // GREATEST ignores NULLs, so an unset period never expires anything on its own.
+        let res = sqlx::query_as::<_, Demos>(
+            r#"
+                SELECT demos.* FROM "p2boards".demos
+                WHERE demos.uploaded_at < GREATEST(now() - ($1 * INTERVAL '1 day'), now() - ($4 * INTERVAL '1 month'))
+                AND EXISTS
+                    (SELECT 1 FROM "p2boards".changelog
+                    WHERE changelog.demo_id = demos.id)
+                AND NOT EXISTS
+                    (SELECT 1 FROM "p2boards".changelog AS cl
+                    WHERE cl.demo_id = demos.id
+                    AND (cl.post_rank IS NULL
+                        OR cl.post_rank <= GREATEST($3, 1)
+                        OR (SELECT COUNT(DISTINCT better.profile_number)
+                            FROM "p2boards".changelog AS better
+                            INNER JOIN "p2boards".users ON (users.profile_number = better.profile_number)
+                            WHERE better.map_id = cl.map_id
+                            AND better.category_id = cl.category_id
+                            AND better.profile_number <> cl.profile_number
+                            AND better.score < cl.score
+                            AND better.banned = False
+                            AND better.verified = True
+                            AND users.banned = False) < GREATEST($2, 1)))
+                ORDER BY demos.id"#,
+        )
+        .bind(expire_after_days)
+        .bind(keep_top_n)
+        .bind(proof_demo)
+        .bind(expire_after_months)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns the most recently uploaded demos with their run, player and map, regardless of verification status.
+    ///
+    /// Replacing a demo's file counts as a new upload. Intended as the moderators' demo review feed.
+    pub async fn get_recent(pool: &PgPool, limit: i64) -> Result<Vec<DemoWithContext>> {
+        let res = sqlx::query_as::<_, DemoWithContext>(
+            r#"
+                SELECT demos.id, demos.file_id, demos.cl_id, demos.uploaded_at, demos.parsed_successfully,
+                    cl.profile_number, COALESCE(users.board_name, users.steam_name) AS user_name,
+                    cl.map_id, maps.name AS map_name, cl.score, cl.timestamp, cl.verified, cl.banned
+                FROM "p2boards".demos
+                INNER JOIN "p2boards".changelog AS cl ON (cl.id = demos.cl_id)
+                INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                INNER JOIN "p2boards".maps ON (maps.steam_id = cl.map_id)
+                ORDER BY demos.uploaded_at DESC, demos.id DESC
+                LIMIT $1"#,
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Streams a demo to BackBlaze in chunks, so the file is never fully buffered in memory.
+    ///
+    /// `size` must be the exact length of the data produced by `reader`, BackBlaze requires it up-front.
+    /// Returns the BackBlaze file_id of the uploaded demo.
+    pub async fn upload_stream<R>(
+        b2: &BackBlazeClient,
+        reader: R,
+        size: u64,
+        file_name: &str,
+    ) -> Result<Option<String>>
+    where
+        R: AsyncRead + Send + Sync + Unpin + 'static,
+    {
+        Ok(b2.upload_stream(reader, size, file_name).await?.file_id)
+    }
+    /// Replaces the file behind an existing demo, keeping the same demo id and `cl_id` so the changelog linkage is untouched.
+    ///
+    /// Only the run's owner or an admin can replace its demo, errors for anyone else.
+    ///
+    /// The new file is uploaded before the row is updated, so a failed upload leaves the current demo in place.
+    /// The parsed metadata is reset, as it described the old file. The old file is removed from BackBlaze last,
+    /// if that fails the demo still points to the new file and the old file's id is returned in [ReplacedDemo::stale_file_id].
+    ///
+    /// Returns the updated demo, `None` if the demo doesn't exist.
+    pub async fn replace_file<R>(
+        pool: &PgPool,
+        b2: &BackBlazeClient,
+        demo_id: i64,
+        profile_number: &str,
+        reader: R,
+        size: u64,
+        file_name: &str,
+    ) -> Result<Option<ReplacedDemo>>
+    where
+        R: AsyncRead + Send + Sync + Unpin + 'static,
+    {
+        let old_demo = match Demos::get_demo(pool, demo_id).await {
+            Ok(Some(demo)) => demo,
+            Ok(None) => return Ok(None),
+            // `get_demo` reports a missing demo as `RowNotFound`, any other error is from the database.
+            Err(e) => match e.downcast_ref::<sqlx::Error>() {
+                Some(sqlx::Error::RowNotFound) => return Ok(None),
+                _ => return Err(e),
+            },
+        };
+        let owner = Changelog::get_changelog(pool, old_demo.cl_id)
+            .await?
+            .map(|cl| cl.profile_number);
+        if owner.as_deref() != Some(profile_number) {
+            Admin::check_admin(pool, profile_number)
+                .await
+                .context("Only the run's owner or an admin can replace its demo")?;
+        }
+        let file_id = match Demos::upload_stream(b2, reader, size, file_name).await? {
+            Some(file_id) => file_id,
+            None => bail!("BackBlaze did not return a file_id for {}", file_name),
+        };
+        let demo = sqlx::query_as::<_, Demos>(
+            r#"
+                UPDATE "p2boards".demos
+                SET file_id = $1, parsed_successfully = False,
+                partner_name = NULL, sar_version = NULL, uploaded_at = now()
+                WHERE id = $2
+                RETURNING *"#,
+        )
+        .bind(file_id)
+        .bind(demo_id)
+        .fetch_one(pool)
+        .await?;
+        let deleted = match b2.get_file_info(&old_demo.file_id).await {
+            Ok(info) => {
+                b2.delete_file_version(&info.file_name, &old_demo.file_id)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        Ok(Some(ReplacedDemo {
+            demo,
+            stale_file_id: deleted.err().map(|_| old_demo.file_id),
+        }))
+    }
+    /// Returns the playback-friendly file name for a demo (`{map_name}_{player}_{score}.dem`), `None` if the demo doesn't exist.
+    pub async fn get_download_name(pool: &PgPool, demo_id: i64) -> Result<Option<String>> {
+        let res = sqlx::query(
+            r#"
+                SELECT maps.name,
+                CASE
+                    WHEN users.board_name IS NULL
+                        THEN users.steam_name
+                    WHEN users.board_name IS NOT NULL
+                        THEN users.board_name
+                END user_name, changelog.profile_number, changelog.score
+                FROM "p2boards".demos
+                INNER JOIN "p2boards".changelog ON (changelog.id = demos.cl_id)
+                INNER JOIN "p2boards".maps ON (maps.steam_id = changelog.map_id)
+                INNER JOIN "p2boards".users ON (users.profile_number = changelog.profile_number)
+                WHERE demos.id = $1"#,
+        )
+        .bind(demo_id)
+        .map(|row: PgRow| {
+            let user_name: Option<String> = row.get(1);
+            demo_file_name(row.get(0), user_name.as_deref(), row.get(2), row.get(3))
+        })
+        .fetch_optional(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Starts downloading a demo from BackBlaze, returns the playback-friendly file name alongside the response.
+    ///
+    /// Returns `None` if the demo doesn't exist.
+    pub async fn download_demo(
+        pool: &PgPool,
+        b2: &BackBlazeClient,
+        demo_id: i64,
+    ) -> Result<Option<(String, reqwest::Response)>> {
+        let demo = match Demos::get_demo(pool, demo_id).await {
+            Ok(Some(demo)) => demo,
+            Ok(None) => return Ok(None),
+            // `get_demo` reports a missing demo as `RowNotFound`, any other error is from the database.
+            Err(e) => match e.downcast_ref::<sqlx::Error>() {
+                Some(sqlx::Error::RowNotFound) => return Ok(None),
+                _ => return Err(e),
+            },
+        };
+        let file_name = match Demos::get_download_name(pool, demo_id).await? {
+            Some(file_name) => file_name,
+            None => return Ok(None),
+        };
+        let resp = b2.download(&demo.file_id).await?;
+        Ok(Some((file_name, resp)))
+    }
     /// Adds a new demo to the database, returns the demo's id
+    #[allow(dead_code)]
     pub async fn insert_demo(pool: &PgPool, demo: DemoInsert) -> Result<i64> {
+        let mut tx = pool.begin().await?;
+        let id = Demos::insert_demo_tx(&mut tx, demo).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+    /// [Demos::insert_demo] as part of a larger transaction, see [crate::tools::helpers::with_transaction].
+    pub async fn insert_demo_tx(
+        tx: &mut Transaction<'_, Postgres>,
+        demo: DemoInsert,
+    ) -> Result<i64> {
         let mut res: i64 = 0;
         let _ = sqlx::query(
             r#"
@@ -70,7 +381,7 @@ impl Demos {
         .bind(demo.sar_version)
         .bind(demo.cl_id)
         .map(|row: PgRow| res = row.get(0))
-        .fetch_one(pool)
+        .fetch_one(tx)
         .await?;
         Ok(res)
     }
@@ -96,13 +407,18 @@ impl Demos {
         Ok(true)
     }
     /// Deletes a demo
-    pub async fn delete_demo(pool: &PgPool, demo_id: i64) -> Result<bool> {
+    ///
+    /// Accepts either a pool or a transaction, so it can be deleted alongside its changelog references.
+    pub async fn delete_demo<'e, E>(executor: E, demo_id: i64) -> Result<bool>
+    where
+        E: Executor<'e, Database = Postgres>,
+    {
         let res = sqlx::query_as::<_, Demos>(
             r#"DELETE FROM "p2boards".demos 
                 WHERE id = $1 RETURNING *"#,
         )
         .bind(demo_id)
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await;
         match res {
             Ok(_) => Ok(true),
@@ -113,3 +429,18 @@ impl Demos {
         }
     }
 }
+
+/// Checks that a file exists on BackBlaze, waiting and retrying while the request is rate limited.
+async fn file_exists_with_backoff(b2: &BackBlazeClient, file_id: &str) -> Result<bool> {
+    let mut wait = AUDIT_BACKOFF;
+    for _ in 0..AUDIT_MAX_RETRIES {
+        match b2.file_exists(file_id).await {
+            Err(e) if e.downcast_ref::<RateLimited>().is_some() => {
+                tokio::time::sleep(wait).await;
+                wait *= 2;
+            }
+            res => return res,
+        }
+    }
+    b2.file_exists(file_id).await
+}
@@ -0,0 +1,15 @@
+use crate::models::models::*;
+use anyhow::Result;
+use sqlx::PgPool;
+
+impl Games {
+    /// Returns all games supported by the board, ordered by id.
+    pub async fn get_games(pool: &PgPool) -> Result<Vec<Games>> {
+        let res = sqlx::query_as::<_, Games>(
+            r#"SELECT games.id, games.game_name, games.default_category FROM "p2boards".games ORDER BY games.id"#,
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+}
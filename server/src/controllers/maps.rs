@@ -1,9 +1,29 @@
 use crate::models::models::*;
-use anyhow::Result;
+use anyhow::{bail, Result};
+use chrono::{NaiveDateTime, Utc};
 use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
 use std::collections::HashMap;
 
+/// A map's effective default category, its own `default_cat_id`, or the map's category named after its game's `default_category`.
+pub(crate) const DEFAULT_CAT: &str = r#"
+    COALESCE(maps.default_cat_id, (
+        SELECT MIN(categories.id) FROM "p2boards".categories
+        WHERE categories.map_id = maps.steam_id
+        AND categories.name = games.default_category)) AS default_cat_id"#;
+
+/// The joins needed by [DEFAULT_CAT].
+pub(crate) const DEFAULT_CAT_FROM: &str = r#"
+    FROM "p2boards".maps
+    LEFT JOIN "p2boards".chapters ON (chapters.id = maps.chapter_id)
+    LEFT JOIN "p2boards".games ON (games.id = chapters.game_id)"#;
+
+/// Difficulty tiers run from 1 (easiest) to this value (hardest).
+pub const MAX_DIFFICULTY_TIER: i32 = 10;
+
+/// The # of recent changelog entries on a map's page.
+const MAP_PAGE_RECENT_ENTRIES: u32 = 10;
+
 impl Maps {
     /// Takes in a bool, if true returns MP map_ids, if false, returns as SP map_ids
     pub async fn get_steam_ids(pool: &PgPool, is_mp: bool) -> Result<Vec<String>> {
@@ -20,36 +40,763 @@ impl Maps {
         .await?;
         Ok(res)
     }
+    /// Returns the full map row for a given steam_id, `None` if no map exists with that id.
+    pub async fn get_map(pool: &PgPool, map_id: String) -> Result<Option<Maps>> {
+        let res = sqlx::query_as::<_, Maps>(r#"SELECT * FROM "p2boards".maps WHERE steam_id = $1"#)
+            .bind(map_id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(res)
+    }
+    /// Returns the current WR on the default category of every SP (`is_mp = false`) or coop (`is_mp = true`) map.
+    ///
+    /// Banned runs and players are excluded, ties go to the run that was set first.
+    pub async fn get_all_current_wrs(pool: &PgPool, is_mp: bool) -> Result<Vec<MapWr>> {
+        let res = sqlx::query_as::<_, MapWr>(&format!(
+            r#"
+                SELECT wr.steam_id, wr.name, wr.score, wr.profile_number, wr.user_name, wr.avatar,
+                    wr.has_demo, wr.has_video,
+                    partner.profile_number AS partner_profile_number,
+                    COALESCE(partner.board_name, partner.steam_name) AS partner_name
+                FROM (
+                    SELECT maps.id AS map_order, maps.steam_id, maps.name, cl.score, cl.profile_number,
+                        COALESCE(users.board_name, users.steam_name) AS user_name, users.avatar,
+                        cl.demo_id IS NOT NULL AS has_demo, cl.youtube_id IS NOT NULL AS has_video,
+                        cl.coop_id,
+                        ROW_NUMBER() OVER (
+                            PARTITION BY maps.steam_id
+                            ORDER BY cl.score, cl.timestamp NULLS LAST, cl.id) AS rn
+                    FROM "p2boards".maps
+                    INNER JOIN "p2boards".chapters ON (maps.chapter_id = chapters.id)
+                    INNER JOIN (SELECT maps.steam_id, {} {}) AS defaults ON (defaults.steam_id = maps.steam_id)
+                    INNER JOIN "p2boards".changelog AS cl ON (cl.map_id = maps.steam_id
+                        AND cl.category_id = defaults.default_cat_id)
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    WHERE chapters.is_multiplayer = $1
+                    AND cl.banned = False
+                    AND cl.verified = True
+                    AND users.banned = False
+                ) AS wr
+                LEFT JOIN "p2boards".coop_bundled AS cb ON (cb.id = wr.coop_id)
+                LEFT JOIN "p2boards".users AS partner ON (partner.profile_number =
+                    CASE
+                        WHEN cb.p_id1 = wr.profile_number
+                            THEN cb.p_id2
+                        ELSE cb.p_id1
+                    END)
+                WHERE wr.rn = 1
+                ORDER BY wr.map_order"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .bind(is_mp)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns the current WR on every map for a records grid, with the map's chapter and the holder's display info.
+    ///
+    /// `category_scope` picks either each map's default category, or every category on every map. Maps or categories without a
+    /// valid score are still included, with the WR fields left empty. Banned runs and players are excluded, ties go to the run set first.
+    pub async fn get_all_wrs(
+        pool: &PgPool,
+        category_scope: CategoryScope,
+    ) -> Result<Vec<MapWrSummary>> {
+        let res = sqlx::query_as::<_, MapWrSummary>(&format!(
+            r#"
+                SELECT maps.steam_id AS map_id, maps.name AS map_name, chapters.id AS chapter_id, chapters.chapter_name,
+                    categories.id AS category_id, categories.name AS category_name,
+                    wr.score, wr.profile_number, wr.user_name, wr.avatar, wr.timestamp
+                FROM (SELECT maps.steam_id, {} {}) AS defaults
+                INNER JOIN "p2boards".maps ON (maps.steam_id = defaults.steam_id)
+                LEFT JOIN "p2boards".chapters ON (chapters.id = maps.chapter_id)
+                INNER JOIN "p2boards".categories ON (categories.map_id = maps.steam_id
+                    AND ($1 = False OR categories.id = defaults.default_cat_id))
+                LEFT JOIN LATERAL (
+                    SELECT cl.score, cl.profile_number,
+                        COALESCE(users.board_name, users.steam_name) AS user_name, users.avatar, cl.timestamp
+                    FROM "p2boards".changelog AS cl
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    WHERE cl.map_id = maps.steam_id
+                    AND cl.category_id = categories.id
+                    AND cl.banned = False
+                    AND cl.verified = True
+                    AND users.banned = False
+                    ORDER BY cl.score, cl.timestamp NULLS LAST, cl.id
+                    LIMIT 1
+                ) AS wr ON True
+                ORDER BY maps.id, categories.id"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .bind(category_scope == CategoryScope::Default)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns the current WRs on SP (`is_mp = false`) or coop (`is_mp = true`) maps that are closest to being beaten,
+    /// ordered by the gap between the WR and the #2 run on each map's default category, smallest first.
+    ///
+    /// Only each player's best run counts, and a coop run is only counted once, so the WR holder's partner isn't their own challenger.
+    /// Banned runs and players are excluded, maps with fewer than two valid runs are left out.
+    pub async fn get_softest_wrs(pool: &PgPool, is_mp: bool, limit: i64) -> Result<Vec<SoftWr>> {
+        let res = sqlx::query_as::<_, SoftWr>(&format!(
+            r#"
+                WITH bests AS (
+                    SELECT DISTINCT ON (cl.map_id, cl.profile_number)
+                        cl.id, cl.map_id, cl.profile_number, cl.score, cl.timestamp, cl.coop_id
+                    FROM (SELECT maps.steam_id, {} {}
+                        WHERE chapters.is_multiplayer = $1) AS defaults
+                    INNER JOIN "p2boards".changelog AS cl ON (cl.map_id = defaults.steam_id
+                        AND cl.category_id = defaults.default_cat_id)
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    WHERE cl.banned = False
+                    AND cl.verified = True
+                    AND users.banned = False
+                    ORDER BY cl.map_id, cl.profile_number, cl.score, cl.timestamp NULLS LAST, cl.id
+                ), runs AS (
+                    SELECT DISTINCT ON (bests.map_id, COALESCE(bests.coop_id, -bests.id)) bests.*
+                    FROM bests
+                    ORDER BY bests.map_id, COALESCE(bests.coop_id, -bests.id), bests.id
+                ), ranked AS (
+                    SELECT runs.*,
+                        ROW_NUMBER() OVER (
+                            PARTITION BY runs.map_id
+                            ORDER BY runs.score, runs.timestamp NULLS LAST, runs.id) AS rank
+                    FROM runs
+                )
+                SELECT maps.steam_id AS map_id, maps.name AS map_name,
+                    wr.score AS wr_score, wr.profile_number AS holder_profile_number,
+                    COALESCE(holder.board_name, holder.steam_name) AS holder_name, holder.avatar AS holder_avatar,
+                    challenger.score AS challenger_score, challenger.profile_number AS challenger_profile_number,
+                    COALESCE(challenger_user.board_name, challenger_user.steam_name) AS challenger_name,
+                    challenger_user.avatar AS challenger_avatar,
+                    challenger.score - wr.score AS gap
+                FROM ranked AS wr
+                INNER JOIN ranked AS challenger ON (challenger.map_id = wr.map_id AND challenger.rank = 2)
+                INNER JOIN "p2boards".maps ON (maps.steam_id = wr.map_id)
+                INNER JOIN "p2boards".users AS holder ON (holder.profile_number = wr.profile_number)
+                INNER JOIN "p2boards".users AS challenger_user ON (challenger_user.profile_number = challenger.profile_number)
+                WHERE wr.rank = 1
+                ORDER BY gap, maps.id
+                LIMIT $2"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .bind(is_mp)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns every category on a map, with the # of players holding a valid score in each (one per player).
+    ///
+    /// Categories without any scores are included with a count of 0.
+    pub async fn get_categories_with_counts(
+        pool: &PgPool,
+        map_id: String,
+    ) -> Result<Vec<CategoryWithCount>> {
+        let res = sqlx::query_as::<_, CategoryWithCount>(
+            r#"
+                SELECT categories.id, categories.name, categories.map_id, categories.rules,
+                    COUNT(DISTINCT users.profile_number) AS count
+                FROM "p2boards".categories
+                LEFT JOIN "p2boards".changelog cl ON (cl.category_id = categories.id
+                    AND cl.map_id = categories.map_id
+                    AND cl.banned = False
+                    AND cl.verified = True)
+                LEFT JOIN "p2boards".users ON (users.profile_number = cl.profile_number
+                    AND users.banned = False)
+                WHERE categories.map_id = $1
+                GROUP BY categories.id
+                ORDER BY categories.id"#,
+        )
+        .bind(map_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns the average & median of players' best scores for each category on a map, one best per player.
+    ///
+    /// Categories without any valid scores are omitted.
+    pub async fn get_avg_times(pool: &PgPool, map_id: String) -> Result<Vec<CategoryAvg>> {
+        let res = sqlx::query_as::<_, CategoryAvg>(
+            r#"
+                SELECT bests.category_id, categories.name AS category_name,
+                    COUNT(*) AS players, MIN(bests.score) AS wr,
+                    AVG(bests.score)::float8 AS average,
+                    PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY bests.score) AS median
+                FROM (
+                    SELECT cl.category_id, cl.profile_number, MIN(cl.score) AS score
+                    FROM "p2boards".changelog cl
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    WHERE cl.map_id = $1
+                    AND cl.banned = False
+                    AND cl.verified = True
+                    AND users.banned = False
+                    GROUP BY cl.category_id, cl.profile_number
+                ) AS bests
+                INNER JOIN "p2boards".categories ON (categories.id = bests.category_id)
+                GROUP BY bests.category_id, categories.name
+                ORDER BY bests.category_id"#,
+        )
+        .bind(map_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns the public maps with the fewest and most distinct players holding a valid score, in any category.
+    ///
+    /// Maps without any scores count as 0 completions. Both lists hold at most `limit` maps.
+    pub async fn get_completion_extremes(
+        pool: &PgPool,
+        limit: usize,
+    ) -> Result<CompletionExtremes> {
+        let maps = sqlx::query_as::<_, MapCompletions>(
+            r#"
+                SELECT maps.steam_id AS map_id, maps.name AS map_name,
+                    COUNT(DISTINCT users.profile_number) AS players
+                FROM "p2boards".maps
+                LEFT JOIN "p2boards".changelog cl ON (cl.map_id = maps.steam_id
+                    AND cl.banned = False
+                    AND cl.verified = True)
+                LEFT JOIN "p2boards".users ON (users.profile_number = cl.profile_number
+                    AND users.banned = False)
+                WHERE maps.is_public = True
+                GROUP BY maps.steam_id, maps.name, maps.id
+                ORDER BY players, maps.id"#,
+        )
+        .fetch_all(pool)
+        .await?;
+        let hardest = maps.iter().take(limit).cloned().collect();
+        let easiest = maps.into_iter().rev().take(limit).collect();
+        Ok(CompletionExtremes { hardest, easiest })
+    }
+    /// Returns the top `n` players on the default category of every SP (`is_mp = false`) or coop (`is_mp = true`) map, grouped by steam_id.
+    ///
+    /// One scan over the changelog ranks every player's best run per map, rather than a query per map.
+    /// Banned runs and players are excluded, maps without any valid scores are omitted.
+    pub async fn get_top_n_all_maps(
+        pool: &PgPool,
+        n: i64,
+        is_mp: bool,
+    ) -> Result<HashMap<String, Vec<MapLeaderboardEntry>>> {
+        let entries = sqlx::query_as::<_, MapLeaderboardEntry>(&format!(
+            r#"
+                SELECT ranked.map_id, ranked.rank, ranked.profile_number, ranked.user_name,
+                    ranked.avatar, ranked.score, ranked.timestamp, ranked.has_demo, ranked.has_video
+                FROM (
+                    SELECT bests.*,
+                        ROW_NUMBER() OVER (
+                            PARTITION BY bests.map_id
+                            ORDER BY bests.score, bests.timestamp NULLS LAST, bests.id) AS rank
+                    FROM (
+                        SELECT DISTINCT ON (cl.map_id, cl.profile_number)
+                            cl.id, cl.map_id, cl.profile_number, cl.score, cl.timestamp,
+                            COALESCE(users.board_name, users.steam_name) AS user_name, users.avatar,
+                            cl.demo_id IS NOT NULL AS has_demo, cl.youtube_id IS NOT NULL AS has_video
+                        FROM "p2boards".maps
+                        INNER JOIN "p2boards".chapters ON (maps.chapter_id = chapters.id)
+                        INNER JOIN (SELECT maps.steam_id, {} {}) AS defaults ON (defaults.steam_id = maps.steam_id)
+                        INNER JOIN "p2boards".changelog AS cl ON (cl.map_id = maps.steam_id
+                            AND cl.category_id = defaults.default_cat_id)
+                        INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                        WHERE chapters.is_multiplayer = $1
+                        AND cl.banned = False
+                        AND cl.verified = True
+                        AND users.banned = False
+                        ORDER BY cl.map_id, cl.profile_number, cl.score, cl.timestamp NULLS LAST, cl.id
+                    ) AS bests
+                ) AS ranked
+                WHERE ranked.rank <= $2
+                ORDER BY ranked.map_id, ranked.rank"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .bind(is_mp)
+        .bind(n)
+        .fetch_all(pool)
+        .await?;
+        let mut res: HashMap<String, Vec<MapLeaderboardEntry>> = HashMap::new();
+        for entry in entries {
+            res.entry(entry.map_id.clone()).or_default().push(entry);
+        }
+        Ok(res)
+    }
+    /// Returns every player's best score on a map for a given category, ranked with ties going to the run set first.
+    ///
+    /// Banned runs and players are excluded. Served through [crate::tools::cache::CacheState::get_map_leaderboard].
+    pub async fn get_map_leaderboard(
+        pool: &PgPool,
+        map_id: String,
+        cat_id: i32,
+    ) -> Result<Vec<MapLeaderboardEntry>> {
+        let res = sqlx::query_as::<_, MapLeaderboardEntry>(
+            r#"
+                SELECT bests.map_id,
+                    ROW_NUMBER() OVER (ORDER BY bests.score, bests.timestamp NULLS LAST, bests.id) AS rank,
+                    bests.profile_number, bests.user_name, bests.avatar, bests.score, bests.timestamp,
+                    bests.has_demo, bests.has_video
+                FROM (
+                    SELECT DISTINCT ON (cl.profile_number)
+                        cl.id, cl.map_id, cl.profile_number, cl.score, cl.timestamp,
+                        COALESCE(users.board_name, users.steam_name) AS user_name, users.avatar,
+                        cl.demo_id IS NOT NULL AS has_demo, cl.youtube_id IS NOT NULL AS has_video
+                    FROM "p2boards".changelog AS cl
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    WHERE cl.map_id = $1
+                    AND cl.category_id = $2
+                    AND cl.banned = False
+                    AND cl.verified = True
+                    AND users.banned = False
+                    ORDER BY cl.profile_number, cl.score, cl.timestamp NULLS LAST, cl.id
+                ) AS bests
+                ORDER BY rank"#,
+        )
+        .bind(map_id)
+        .bind(cat_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Reconstructs a map's leaderboard for a given category as it stood at `at`, using each player's best run submitted on or before then.
+    ///
+    /// By default this is the corrected view, runs and players that are banned now are excluded as with [Maps::get_map_leaderboard].
+    /// With `include_banned`, they are kept to show the board as it appeared at the time (bans aren't timestamped, so every banned run is kept).
+    /// Runs without a timestamp can't be placed in time, and are always excluded.
+    pub async fn get_leaderboard_at(
+        pool: &PgPool,
+        map_id: String,
+        cat_id: i32,
+        at: NaiveDateTime,
+        include_banned: bool,
+    ) -> Result<Vec<MapLeaderboardEntry>> {
+        let res = sqlx::query_as::<_, MapLeaderboardEntry>(
+            r#"
+                SELECT bests.map_id,
+                    ROW_NUMBER() OVER (ORDER BY bests.score, bests.timestamp, bests.id) AS rank,
+                    bests.profile_number, bests.user_name, bests.avatar, bests.score, bests.timestamp,
+                    bests.has_demo, bests.has_video
+                FROM (
+                    SELECT DISTINCT ON (cl.profile_number)
+                        cl.id, cl.map_id, cl.profile_number, cl.score, cl.timestamp,
+                        COALESCE(users.board_name, users.steam_name) AS user_name, users.avatar,
+                        cl.demo_id IS NOT NULL AS has_demo, cl.youtube_id IS NOT NULL AS has_video
+                    FROM "p2boards".changelog AS cl
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    WHERE cl.map_id = $1
+                    AND cl.category_id = $2
+                    AND cl.timestamp <= $3
+                    AND cl.verified = True
+                    AND ($4 OR (cl.banned = False AND users.banned = False))
+                    ORDER BY cl.profile_number, cl.score, cl.timestamp, cl.id
+                ) AS bests
+                ORDER BY rank"#,
+        )
+        .bind(map_id)
+        .bind(cat_id)
+        .bind(at)
+        .bind(include_banned)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Assembles everything shown on a map's page in one call, the map itself, its categories, the top `top_n` of the leaderboard,
+    /// per-category stats and the map's most recent changelog entries.
+    ///
+    /// Uses the map's default category if `cat_id` is `None`. The independent queries run concurrently. Returns `None` if the map doesn't exist.
+    pub async fn get_map_page(
+        pool: &PgPool,
+        map_id: String,
+        cat_id: Option<i32>,
+        top_n: i64,
+    ) -> Result<Option<MapPage>> {
+        let map = match Maps::get_map(pool, map_id.clone()).await? {
+            Some(map) => map,
+            None => return Ok(None),
+        };
+        let cat_id = match cat_id {
+            Some(cat_id) => cat_id,
+            None => match Maps::get_default_cat(pool, map_id.clone()).await? {
+                Some(cat_id) => cat_id,
+                None => bail!("No default category found for map {}", map_id),
+            },
+        };
+        let recent_params = ChangelogQueryParams {
+            limit: Some(MAP_PAGE_RECENT_ENTRIES),
+            chamber: Some(map_id.clone()),
+            ..Default::default()
+        };
+        let (categories, mut leaderboard, stats, recent) = tokio::try_join!(
+            Maps::get_categories_with_counts(pool, map_id.clone()),
+            Maps::get_map_leaderboard(pool, map_id.clone(), cat_id),
+            Maps::get_avg_times(pool, map_id.clone()),
+            ChangelogPage::get_changelog_page(pool, recent_params),
+        )?;
+        leaderboard.truncate(top_n.max(0) as usize);
+        Ok(Some(MapPage {
+            map,
+            cat_id,
+            categories,
+            leaderboard,
+            stats,
+            recent: recent.unwrap_or_default(),
+        }))
+    }
+    /// Returns every verified run on a map for a given category, not just each player's best, for a detailed board view.
+    ///
+    /// Each run carries its player's rank on the board from [Maps::get_map_leaderboard], so a player's older runs share their rank.
+    /// Runs are ordered by that rank, then by score. Banned runs and players are excluded.
+    pub async fn get_map_board_history(
+        pool: &PgPool,
+        map_id: String,
+        cat_id: i32,
+    ) -> Result<Vec<MapLeaderboardEntry>> {
+        let res = sqlx::query_as::<_, MapLeaderboardEntry>(
+            r#"
+                SELECT cl.map_id, ranks.rank, cl.profile_number,
+                    COALESCE(users.board_name, users.steam_name) AS user_name, users.avatar,
+                    cl.score, cl.timestamp,
+                    cl.demo_id IS NOT NULL AS has_demo, cl.youtube_id IS NOT NULL AS has_video
+                FROM "p2boards".changelog AS cl
+                INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                INNER JOIN (
+                    SELECT bests.profile_number,
+                        ROW_NUMBER() OVER (ORDER BY bests.score, bests.timestamp NULLS LAST, bests.id) AS rank
+                    FROM (
+                        SELECT DISTINCT ON (cl.profile_number)
+                            cl.id, cl.profile_number, cl.score, cl.timestamp
+                        FROM "p2boards".changelog AS cl
+                        WHERE cl.map_id = $1
+                        AND cl.category_id = $2
+                        AND cl.banned = False
+                        AND cl.verified = True
+                        ORDER BY cl.profile_number, cl.score, cl.timestamp NULLS LAST, cl.id
+                    ) AS bests
+                    INNER JOIN "p2boards".users ON (users.profile_number = bests.profile_number)
+                    WHERE users.banned = False
+                ) AS ranks ON (ranks.profile_number = cl.profile_number)
+                WHERE cl.map_id = $1
+                AND cl.category_id = $2
+                AND cl.banned = False
+                AND cl.verified = True
+                ORDER BY ranks.rank, cl.score, cl.timestamp NULLS LAST, cl.id"#,
+        )
+        .bind(map_id)
+        .bind(cat_id)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns the sequence of players who held the world record on a map for a given category, oldest first.
+    ///
+    /// Consecutive records by the same player are merged into a single reign, see [WrReign].
+    pub async fn get_wr_reign_history(
+        pool: &PgPool,
+        map_id: String,
+        category_id: i32,
+    ) -> Result<Vec<WrReign>> {
+        let now = Utc::now().naive_utc();
+        let history = Changelog::get_wr_history(pool, map_id, category_id).await?;
+        let mut reigns: Vec<WrReign> = Vec::new();
+        for wr in history {
+            let end = wr.ended.unwrap_or(now);
+            match reigns.last_mut() {
+                Some(reign) if reign.profile_number == wr.profile_number => {
+                    reign.score = wr.score;
+                    reign.end = end;
+                    reign.current = wr.ended.is_none();
+                }
+                _ => reigns.push(WrReign {
+                    profile_number: wr.profile_number,
+                    user_name: wr.user_name,
+                    score: wr.score,
+                    start: wr.timestamp,
+                    end,
+                    current: wr.ended.is_none(),
+                    duration_secs: 0,
+                }),
+            }
+        }
+        for reign in reigns.iter_mut() {
+            reign.duration_secs = (reign.end - reign.start).num_seconds();
+        }
+        Ok(reigns)
+    }
+    /// Returns the currently featured map with its board stats, `None` if no map is featured right now.
+    ///
+    /// Stats only count verified, unbanned runs on the map's default category.
+    pub async fn get_featured(pool: &PgPool) -> Result<Option<MapWithStats>> {
+        let res = sqlx::query_as::<_, MapWithStats>(&format!(
+            r#"
+                SELECT maps.steam_id, maps.name, maps.chapter_id, defaults.default_cat_id,
+                    featured.featured_at, featured.until,
+                    COUNT(DISTINCT users.profile_number) AS players,
+                    wr.score AS wr, wr.profile_number AS wr_holder, wr.user_name AS wr_holder_name
+                FROM (
+                    SELECT * FROM "p2boards".featured_maps
+                    WHERE until > now()
+                    ORDER BY featured_at DESC, id DESC
+                    LIMIT 1
+                ) AS featured
+                INNER JOIN "p2boards".maps ON (maps.steam_id = featured.map_id)
+                INNER JOIN (SELECT maps.steam_id, {} {}) AS defaults ON (defaults.steam_id = maps.steam_id)
+                LEFT JOIN "p2boards".changelog AS cl ON (cl.map_id = maps.steam_id
+                    AND cl.category_id = defaults.default_cat_id
+                    AND cl.banned = False
+                    AND cl.verified = True)
+                LEFT JOIN "p2boards".users ON (users.profile_number = cl.profile_number
+                    AND users.banned = False)
+                LEFT JOIN LATERAL (
+                    SELECT cl.score, cl.profile_number,
+                        COALESCE(users.board_name, users.steam_name) AS user_name
+                    FROM "p2boards".changelog AS cl
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    WHERE cl.map_id = maps.steam_id
+                    AND cl.category_id = defaults.default_cat_id
+                    AND cl.banned = False
+                    AND cl.verified = True
+                    AND users.banned = False
+                    ORDER BY cl.score, cl.timestamp NULLS LAST, cl.id
+                    LIMIT 1
+                ) AS wr ON True
+                GROUP BY maps.steam_id, maps.name, maps.chapter_id, defaults.default_cat_id,
+                    featured.featured_at, featured.until, wr.score, wr.profile_number, wr.user_name"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .fetch_optional(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Features a map until `until`, ending the currently featured map early so only one is featured at a time.
+    ///
+    /// `admin_profile_number` must belong to an admin, the change is recorded in the admin audit log.
+    /// Returns the id of the new featured entry.
+    pub async fn set_featured(
+        pool: &PgPool,
+        map_id: String,
+        until: NaiveDateTime,
+        admin_profile_number: String,
+    ) -> Result<i64> {
+        if until <= Utc::now().naive_utc() {
+            bail!("A featured map must be featured until a time in the future");
+        }
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, &admin_profile_number).await?;
+        sqlx::query(r#"UPDATE "p2boards".featured_maps SET until = now() WHERE until > now()"#)
+            .execute(&mut tx)
+            .await?;
+        let id: i64 = sqlx::query(
+            r#"
+                INSERT INTO "p2boards".featured_maps (map_id, until, featured_by)
+                VALUES ($1, $2, $3)
+                RETURNING id"#,
+        )
+        .bind(&map_id)
+        .bind(until)
+        .bind(&admin_profile_number)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(&mut tx)
+        .await?;
+        Admin::log_admin_action(
+            &mut tx,
+            &admin_profile_number,
+            "feature_map",
+            Some(map_id),
+            Some(format!("Featured until {}", until)),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+    /// Returns every map in a given difficulty tier, in map order.
+    pub async fn get_by_tier(pool: &PgPool, tier: i32) -> Result<Vec<Maps>> {
+        let res = sqlx::query_as::<_, Maps>(
+            r#"SELECT * FROM "p2boards".maps WHERE difficulty_tier = $1 ORDER BY id"#,
+        )
+        .bind(tier)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Sets the difficulty tier of a map, `None` clears it. Tiers must be between 1 and [MAX_DIFFICULTY_TIER].
+    ///
+    /// `admin_profile_number` must belong to an admin, the change is recorded in the admin audit log.
+    /// Returns `false` if no map exists with the given steam_id.
+    pub async fn set_tier(
+        pool: &PgPool,
+        map_id: String,
+        tier: Option<i32>,
+        admin_profile_number: String,
+    ) -> Result<bool> {
+        if let Some(tier) = tier {
+            if !(1..=MAX_DIFFICULTY_TIER).contains(&tier) {
+                bail!(
+                    "Difficulty tier {} is outside of 1-{}",
+                    tier,
+                    MAX_DIFFICULTY_TIER
+                );
+            }
+        }
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, &admin_profile_number).await?;
+        let updated =
+            sqlx::query(r#"UPDATE "p2boards".maps SET difficulty_tier = $1 WHERE steam_id = $2"#)
+                .bind(tier)
+                .bind(&map_id)
+                .execute(&mut tx)
+                .await?
+                .rows_affected();
+        if updated == 0 {
+            return Ok(false);
+        }
+        Admin::log_admin_action(
+            &mut tx,
+            &admin_profile_number,
+            "set_map_tier",
+            Some(map_id),
+            Some(match tier {
+                Some(tier) => format!("Set difficulty tier to {}", tier),
+                None => "Cleared difficulty tier".to_string(),
+            }),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+    /// Returns whether a map is currently accepting submissions.
+    ///
+    /// A map is open if `submissions_open` is set and the current (UTC) time is within its `opens_at`/`closes_at` window,
+    /// a missing bound leaves that side of the window unbounded. Returns `false` if no map exists with the given steam_id.
+    pub async fn is_submission_open(pool: &PgPool, map_id: String) -> Result<bool> {
+        let res = sqlx::query(
+            r#"
+                SELECT submissions_open
+                    AND (opens_at IS NULL OR opens_at <= $2)
+                    AND (closes_at IS NULL OR closes_at > $2)
+                FROM "p2boards".maps
+                WHERE steam_id = $1"#,
+        )
+        .bind(map_id)
+        .bind(Utc::now().naive_utc())
+        .map(|row: PgRow| row.get(0))
+        .fetch_optional(pool)
+        .await?;
+        Ok(res.unwrap_or(false))
+    }
+    /// Opens or closes submissions on a map, with an optional window they're accepted in, see [Maps::is_submission_open].
+    ///
+    /// `admin_profile_number` must belong to an admin, the change is recorded in the admin audit log.
+    /// Returns `false` if no map exists with the given steam_id.
+    pub async fn set_submission_window(
+        pool: &PgPool,
+        map_id: String,
+        submissions_open: bool,
+        opens_at: Option<NaiveDateTime>,
+        closes_at: Option<NaiveDateTime>,
+        admin_profile_number: String,
+    ) -> Result<bool> {
+        if let (Some(opens_at), Some(closes_at)) = (opens_at, closes_at) {
+            if opens_at >= closes_at {
+                bail!(
+                    "Submission window opens at {}, after it closes at {}",
+                    opens_at,
+                    closes_at
+                );
+            }
+        }
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, &admin_profile_number).await?;
+        let updated = sqlx::query(
+            r#"UPDATE "p2boards".maps
+                SET submissions_open = $1, opens_at = $2, closes_at = $3
+                WHERE steam_id = $4"#,
+        )
+        .bind(submissions_open)
+        .bind(opens_at)
+        .bind(closes_at)
+        .bind(&map_id)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        if updated == 0 {
+            return Ok(false);
+        }
+        let bound = |time: Option<NaiveDateTime>| match time {
+            Some(time) => time.to_string(),
+            None => "unbounded".to_string(),
+        };
+        Admin::log_admin_action(
+            &mut tx,
+            &admin_profile_number,
+            if submissions_open {
+                "open_map_submissions"
+            } else {
+                "close_map_submissions"
+            },
+            Some(map_id),
+            Some(format!(
+                "Window from {} to {}",
+                bound(opens_at),
+                bound(closes_at)
+            )),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
     /// Returns the map name for a given steam_id.
     pub async fn get_map_name(pool: &PgPool, map_id: String) -> Result<Option<String>> {
         let res = sqlx::query(r#"SELECT maps.name FROM "p2boards".maps WHERE maps.steam_id = $1"#)
             .bind(map_id)
             .map(|row: PgRow| row.get(0))
-            .fetch_one(pool)
+            .fetch_optional(pool)
             .await?;
-        Ok(Some(res))
+        Ok(res)
     }
-    /// Returns all default cats
+    /// Returns the default category of every map, maps without a default of their own use their game's `default_category`.
+    ///
+    /// Maps without a default on either the map or the game are left out. Used to build the default category cache.
     pub async fn get_all_default_cats(pool: &PgPool) -> Result<HashMap<String, i32>> {
-        let mut hm: HashMap<String, i32> = HashMap::with_capacity(108);
-        sqlx::query(r#"SELECT steam_id, default_cat_id FROM "p2boards".maps"#)
-            .map(|row: PgRow| hm.insert(row.get(0), row.get(1)))
-            .fetch_all(pool)
-            .await?;
-        Ok(hm)
+        let res: Vec<(String, Option<i32>)> = sqlx::query(&format!(
+            r#"SELECT maps.steam_id, {} {}"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_all(pool)
+        .await?;
+        Ok(res
+            .into_iter()
+            .filter_map(|(map_id, cat_id)| cat_id.map(|cat_id| (map_id, cat_id)))
+            .collect())
     }
-    /// Returns the default category for a given map.
+    /// Returns the default category of each of the given maps, see [Maps::get_all_default_cats].
+    ///
+    /// Map ids that don't exist, or have no default on either the map or the game, are left out.
+    pub async fn get_default_cats(
+        pool: &PgPool,
+        map_ids: &[String],
+    ) -> Result<HashMap<String, i32>> {
+        let res: Vec<(String, Option<i32>)> = sqlx::query(&format!(
+            r#"SELECT maps.steam_id, {} {} WHERE maps.steam_id = ANY($1)"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .bind(map_ids)
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_all(pool)
+        .await?;
+        Ok(res
+            .into_iter()
+            .filter_map(|(map_id, cat_id)| cat_id.map(|cat_id| (map_id, cat_id)))
+            .collect())
+    }
+    /// Returns the default category for a given map, falling back to its game's `default_category` if the map doesn't set one.
     pub async fn get_default_cat(pool: &PgPool, map_id: String) -> Result<Option<i32>> {
-        let res = sqlx::query(
-            r#"
-                SELECT default_cat_id FROM "p2boards".maps
-                WHERE steam_id = $1;"#,
-        )
+        let res: Option<Option<i32>> = sqlx::query(&format!(
+            r#"SELECT {} {} WHERE maps.steam_id = $1"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
         .bind(map_id)
         .map(|row: PgRow| row.get(0))
-        .fetch_one(pool)
+        .fetch_optional(pool)
         .await?;
-        Ok(res)
+        Ok(res.flatten())
     }
     /// Returns chapter information for a given map_id (steam_id)
     #[allow(dead_code)]
@@ -69,6 +816,51 @@ impl Maps {
         .await?;
         Ok(Some(res))
     }
+    /// Searches public maps by name, chapter name, or the name of one of their categories (case-insensitive).
+    ///
+    /// Each map is returned once, tagged with the strongest way it matched. Exact name matches come first,
+    /// then maps matched by name, by chapter and by category, alphabetically within each.
+    pub async fn global_search(
+        pool: &PgPool,
+        query: String,
+        limit: i64,
+    ) -> Result<Vec<MapSearchResult>> {
+        let query = query.trim().to_string();
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+        let res = sqlx::query_as::<_, MapSearchResult>(
+            r#"
+                SELECT maps.steam_id, maps.name, maps.chapter_id, chapters.chapter_name,
+                    CASE best.kind WHEN 0 THEN 'name' WHEN 1 THEN 'chapter' ELSE 'category' END AS matched_on
+                FROM (
+                    SELECT matches.steam_id, MIN(matches.kind) AS kind
+                    FROM (
+                        SELECT maps.steam_id, 0 AS kind FROM "p2boards".maps
+                        WHERE LOWER(maps.name) LIKE LOWER($1)
+                        UNION ALL
+                        SELECT maps.steam_id, 1 AS kind FROM "p2boards".maps
+                        INNER JOIN "p2boards".chapters ON (chapters.id = maps.chapter_id)
+                        WHERE LOWER(chapters.chapter_name) LIKE LOWER($1)
+                        UNION ALL
+                        SELECT categories.map_id AS steam_id, 2 AS kind FROM "p2boards".categories
+                        WHERE LOWER(categories.name) LIKE LOWER($1)
+                    ) AS matches
+                    GROUP BY matches.steam_id
+                ) AS best
+                INNER JOIN "p2boards".maps ON (maps.steam_id = best.steam_id)
+                LEFT JOIN "p2boards".chapters ON (chapters.id = maps.chapter_id)
+                WHERE maps.is_public = True
+                ORDER BY LOWER(maps.name) = LOWER($2) DESC, best.kind, maps.name
+                LIMIT $3"#,
+        )
+        .bind(format!("%{}%", &query))
+        .bind(&query)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
     /// Searches for all chapter IDs that match a given search pattern.
     #[allow(dead_code)]
     pub async fn get_steam_id_by_name(
@@ -92,8 +884,8 @@ impl Maps {
         let res = sqlx::query(r#"SELECT is_public FROM "p2boards".maps WHERE steam_id = $1;"#)
             .bind(map_id)
             .map(|row: PgRow| row.get(0))
-            .fetch_one(pool)
+            .fetch_optional(pool)
             .await?;
-        Ok(Some(res))
+        Ok(res)
     }
 }
@@ -1,5 +1,7 @@
 /// Controllers for admin-specific functions
 pub mod admin;
+/// Controllers for categories
+pub mod categories;
 /// Controllers for changelog
 pub mod changelog;
 /// Controllers for chapters
@@ -8,6 +10,8 @@ pub mod chapters;
 pub mod coop;
 /// Controllers for demos
 pub mod demos;
+/// Controllers for games
+pub mod games;
 /// Controllers for maps
 pub mod maps;
 /// Controllers for sp
@@ -1,7 +1,11 @@
+use crate::controllers::maps::{DEFAULT_CAT, DEFAULT_CAT_FROM};
 use crate::models::models::*;
-use anyhow::Result;
+use crate::tools::helpers::validate_steam_id64;
+use anyhow::{bail, Result};
+use chrono::{Duration, NaiveDateTime, Utc};
 use sqlx::postgres::PgRow;
 use sqlx::{PgPool, Row};
+use std::collections::HashMap;
 
 impl Users {
     /// Returns user information
@@ -16,7 +20,13 @@ impl Users {
         Ok(Some(res))
     }
     /// Gets a user's avatar and user_name/board_name (favors board_name)
-    pub async fn get_user_data(pool: &PgPool, profile_number: String) -> Result<Option<UsersPage>> {
+    ///
+    /// Users without an avatar on record get `default_avatar` instead.
+    pub async fn get_user_data(
+        pool: &PgPool,
+        profile_number: String,
+        default_avatar: &str,
+    ) -> Result<Option<UsersPage>> {
         let res = sqlx::query_as::<_, UsersPage>(
             r#"
                 SELECT            
@@ -25,12 +35,13 @@ impl Users {
                         THEN users.steam_name
                     WHEN users.board_name IS NOT NULL
                         THEN users.board_name
-                    END user_name, users.avatar
+                    END user_name, COALESCE(users.avatar, $2) AS avatar
                 FROM "p2boards".users
                 WHERE users.profile_number = $1
                 "#,
         )
         .bind(profile_number)
+        .bind(default_avatar)
         .fetch_optional(pool)
         .await?;
         Ok(res)
@@ -59,6 +70,18 @@ impl Users {
         }
         Ok(Some(res))
     }
+    /// Returns the profile_numbers of every user on the board, excluding accounts that were merged into another.
+    pub async fn get_all_profile_numbers(pool: &PgPool) -> Result<Vec<String>> {
+        let res = sqlx::query(
+            r#"SELECT users.profile_number FROM "p2boards".users
+                WHERE users.merged_into IS NULL
+                ORDER BY users.profile_number"#,
+        )
+        .map(|row: PgRow| row.get(0))
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
     /// Returns a list of all banned player's profile_numbers.
     pub async fn get_banned(pool: &PgPool) -> Result<Vec<String>> {
         let res = sqlx::query(
@@ -69,18 +92,117 @@ impl Users {
         .await?;
         Ok(res)
     }
-    /// Returns a list of all banned player's as a UsersDisplay object.
-    pub async fn get_banned_display(pool: &PgPool) -> Result<Option<Vec<UsersDisplay>>> {
+    /// Returns a list of all banned player's as a UsersDisplay object, falls back to `default_avatar` for missing avatars.
+    #[allow(dead_code)]
+    pub async fn get_banned_display(
+        pool: &PgPool,
+        default_avatar: &str,
+    ) -> Result<Option<Vec<UsersDisplay>>> {
         let res = sqlx::query_as::<_, UsersDisplay>(
             r#" SELECT users.profile_number,
                 COALESCE(users.board_name, users.steam_name) as user_name, 
-                users.avatar
+                COALESCE(users.avatar, $1) AS avatar
                     FROM "p2boards".users WHERE users.banned = 'true'"#,
         )
+        .bind(default_avatar)
         .fetch_all(pool)
         .await?;
         Ok(Some(res))
     }
+    /// Returns display info for all banned users, with their public ban reason.
+    ///
+    /// Only the public `ban_reason` is included, internal notes stay in the admin audit log.
+    pub async fn get_banned_with_reasons(
+        pool: &PgPool,
+        default_avatar: &str,
+    ) -> Result<Vec<BannedUserDisplay>> {
+        let res = sqlx::query_as::<_, BannedUserDisplay>(
+            r#"
+                SELECT users.profile_number,
+                    COALESCE(users.board_name, users.steam_name) AS user_name,
+                    COALESCE(users.avatar, $1) AS avatar,
+                    users.ban_reason AS reason
+                FROM "p2boards".users
+                WHERE users.banned = True
+                ORDER BY users.profile_number"#,
+        )
+        .bind(default_avatar)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns players who submitted a run in the last `within_minutes`, with their latest run, most recent first.
+    ///
+    /// A lighter, live view of [Users::get_recently_active] for a "who's grinding now" widget. Banned runs and players are excluded.
+    pub async fn get_active_now(
+        pool: &PgPool,
+        within_minutes: i64,
+        limit: i64,
+        default_avatar: &str,
+    ) -> Result<Vec<ActivePlayer>> {
+        let since = Utc::now().naive_utc() - Duration::minutes(within_minutes);
+        let res = sqlx::query_as::<_, ActivePlayer>(
+            r#"
+                SELECT latest.profile_number,
+                    COALESCE(users.board_name, users.steam_name) AS user_name,
+                    COALESCE(users.avatar, $3) AS avatar,
+                    latest.id AS cl_id, latest.map_id, maps.name AS map_name,
+                    latest.score, latest.timestamp
+                FROM (
+                    SELECT DISTINCT ON (cl.profile_number)
+                        cl.id, cl.profile_number, cl.map_id, cl.score, cl.timestamp
+                    FROM "p2boards".changelog AS cl
+                    WHERE cl.timestamp >= $1
+                    AND cl.banned = False
+                    ORDER BY cl.profile_number, cl.timestamp DESC, cl.id DESC
+                ) AS latest
+                INNER JOIN "p2boards".users ON (users.profile_number = latest.profile_number)
+                INNER JOIN "p2boards".maps ON (maps.steam_id = latest.map_id)
+                WHERE users.banned = False
+                ORDER BY latest.timestamp DESC, latest.id DESC
+                LIMIT $2"#,
+        )
+        .bind(since)
+        .bind(limit)
+        .bind(default_avatar)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns players who submitted a run in the last `hours`, most recently active first. Banned players and banned runs are excluded.
+    ///
+    /// Activity is based on changelog timestamps, not registrations. Falls back to `default_avatar` for missing avatars.
+    pub async fn get_recently_active(
+        pool: &PgPool,
+        hours: i64,
+        limit: i64,
+        default_avatar: &str,
+    ) -> Result<Vec<UsersDisplay>> {
+        let since = Utc::now().naive_utc() - Duration::hours(hours);
+        let res = sqlx::query_as::<_, UsersDisplay>(
+            r#"
+                SELECT users.profile_number,
+                    COALESCE(users.board_name, users.steam_name) AS user_name,
+                    COALESCE(users.avatar, $3) AS avatar
+                FROM (
+                    SELECT cl.profile_number, MAX(cl.timestamp) AS last_active
+                    FROM "p2boards".changelog AS cl
+                    WHERE cl.timestamp >= $1
+                    AND cl.banned = False
+                    GROUP BY cl.profile_number
+                ) AS activity
+                INNER JOIN "p2boards".users ON (users.profile_number = activity.profile_number)
+                WHERE users.banned = False
+                ORDER BY activity.last_active DESC, users.profile_number
+                LIMIT $2"#,
+        )
+        .bind(since)
+        .bind(limit)
+        .bind(default_avatar)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
     /// Returns the boolean flag associated with the user in the boards, if Err, assumed User does not exist.
     pub async fn check_banned(pool: &PgPool, profile_number: String) -> Result<bool> {
         let res = sqlx::query(
@@ -136,20 +258,24 @@ impl Users {
     ///             (Typically reserved for former admins, trusted players)
     ///         admin_value = 3     -> Developer admin
     ///             (Has admin permissions as an activen developer only)
+    ///
+    /// Admins without an avatar on record get `default_avatar` instead.
     pub async fn get_all_admins(
         pool: &PgPool,
         admin_value: i32,
+        default_avatar: &str,
     ) -> Result<Option<Vec<UsersDisplay>>> {
         let res = sqlx::query_as::<_, UsersDisplay>(
             r#"
                 SELECT users.profile_number, 
                     COALESCE(users.board_name, users.steam_name) AS user_name,
-                    users.avatar
+                    COALESCE(users.avatar, $2) AS avatar
                 FROM "p2boards".users
                 WHERE users.admin = $1
                 "#,
         )
         .bind(admin_value)
+        .bind(default_avatar)
         .fetch_all(pool)
         .await?;
         Ok(Some(res))
@@ -166,59 +292,528 @@ impl Users {
         .await?;
         Ok(Some(res))
     }
+    /// Returns the oldest and newest scores for a user's profile, only considers the default category of each map.
+    ///
+    /// The meaning of "oldest" and "newest" depends on `pb_only`:
+    /// - `true`: Looks at the user's full history, but only at runs that were a PB at the time they were set.
+    ///   "oldest" is the first PB the user ever set, "newest" is the user's most recent improvement on any map.
+    /// - `false`: Only looks at the user's current best score on each map.
+    ///   "oldest" is the longest standing current PB, "newest" is the most recently set current PB.
+    ///
+    /// `oldest`/`newest` cover all maps, `sp_stats`/`coop_stats` apply the same logic to only SP or only coop maps.
+    /// Coop scores include the partner for the run.
+    ///
+    /// `sp_default_cats` is the default category of every SP map, used for the SP total, see [Users::get_sp_total].
     pub async fn get_profile(
         pool: &PgPool,
         profile_number: &String,
+        pb_only: bool,
+        sp_default_cats: &HashMap<String, i32>,
     ) -> Result<Option<ProfileData>> {
-        let oldest = sqlx::query_as::<_, MapScoreDate>(r#"
-            SELECT old.steam_id AS map, old.name AS map_name, old.score, old.timestamp FROM 
-                (SELECT maps.steam_id, maps.name, changelog.score, changelog.timestamp FROM "p2boards".maps 
-                INNER JOIN "p2boards".changelog ON (maps.steam_id = changelog.map_id) WHERE changelog.timestamp = (
-                SELECT *
-                    FROM (
-                        SELECT MIN(o1.timestamp)
-                        FROM
-                        (SELECT DISTINCT ON (m1.steam_id) m1.steam_id, m1.name, cl1.score, cl1.timestamp, cl1.id
-                            FROM "p2boards".changelog AS cl1
-                            INNER JOIN "p2boards".maps AS m1
-                                ON (cl1.map_id = m1.steam_id)
-                            WHERE cl1.profile_number = $1
-                            AND cl1.banned = 'false'
-                            AND cl1.verified = 'true'
-                            AND cl1.category_id = m1.default_cat_id
-                            ORDER BY m1.steam_id, cl1.score) AS o1) AS a)) AS old;"#)
-            .bind(profile_number)
-            .fetch_one(pool)
-            .await?;
-        let newest = sqlx::query_as::<_, MapScoreDate>(r#"
-            SELECT old.steam_id AS map, old.name AS map_name, old.score, old.timestamp FROM 
-                (SELECT maps.steam_id, maps.name, changelog.score, changelog.timestamp FROM "p2boards".maps 
-                INNER JOIN "p2boards".changelog ON (maps.steam_id = changelog.map_id) WHERE changelog.timestamp = (
-                SELECT *
-                    FROM (
-                        SELECT MAX(o1.timestamp)
-                        FROM
-                        (SELECT DISTINCT ON (m1.steam_id) m1.steam_id, m1.name, cl1.score, cl1.timestamp, cl1.id
-                            FROM "p2boards".changelog AS cl1
-                            INNER JOIN "p2boards".maps AS m1
-                                ON (cl1.map_id = m1.steam_id)
-                            WHERE cl1.profile_number = $1
-                            AND cl1.banned = 'false'
-                            AND cl1.verified = 'true'
-                            AND cl1.category_id = m1.default_cat_id
-                            ORDER BY m1.steam_id, cl1.score) AS o1) AS a)) AS old;"#)
-            .bind(profile_number)
-            .fetch_one(pool)
-            .await?;
+        let oldest = Users::get_profile_score(pool, profile_number, pb_only, "ASC", None).await?;
+        let newest = Users::get_profile_score(pool, profile_number, pb_only, "DESC", None).await?;
+        let (oldest, newest) = match (oldest, newest) {
+            (Some(oldest), Some(newest)) => (oldest, newest),
+            _ => bail!("No scores found for user {}", profile_number),
+        };
+        let sp_stats = ProfileStats {
+            oldest: Users::get_profile_score(pool, profile_number, pb_only, "ASC", Some(false))
+                .await?,
+            newest: Users::get_profile_score(pool, profile_number, pb_only, "DESC", Some(false))
+                .await?,
+        };
+        let coop_stats = ProfileStats {
+            oldest: Users::get_profile_score(pool, profile_number, pb_only, "ASC", Some(true))
+                .await?,
+            newest: Users::get_profile_score(pool, profile_number, pb_only, "DESC", Some(true))
+                .await?,
+        };
         // let wrs = sqlx::query_as::<_, ProfileWrs>(r#""#)
         //     .bind(profile_number)
         //     .fetch_one(pool)
         //     .await?;
-        Ok(Some(ProfileData { oldest, newest }))
+        let categories = Users::get_scores_by_category(pool, profile_number.clone()).await?;
+        let sp_total = Users::get_sp_total(&categories, sp_default_cats);
+        Ok(Some(ProfileData {
+            oldest,
+            newest,
+            sp_stats,
+            coop_stats,
+            categories,
+            sp_total,
+        }))
+    }
+    /// Sums a user's best scores in the default category of every SP map, from the scores returned by [Users::get_scores_by_category].
+    ///
+    /// Only maps with a verified score count towards the total, see [SpTotal] for how many maps are missing.
+    /// `sp_default_cats` maps every SP map to its default category, SP maps without one aren't part of the total.
+    pub fn get_sp_total(
+        categories: &HashMap<i32, Vec<UserScoreRow>>,
+        sp_default_cats: &HashMap<String, i32>,
+    ) -> SpTotal {
+        let mut total = SpTotal {
+            maps_total: sp_default_cats.len() as i64,
+            ..Default::default()
+        };
+        for score in categories.values().flatten() {
+            if sp_default_cats.get(&score.map_id) == Some(&score.category_id) {
+                total.total_score += score.score as i64;
+                total.maps_completed += 1;
+            }
+        }
+        total
+    }
+    /// Returns a user's best verified, unbanned score on every map & category they've played, grouped by category id.
+    ///
+    /// Categories are stored per map, so each category holds the user's score on its map. Scores are in map order.
+    pub async fn get_scores_by_category(
+        pool: &PgPool,
+        profile_number: String,
+    ) -> Result<HashMap<i32, Vec<UserScoreRow>>> {
+        let res = sqlx::query(
+            r#"
+                SELECT best.id, best.map_id, maps.name, best.category_id,
+                    best.score, best.timestamp, best.has_demo, best.has_video
+                FROM (
+                    SELECT DISTINCT ON (cl.map_id, cl.category_id)
+                        cl.id, cl.map_id, cl.category_id, cl.score, cl.timestamp,
+                        cl.demo_id IS NOT NULL AS has_demo, cl.youtube_id IS NOT NULL AS has_video
+                    FROM "p2boards".changelog AS cl
+                    WHERE cl.profile_number = $1
+                    AND cl.banned = False
+                    AND cl.verified = True
+                    ORDER BY cl.map_id, cl.category_id, cl.score, cl.timestamp NULLS LAST, cl.id
+                ) AS best
+                INNER JOIN "p2boards".maps ON (maps.steam_id = best.map_id)
+                ORDER BY maps.id, best.category_id"#,
+        )
+        .bind(profile_number)
+        .map(|row: PgRow| UserScoreRow {
+            cl_id: row.get(0),
+            map_id: row.get(1),
+            map_name: row.get(2),
+            category_id: row.get(3),
+            score: row.get(4),
+            timestamp: row.get(5),
+            has_demo: row.get(6),
+            has_video: row.get(7),
+        })
+        .fetch_all(pool)
+        .await?;
+        let mut categories: HashMap<i32, Vec<UserScoreRow>> = HashMap::new();
+        for score in res.into_iter() {
+            categories.entry(score.category_id).or_default().push(score);
+        }
+        Ok(categories)
+    }
+    /// Returns either the oldest (`"ASC"`) or newest (`"DESC"`) score for a user, see [Users::get_profile] for `pb_only`.
+    ///
+    /// A run is a PB if it beats every earlier run by the user on the same map.
+    /// `is_mp` limits the search to only SP (`Some(false)`) or only coop (`Some(true)`) maps.
+    async fn get_profile_score(
+        pool: &PgPool,
+        profile_number: &String,
+        pb_only: bool,
+        order: &str,
+        is_mp: Option<bool>,
+    ) -> Result<Option<MapScoreDate>> {
+        let mode_filter = match is_mp {
+            Some(is_mp) => format!("AND chapters.is_multiplayer = {}", is_mp),
+            None => String::new(),
+        };
+        let from = format!(
+            r#"
+                    FROM "p2boards".changelog AS cl
+                    INNER JOIN "p2boards".maps AS m1 ON (cl.map_id = m1.steam_id)
+                    INNER JOIN "p2boards".chapters ON (m1.chapter_id = chapters.id)
+                    INNER JOIN (SELECT maps.steam_id, {} {}) AS defaults ON (defaults.steam_id = m1.steam_id)
+                    WHERE cl.profile_number = $1
+                    AND cl.banned = 'false'
+                    AND cl.verified = 'true'
+                    AND cl.category_id = defaults.default_cat_id
+                    {}"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM, mode_filter
+        );
+        let scores = if pb_only {
+            format!(
+                r#"
+                SELECT * FROM 
+                    (SELECT cl.id, cl.map_id, cl.score, cl.timestamp, cl.coop_id,
+                        MIN(cl.score) OVER (
+                            PARTITION BY cl.map_id ORDER BY cl.timestamp, cl.id
+                            ROWS BETWEEN UNBOUNDED PRECEDING AND 1 PRECEDING) AS previous_best
+                    {}
+                    AND cl.timestamp IS NOT NULL) AS history
+                WHERE history.previous_best IS NULL OR history.score < history.previous_best"#,
+                from
+            )
+        } else {
+            format!(
+                r#"
+                SELECT DISTINCT ON (cl.map_id) cl.id, cl.map_id, cl.score, cl.timestamp, cl.coop_id
+                    {}
+                    ORDER BY cl.map_id, cl.score, cl.timestamp"#,
+                from
+            )
+        };
+        let res = sqlx::query_as::<_, MapScoreDate>(&format!(
+            r#"
+            SELECT s.map_id AS map, maps.name AS map_name, s.score, s.timestamp,
+                partner.profile_number AS partner_profile_number,
+                CASE
+                    WHEN partner.board_name IS NULL
+                        THEN partner.steam_name
+                    WHEN partner.board_name IS NOT NULL
+                        THEN partner.board_name
+                END partner_name
+            FROM ({scores}) AS s
+            INNER JOIN "p2boards".maps ON (s.map_id = maps.steam_id)
+            LEFT JOIN "p2boards".coop_bundled AS cb ON (s.coop_id = cb.id)
+            LEFT JOIN "p2boards".users AS partner ON (partner.profile_number = 
+                CASE 
+                    WHEN cb.p_id1 = $1 
+                        THEN cb.p_id2 
+                    ELSE cb.p_id1 
+                END)
+                WHERE s.timestamp IS NOT NULL
+                ORDER BY s.timestamp {order}, s.id {order}
+                LIMIT 1;"#,
+            scores = scores,
+            order = order
+        ))
+        .bind(profile_number)
+        .fetch_optional(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns `(completed, total)` for a user on a chapter, where completed is the # of the chapter's maps
+    /// the user has a verified, non-banned score on.
+    pub async fn get_chapter_completion(
+        pool: &PgPool,
+        profile_number: String,
+        chapter_id: i32,
+    ) -> Result<(i64, i64)> {
+        Users::get_completion(pool, profile_number, "maps.chapter_id = $2", chapter_id).await
+    }
+    /// Returns `(completed, total)` for a user on every map in a game, see [Users::get_chapter_completion].
+    pub async fn get_game_completion(
+        pool: &PgPool,
+        profile_number: String,
+        game_id: i32,
+    ) -> Result<(i64, i64)> {
+        Users::get_completion(pool, profile_number, "chapters.game_id = $2", game_id).await
+    }
+    /// Counts the public maps matching `map_filter`, and how many of them the user has a valid score on.
+    async fn get_completion(
+        pool: &PgPool,
+        profile_number: String,
+        map_filter: &str,
+        id: i32,
+    ) -> Result<(i64, i64)> {
+        let res = sqlx::query(&format!(
+            r#"
+                SELECT
+                    COUNT(*) FILTER (WHERE EXISTS (
+                        SELECT 1 FROM "p2boards".changelog
+                        WHERE changelog.map_id = maps.steam_id
+                        AND changelog.profile_number = $1
+                        AND changelog.verified = True
+                        AND changelog.banned = False)),
+                    COUNT(*)
+                FROM "p2boards".maps
+                INNER JOIN "p2boards".chapters ON (maps.chapter_id = chapters.id)
+                WHERE maps.is_public = True
+                AND {}"#,
+            map_filter
+        ))
+        .bind(profile_number)
+        .bind(id)
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_one(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns the user's rank on every SP (`is_mp = false`) or coop (`is_mp = true`) map they have a score on,
+    /// out of all ranked players on the map's default category.
+    ///
+    /// `percentile` is `rank / total * 100`, so lower is better (`5.0` is the top 5%).
+    /// Banned runs and players are excluded from both the rank and the total.
+    pub async fn get_percentiles(
+        pool: &PgPool,
+        profile_number: String,
+        is_mp: bool,
+    ) -> Result<Vec<MapPercentile>> {
+        let res = sqlx::query_as::<_, MapPercentile>(&format!(
+            r#"
+                SELECT board.map_id, board.map_name, board.score, board.rank, board.total,
+                    (board.rank::float8 / board.total::float8) * 100 AS percentile
+                FROM (
+                    SELECT pbs.map_id, pbs.map_name, pbs.profile_number, pbs.score,
+                        RANK() OVER (PARTITION BY pbs.map_id ORDER BY pbs.score) AS rank,
+                        COUNT(*) OVER (PARTITION BY pbs.map_id) AS total,
+                        pbs.map_order
+                    FROM (
+                        SELECT DISTINCT ON (cl.map_id, cl.profile_number)
+                            cl.map_id, maps.name AS map_name, maps.id AS map_order,
+                            cl.profile_number, cl.score
+                        FROM "p2boards".changelog AS cl
+                        INNER JOIN "p2boards".maps ON (cl.map_id = maps.steam_id)
+                        INNER JOIN "p2boards".chapters ON (maps.chapter_id = chapters.id)
+                        INNER JOIN (SELECT maps.steam_id, {} {}) AS defaults ON (defaults.steam_id = maps.steam_id)
+                        INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                        WHERE chapters.is_multiplayer = $2
+                        AND cl.category_id = defaults.default_cat_id
+                        AND cl.banned = False
+                        AND cl.verified = True
+                        AND users.banned = False
+                        ORDER BY cl.map_id, cl.profile_number, cl.score
+                    ) AS pbs
+                ) AS board
+                WHERE board.profile_number = $1
+                ORDER BY board.map_order"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .bind(profile_number)
+        .bind(is_mp)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns a user's best run on every SP or coop map they have a score on, keyed by the map's steam_id.
+    ///
+    /// Only the default category is considered. Banned runs are excluded, but the user's unverified runs are included (and flagged).
+    /// `rank` is where the score places among other players' verified bests, ties share a rank.
+    pub async fn get_bests(
+        pool: &PgPool,
+        profile_number: String,
+        is_mp: bool,
+    ) -> Result<HashMap<String, PlayerBest>> {
+        let res = sqlx::query(&format!(
+            r#"
+                SELECT best.map_id, best.id, best.score, best.verified, best.has_demo, best.has_video,
+                    1 + (
+                        SELECT COUNT(DISTINCT other.profile_number)
+                        FROM "p2boards".changelog AS other
+                        INNER JOIN "p2boards".users ON (users.profile_number = other.profile_number)
+                        WHERE other.map_id = best.map_id
+                        AND other.category_id = best.category_id
+                        AND other.profile_number <> best.profile_number
+                        AND other.score < best.score
+                        AND other.banned = False
+                        AND other.verified = True
+                        AND users.banned = False
+                    ) AS rank
+                FROM (
+                    SELECT DISTINCT ON (cl.map_id)
+                        cl.map_id, cl.id, cl.profile_number, cl.category_id, cl.score,
+                        COALESCE(cl.verified, False) AS verified,
+                        cl.demo_id IS NOT NULL AS has_demo, cl.youtube_id IS NOT NULL AS has_video
+                    FROM "p2boards".changelog AS cl
+                    INNER JOIN "p2boards".maps ON (cl.map_id = maps.steam_id)
+                    INNER JOIN "p2boards".chapters ON (maps.chapter_id = chapters.id)
+                    INNER JOIN (SELECT maps.steam_id, {} {}) AS defaults ON (defaults.steam_id = maps.steam_id)
+                    WHERE cl.profile_number = $1
+                    AND chapters.is_multiplayer = $2
+                    AND cl.category_id = defaults.default_cat_id
+                    AND cl.banned = False
+                    ORDER BY cl.map_id, cl.score, cl.timestamp NULLS LAST, cl.id
+                ) AS best"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .bind(profile_number)
+        .bind(is_mp)
+        .map(|row: PgRow| {
+            (
+                row.get(0),
+                PlayerBest {
+                    cl_id: row.get(1),
+                    score: row.get(2),
+                    verified: row.get(3),
+                    has_demo: row.get(4),
+                    has_video: row.get(5),
+                    rank: row.get(6),
+                },
+            )
+        })
+        .fetch_all(pool)
+        .await?;
+        Ok(res.into_iter().collect())
+    }
+    /// Returns a user's longest run of consecutive WR-setting submissions (`post_rank = 1`), and their current run.
+    ///
+    /// Every unbanned submission counts in timestamp order, across all maps & categories, any non-WR submission breaks the streak.
+    /// `start`/`end` are the timestamps of the first and last submission in the longest streak, the earliest streak wins ties.
+    pub async fn get_wr_streak(pool: &PgPool, profile_number: String) -> Result<WrStreak> {
+        let history: Vec<(bool, Option<NaiveDateTime>)> = sqlx::query(
+            r#"
+                SELECT COALESCE(cl.post_rank = 1, False), cl.timestamp
+                FROM "p2boards".changelog AS cl
+                WHERE cl.profile_number = $1
+                AND cl.banned = False
+                ORDER BY cl.timestamp ASC NULLS FIRST, cl.id ASC"#,
+        )
+        .bind(profile_number)
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_all(pool)
+        .await?;
+        let mut streak = WrStreak::default();
+        let mut start = None;
+        for (is_wr, timestamp) in history.into_iter() {
+            if !is_wr {
+                streak.current = 0;
+                continue;
+            }
+            if streak.current == 0 {
+                start = timestamp;
+            }
+            streak.current += 1;
+            if streak.current > streak.longest {
+                streak.longest = streak.current;
+                streak.start = start;
+                streak.end = timestamp;
+            }
+        }
+        Ok(streak)
+    }
+    /// Returns the maps a user has the most submissions on, most submissions first, up to `limit` maps.
+    ///
+    /// Every unbanned changelog entry counts, across all categories.
+    pub async fn get_most_played_maps(
+        pool: &PgPool,
+        profile_number: String,
+        limit: i64,
+    ) -> Result<Vec<MapPlayCount>> {
+        let res = sqlx::query_as::<_, MapPlayCount>(
+            r#"
+                SELECT cl.map_id, maps.name AS map_name, COUNT(*) AS submissions
+                FROM "p2boards".changelog AS cl
+                INNER JOIN "p2boards".maps ON (cl.map_id = maps.steam_id)
+                WHERE cl.profile_number = $1
+                AND cl.banned = False
+                GROUP BY cl.map_id, maps.name, maps.id
+                ORDER BY submissions DESC, maps.id
+                LIMIT $2"#,
+        )
+        .bind(profile_number)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns every partner a user has coop runs with, and the # of unbanned runs they share, most frequent first.
+    ///
+    /// Runs are linked through the changelog's `coop_id`, solo runs (no partner on record) are skipped.
+    pub async fn get_coop_partners(
+        pool: &PgPool,
+        profile_number: String,
+    ) -> Result<Vec<CoopPartner>> {
+        let res = sqlx::query_as::<_, CoopPartner>(
+            r#"
+                SELECT partner.profile_number,
+                    CASE
+                        WHEN partner.board_name IS NULL
+                            THEN partner.steam_name
+                        WHEN partner.board_name IS NOT NULL
+                            THEN partner.board_name
+                    END user_name,
+                    partner.avatar,
+                    COUNT(*) AS runs
+                FROM "p2boards".coop_bundled AS cb
+                INNER JOIN "p2boards".changelog AS cl ON (cl.coop_id = cb.id AND cl.profile_number = $1)
+                INNER JOIN "p2boards".users AS partner ON (partner.profile_number =
+                    CASE
+                        WHEN cb.p_id1 = $1
+                            THEN cb.p_id2
+                        ELSE cb.p_id1
+                    END)
+                WHERE (cb.p_id1 = $1 OR cb.p_id2 = $1)
+                AND cl.banned = False
+                GROUP BY partner.profile_number, partner.board_name, partner.steam_name, partner.avatar
+                ORDER BY runs DESC, partner.profile_number"#,
+        )
+        .bind(profile_number)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Returns a summary of a user's coop activity, see [CoopSummary].
+    ///
+    /// Maps completed solo (no partner on record) count towards completion and submissions, but not partners.
+    pub async fn get_coop_summary(pool: &PgPool, profile_number: String) -> Result<CoopSummary> {
+        let (maps_completed, submissions): (i64, i64) = sqlx::query(
+            r#"
+                SELECT
+                    COUNT(DISTINCT cl.map_id) FILTER (WHERE cl.verified = True),
+                    COUNT(*)
+                FROM "p2boards".changelog AS cl
+                INNER JOIN "p2boards".maps ON (cl.map_id = maps.steam_id)
+                INNER JOIN "p2boards".chapters ON (maps.chapter_id = chapters.id)
+                WHERE cl.profile_number = $1
+                AND cl.banned = False
+                AND chapters.is_multiplayer = True"#,
+        )
+        .bind(profile_number.clone())
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_one(pool)
+        .await?;
+        // A WR is held if the user's best on the map's default category ties the best valid score.
+        let wrs: i64 = sqlx::query(&format!(
+            r#"
+                SELECT COUNT(*) FROM (
+                    SELECT cl.map_id,
+                        MIN(cl.score) FILTER (WHERE cl.profile_number = $1) AS user_best,
+                        MIN(cl.score) AS wr
+                    FROM "p2boards".changelog AS cl
+                    INNER JOIN "p2boards".maps ON (cl.map_id = maps.steam_id)
+                    INNER JOIN "p2boards".chapters ON (maps.chapter_id = chapters.id)
+                    INNER JOIN (SELECT maps.steam_id, {} {}) AS defaults ON (defaults.steam_id = maps.steam_id)
+                    INNER JOIN "p2boards".users ON (users.profile_number = cl.profile_number)
+                    WHERE chapters.is_multiplayer = True
+                    AND cl.category_id = defaults.default_cat_id
+                    AND cl.banned = False
+                    AND cl.verified = True
+                    AND users.banned = False
+                    GROUP BY cl.map_id) AS boards
+                WHERE boards.user_best = boards.wr"#,
+            DEFAULT_CAT, DEFAULT_CAT_FROM
+        ))
+        .bind(profile_number.clone())
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(pool)
+        .await?;
+        let partner = Users::get_coop_partners(pool, profile_number)
+            .await?
+            .into_iter()
+            .next();
+        let (most_frequent_partner, most_frequent_partner_name, runs_with_partner) = match partner {
+            Some(partner) => (
+                Some(partner.profile_number),
+                partner.user_name,
+                partner.runs,
+            ),
+            None => (None, None, 0),
+        };
+        Ok(CoopSummary {
+            maps_completed,
+            wrs,
+            submissions,
+            most_frequent_partner,
+            most_frequent_partner_name,
+            runs_with_partner,
+        })
     }
     // TODO: Consider using profanity filter (only for really bad names): https://docs.rs/censor/latest/censor/
     /// Inserts a new user into the databse
-    pub async fn insert_new_users(pool: &PgPool, new_user: Users) -> Result<bool> {
+    ///
+    /// With the `notifications` feature, a welcome is posted to `webhook_url` in the background.
+    /// The webhook is best-effort, failures are logged and never fail the insert.
+    /// Profile numbers that aren't a valid SteamID64 are rejected, see [validate_steam_id64].
+    pub async fn insert_new_users(
+        pool: &PgPool,
+        new_user: Users,
+        webhook_url: Option<&str>,
+    ) -> Result<bool> {
+        validate_steam_id64(&new_user.profile_number)?;
         // let mut res = String::new();
         // We do not care about the returning profile_number. As it is not generated and we already have it
         let res = sqlx::query_as::<_, Users>(
@@ -245,17 +840,83 @@ impl Users {
         .await?;
 
         if res.profile_number == new_user.profile_number {
+            #[cfg(feature = "notifications")]
+            if let Some(webhook_url) = webhook_url {
+                let webhook_url = webhook_url.to_string();
+                let user = UsersDisplay {
+                    user_name: res
+                        .board_name
+                        .or(res.steam_name)
+                        .unwrap_or_else(|| res.profile_number.clone()),
+                    avatar: res.avatar.unwrap_or_default(),
+                    profile_number: res.profile_number,
+                };
+                tokio::spawn(async move {
+                    if let Err(e) =
+                        crate::tools::notifications::notify_new_user(&webhook_url, &user).await
+                    {
+                        eprintln!("Error notifying webhook of new user -> {}", e);
+                    }
+                });
+            }
+            #[cfg(not(feature = "notifications"))]
+            let _ = webhook_url;
             Ok(true)
         } else {
             Ok(false)
         }
     }
+    /// Updates every field on an existing user, renames are recorded in the user's name history.
+    ///
+    /// Changing a user's title or admin level needs `updated_by` to be an admin, the change is recorded in the audit log.
     #[allow(dead_code)]
-    pub async fn update_existing_user(pool: &PgPool, updated_user: Users) -> Result<bool> {
+    pub async fn update_existing_user(
+        pool: &PgPool,
+        updated_user: Users,
+        updated_by: Option<String>,
+    ) -> Result<bool> {
         // If this gives us an error, we're updaing a user that already exists.
         let _ = Users::get_user(pool, updated_user.profile_number.clone()).await?;
         // TODO: Check to make sure user has correct AUTH to update specific items
-        // (board_name should only be changed by the backend, admin should only be updated by admin etc)
+        // (board_name should only be changed by the backend)
+        let mut tx = pool.begin().await?;
+        let current: Option<(Option<String>, i32)> = sqlx::query_as(
+            r#"SELECT title, admin FROM "p2boards".users WHERE profile_number = $1 FOR UPDATE"#,
+        )
+        .bind(&updated_user.profile_number)
+        .fetch_optional(&mut tx)
+        .await?;
+        let mut audited: Vec<(&str, String)> = Vec::new();
+        if let Some((title, admin)) = current {
+            if title != updated_user.title {
+                audited.push((
+                    "set_title",
+                    format!("{:?} -> {:?}", title, updated_user.title),
+                ));
+            }
+            if admin != updated_user.admin {
+                audited.push(("set_admin", format!("{} -> {}", admin, updated_user.admin)));
+            }
+        }
+        if !audited.is_empty() {
+            match &updated_by {
+                Some(admin) => {
+                    Admin::check_admin(&mut tx, admin).await?;
+                }
+                None => bail!("Changing a user's title or admin level requires an admin"),
+            }
+        }
+        sqlx::query(
+            r#"
+                INSERT INTO "p2boards".name_history (profile_number, old_name, new_name)
+                SELECT profile_number, board_name, $2 FROM "p2boards".users
+                WHERE profile_number = $1
+                AND board_name IS DISTINCT FROM $2"#,
+        )
+        .bind(&updated_user.profile_number)
+        .bind(&updated_user.board_name)
+        .execute(&mut tx)
+        .await?;
         let _ = sqlx::query(
             r#"
                 UPDATE "p2boards".Users
@@ -275,11 +936,489 @@ impl Users {
         .bind(updated_user.admin)
         .bind(updated_user.donation_amount)
         .bind(updated_user.discord_id)
-        .bind(updated_user.profile_number)
-        .fetch_optional(pool)
+        .bind(&updated_user.profile_number)
+        .fetch_optional(&mut tx)
         .await?;
+        if let Some(admin) = &updated_by {
+            for (action, details) in audited.into_iter() {
+                Admin::log_admin_action(
+                    &mut tx,
+                    admin,
+                    action,
+                    Some(updated_user.profile_number.clone()),
+                    Some(details),
+                )
+                .await?;
+            }
+        }
+        tx.commit().await?;
         Ok(true)
     }
+    /// Returns every board_name change for a user, most recent first.
+    pub async fn get_name_history(
+        pool: &PgPool,
+        profile_number: String,
+    ) -> Result<Vec<NameHistory>> {
+        let res = sqlx::query_as::<_, NameHistory>(
+            r#"
+                SELECT * FROM "p2boards".name_history
+                WHERE profile_number = $1
+                ORDER BY timestamp DESC, id DESC"#,
+        )
+        .bind(profile_number)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Records a donation and recomputes the user's cumulative `donation_amount`, within a single transaction.
+    ///
+    /// `amount` is rounded to cents, `currency` is a 3 letter code. Amounts are summed as-is, so donations should
+    /// be recorded in a single currency for the total to be meaningful. The donation is recorded in the admin audit log.
+    /// On a user's first ledger entry, any legacy `donation_amount` is recorded first as an "Opening balance" donation,
+    /// so the recomputed total doesn't drop it.
+    /// Returns the user's new `donation_amount`, or `None` if the user doesn't exist.
+    pub async fn add_donation(
+        pool: &PgPool,
+        profile_number: String,
+        amount: f64,
+        currency: String,
+        note: Option<String>,
+        admin_profile_number: String,
+    ) -> Result<Option<String>> {
+        if !amount.is_finite() || amount < 0.01 {
+            bail!("Donation amount must be at least 0.01, got {}", amount);
+        }
+        let currency = currency.to_uppercase();
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            bail!("Invalid currency code {}", currency);
+        }
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, &admin_profile_number).await?;
+        let user: Option<(Option<String>, bool)> = sqlx::query(
+            r#"
+                SELECT donation_amount, EXISTS (SELECT 1 FROM "p2boards".donations
+                    WHERE donations.profile_number = users.profile_number)
+                FROM "p2boards".users
+                WHERE profile_number = $1
+                FOR UPDATE"#,
+        )
+        .bind(&profile_number)
+        .map(|row: PgRow| (row.get(0), row.get(1)))
+        .fetch_optional(&mut tx)
+        .await?;
+        let (legacy_amount, has_ledger) = match user {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+        // Totals recorded before the ledger existed would be overwritten by the SUM below,
+        // so carry them over as an opening balance on the user's first ledger entry.
+        if !has_ledger {
+            if let Some(legacy_amount) = legacy_amount.filter(|a| !a.trim().is_empty()) {
+                let opening: f64 = legacy_amount.trim().parse().map_err(|_| {
+                    anyhow::anyhow!(
+                        "Existing donation_amount {:?} for {} is not a number, fix it before adding donations",
+                        legacy_amount,
+                        profile_number
+                    )
+                })?;
+                if opening >= 0.01 {
+                    sqlx::query(
+                        r#"
+                            INSERT INTO "p2boards".donations (profile_number, amount, currency, note)
+                            VALUES ($1, CAST($2 AS numeric(10,2)), $3, 'Opening balance')"#,
+                    )
+                    .bind(&profile_number)
+                    .bind(opening)
+                    .bind(&currency)
+                    .execute(&mut tx)
+                    .await?;
+                }
+            }
+        }
+        let id: i64 = sqlx::query(
+            r#"
+                INSERT INTO "p2boards".donations (profile_number, amount, currency, note)
+                VALUES ($1, CAST($2 AS numeric(10,2)), $3, $4)
+                RETURNING id"#,
+        )
+        .bind(&profile_number)
+        .bind(amount)
+        .bind(&currency)
+        .bind(&note)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(&mut tx)
+        .await?;
+        let total: Option<String> = sqlx::query(
+            r#"
+                UPDATE "p2boards".users
+                SET donation_amount = (SELECT CAST(SUM(amount) AS text) FROM "p2boards".donations
+                    WHERE donations.profile_number = users.profile_number)
+                WHERE profile_number = $1
+                RETURNING donation_amount"#,
+        )
+        .bind(&profile_number)
+        .map(|row: PgRow| row.get(0))
+        .fetch_one(&mut tx)
+        .await?;
+        Admin::log_admin_action(
+            &mut tx,
+            &admin_profile_number,
+            "add_donation",
+            Some(profile_number),
+            Some(format!("Donation {}: {:.2} {}", id, amount, currency)),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(total)
+    }
+    /// Returns every donation recorded for a user, most recent first.
+    pub async fn get_donation_history(
+        pool: &PgPool,
+        profile_number: String,
+    ) -> Result<Vec<Donation>> {
+        let res = sqlx::query_as::<_, Donation>(
+            r#"
+                SELECT id, profile_number, timestamp, CAST(amount AS text) AS amount, currency, note
+                FROM "p2boards".donations
+                WHERE profile_number = $1
+                ORDER BY timestamp DESC, id DESC"#,
+        )
+        .bind(profile_number)
+        .fetch_all(pool)
+        .await?;
+        Ok(res)
+    }
+    /// Updates the Steam name and/or avatar for several users at once, within a single transaction.
+    ///
+    /// Only users whose data actually changed are written, returns the number of users updated.
+    /// `admin_profile_number` must belong to an admin, the sync is recorded in the admin audit log if anything changed.
+    pub async fn update_steam_data(
+        pool: &PgPool,
+        updates: &[SteamDataUpdate],
+        admin_profile_number: &str,
+    ) -> Result<u64> {
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, admin_profile_number).await?;
+        let mut updated = 0;
+        for update in updates.iter() {
+            let res = sqlx::query(
+                r#"
+                    UPDATE "p2boards".users
+                    SET steam_name = COALESCE($1, steam_name), avatar = COALESCE($2, avatar)
+                    WHERE profile_number = $3
+                    AND (steam_name IS DISTINCT FROM COALESCE($1, steam_name)
+                        OR avatar IS DISTINCT FROM COALESCE($2, avatar))"#,
+            )
+            .bind(update.steam_name.clone())
+            .bind(update.avatar.clone())
+            .bind(update.profile_number.clone())
+            .execute(&mut tx)
+            .await?;
+            updated += res.rows_affected();
+        }
+        if updated > 0 {
+            Admin::log_admin_action(
+                &mut tx,
+                admin_profile_number,
+                "sync_steam_data",
+                None,
+                Some(format!("Updated Steam data for {} users", updated)),
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(updated)
+    }
+    /// Finds groups of users that might be alt accounts of the same person, strongest matches first.
+    ///
+    /// Users are linked when their board/steam names have a trigram similarity of at least `threshold`,
+    /// or when they share a twitch, youtube or discord_id. Linked users are grouped together transitively.
+    pub async fn find_possible_duplicates(
+        pool: &PgPool,
+        threshold: f32,
+    ) -> Result<Vec<DuplicateCandidate>> {
+        let pairs = sqlx::query_as::<_, DuplicatePair>(
+            r#"
+                WITH names AS (
+                    SELECT profile_number, COALESCE(board_name, steam_name) AS user_name,
+                        NULLIF(LOWER(twitch), '') AS twitch, NULLIF(LOWER(youtube), '') AS youtube,
+                        NULLIF(discord_id, '') AS discord_id
+                    FROM "p2boards".users
+                )
+                SELECT a.profile_number, a.user_name, b.profile_number AS other_profile_number,
+                    b.user_name AS other_user_name, matches.reason, matches.similarity
+                FROM names a
+                INNER JOIN names b ON (a.profile_number < b.profile_number)
+                CROSS JOIN LATERAL (VALUES
+                    ('name', similarity(LOWER(a.user_name), LOWER(b.user_name))),
+                    ('twitch', CASE WHEN a.twitch = b.twitch THEN 1 ELSE 0 END::real),
+                    ('youtube', CASE WHEN a.youtube = b.youtube THEN 1 ELSE 0 END::real),
+                    ('discord_id', CASE WHEN a.discord_id = b.discord_id THEN 1 ELSE 0 END::real)
+                ) AS matches(reason, similarity)
+                WHERE (matches.reason = 'name' AND matches.similarity >= $1)
+                OR (matches.reason <> 'name' AND matches.similarity = 1)
+                ORDER BY matches.similarity DESC, a.profile_number, b.profile_number"#,
+        )
+        .bind(threshold)
+        .fetch_all(pool)
+        .await?;
+        // Group the pairs into clusters, merging clusters whenever a pair links two of them.
+        let mut cluster_of: HashMap<String, usize> = HashMap::new();
+        let mut clusters: Vec<Option<DuplicateCandidate>> = Vec::new();
+        for pair in pairs {
+            let a = cluster_of.get(&pair.profile_number).copied();
+            let b = cluster_of.get(&pair.other_profile_number).copied();
+            let idx = match (a, b) {
+                (Some(a), Some(b)) if a != b => {
+                    let merged = clusters[b].take().unwrap();
+                    for profile_number in merged.profile_numbers.iter() {
+                        cluster_of.insert(profile_number.clone(), a);
+                    }
+                    let cluster = clusters[a].as_mut().unwrap();
+                    cluster.profile_numbers.extend(merged.profile_numbers);
+                    cluster.matches.extend(merged.matches);
+                    cluster.similarity = cluster.similarity.max(merged.similarity);
+                    a
+                }
+                (Some(idx), _) | (_, Some(idx)) => idx,
+                (None, None) => {
+                    clusters.push(Some(DuplicateCandidate {
+                        profile_numbers: Vec::new(),
+                        similarity: pair.similarity,
+                        matches: Vec::new(),
+                    }));
+                    clusters.len() - 1
+                }
+            };
+            let cluster = clusters[idx].as_mut().unwrap();
+            for profile_number in [&pair.profile_number, &pair.other_profile_number] {
+                if !cluster_of.contains_key(profile_number) {
+                    cluster_of.insert(profile_number.clone(), idx);
+                    cluster.profile_numbers.push(profile_number.clone());
+                }
+            }
+            cluster.similarity = cluster.similarity.max(pair.similarity);
+            cluster.matches.push(pair);
+        }
+        let mut res: Vec<DuplicateCandidate> = clusters.into_iter().flatten().collect();
+        res.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+        Ok(res)
+    }
+    /// Bans or unbans a user, within a single transaction.
+    ///
+    /// `reason` is stored on the user as the public ban reason (cleared on unban).
+    /// With `cascade_scores`, every one of the user's unbanned changelog entries is banned to match, and marked as banned by
+    /// the cascade. Unbanning this way only restores those entries, runs that were banned individually stay banned.
+    /// Ranks on every board the user has a score on are recomputed, callers should drop any cached boards.
+    /// The action is recorded in the admin audit log, with `internal_note` as the details.
+    ///
+    /// Returns the # of changelog entries whose banned status changed, or `None` if the user doesn't exist.
+    pub async fn set_ban(
+        pool: &PgPool,
+        profile_number: String,
+        banned: bool,
+        reason: Option<String>,
+        internal_note: Option<String>,
+        cascade_scores: bool,
+        admin_profile_number: String,
+    ) -> Result<Option<u64>> {
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, &admin_profile_number).await?;
+        let res = sqlx::query(
+            r#"UPDATE "p2boards".users SET banned = $1, ban_reason = $2 WHERE profile_number = $3"#,
+        )
+        .bind(banned)
+        .bind(if banned { reason } else { None })
+        .bind(&profile_number)
+        .execute(&mut tx)
+        .await?;
+        if res.rows_affected() == 0 {
+            return Ok(None);
+        }
+        let action = if banned { "ban_user" } else { "unban_user" };
+        Admin::log_admin_action(
+            &mut tx,
+            &admin_profile_number,
+            action,
+            Some(profile_number.clone()),
+            internal_note,
+        )
+        .await?;
+        let mut scores_affected = 0;
+        if cascade_scores {
+            scores_affected = sqlx::query(
+                r#"
+                    UPDATE "p2boards".changelog SET banned = $1, banned_by_cascade = $1
+                    WHERE profile_number = $2
+                    AND banned <> $1
+                    AND ($1 = True OR banned_by_cascade = True)"#,
+            )
+            .bind(banned)
+            .bind(&profile_number)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+            let action = if banned {
+                "ban_user_scores"
+            } else {
+                "unban_user_scores"
+            };
+            Admin::log_admin_action(
+                &mut tx,
+                &admin_profile_number,
+                action,
+                Some(profile_number.clone()),
+                Some(format!("{} scores", scores_affected)),
+            )
+            .await?;
+        }
+        // Ranks only count unbanned players, so every board the user is on changes, not only those with cascaded scores.
+        let boards = sqlx::query_as::<_, MapCategory>(
+            r#"
+                SELECT DISTINCT map_id, category_id FROM "p2boards".changelog
+                WHERE profile_number = $1"#,
+        )
+        .bind(&profile_number)
+        .fetch_all(&mut tx)
+        .await?;
+        for board in boards.iter() {
+            Changelog::recompute_ranks(&mut tx, &board.map_id, board.category_id, None).await?;
+        }
+        tx.commit().await?;
+        Ok(Some(scores_affected))
+    }
+    /// Merges a duplicate account into another, within a single transaction.
+    ///
+    /// All of `remove_profile_number`'s scores (and their demos) and coop runs are moved to `keep_profile_number`.
+    /// On boards where both accounts have a score, the runs of the account with the worse best score are archived
+    /// (ties favour the kept account). Socials and title are copied over where the kept account has none.
+    /// The removed account is then deactivated, by clearing its socials/admin level and setting `merged_into`.
+    ///
+    /// The merge is recorded in the admin audit log.
+    pub async fn merge_accounts(
+        pool: &PgPool,
+        keep_profile_number: String,
+        remove_profile_number: String,
+        admin_profile_number: String,
+    ) -> Result<MergeSummary> {
+        if keep_profile_number == remove_profile_number {
+            bail!("Cannot merge an account into itself");
+        }
+        let mut tx = pool.begin().await?;
+        Admin::check_admin(&mut tx, &admin_profile_number).await?;
+        let users = sqlx::query_as::<_, Users>(
+            r#"SELECT * FROM "p2boards".users WHERE profile_number = ANY($1) FOR UPDATE"#,
+        )
+        .bind(vec![
+            keep_profile_number.clone(),
+            remove_profile_number.clone(),
+        ])
+        .fetch_all(&mut tx)
+        .await?;
+        if users.len() != 2 {
+            bail!("Both accounts must exist to be merged");
+        }
+        if users.iter().any(|user| user.merged_into.is_some()) {
+            bail!("Cannot merge an account that was already merged");
+        }
+        let archived = sqlx::query(
+            r#"
+                WITH bests AS (
+                    SELECT profile_number, map_id, category_id, MIN(score) AS best
+                    FROM "p2boards".changelog
+                    WHERE profile_number IN ($1, $2)
+                    AND banned = False
+                    AND archived = False
+                    GROUP BY profile_number, map_id, category_id
+                ), losers AS (
+                    SELECT keep.map_id, keep.category_id,
+                        CASE WHEN remove.best < keep.best THEN $1 ELSE $2 END AS profile_number
+                    FROM bests keep
+                    INNER JOIN bests remove ON (remove.map_id = keep.map_id
+                        AND remove.category_id = keep.category_id
+                        AND remove.profile_number = $2)
+                    WHERE keep.profile_number = $1
+                )
+                UPDATE "p2boards".changelog
+                SET archived = True
+                FROM losers
+                WHERE changelog.profile_number = losers.profile_number
+                AND changelog.map_id = losers.map_id
+                AND changelog.category_id = losers.category_id
+                AND changelog.banned = False
+                AND changelog.archived = False"#,
+        )
+        .bind(&keep_profile_number)
+        .bind(&remove_profile_number)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        // Demos reference changelog entries, so they follow the scores.
+        let transferred = sqlx::query(
+            r#"UPDATE "p2boards".changelog SET profile_number = $1 WHERE profile_number = $2"#,
+        )
+        .bind(&keep_profile_number)
+        .bind(&remove_profile_number)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+        let mut coop_transferred = 0;
+        for column in ["p_id1", "p_id2"] {
+            coop_transferred += sqlx::query(&format!(
+                r#"UPDATE "p2boards".coop_bundled SET {0} = $1 WHERE {0} = $2"#,
+                column
+            ))
+            .bind(&keep_profile_number)
+            .bind(&remove_profile_number)
+            .execute(&mut tx)
+            .await?
+            .rows_affected();
+        }
+        sqlx::query(
+            r#"
+                UPDATE "p2boards".users keep
+                SET twitch = COALESCE(keep.twitch, remove.twitch),
+                    youtube = COALESCE(keep.youtube, remove.youtube),
+                    discord_id = COALESCE(keep.discord_id, remove.discord_id),
+                    title = COALESCE(keep.title, remove.title)
+                FROM "p2boards".users remove
+                WHERE keep.profile_number = $1
+                AND remove.profile_number = $2"#,
+        )
+        .bind(&keep_profile_number)
+        .bind(&remove_profile_number)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query(
+            r#"
+                UPDATE "p2boards".users
+                SET twitch = NULL, youtube = NULL, discord_id = NULL, title = NULL,
+                    admin = 0, merged_into = $1
+                WHERE profile_number = $2"#,
+        )
+        .bind(&keep_profile_number)
+        .bind(&remove_profile_number)
+        .execute(&mut tx)
+        .await?;
+        let details = format!(
+            "Merged into {}, {} scores transferred, {} scores archived, {} coop runs transferred",
+            keep_profile_number, transferred, archived, coop_transferred
+        );
+        Admin::log_admin_action(
+            &mut tx,
+            &admin_profile_number,
+            "merge_accounts",
+            Some(remove_profile_number),
+            Some(details),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(MergeSummary {
+            scores_transferred: transferred,
+            scores_archived: archived,
+            coop_runs_transferred: coop_transferred,
+        })
+    }
     #[allow(dead_code)]
     pub async fn delete_user(pool: &PgPool, profile_number: String) -> Result<bool> {
         let res = sqlx::query_as::<_, Users>(
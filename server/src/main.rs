@@ -19,6 +19,9 @@ mod tests;
 /// Helpter functions used for the boards
 mod tools;
 
+/// Seconds workers are given to finish in-flight requests on shutdown, long enough for a demo upload.
+const SHUTDOWN_TIMEOUT_SECS: u64 = 60;
+
 /// Driver code to start and mount all compontents to the webserver we create.
 #[actix_web::main]
 async fn main() -> Result<(), Error> {
@@ -43,7 +46,20 @@ async fn main() -> Result<(), Error> {
     // Get a map of map_ids to default category IDs.
     let default_cat_ids = crate::tools::helpers::get_default_cat_ids(&pool).await;
     // Construct the cache.
-    let init_data = crate::tools::cache::CacheState::new(default_cat_ids);
+    let init_data = crate::tools::cache::CacheState::new(
+        default_cat_ids,
+        std::time::Duration::from_secs(config.cache.leaderboard_ttl_secs),
+    );
+    // Shared BackBlaze client, caches the authorization across all demo uploads/deletes.
+    let b2 = crate::tools::backblaze::BackBlazeClient::new(config.backblaze.clone())?;
+    // Optional daily recap of the boards, posted to a webhook.
+    #[cfg(feature = "notifications")]
+    if let Some(webhook_url) = config.notifications.daily_summary_webhook() {
+        tokio::spawn(crate::tools::notifications::run_daily_recap(
+            pool.clone(),
+            webhook_url.to_string(),
+        ));
+    }
     // Start our web server, mount and set up routes, data, wrapping, middleware and loggers
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -56,9 +72,12 @@ async fn main() -> Result<(), Error> {
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(init_data.clone()))
+            .app_data(web::Data::new(b2.clone()))
             .configure(api::v1::handlers::init::init)
     })
     .bind(format!("{}:{}", host, port))?
+    // On SIGTERM/SIGINT, stop accepting connections and give in-flight requests (demo uploads, transactions) time to finish.
+    .shutdown_timeout(SHUTDOWN_TIMEOUT_SECS)
     .run()
     .await?;
     Ok(())
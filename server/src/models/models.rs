@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use sqlx::FromRow;
 use std::collections::HashMap;
 
@@ -40,6 +40,24 @@ pub struct Changelog {
     pub score_delta: Option<i32>,
     pub verified: Option<bool>,
     pub admin_note: Option<String>,
+    #[serde(default)]
+    pub archived: bool,
+}
+/// A snapshot of a changelog entry taken right before it was edited.
+///
+/// `edited_by` is the admin that made the edit, `None` for automated edits.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChangelogHistory {
+    pub id: i64,
+    pub cl_id: i64,
+    pub timestamp: NaiveDateTime,
+    pub edited_by: Option<String>,
+    pub previous: Changelog,
+}
+/// Query parameters for editing a changelog entry, `admin` must be an admin and is recorded in the entry's edit history.
+#[derive(Deserialize, Debug)]
+pub struct UpdateChangelogParams {
+    pub admin: String,
 }
 /// All changelog data except for the ID, for table insertion.
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -98,6 +116,16 @@ pub struct Categories {
     pub rules: String,
 }
 
+/// A category on a map, with the # of players that have a valid score on it.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct CategoryWithCount {
+    pub id: i32,
+    pub name: String,
+    pub map_id: String,
+    pub rules: String,
+    pub count: i64,
+}
+
 /// One-to-one struct for chapter data.
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct Chapters {
@@ -127,6 +155,20 @@ pub struct CoopBundledInsert {
     pub cl_id2: Option<i64>,
 }
 
+/// A problem with a coop_bundled entry, see [Changelog::find_invalid_coop].
+///
+/// `reason` is one of `self_partner`, `missing_half`, `mismatched_map`, `mismatched_category` or `mismatched_score`,
+/// a bundle with several problems is reported once for each.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct CoopAnomaly {
+    pub coop_id: i64,
+    pub p_id1: String,
+    pub p_id2: Option<String>,
+    pub cl_id1: i64,
+    pub cl_id2: Option<i64>,
+    pub reason: String,
+}
+
 /// One-to-one struct for demo data.
 #[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
 pub struct Demos {
@@ -138,6 +180,50 @@ pub struct Demos {
     pub cl_id: i64,
 }
 
+/// A demo alongside the run it belongs to, for the moderators' review feed.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct DemoWithContext {
+    pub id: i64,
+    pub file_id: String,
+    pub cl_id: i64,
+    pub uploaded_at: NaiveDateTime,
+    pub parsed_successfully: bool,
+    pub profile_number: String,
+    pub user_name: Option<String>,
+    pub map_id: String,
+    pub map_name: String,
+    pub score: i32,
+    pub timestamp: Option<NaiveDateTime>,
+    pub verified: Option<bool>,
+    pub banned: bool,
+}
+
+/// A demo whose file was replaced, `stale_file_id` is set when the old file couldn't be removed from BackBlaze and is left behind.
+#[derive(Serialize, Debug, Clone)]
+pub struct ReplacedDemo {
+    #[serde(flatten)]
+    pub demo: Demos,
+    pub stale_file_id: Option<String>,
+}
+
+/// Query parameters for replacing a demo's file, `profile_number` must own the run or be an admin.
+#[derive(Debug, Deserialize)]
+pub struct ReplaceDemoParams {
+    pub profile_number: String,
+}
+
+/// Query parameters for finding demos to reparse, every demo parsed by a SAR version older than `min_sar_version` is returned.
+#[derive(Debug, Deserialize)]
+pub struct PendingReparseParams {
+    pub min_sar_version: String,
+}
+
+/// Query parameters for the recent demo feed, `limit` defaults to 50 (max 200).
+#[derive(Debug, Deserialize)]
+pub struct RecentDemosParams {
+    pub limit: Option<i64>,
+}
+
 #[derive(Debug, Default, Serialize, Deserialize, FromRow, Clone)]
 pub struct DemoInsert {
     pub file_id: String,
@@ -152,6 +238,8 @@ pub struct DemoInsert {
 pub struct Games {
     pub id: i32,
     pub game_name: String,
+    /// Name of the category used as the default for the game's maps that don't set their own `default_cat_id`.
+    pub default_category: Option<String>,
 }
 
 /// One-to-one struct for map data.
@@ -162,8 +250,14 @@ pub struct Maps {
     pub lp_id: String,
     pub name: String,
     pub chapter_id: Option<i32>,
-    pub default_cat_id: i32,
+    /// `None` if the map falls back to its game's `default_category`, see [Maps::get_default_cat].
+    pub default_cat_id: Option<i32>,
     pub is_public: bool,
+    pub difficulty_tier: Option<i32>,
+    /// Scores are only accepted while this is set, and the current time is within `opens_at`/`closes_at`, see [Maps::is_submission_open].
+    pub submissions_open: bool,
+    pub opens_at: Option<NaiveDateTime>,
+    pub closes_at: Option<NaiveDateTime>,
 }
 
 /// One-to-one struct for user data.
@@ -181,6 +275,12 @@ pub struct Users {
     pub admin: i32,
     pub donation_amount: Option<String>,
     pub discord_id: Option<String>,
+    /// Set when the account was merged into another, the profile_number of the account that was kept.
+    #[serde(default)]
+    pub merged_into: Option<String>,
+    /// Short, public-safe reason shown on the ban wall. Detailed notes are only kept in the admin audit log.
+    #[serde(default)]
+    pub ban_reason: Option<String>,
 }
 
 #[derive(Debug, FromRow, Deserialize, Serialize, Clone)]
@@ -198,6 +298,122 @@ pub struct UsersDisplay {
     pub avatar: String,
 }
 
+/// Display info for a banned user on the public ban wall, with their public ban reason (if any).
+#[derive(Debug, FromRow, Deserialize, Serialize, Clone)]
+pub struct BannedUserDisplay {
+    pub profile_number: String,
+    pub user_name: String,
+    pub avatar: String,
+    pub reason: Option<String>,
+}
+
+/// Body for banning/unbanning a user.
+///
+/// `reason` is public and shown on the ban wall, `internal_note` is only recorded in the admin audit log.
+/// `cascade_scores` (defaults to `false`) bans all of the user's changelog entries as well, or unbans the ones a cascading ban changed.
+#[derive(Debug, Deserialize)]
+pub struct BanUserParams {
+    pub profile_number: String,
+    pub banned: bool,
+    pub reason: Option<String>,
+    pub internal_note: Option<String>,
+    pub cascade_scores: Option<bool>,
+    pub admin_profile_number: String,
+}
+
+/// Steam profile data for a user, used by the backend to keep names/avatars in sync with Steam.
+///
+/// Fields that are `None` are left unchanged.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SteamDataUpdate {
+    pub profile_number: String,
+    pub steam_name: Option<String>,
+    pub avatar: Option<String>,
+}
+
+/// Body for syncing Steam data, the updates are only applied if `admin_profile_number` belongs to an admin.
+#[derive(Debug, Deserialize)]
+pub struct SteamDataParams {
+    pub admin_profile_number: String,
+    pub updates: Vec<SteamDataUpdate>,
+}
+
+/// A change to a user's board_name, `old_name`/`new_name` are `None` when no board_name was set.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct NameHistory {
+    pub id: i64,
+    pub profile_number: String,
+    pub timestamp: NaiveDateTime,
+    pub old_name: Option<String>,
+    pub new_name: Option<String>,
+}
+
+/// A single donation, see [crate::models::models::Users::add_donation]. `amount` is the exact decimal, e.g. `"5.00"`.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct Donation {
+    pub id: i64,
+    pub profile_number: String,
+    pub timestamp: NaiveDateTime,
+    pub amount: String,
+    pub currency: String,
+    pub note: Option<String>,
+}
+
+/// Body for recording a donation, `currency` is a 3 letter code and defaults to `USD`.
+#[derive(Debug, Deserialize)]
+pub struct DonationParams {
+    pub profile_number: String,
+    pub amount: f64,
+    pub currency: Option<String>,
+    pub note: Option<String>,
+    pub admin_profile_number: String,
+}
+
+/// Body for merging a duplicate account into another, see [crate::models::models::Users::merge_accounts].
+#[derive(Debug, Deserialize)]
+pub struct MergeAccountsParams {
+    pub keep_profile_number: String,
+    pub remove_profile_number: String,
+    pub admin_profile_number: String,
+}
+
+/// The result of merging two accounts.
+///
+/// `scores_archived` are runs on boards where both accounts had a score, from the account with the worse score.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MergeSummary {
+    pub scores_transferred: u64,
+    pub scores_archived: u64,
+    pub coop_runs_transferred: u64,
+}
+
+/// Query parameters for the duplicate account search, `threshold` is the minimum name similarity (0-1), defaults to 0.6.
+#[derive(Deserialize, Debug)]
+pub struct DuplicateParams {
+    pub threshold: Option<f32>,
+}
+
+/// A single reason two users might be the same person.
+///
+/// `reason` is one of `name`, `twitch`, `youtube` or `discord_id`. Shared socials always have a `similarity` of 1.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct DuplicatePair {
+    pub profile_number: String,
+    pub user_name: Option<String>,
+    pub other_profile_number: String,
+    pub other_user_name: Option<String>,
+    pub reason: String,
+    pub similarity: f32,
+}
+
+/// A group of users that are linked by one or more [DuplicatePair]s, `similarity` is the strongest match in the group.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DuplicateCandidate {
+    pub profile_numbers: Vec<String>,
+    pub similarity: f32,
+    pub matches: Vec<DuplicatePair>,
+}
+
 #[derive(Serialize, Deserialize, Debug, FromRow)]
 pub struct Socials {
     pub twitch: Option<String>,
@@ -244,6 +460,293 @@ pub struct CoopMap {
     pub avatar2: Option<String>,
 }
 
+/// A player's best score on a map, with their rank relative to the full board.
+#[derive(Serialize, FromRow, Clone, Debug)]
+pub struct MapBoardEntry {
+    pub timestamp: Option<NaiveDateTime>,
+    #[sqlx(rename = "cl_profile_number")]
+    pub profile_number: String,
+    pub score: i32,
+    pub demo_id: Option<i64>,
+    pub youtube_id: Option<String>,
+    pub submission: bool,
+    pub note: Option<String>,
+    pub category_id: i32,
+    pub user_name: Option<String>,
+    pub avatar: Option<String>,
+    pub rank: i64,
+}
+
+/// A world record, and how long it held the #1 spot.
+///
+/// `ended` is `None` if the record is the current world record, in which case the duration is measured up to now.
+#[derive(Serialize, Debug, Clone)]
+pub struct StandingWr {
+    pub cl_id: i64,
+    pub profile_number: String,
+    pub user_name: Option<String>,
+    pub score: i32,
+    pub timestamp: NaiveDateTime,
+    pub ended: Option<NaiveDateTime>,
+    pub duration_secs: i64,
+}
+
+/// The current world record on a map's default category, used for the records overview.
+///
+/// For coop maps the partner on the run is included, `None` for SP maps.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct MapWr {
+    pub steam_id: String,
+    pub name: String,
+    pub score: i32,
+    pub profile_number: String,
+    pub user_name: Option<String>,
+    pub avatar: Option<String>,
+    pub has_demo: bool,
+    pub has_video: bool,
+    pub partner_profile_number: Option<String>,
+    pub partner_name: Option<String>,
+}
+
+/// A row of the records grid, the current WR for a single map & category, see [Maps::get_all_wrs].
+///
+/// The WR fields are `None` when nobody has a valid score in the category.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct MapWrSummary {
+    pub map_id: String,
+    pub map_name: String,
+    pub chapter_id: Option<i32>,
+    pub chapter_name: Option<String>,
+    pub category_id: i32,
+    pub category_name: String,
+    pub score: Option<i32>,
+    pub profile_number: Option<String>,
+    pub user_name: Option<String>,
+    pub avatar: Option<String>,
+    pub timestamp: Option<NaiveDateTime>,
+}
+
+/// A WR and the #2 run on the same map, with how close the challenger is, see [Maps::get_softest_wrs].
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct SoftWr {
+    pub map_id: String,
+    pub map_name: String,
+    pub wr_score: i32,
+    pub holder_profile_number: String,
+    pub holder_name: Option<String>,
+    pub holder_avatar: Option<String>,
+    pub challenger_score: i32,
+    pub challenger_profile_number: String,
+    pub challenger_name: Option<String>,
+    pub challenger_avatar: Option<String>,
+    /// The challenger's score minus the WR, `0` for a tie.
+    pub gap: i32,
+}
+
+/// An uninterrupted stretch of time a single player held the world record on a map.
+///
+/// Improving on your own record extends the reign, `score` is the best score the holder reached during it.
+/// For the current holder `end` is now and `current` is `true`.
+#[derive(Serialize, Debug, Clone)]
+pub struct WrReign {
+    pub profile_number: String,
+    pub user_name: Option<String>,
+    pub score: i32,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub current: bool,
+    pub duration_secs: i64,
+}
+
+/// Query parameters for the records overview, `is_mp` defaults to `false` (SP).
+#[derive(Deserialize, Debug)]
+pub struct RecordsParams {
+    pub is_mp: Option<bool>,
+}
+
+/// Query parameters for the records grid, `scope` defaults to [CategoryScope::Default].
+#[derive(Deserialize, Debug)]
+pub struct RecordsGridParams {
+    pub scope: Option<CategoryScope>,
+}
+
+/// Query parameters for the completion extremes, `limit` is the # of maps on each side, defaults to 5 (max 50).
+#[derive(Deserialize, Debug)]
+pub struct CompletionExtremesParams {
+    pub limit: Option<i32>,
+}
+
+/// The currently featured map, with its board stats on the default category.
+///
+/// `wr`/`wr_holder`/`wr_holder_name` are `None` if the map has no valid scores yet.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct MapWithStats {
+    pub steam_id: String,
+    pub name: String,
+    pub chapter_id: Option<i32>,
+    pub default_cat_id: Option<i32>,
+    pub featured_at: NaiveDateTime,
+    pub until: NaiveDateTime,
+    pub players: i64,
+    pub wr: Option<i32>,
+    pub wr_holder: Option<String>,
+    pub wr_holder_name: Option<String>,
+}
+
+/// Body for featuring a map on the homepage until a given time, see [crate::models::models::Maps::set_featured].
+#[derive(Debug, Deserialize)]
+pub struct FeaturedMapParams {
+    pub map_id: String,
+    pub until: NaiveDateTime,
+    pub admin_profile_number: String,
+}
+
+/// Body for setting a map's difficulty tier, see [crate::models::models::Maps::set_tier].
+///
+/// A `null` tier clears it.
+#[derive(Debug, Deserialize)]
+pub struct MapTierParams {
+    pub map_id: String,
+    pub tier: Option<i32>,
+    pub admin_profile_number: String,
+}
+
+/// Body for opening or closing submissions on a map.
+///
+/// `opens_at` & `closes_at` are optional UTC bounds, `null` leaves that side of the window unbounded.
+#[derive(Debug, Deserialize)]
+pub struct SubmissionWindowParams {
+    pub map_id: String,
+    pub submissions_open: bool,
+    pub opens_at: Option<NaiveDateTime>,
+    pub closes_at: Option<NaiveDateTime>,
+    pub admin_profile_number: String,
+}
+
+/// Query parameters for the top scores on every map, `n` is the # of entries per map, defaults to 3 (max 20).
+///
+/// `is_mp` defaults to `false` (SP).
+#[derive(Deserialize, Debug)]
+pub struct TopNParams {
+    pub n: Option<i32>,
+    pub is_mp: Option<bool>,
+}
+
+/// Query parameters for the softest WRs, `limit` defaults to 10 (max 100).
+///
+/// `is_mp` defaults to `false` (SP).
+#[derive(Deserialize, Debug)]
+pub struct SoftestWrsParams {
+    pub limit: Option<i64>,
+    pub is_mp: Option<bool>,
+}
+
+/// Query parameters for the global map search, `limit` defaults to 10 (max 50).
+#[derive(Deserialize, Debug)]
+pub struct MapSearchParams {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+/// A public map found by [Maps::global_search].
+///
+/// `matched_on` is the strongest way the map matched, one of `name`, `chapter` or `category`.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct MapSearchResult {
+    pub steam_id: String,
+    pub name: String,
+    pub chapter_id: Option<i32>,
+    pub chapter_name: Option<String>,
+    pub matched_on: String,
+}
+
+/// A player's best score on a map's default category, with their position on that map's leaderboard.
+///
+/// Ties on score go to the run that was set first.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct MapLeaderboardEntry {
+    pub map_id: String,
+    pub rank: i64,
+    pub profile_number: String,
+    pub user_name: Option<String>,
+    pub avatar: Option<String>,
+    pub score: i32,
+    pub timestamp: Option<NaiveDateTime>,
+    pub has_demo: bool,
+    pub has_video: bool,
+}
+
+/// Everything shown on a map's page, see [Maps::get_map_page].
+///
+/// `leaderboard` and `recent` are for `cat_id`, `categories` and `stats` cover every category on the map.
+#[derive(Serialize)]
+pub struct MapPage {
+    pub map: Maps,
+    pub cat_id: i32,
+    pub categories: Vec<CategoryWithCount>,
+    pub leaderboard: Vec<MapLeaderboardEntry>,
+    pub stats: Vec<CategoryAvg>,
+    pub recent: Vec<ChangelogPage>,
+}
+
+/// Query parameters for a map's page, `top_n` defaults to 10 (max 100).
+#[derive(Debug, Deserialize)]
+pub struct MapPageParams {
+    /// The category, the map's default category if not provided.
+    pub cat_id: Option<i32>,
+    pub top_n: Option<i64>,
+}
+
+/// Query parameters for a map's leaderboard.
+#[derive(Debug, Deserialize)]
+pub struct MapLeaderboardParams {
+    /// The category, the map's default category if not provided.
+    pub cat_id: Option<i32>,
+    /// Return every run instead of only each player's best, defaults to `false`.
+    pub include_history: Option<bool>,
+    /// Reconstruct the board as it stood at this time, e.g. `2021-06-01T00:00:00`. Can't be combined with `include_history`.
+    pub at: Option<NaiveDateTime>,
+    /// With `at`, keep runs that have since been banned to show the board as it appeared then, defaults to `false`.
+    pub include_banned: Option<bool>,
+}
+
+/// Query parameters for the world record on a map at a point in time.
+#[derive(Debug, Deserialize)]
+pub struct WrAtParams {
+    /// The time to look up the world record at, e.g. `2021-06-01T00:00:00`.
+    pub at: NaiveDateTime,
+    /// The category, the map's default category if not provided.
+    pub cat_id: Option<i32>,
+}
+
+/// A public map, with the # of distinct players that have a valid score on it.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct MapCompletions {
+    pub map_id: String,
+    pub map_name: String,
+    pub players: i64,
+}
+
+/// The public maps with the fewest (`hardest`) and most (`easiest`) completions, each ordered from the most extreme.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompletionExtremes {
+    pub hardest: Vec<MapCompletions>,
+    pub easiest: Vec<MapCompletions>,
+}
+
+/// Average and median of every player's best score on a category, alongside the current record.
+///
+/// Only verified, unbanned runs from unbanned players are counted.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct CategoryAvg {
+    pub category_id: i32,
+    pub category_name: String,
+    pub players: i64,
+    pub wr: i32,
+    pub average: f64,
+    pub median: f64,
+}
+
 /// Wrapper for the sp map data and the rank/score.
 #[derive(Serialize)]
 pub struct SpRanked {
@@ -279,12 +782,20 @@ pub struct SpPreviews {
     pub scores: Vec<SpPreview>,
 }
 
-/// Wrapper for a player's SP PB history.
+/// Wrapper for a page of a player's SP PB history, `total` is the # of entries across all pages.
 #[derive(Serialize, Deserialize)]
 pub struct SpPbHistory {
     pub user_name: Option<String>,
     pub avatar: Option<String>,
     pub pb_history: Option<Vec<Changelog>>,
+    pub total: Option<i64>,
+}
+
+/// Query parameters for paging through a player's PB history, `limit` defaults to 50 (max 500).
+#[derive(Deserialize, Debug)]
+pub struct PbHistoryParams {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
 }
 
 /// The data for the preview page for all Coop Maps
@@ -336,6 +847,41 @@ pub struct CalcValues {
     pub banned: bool,
 }
 
+/// A submission that passed validation, ready to be inserted into the changelog.
+///
+/// `verified` assumes no demo is attached, submissions that include a demo should use [ValidatedSubmission::verified_with_demo].
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidatedSubmission {
+    pub insert: ChangelogInsert,
+    pub verified: bool,
+    pub requires_demo: bool,
+    pub requires_video: bool,
+}
+
+/// A single reason a submission, or a request's query parameters, were rejected.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub enum ValidationError {
+    UnknownMap(String),
+    InvalidCategory(i32),
+    InvalidTimestamp(String),
+    InvalidScore(i32),
+    InvalidYoutube(String),
+    UnknownUser(String),
+    InvalidProfileNumber(String),
+    UserBanned(String),
+    NotAnImprovement,
+    /// The map isn't currently accepting submissions, see [Maps::is_submission_open].
+    SubmissionsClosed(String),
+    /// No category was given, and the map has no default category on either the map or its game.
+    NoDefaultCategory(String),
+    /// A query parameter's value couldn't be parsed, as `(parameter, value)`.
+    InvalidParameter(String, String),
+}
+
+/// All the reasons a submission was rejected, in the order the checks were run.
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
 // Currently a dumbass work around to issues with deserializing an option natively theough the Query
 #[derive(Debug, Deserialize)]
 pub struct Opti32 {
@@ -350,6 +896,15 @@ pub struct ScoreParams {
     pub cat_id: Option<i32>,
 }
 
+/// Query parameters for comparing a set of players on a single map.
+///
+/// `profile_numbers` is a comma separated list of profile numbers.
+#[derive(Deserialize, Debug)]
+pub struct CompareParams {
+    pub profile_numbers: String,
+    pub cat_id: Option<i32>,
+}
+
 /// All the accepted query parameters for the changelog page.
 #[derive(Deserialize, Debug)]
 pub struct ChangelogQueryParams {
@@ -364,11 +919,148 @@ pub struct ChangelogQueryParams {
     pub yt: Option<bool>,
     pub first: Option<i64>,
     pub last: Option<i64>,
+    pub proof_level: Option<ProofLevel>,
+    /// Only include entries that were a PB for the player on that map & category when they were submitted.
+    pub pbs_only: Option<bool>,
+}
+
+/// Common combinations of proof on a changelog entry, used as a single filter instead of `has_demo` & `yt`.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofLevel {
+    /// Neither a demo nor a video.
+    None,
+    /// A demo, but no video.
+    DemoOnly,
+    /// A video, but no demo.
+    VideoOnly,
+    /// Both a demo and a video.
+    Both,
+    /// At least one of a demo or a video.
+    Any,
+}
+
+/// Which categories are counted when totalling a player's WRs.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CategoryScope {
+    /// Only each map's default category.
+    #[default]
+    Default,
+    /// Every category on every map.
+    All,
+}
+
+/// Query parameters for the WR counts, `scope` defaults to [CategoryScope::Default].
+#[derive(Deserialize, Debug)]
+pub struct WrCountParams {
+    pub scope: Option<CategoryScope>,
+}
+
+/// Query parameters for the large score jump review queue.
+///
+/// `min_ratio` defaults to `0.2` (a run 20% faster than the one it replaced), `limit` to 200.
+#[derive(Deserialize, Debug)]
+pub struct LargeJumpParams {
+    pub min_ratio: Option<f64>,
+    pub limit: Option<u32>,
+    pub before: Option<i64>,
+}
+
+/// Query parameters for the per-category changelog feed.
+#[derive(Deserialize, Debug)]
+pub struct CategoryFeedParams {
+    pub limit: Option<u32>,
+    pub before: Option<i64>,
+}
+
+/// Query parameters for syncing the changelog, `limit` defaults to 200 (max 1000).
+#[derive(Deserialize, Debug)]
+pub struct ChangelogSinceParams {
+    pub limit: Option<i64>,
+}
+
+/// Query parameters for the top improvers leaderboard.
+#[derive(Deserialize, Debug)]
+pub struct TopImproversParams {
+    pub since: Option<NaiveDateTime>,
+    pub limit: Option<i64>,
+}
+
+/// A player's total improvement over a window of time, `improvement` is the total time saved across all of their new scores.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct TopImprover {
+    pub profile_number: String,
+    pub user_name: String,
+    pub avatar: Option<String>,
+    pub improvement: i64,
+    pub improvements: i64,
+}
+
+/// Query parameters for the daily summary, `date` defaults to today (UTC).
+#[derive(Deserialize, Debug)]
+pub struct DailySummaryParams {
+    pub date: Option<NaiveDate>,
+}
+
+/// What changed on the boards on a single day, see [crate::models::models::Changelog::get_daily_summary].
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DailySummary {
+    pub date: NaiveDate,
+    pub submissions: i64,
+    pub new_wrs: i64,
+    pub active_players: i64,
+    pub top_improvement: Option<TopImprovement>,
+}
+
+/// The single largest improvement to a player's time on a map.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct TopImprovement {
+    pub id: i64,
+    pub profile_number: String,
+    pub user_name: Option<String>,
+    pub map_id: String,
+    pub map_name: String,
+    pub score: i32,
+    pub improvement: i32,
+}
+
+/// Query parameters for a score's board context, `window` is the # of entries on either side (defaults to 5, max 50).
+#[derive(Deserialize, Debug)]
+pub struct ScoreContextParams {
+    pub window: Option<i64>,
+}
+
+/// Query parameters for a map's activity, `days` defaults to 30.
+#[derive(Deserialize, Debug)]
+pub struct ActivityParams {
+    pub days: Option<i32>,
+}
+
+/// # of changelog entries on a single day, days without any entries have a `count` of 0.
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct DailyCount {
+    pub date: NaiveDate,
+    pub count: i64,
+}
+
+/// Query parameters for the hourly submission distribution, scoped to a single player if `profile_number` is set.
+#[derive(Deserialize, Debug)]
+pub struct HourlyActivityParams {
+    pub profile_number: Option<String>,
+}
+
+/// # of changelog entries submitted during a single hour of the day (0-23, UTC).
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct HourlyCount {
+    pub hour: i32,
+    pub count: i64,
 }
 
 /// Fields for a submission to the changelog
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SubmissionChangelog {
+    #[serde(default)]
     pub timestamp: String,
     pub profile_number: String,
     pub score: i32,
@@ -376,6 +1068,9 @@ pub struct SubmissionChangelog {
     pub youtube_id: Option<String>,
     pub note: Option<String>,
     pub category_id: Option<i32>,
+    /// Client-generated key that makes retrying `POST /changelog` safe, a repeat within the window returns the original id.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
 /// Allows us to accept an optional demo_id or cl_id as a set of query parameters for demo endpoints.
@@ -392,6 +1087,125 @@ pub struct AdminLevel {
     pub admin_level: Option<i32>,
 }
 
+/// One-to-one struct for an entry in the admin audit log.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AdminAudit {
+    pub id: i64,
+    pub timestamp: NaiveDateTime,
+    pub admin_profile_number: String,
+    pub action: String,
+    pub target: Option<String>,
+    pub details: Option<String>,
+}
+
+/// Paging parameters for the admin audit log.
+#[derive(Debug, Deserialize)]
+pub struct AuditLogParams {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Query parameters for searching admin notes, `pattern` is matched anywhere in the note.
+///
+/// `limit` defaults to 100 (max 500). `admin_profile_number` must belong to an admin.
+#[derive(Debug, Deserialize)]
+pub struct AdminNoteSearchParams {
+    pub pattern: String,
+    pub limit: Option<i64>,
+    pub admin_profile_number: String,
+}
+
+/// Body for banning/unbanning several changelog entries at once.
+#[derive(Debug, Deserialize)]
+pub struct BulkBanParams {
+    pub ids: Vec<i64>,
+    pub banned: bool,
+    pub admin_profile_number: String,
+}
+
+/// A single changelog field to update, with its new value.
+///
+/// Serialized as `{"field": "note", "value": "..."}`, `field` is one of `note`, `youtube_id` or `banned`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "field", content = "value", rename_all = "snake_case")]
+pub enum ChangelogField {
+    Note(Option<String>),
+    YoutubeId(Option<String>),
+    Banned(bool),
+}
+
+/// Body for updating a single field on a changelog entry, and the profile_number of the admin making the change.
+#[derive(Debug, Deserialize)]
+pub struct ChangelogFieldParams {
+    #[serde(flatten)]
+    pub field: ChangelogField,
+    pub admin_profile_number: String,
+}
+
+/// Body for verifying all pending scores on a map. Uses the map's default category if `cat_id` isn't provided.
+#[derive(Debug, Deserialize)]
+pub struct VerifyPendingParams {
+    pub cat_id: Option<i32>,
+    pub admin_profile_number: String,
+}
+
+/// Body for compacting a player's history on a map. Uses the map's default category if `cat_id` isn't provided.
+///
+/// `keep_pbs_only` defaults to `true`, when `false` non-PB runs with a demo or video attached are also kept.
+#[derive(Debug, Deserialize)]
+pub struct CompactHistoryParams {
+    pub profile_number: String,
+    pub cat_id: Option<i32>,
+    pub keep_pbs_only: Option<bool>,
+    pub admin_profile_number: String,
+}
+
+/// A map and category pair, used to report which boards were affected by a change.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, PartialEq, Eq, Hash)]
+pub struct MapCategory {
+    pub map_id: String,
+    pub category_id: i32,
+}
+
+/// Query parameters for maintenance tasks that support previewing changes, `admin_profile_number` must belong to an admin.
+#[derive(Debug, Deserialize)]
+pub struct DryRun {
+    pub dry_run: Option<bool>,
+    pub admin_profile_number: String,
+}
+
+/// Query parameters for the changelog backfills, `limit` is the max # of entries updated per run, defaults to 1000 (max 10000).
+///
+/// `admin_profile_number` must belong to an admin.
+#[derive(Debug, Deserialize)]
+pub struct BackfillParams {
+    pub limit: Option<i64>,
+    pub admin_profile_number: String,
+}
+
+/// Results of enforcing the demo retention policy, see [crate::tools::maintenance::enforce_retention].
+#[derive(Debug, Default, Serialize)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    /// The ids of every demo past the retention period, removed unless this was a dry run.
+    pub expired: Vec<i64>,
+    /// The ids of the demos that were actually removed, demos whose file couldn't be deleted are kept for the next run.
+    pub deleted: Vec<i64>,
+    pub deleted_db: i64,
+    pub deleted_files: i64,
+    pub reclaimed_bytes: u64,
+}
+
+/// Results of a demo garbage collection run.
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub orphaned: Vec<i64>,
+    pub deleted_db: i64,
+    pub deleted_files: i64,
+    pub reclaimed_bytes: u64,
+}
+
 // Points
 
 ///
@@ -429,18 +1243,186 @@ pub struct Points {
     pub avatar: Option<String>,
 }
 
+/// A score on a map, and when it was set. For coop scores, includes the partner for the run.
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct MapScoreDate {
     pub map: String,
     pub map_name: String,
     pub score: i32,
     pub timestamp: Option<NaiveDateTime>,
+    pub partner_profile_number: Option<String>,
+    pub partner_name: Option<String>,
+}
+
+/// A user's best score on a map & category, for the per-category tabs on their profile.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UserScoreRow {
+    pub cl_id: i64,
+    pub map_id: String,
+    pub map_name: String,
+    pub category_id: i32,
+    pub score: i32,
+    pub timestamp: Option<NaiveDateTime>,
+    pub has_demo: bool,
+    pub has_video: bool,
+}
+
+/// Oldest and newest scores for a single game mode, `None` if the user has no scores in that mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileStats {
+    pub oldest: Option<MapScoreDate>,
+    pub newest: Option<MapScoreDate>,
+}
+
+/// Query parameters for the profile page.
+///
+/// `pb_only` defaults to `true`, see [crate::models::models::Users::get_profile] for the semantics.
+#[derive(Debug, Deserialize)]
+pub struct ProfileParams {
+    pub pb_only: Option<bool>,
+}
+
+/// A user's rank on a map out of all ranked players, `percentile` is `rank / total * 100` (lower is better).
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MapPercentile {
+    pub map_id: String,
+    pub map_name: String,
+    pub score: i32,
+    pub rank: i64,
+    pub total: i64,
+    pub percentile: f64,
+}
+
+/// A user's best run on a map, for the profile's score grid.
+///
+/// `verified` is `false` for runs still awaiting verification, these aren't on the board yet but show where they would place.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerBest {
+    pub cl_id: i64,
+    pub score: i32,
+    pub rank: i64,
+    pub verified: bool,
+    pub has_demo: bool,
+    pub has_video: bool,
+}
+
+/// Query parameters for a user's per-map percentiles and bests, `is_mp` defaults to `false` (SP).
+#[derive(Debug, Deserialize)]
+pub struct PercentileParams {
+    pub is_mp: Option<bool>,
+}
+
+/// A user's longest streak of consecutive WR-setting submissions, see [Users::get_wr_streak].
+///
+/// `current` is the streak the user is on right now, `0` if their latest submission wasn't a WR.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WrStreak {
+    pub longest: i64,
+    pub start: Option<NaiveDateTime>,
+    pub end: Option<NaiveDateTime>,
+    pub current: i64,
+}
+
+/// The # of unbanned submissions a user has on a map.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MapPlayCount {
+    pub map_id: String,
+    pub map_name: String,
+    pub submissions: i64,
+}
+
+/// Query parameters for recently active players, `hours` defaults to 24 (max 720) and `limit` to 50 (max 100).
+#[derive(Debug, Deserialize)]
+pub struct RecentlyActiveParams {
+    pub hours: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Query parameters for players active right now, `minutes` defaults to 30 (max 1440) and `limit` to 20 (max 100).
+#[derive(Debug, Deserialize)]
+pub struct ActiveNowParams {
+    pub minutes: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// A player who submitted recently, with their latest run, see [Users::get_active_now].
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct ActivePlayer {
+    pub profile_number: String,
+    pub user_name: String,
+    pub avatar: String,
+    pub cl_id: i64,
+    pub map_id: String,
+    pub map_name: String,
+    pub score: i32,
+    pub timestamp: Option<NaiveDateTime>,
+}
+
+/// Query parameters for a user's most played maps, `limit` defaults to 10 (max 100).
+#[derive(Debug, Deserialize)]
+pub struct MostPlayedParams {
+    pub limit: Option<i64>,
+}
+
+/// Query parameters for a user's full changelog, `limit` defaults to 200 (max 1000).
+///
+/// `before` is a cursor, only entries with an id lower than it are returned. Banned entries are shown unless `hide_banned` is set.
+#[derive(Debug, Deserialize)]
+pub struct ProfileChangelogParams {
+    pub limit: Option<u32>,
+    pub before: Option<i64>,
+    pub hide_banned: Option<bool>,
+}
+
+/// Summary of a user's coop activity for their profile.
+///
+/// `maps_completed` counts coop maps with a verified score, `wrs` counts coop maps where the user ties or holds the WR.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoopSummary {
+    pub maps_completed: i64,
+    pub wrs: i64,
+    pub submissions: i64,
+    pub most_frequent_partner: Option<String>,
+    pub most_frequent_partner_name: Option<String>,
+    pub runs_with_partner: i64,
+}
+
+/// A player a user has run coop with, and the # of runs they share.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CoopPartner {
+    pub profile_number: String,
+    pub user_name: Option<String>,
+    pub avatar: Option<String>,
+    pub runs: i64,
+}
+
+/// A user's progress on a chapter or game, `completed` out of `total` maps.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Completion {
+    pub completed: i64,
+    pub total: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProfileData {
     pub oldest: MapScoreDate,
     pub newest: MapScoreDate,
+    pub sp_stats: ProfileStats,
+    pub coop_stats: ProfileStats,
+    /// The user's best scores in every category, keyed by category id, see [Users::get_scores_by_category].
+    pub categories: HashMap<i32, Vec<UserScoreRow>>,
+    /// The user's single-segment total across SP maps, see [Users::get_sp_total].
+    pub sp_total: SpTotal,
+}
+
+/// The sum of a user's best default category scores on SP maps, shown as "full game: `maps_completed`/`maps_total` maps".
+///
+/// Maps without a verified score are left out of `total_score`, `maps_total - maps_completed` maps are missing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpTotal {
+    pub total_score: i64,
+    pub maps_completed: i64,
+    pub maps_total: i64,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -19,6 +19,7 @@ async fn get_config() -> Result<(Config, PgPool)> {
 #[actix_web::test]
 async fn test_db_maps() {
     use crate::models::models::*;
+    use chrono::NaiveDateTime;
     let (_, pool) = get_config().await.expect("Error getting config and DB pool");
     let sp = Maps::get_steam_ids(&pool, false).await.unwrap();
     let coop = Maps::get_steam_ids(&pool, true).await.unwrap();
@@ -34,8 +35,136 @@ async fn test_db_maps() {
     assert_eq!(chapter_id.chapter_name, Some("The Courtesy Call".to_string()));
     let id = Maps::get_steam_id_by_name(&pool, pgun.clone()).await.unwrap().unwrap();
     assert_eq!(sp[0], id[0]);
+    // Global search, exact name matches first, chapter matches only show up once.
+    let found = Maps::global_search(&pool, "portal gun".to_string(), 10).await.unwrap();
+    assert_eq!((found[0].steam_id.as_str(), found[0].matched_on.as_str()), (sp[0].as_str(), "name"));
+    let found = Maps::global_search(&pool, "Courtesy Call".to_string(), 50).await.unwrap();
+    assert!(found.iter().any(|m| m.steam_id == sp[0] && m.matched_on == "chapter"));
+    let mut ids: Vec<&String> = found.iter().map(|m| &m.steam_id).collect();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), found.len());
+    assert!(Maps::global_search(&pool, "  ".to_string(), 10).await.unwrap().is_empty());
     let public = Maps::get_is_public_by_steam_id(&pool, sp[0].clone()).await.unwrap().unwrap();
     assert!(public);
+    // Unknown maps are `None`, not an error.
+    assert!(Maps::get_map_name(&pool, "0".to_string()).await.unwrap().is_none());
+    assert!(Maps::get_default_cat(&pool, "0".to_string()).await.unwrap().is_none());
+    assert!(Maps::get_is_public_by_steam_id(&pool, "0".to_string()).await.unwrap().is_none());
+    let map = Maps::get_map(&pool, sp[0].clone()).await.unwrap().unwrap();
+    assert_eq!(map.steam_id, sp[0]);
+    assert_eq!(map.name, pgun);
+    assert_eq!(map.chapter_id, Some(chapter_id.id));
+    assert_eq!(map.default_cat_id, Some(default_cat));
+    assert!(Maps::get_map(&pool, "000000".to_string()).await.unwrap().is_none());
+    // Default categories from the cache, a cold cache only looks up the missing maps, and remembers maps without one.
+    let cache = crate::tools::cache::CacheState::new(std::collections::HashMap::new(), std::time::Duration::from_secs(60));
+    let cats = cache.get_default_cats(&pool, &[sp[0].clone(), coop[0].clone(), "0".to_string()]).await.unwrap();
+    assert_eq!(cats.len(), 2);
+    assert_eq!(cats[&sp[0]], default_cat);
+    assert_eq!(cache.default_cat_ids.lock().await.len(), 2);
+    assert!(cache.default_cat_misses.lock().await.contains_key("0"));
+    assert!(cache.get_default_cat(&pool, "0").await.unwrap().is_none());
+    assert_eq!(Maps::get_default_cats(&pool, &[sp[0].clone(), "0".to_string()]).await.unwrap().len(), 1);
+    // Category tabs include every category on the map, even those without scores.
+    let categories = Maps::get_categories_with_counts(&pool, "47802".to_string()).await.unwrap();
+    assert_eq!(categories.len(), Categories::get_category_ids(&pool, "47802".to_string()).await.unwrap().len());
+    assert!(categories.iter().any(|category| category.count > 0));
+    // Average times, the record is never slower than the median or average.
+    let avgs = Maps::get_avg_times(&pool, "47802".to_string()).await.unwrap();
+    assert!(!avgs.is_empty());
+    assert!(avgs.iter().all(|avg| avg.players > 0 && avg.wr as f64 <= avg.median && avg.wr as f64 <= avg.average));
+    // Completion extremes, hardest maps have the fewest players.
+    let extremes = Maps::get_completion_extremes(&pool, 3).await.unwrap();
+    assert_eq!(extremes.hardest.len(), 3);
+    assert_eq!(extremes.easiest.len(), 3);
+    assert!(extremes.hardest.windows(2).all(|w| w[0].players <= w[1].players));
+    assert!(extremes.easiest.windows(2).all(|w| w[0].players >= w[1].players));
+    assert!(extremes.hardest[0].players <= extremes.easiest[0].players);
+    // Current WRs, at most one per map.
+    let sp_wrs = Maps::get_all_current_wrs(&pool, false).await.unwrap();
+    let coop_wrs = Maps::get_all_current_wrs(&pool, true).await.unwrap();
+    assert!(!sp_wrs.is_empty() && sp_wrs.len() <= sp.len());
+    assert!(!coop_wrs.is_empty() && coop_wrs.len() <= coop.len());
+    assert!(sp_wrs.iter().all(|wr| sp.contains(&wr.steam_id) && wr.partner_profile_number.is_none()));
+    assert!(coop_wrs.iter().all(|wr| coop.contains(&wr.steam_id)));
+    // The records grid has a row for every map's default category, matching the current WRs.
+    let grid = Maps::get_all_wrs(&pool, CategoryScope::Default).await.unwrap();
+    assert_eq!(grid.len(), Maps::get_all_default_cats(&pool).await.unwrap().len());
+    for wr in sp_wrs.iter() {
+        let row = grid.iter().find(|row| row.map_id == wr.steam_id).unwrap();
+        assert_eq!((row.score, row.profile_number.as_ref()), (Some(wr.score), Some(&wr.profile_number)));
+    }
+    assert!(Maps::get_all_wrs(&pool, CategoryScope::All).await.unwrap().len() >= grid.len());
+    // Softest WRs, the smallest gaps first, the holder matches the current WR.
+    let soft = Maps::get_softest_wrs(&pool, false, 5).await.unwrap();
+    assert!(!soft.is_empty() && soft.len() <= 5);
+    assert!(soft.windows(2).all(|w| w[0].gap <= w[1].gap));
+    for wr in soft.iter() {
+        assert!(wr.gap >= 0 && wr.holder_profile_number != wr.challenger_profile_number);
+        let current = sp_wrs.iter().find(|current| current.steam_id == wr.map_id).unwrap();
+        assert_eq!(wr.wr_score, current.score);
+    }
+    // Top N on every map, ranked in order with the WR first.
+    let top = Maps::get_top_n_all_maps(&pool, 3, false).await.unwrap();
+    assert_eq!(top.len(), sp_wrs.len());
+    for wr in sp_wrs.iter() {
+        let entries = &top[&wr.steam_id];
+        assert!(!entries.is_empty() && entries.len() <= 3);
+        assert_eq!(entries[0].profile_number, wr.profile_number);
+        assert_eq!(entries[0].score, wr.score);
+        assert!(entries.iter().enumerate().all(|(i, e)| e.rank == i as i64 + 1 && e.map_id == wr.steam_id));
+        assert!(entries.windows(2).all(|w| w[0].score <= w[1].score && w[0].profile_number != w[1].profile_number));
+    }
+    // Map leaderboards are cached until invalidated, or the TTL expires.
+    let wr_cat = cache.get_default_cat(&pool, &sp_wrs[0].steam_id).await.unwrap().unwrap();
+    let board = cache.get_map_leaderboard(&pool, &sp_wrs[0].steam_id, wr_cat).await.unwrap();
+    assert_eq!(board.len(), Maps::get_map_leaderboard(&pool, sp_wrs[0].steam_id.clone(), wr_cat).await.unwrap().len());
+    assert_eq!((board[0].rank, &board[0].profile_number), (1, &sp_wrs[0].profile_number));
+    assert!(cache.leaderboards.get(&sp_wrs[0].steam_id, wr_cat).await.is_some());
+    cache.invalidate_leaderboard(&sp_wrs[0].steam_id).await;
+    assert!(cache.leaderboards.get(&sp_wrs[0].steam_id, wr_cat).await.is_none());
+    // One row per player on the board, at their best. Their older runs only show up with the full history.
+    let chamber_cat = Maps::get_default_cat(&pool, "47763".to_string()).await.unwrap().unwrap();
+    let chamber_board = Maps::get_map_leaderboard(&pool, "47763".to_string(), chamber_cat).await.unwrap();
+    let history = Maps::get_map_board_history(&pool, "47763".to_string(), chamber_cat).await.unwrap();
+    assert!(history.len() >= chamber_board.len());
+    let mut profiles: Vec<&String> = chamber_board.iter().map(|e| &e.profile_number).collect();
+    profiles.sort();
+    profiles.dedup();
+    assert_eq!(profiles.len(), chamber_board.len());
+    let runs = |p: &String| history.iter().filter(|e| &e.profile_number == p).collect::<Vec<_>>();
+    let repeat = chamber_board.iter().find(|e| runs(&e.profile_number).len() >= 3).expect("a player with 3+ runs on the map");
+    let repeat_runs = runs(&repeat.profile_number);
+    assert_eq!(chamber_board.iter().filter(|e| e.profile_number == repeat.profile_number).count(), 1);
+    assert_eq!(repeat.score, repeat_runs.iter().map(|e| e.score).min().unwrap());
+    assert!(repeat_runs.iter().all(|e| e.rank == repeat.rank));
+    // Map page, the leaderboard is the top of the full board and everything is for the same map.
+    let page = Maps::get_map_page(&pool, "47763".to_string(), None, 5).await.unwrap().unwrap();
+    assert_eq!((page.map.steam_id.as_str(), page.cat_id), ("47763", chamber_cat));
+    assert_eq!(page.leaderboard.iter().map(|e| e.score).collect::<Vec<_>>(), chamber_board.iter().take(5).map(|e| e.score).collect::<Vec<_>>());
+    assert!(page.categories.iter().any(|c| c.id == chamber_cat) && page.categories.iter().all(|c| c.map_id == "47763"));
+    assert!(!page.stats.is_empty() && page.recent.len() <= 10 && page.recent.iter().all(|e| e.map_id == "47763"));
+    assert!(Maps::get_map_page(&pool, "0".to_string(), None, 5).await.unwrap().is_none());
+    // Time machine, nothing was run before the boards existed, and today's reconstruction matches the current board
+    // (barring runs without a timestamp). Keeping banned runs can only add players.
+    let before = NaiveDateTime::parse_from_str("2000-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    assert!(Maps::get_leaderboard_at(&pool, "47763".to_string(), chamber_cat, before, true).await.unwrap().is_empty());
+    let now = chrono::Utc::now().naive_utc();
+    let board_now = Maps::get_leaderboard_at(&pool, "47763".to_string(), chamber_cat, now, false).await.unwrap();
+    assert!(!board_now.is_empty() && board_now.len() <= chamber_board.len());
+    for entry in board_now.iter() {
+        let current = chamber_board.iter().find(|e| e.profile_number == entry.profile_number).unwrap();
+        assert!(entry.score >= current.score);
+    }
+    let as_appeared = Maps::get_leaderboard_at(&pool, "47763".to_string(), chamber_cat, now, true).await.unwrap();
+    assert!(as_appeared.len() >= board_now.len());
+    let expired = crate::tools::cache::LeaderboardCache::new(std::time::Duration::ZERO);
+    expired.insert(&sp_wrs[0].steam_id, wr_cat, board).await;
+    assert!(expired.get(&sp_wrs[0].steam_id, wr_cat).await.is_none());
+    let games = Games::get_games(&pool).await.unwrap();
+    assert_eq!(games[0].id, 1);
+    assert_eq!(games[0].game_name, "Portal 2".to_string());
 }
 
 #[actix_web::test]
@@ -69,7 +198,7 @@ async fn test_db_chapters() {
 #[actix_web::test]
 async fn test_db_users() {
     use crate::models::models::*;
-    let (_, pool) = get_config().await.expect("Error getting config and DB pool");
+    let (config, pool) = get_config().await.expect("Error getting config and DB pool");
     
     let user: Users = Users{ 
         profile_number: "76561198040982247".to_string(),
@@ -84,6 +213,8 @@ async fn test_db_users() {
         admin: 1,
         donation_amount: None,
         discord_id: None,
+        merged_into: None,
+        ban_reason: None,
     };
     let mut insert_user = user.clone();
     let test_user = Users::get_user(&pool, user.profile_number.clone()).await.unwrap().unwrap();
@@ -99,7 +230,7 @@ async fn test_db_users() {
     assert_eq!(user.admin, test_user.admin);
     assert_eq!(user.donation_amount, test_user.donation_amount);
     assert_eq!(user.discord_id, test_user.discord_id);
-    let test_user = Users::get_user_data(&pool, user.profile_number.clone()).await.unwrap().unwrap();
+    let test_user = Users::get_user_data(&pool, user.profile_number.clone(), &config.users.default_avatar).await.unwrap().unwrap();
     assert_eq!(user.board_name, Some(test_user.user_name));
     assert_eq!(user.avatar, Some(test_user.avatar));
     let test_vec = Users::check_board_name(&pool, "Daniel".to_string()).await.unwrap().unwrap();
@@ -119,20 +250,161 @@ async fn test_db_users() {
     assert_eq!(user.discord_id, socials.discord_id);
     let admin = Users::get_admin_for_user(&pool, user.profile_number.clone()).await.unwrap().unwrap();
     assert_eq!(user.admin, admin);
-    let admin_vec = Users::get_all_admins(&pool, 1).await.unwrap().unwrap();
+    let admin_vec = Users::get_all_admins(&pool, 1, &config.users.default_avatar).await.unwrap().unwrap();
     assert_eq!(admin_vec.len(), 8);
     assert_eq!(admin_vec[7].user_name, "Lathil".to_string());
-    insert_user.profile_number = "0".to_string();
+    // Recently active players, banned players never show up.
+    let active = Users::get_recently_active(&pool, 24 * 365 * 30, 20, &config.users.default_avatar).await.unwrap();
+    assert!(!active.is_empty() && active.len() <= 20);
+    let banned = Users::get_banned(&pool).await.unwrap();
+    assert!(active.iter().all(|user| !banned.contains(&user.profile_number)));
+    let most_recent = Users::get_recently_active(&pool, 24 * 365 * 30, 1, &config.users.default_avatar).await.unwrap();
+    assert_eq!(most_recent[0].profile_number, active[0].profile_number);
+    // Active now, one row per player at their latest run, most recent first.
+    let now = Users::get_active_now(&pool, 60 * 24 * 365 * 30, 20, &config.users.default_avatar).await.unwrap();
+    assert!(!now.is_empty() && now.len() <= 20);
+    assert!(now.windows(2).all(|w| w[0].timestamp >= w[1].timestamp));
+    assert!(now.iter().all(|player| !banned.contains(&player.profile_number)));
+    let mut players: Vec<&String> = now.iter().map(|player| &player.profile_number).collect();
+    players.sort();
+    players.dedup();
+    assert_eq!(players.len(), now.len());
+    // Profile oldest/newest, PB history vs current PBs.
+    let sp_maps = Maps::get_steam_ids(&pool, false).await.unwrap();
+    let sp_default_cats = Maps::get_default_cats(&pool, &sp_maps).await.unwrap();
+    let pb_profile = Users::get_profile(&pool, &user.profile_number, true, &sp_default_cats).await.unwrap().unwrap();
+    let current_profile = Users::get_profile(&pool, &user.profile_number, false, &sp_default_cats).await.unwrap().unwrap();
+    assert!(pb_profile.oldest.timestamp <= current_profile.oldest.timestamp);
+    assert!(pb_profile.newest.timestamp >= current_profile.newest.timestamp);
+    // Per-mode stats should never fall outside of the blended oldest/newest.
+    for stats in [&current_profile.sp_stats, &current_profile.coop_stats] {
+        if let (Some(oldest), Some(newest)) = (&stats.oldest, &stats.newest) {
+            assert!(oldest.timestamp >= current_profile.oldest.timestamp);
+            assert!(newest.timestamp <= current_profile.newest.timestamp);
+        }
+    }
+    assert!(current_profile.sp_stats.oldest.as_ref().unwrap().partner_profile_number.is_none());
+    // Every map shows up at most once per category tab.
+    assert!(!current_profile.categories.is_empty());
+    for (category_id, scores) in current_profile.categories.iter() {
+        let maps: std::collections::HashSet<&String> = scores.iter().map(|score| &score.map_id).collect();
+        assert_eq!(maps.len(), scores.len());
+        assert!(scores.iter().all(|score| score.category_id == *category_id));
+    }
+    // The SP total only counts default category scores, one per map.
+    let sp_total = &current_profile.sp_total;
+    assert!(sp_total.maps_completed > 0 && sp_total.maps_completed <= sp_total.maps_total);
+    assert_eq!(sp_total.maps_total, sp_default_cats.len() as i64);
+    assert!(sp_total.maps_total <= sp_maps.len() as i64);
+    assert!(sp_total.total_score > 0);
+    // Cached profiles match the uncached query, until the user is invalidated.
+    let cache = crate::tools::cache::CacheState::new(std::collections::HashMap::new(), std::time::Duration::from_secs(60));
+    let cached = cache.get_profile(&pool, &user.profile_number, true).await.unwrap().unwrap();
+    assert_eq!(cached.newest.timestamp, pb_profile.newest.timestamp);
+    assert!(cache.profiles.lock().await.contains_key(&(user.profile_number.clone(), true)));
+    cache.invalidate_profile(&user.profile_number).await;
+    assert!(cache.profiles.lock().await.is_empty());
+    // Completion, a game's totals should cover each of its chapters.
+    let (chapter_completed, chapter_total) = Users::get_chapter_completion(&pool, user.profile_number.clone(), 7).await.unwrap();
+    let (game_completed, game_total) = Users::get_game_completion(&pool, user.profile_number.clone(), 1).await.unwrap();
+    assert!(chapter_completed <= chapter_total && chapter_total > 0);
+    assert!(game_completed >= chapter_completed && game_total >= chapter_total);
+    assert!(game_completed <= game_total);
+    // Coop summary
+    let coop = Users::get_coop_summary(&pool, user.profile_number.clone()).await.unwrap();
+    assert!(coop.maps_completed <= coop.submissions && coop.wrs <= coop.maps_completed);
+    assert_eq!(coop.most_frequent_partner.is_some(), coop.runs_with_partner > 0);
+    assert_ne!(coop.most_frequent_partner, Some(user.profile_number.clone()));
+    let partners = Users::get_coop_partners(&pool, user.profile_number.clone()).await.unwrap();
+    assert_eq!(partners.first().map(|p| p.profile_number.clone()), coop.most_frequent_partner);
+    assert!(partners.windows(2).all(|w| w[0].runs >= w[1].runs && w[0].profile_number != w[1].profile_number));
+    assert!(partners.iter().all(|p| p.runs > 0 && p.profile_number != user.profile_number));
+    // Percentiles
+    let percentiles = Users::get_percentiles(&pool, user.profile_number.clone(), false).await.unwrap();
+    assert!(!percentiles.is_empty());
+    assert!(percentiles.iter().all(|p| p.rank >= 1 && p.rank <= p.total && p.percentile > 0.0 && p.percentile <= 100.0));
+    // Bests, verified runs rank the same as on the percentile board.
+    let bests = Users::get_bests(&pool, user.profile_number.clone(), false).await.unwrap();
+    assert!(bests.len() >= percentiles.len());
+    for p in percentiles.iter() {
+        let best = &bests[&p.map_id];
+        if best.verified && best.score == p.score {
+            assert_eq!(best.rank, p.rank);
+        }
+        assert!(best.score <= p.score);
+    }
+    let streak = Users::get_wr_streak(&pool, user.profile_number.clone()).await.unwrap();
+    assert!(streak.current <= streak.longest);
+    if streak.longest == 0 {
+        assert_eq!((streak.start, streak.end), (None, None));
+    }
+    assert!(streak.start <= streak.end);
+    let most_played = Users::get_most_played_maps(&pool, user.profile_number.clone(), 5).await.unwrap();
+    assert!(most_played.len() <= 5);
+    assert!(most_played.windows(2).all(|w| w[0].submissions >= w[1].submissions));
+    insert_user.profile_number = "76561190000000000".to_string();
     
     // Test inserts/updates/deletes
-    assert!(Users::insert_new_users(&pool, insert_user.clone()).await.unwrap());
-    let insert_user_data = Users::get_user_data(&pool, insert_user.profile_number.clone()).await.unwrap().unwrap();
+    assert!(Users::insert_new_users(&pool, insert_user.clone(), None).await.unwrap());
+    let insert_user_data = Users::get_user_data(&pool, insert_user.profile_number.clone(), &config.users.default_avatar).await.unwrap().unwrap();
     assert_eq!(insert_user.board_name, Some(insert_user_data.user_name));
     assert_eq!(insert_user.avatar, Some(insert_user_data.avatar));
+    let old_name = insert_user.board_name.clone();
     insert_user.board_name = Some("BigDaniel11AtlasPog".to_string());
-    assert!(Users::update_existing_user(&pool, insert_user.clone()).await.unwrap());
+    assert!(Users::update_existing_user(&pool, insert_user.clone(), None).await.unwrap());
+    // Only the rename is recorded, updates that keep the board_name aren't.
+    assert!(Users::update_existing_user(&pool, insert_user.clone(), None).await.unwrap());
+    let name_history = Users::get_name_history(&pool, insert_user.profile_number.clone()).await.unwrap();
+    assert_eq!(name_history.len(), 1);
+    assert_eq!(name_history[0].old_name, old_name);
+    assert_eq!(name_history[0].new_name, insert_user.board_name);
+    // Missing avatars fall back to the configured default.
+    insert_user.avatar = None;
+    assert!(Users::update_existing_user(&pool, insert_user.clone(), None).await.unwrap());
+    let insert_user_data = Users::get_user_data(&pool, insert_user.profile_number.clone(), &config.users.default_avatar).await.unwrap().unwrap();
+    assert_eq!(insert_user_data.avatar, config.users.default_avatar);
+    // Granting a title is admin only, and recorded in the audit log.
+    insert_user.title = Some("Runner".to_string());
+    assert!(Users::update_existing_user(&pool, insert_user.clone(), None).await.is_err());
+    assert!(Users::update_existing_user(&pool, insert_user.clone(), Some("0".to_string())).await.is_err());
+    assert!(Users::update_existing_user(&pool, insert_user.clone(), Some("76561198040982247".to_string())).await.unwrap());
+    assert_eq!(Users::get_title(&pool, insert_user.profile_number.clone()).await.unwrap(), insert_user.title);
+    let audit = Admin::get_audit_log(&pool, 50, 0).await.unwrap();
+    assert!(audit.iter().any(|entry| entry.action == "set_title" && entry.target == Some(insert_user.profile_number.clone())));
+    // Steam data sync, unchanged data isn't rewritten.
+    assert!(Users::get_all_profile_numbers(&pool).await.unwrap().contains(&insert_user.profile_number));
+    let steam_data = SteamDataUpdate { profile_number: insert_user.profile_number.clone(), steam_name: Some("NewSteamName".to_string()), avatar: None };
+    assert!(Users::update_steam_data(&pool, std::slice::from_ref(&steam_data), "0").await.is_err());
+    assert_eq!(Users::update_steam_data(&pool, std::slice::from_ref(&steam_data), "76561198040982247").await.unwrap(), 1);
+    assert_eq!(Users::update_steam_data(&pool, &[steam_data], "76561198040982247").await.unwrap(), 0);
+    assert_eq!(Users::get_user(&pool, insert_user.profile_number.clone()).await.unwrap().unwrap().steam_name, Some("NewSteamName".to_string()));
+    // Shares a twitch with the original user, so they should be grouped together.
+    let duplicates = Users::find_possible_duplicates(&pool, 0.6).await.unwrap();
+    let cluster = duplicates.iter().find(|c| c.profile_numbers.contains(&insert_user.profile_number)).unwrap();
+    assert!(cluster.profile_numbers.contains(&user.profile_number));
+    assert!(cluster.matches.iter().any(|m| m.reason == "twitch" && m.similarity == 1.0));
+    assert!(duplicates.windows(2).all(|w| w[0].similarity >= w[1].similarity));
+    // Donations are recorded individually, and the cumulative donation_amount is recomputed from them.
+    let admin = "76561198040982247".to_string();
+    assert!(Users::add_donation(&pool, insert_user.profile_number.clone(), 5.0, "usd".to_string(), None, "0".to_string()).await.is_err());
+    assert!(Users::add_donation(&pool, insert_user.profile_number.clone(), -5.0, "USD".to_string(), None, admin.clone()).await.is_err());
+    assert_eq!(Users::add_donation(&pool, "76561190000000001".to_string(), 5.0, "USD".to_string(), None, admin.clone()).await.unwrap(), None);
+    assert_eq!(Users::add_donation(&pool, insert_user.profile_number.clone(), 5.0, "usd".to_string(), None, admin.clone()).await.unwrap(), Some("5.00".to_string()));
+    assert_eq!(Users::add_donation(&pool, insert_user.profile_number.clone(), 3.5, "USD".to_string(), Some("Ko-fi".to_string()), admin.clone()).await.unwrap(), Some("8.50".to_string()));
+    let donations = Users::get_donation_history(&pool, insert_user.profile_number.clone()).await.unwrap();
+    assert_eq!(donations.len(), 2);
+    assert_eq!((donations[0].amount.as_str(), donations[0].note.as_deref()), ("3.50", Some("Ko-fi")));
+    assert_eq!((donations[1].amount.as_str(), donations[1].currency.as_str()), ("5.00", "USD"));
     assert!(Users::delete_user(&pool, insert_user.profile_number.clone()).await.unwrap());
-    let _res = Users::get_user_data(&pool, insert_user.profile_number.clone()).await;
+    // A legacy donation_amount is carried over as an opening balance on the first ledger entry.
+    let legacy = Users { profile_number: "76561190000000003".to_string(), donation_amount: Some("10.00".to_string()), title: None, admin: 0, ..insert_user.clone() };
+    assert!(Users::insert_new_users(&pool, legacy.clone(), None).await.unwrap());
+    assert_eq!(Users::add_donation(&pool, legacy.profile_number.clone(), 5.0, "USD".to_string(), None, admin.clone()).await.unwrap(), Some("15.00".to_string()));
+    let donations = Users::get_donation_history(&pool, legacy.profile_number.clone()).await.unwrap();
+    assert_eq!(donations.len(), 2);
+    assert_eq!((donations[1].amount.as_str(), donations[1].note.as_deref()), ("10.00", Some("Opening balance")));
+    assert!(Users::delete_user(&pool, legacy.profile_number.clone()).await.unwrap());
+    let _res = Users::get_user_data(&pool, insert_user.profile_number.clone(), &config.users.default_avatar).await;
 
     // Donations
     let donators = Users::get_donators(&pool).await.unwrap().unwrap();
@@ -204,6 +476,35 @@ async fn test_db_demos() {
     assert_eq!(new_demo.parsed_successfully, check_insert.parsed_successfully);
     assert_eq!(new_demo.sar_version, check_insert.sar_version);
     assert_eq!(new_demo.cl_id, check_insert.cl_id);
+    // Recent uploads, newest first. The demo we just inserted is the newest, if its run exists.
+    let recent = Demos::get_recent(&pool, 10).await.unwrap();
+    assert!(!recent.is_empty() && recent.len() <= 10);
+    assert!(recent.windows(2).all(|w| (w[0].uploaded_at, w[0].id) > (w[1].uploaded_at, w[1].id)));
+    assert!(!recent.iter().skip(1).any(|d| d.id == demo_insert));
+    // Reparsing, the demo we just inserted was parsed by a pre-release older than the newest SAR versions.
+    let pending = Demos::get_older_than_version(&pool, "1.12.7").await.unwrap();
+    assert!(!pending.contains(&demo_insert));
+    let pending = Demos::get_older_than_version(&pool, "12.7.2").await.unwrap();
+    assert!(pending.contains(&demo_insert) && pending.windows(2).all(|w| w[0] < w[1]));
+    assert!(Demos::get_older_than_version(&pool, "latest").await.is_err());
+    // Retention. No demo is a century old, and orphaned demos (like the one we just inserted) are left to the GC.
+    let expired = Demos::get_expired(&pool, 365 * 100, 10, 0).await.unwrap();
+    assert!(expired.is_empty());
+    let expired = Demos::get_expired(&pool, 0, 1, 0).await.unwrap();
+    assert!(!expired.iter().any(|d| d.id == demo_insert));
+    // Periods can also be given in months, the shorter period applies and nothing expires without one.
+    assert!(Demos::get_expired_after(&pool, None, Some(12 * 100), 10, 0).await.unwrap().is_empty());
+    assert!(Demos::get_expired_after(&pool, None, None, 0, 0).await.unwrap().is_empty());
+    assert_eq!(Demos::get_expired_after(&pool, Some(0), Some(12 * 100), 1, 0).await.unwrap().len(), expired.len());
+    // WR demos are never pruned, even without a top N to keep.
+    let expired = Demos::get_expired(&pool, 0, 0, 0).await.unwrap();
+    for demo in expired.iter().take(20) {
+        if let Some(cl) = Changelog::get_changelog(&pool, demo.cl_id).await.unwrap() {
+            assert!(cl.post_rank.unwrap() > 1);
+            let board = Maps::get_map_leaderboard(&pool, cl.map_id.clone(), cl.category_id).await.unwrap();
+            assert!(!board.first().is_some_and(|wr| wr.profile_number == cl.profile_number && wr.score == cl.score));
+        }
+    }
     // Testing deleting demos from changelog entries.    
     let new_cl_id = Changelog::insert_changelog(&pool, clinsert.clone()).await.unwrap();
     let new_fid = "Hello World".to_string();
@@ -219,7 +520,7 @@ async fn test_db_demos() {
     assert!(Demos::delete_demo(&pool, check_insert.id).await.unwrap());
     let _res = Demos::get_demo(&pool, check_insert.id).await;
     // Delete the changelog entry
-    let deleted = Changelog::delete_changelog(&pool, new_cl_id).await.unwrap();
+    let deleted = Changelog::delete_changelog(&pool, new_cl_id, "76561198040982247").await.unwrap();
     assert!(deleted);
 }
 
@@ -248,6 +549,7 @@ async fn test_db_changelog() {
         score_delta: Some(-83),
         verified: Some(true),
         admin_note: None,
+        archived: false,
     };
 
     let clinsert = ChangelogInsert {
@@ -272,13 +574,55 @@ async fn test_db_changelog() {
 
     let banned_scores = Changelog::check_banned_scores(&pool, "47763".to_string(), 1763, "76561198040982247".to_string(), 19).await.unwrap();
     assert!(!banned_scores);
-    let pb_history = Changelog::get_sp_pb_history(&pool, "76561198040982247".to_string(), "47763".to_string()).await.unwrap();
+    let pb_history = Changelog::get_sp_pb_history(&pool, "76561198040982247".to_string(), "47763".to_string(), 500, 0).await.unwrap();
     assert_ne!(0, pb_history.len());
+    let total = Changelog::get_sp_pb_history_count(&pool, "76561198040982247".to_string(), "47763".to_string()).await.unwrap();
+    assert_eq!(total, pb_history.len() as i64);
+    // Pages don't overlap, and keep the most recent first ordering.
+    let first_page = Changelog::get_sp_pb_history(&pool, "76561198040982247".to_string(), "47763".to_string(), 1, 0).await.unwrap();
+    let second_page = Changelog::get_sp_pb_history(&pool, "76561198040982247".to_string(), "47763".to_string(), 1, 1).await.unwrap();
+    assert_eq!(first_page[0].id, pb_history[0].id);
+    assert!(second_page.iter().all(|entry| entry.id != first_page[0].id));
+    // The current PB is the best verified, unbanned score in the history.
+    let pb = Changelog::get_current_pb(&pool, "76561198040982247".to_string(), "47763".to_string(), 19).await.unwrap().unwrap();
+    assert!(!pb.banned && pb.verified == Some(true));
+    assert!(pb_history.iter().filter(|cl| cl.category_id == 19 && !cl.banned && cl.verified == Some(true)).all(|cl| cl.score >= pb.score));
+    assert!(Changelog::get_current_pb(&pool, "76561198040982247".to_string(), "-1".to_string(), 19).await.unwrap().is_none());
+    // Runs pending verification count towards the best entry, but not the current PB.
+    let best = Changelog::get_best_entry(&pool, "76561198040982247".to_string(), "47763".to_string(), 19, false).await.unwrap().unwrap();
+    assert!(!best.banned && best.score <= pb.score);
+    // Slower runs and unknown users are validation errors, not database errors.
+    use crate::tools::helpers::check_for_valid_score;
+    let slower = check_for_valid_score(&pool, "76561198040982247".to_string(), pb.score + 1, "47763".to_string(), 200, 19).await.unwrap_err();
+    assert_eq!(slower.downcast_ref::<ValidationErrors>().unwrap().0, vec![ValidationError::NotAnImprovement]);
+    let unknown = check_for_valid_score(&pool, "1".to_string(), pb.score - 1, "47763".to_string(), 200, 19).await.unwrap_err();
+    assert_eq!(unknown.downcast_ref::<ValidationErrors>().unwrap().0, vec![ValidationError::UnknownUser("1".to_string())]);
     let new_cl_id = Changelog::insert_changelog(&pool, clinsert.clone()).await.unwrap();
+    // Re-inserting the same entry returns the existing id.
+    assert_eq!(Changelog::insert_changelog(&pool, clinsert.clone()).await.unwrap(), new_cl_id);
+    // Entries without a timestamp are deduplicated as well.
+    let untimed = ChangelogInsert { timestamp: None, ..clinsert.clone() };
+    let untimed_id = Changelog::insert_changelog(&pool, untimed.clone()).await.unwrap();
+    assert_eq!(Changelog::insert_changelog(&pool, untimed).await.unwrap(), untimed_id);
+    assert!(Changelog::delete_changelog(&pool, untimed_id, "76561198040982247").await.unwrap());
+    // Idempotency keys are scoped per user, and the first entry recorded for a key is kept.
+    let key = format!("test-key-{}", new_cl_id);
+    assert!(Changelog::get_idempotent_submission(&pool, &clinsert.profile_number, &key).await.unwrap().is_none());
+    assert_eq!(Changelog::insert_changelog_with_key(&pool, clinsert.clone(), &key).await.unwrap(), new_cl_id);
+    let retry = ChangelogInsert { score: clinsert.score + 1, ..clinsert.clone() };
+    assert_eq!(Changelog::insert_changelog_with_key(&pool, retry, &key).await.unwrap(), new_cl_id);
+    assert_eq!(Changelog::get_idempotent_submission(&pool, &clinsert.profile_number, &key).await.unwrap(), Some(new_cl_id));
+    assert!(Changelog::get_idempotent_submission(&pool, "76561198039230536", &key).await.unwrap().is_none());
     let mut new_cl = Changelog::get_changelog(&pool, new_cl_id).await.unwrap().unwrap();
     new_cl.note = Some("fat time".to_string());
-    let is_updated = Changelog::update_changelog(&pool, new_cl.clone()).await.unwrap();
+    let is_updated = Changelog::update_changelog(&pool, new_cl.clone(), "76561198040982247").await.unwrap();
     assert!(is_updated);
+    // The row from before the edit is kept in the edit history.
+    let history = Changelog::get_edit_history(&pool, new_cl_id).await.unwrap();
+    assert_eq!(history[0].cl_id, new_cl_id);
+    assert_eq!(history[0].edited_by, Some("76561198040982247".to_string()));
+    assert_eq!(history[0].previous.score, new_cl.score);
+    assert!(Changelog::update_changelog(&pool, new_cl.clone(), "0").await.is_err());
     let updated_changelog = Changelog::get_changelog(&pool, new_cl_id).await.unwrap().unwrap();
     assert_eq!(new_cl.id, updated_changelog.id);
     assert_eq!(new_cl.timestamp, updated_changelog.timestamp);
@@ -297,9 +641,66 @@ async fn test_db_changelog() {
     assert_eq!(new_cl.score_delta, updated_changelog.score_delta);
     assert_eq!(new_cl.verified, updated_changelog.verified);
     assert_eq!(new_cl.admin_note, updated_changelog.admin_note);
-    let deleted = Changelog::delete_changelog(&pool, new_cl_id).await.unwrap();
+    // Single field updates only report a change when the value differs, and are admin only.
+    let admin = "76561198040982247";
+    Changelog::set_note(&pool, new_cl_id, None, admin).await.unwrap();
+    assert!(Changelog::set_note(&pool, new_cl_id, Some("single field".to_string()), admin).await.unwrap());
+    assert!(!Changelog::set_note(&pool, new_cl_id, Some("single field".to_string()), admin).await.unwrap());
+    assert!(Changelog::set_youtube_id(&pool, new_cl_id, Some("dQw4w9WgXcQ".to_string()), "0").await.is_err());
+    let single_field = Changelog::get_changelog(&pool, new_cl_id).await.unwrap().unwrap();
+    assert_eq!(single_field.note, Some("single field".to_string()));
+    assert_eq!(single_field.score, new_cl.score);
+    assert_eq!(Changelog::get_edit_history(&pool, new_cl_id).await.unwrap()[0].edited_by, Some(admin.to_string()));
+    assert!(!Changelog::set_banned(&pool, new_cl_id, single_field.banned, admin).await.unwrap());
+    assert!(Changelog::update_changelog(&pool, single_field.clone(), admin).await.unwrap());
+    assert!(Changelog::delete_changelog(&pool, new_cl_id, "0").await.is_err());
+    let deleted = Changelog::delete_changelog(&pool, new_cl_id, admin).await.unwrap();
     assert!(deleted);
     let _res = Changelog::get_changelog(&pool, new_cl_id).await;
+    // Admin edits and deletes are recorded in the audit log.
+    let audit = Admin::get_audit_log(&pool, 50, 0).await.unwrap();
+    assert!(audit.iter().any(|entry| entry.action == "update_changelog" && entry.target == Some(new_cl_id.to_string())));
+    assert!(audit.iter().any(|entry| entry.action == "delete_changelog" && entry.target == Some(new_cl_id.to_string())));
+    // Transactions commit every step together, or roll all of them back on an error.
+    use crate::tools::helpers::with_transaction;
+    let tx_insert = ChangelogInsert { score: clinsert.score - 1, ..clinsert.clone() };
+    let rolled_back = std::sync::Arc::new(std::sync::Mutex::new(None));
+    let (attempted, cl) = (rolled_back.clone(), tx_insert.clone());
+    let failed: Result<()> = with_transaction(&pool, |tx| Box::pin(async move {
+        let cl_id = Changelog::insert_changelog_tx(tx, cl).await?;
+        *attempted.lock().unwrap() = Some(cl_id);
+        anyhow::bail!("Simulated failure after inserting {}", cl_id)
+    })).await;
+    assert!(failed.is_err());
+    let rolled_back_id = rolled_back.lock().unwrap().unwrap();
+    assert!(!matches!(Changelog::get_changelog(&pool, rolled_back_id).await, Ok(Some(_))));
+    let (tx_cl_id, tx_demo_id) = with_transaction(&pool, |tx| Box::pin(async move {
+        let cl_id = Changelog::insert_changelog_tx(tx, tx_insert).await?;
+        let demo_id = Demos::insert_demo_tx(tx, DemoInsert { file_id: "transaction-test.dem".to_string(), cl_id, ..Default::default() }).await?;
+        Changelog::update_demo_id_in_changelog_tx(tx, cl_id, demo_id).await?;
+        Ok((cl_id, demo_id))
+    })).await.unwrap();
+    assert_ne!(tx_cl_id, rolled_back_id);
+    assert_eq!(Changelog::get_changelog(&pool, tx_cl_id).await.unwrap().unwrap().demo_id, Some(tx_demo_id));
+    assert!(Changelog::delete_changelog(&pool, tx_cl_id, "76561198040982247").await.unwrap());
+    assert!(Demos::delete_demo(&pool, tx_demo_id).await.unwrap());
+    // Rank backfill fills in every entry it picks up, and keeps the ranks that were already stored.
+    let missing = Changelog::get_entries_missing_ranks(&pool, 10).await.unwrap();
+    assert!(missing.iter().all(|cl| !cl.banned && (cl.post_rank.is_none() || cl.pre_rank.is_none())));
+    assert!(Changelog::backfill_ranks(&pool, 10, "0").await.is_err());
+    assert_eq!(Changelog::backfill_ranks(&pool, 10, "76561198040982247").await.unwrap(), missing.len() as u64);
+    for cl in missing.iter() {
+        let backfilled = Changelog::get_changelog(&pool, cl.id).await.unwrap().unwrap();
+        assert!(backfilled.post_rank.is_some() && (cl.post_rank.is_none() || backfilled.post_rank == cl.post_rank));
+        match cl.pre_rank {
+            Some(_) => assert_eq!(backfilled.pre_rank, cl.pre_rank),
+            None => assert_eq!(backfilled.pre_rank.is_some(), backfilled.previous_id.is_some()),
+        }
+    }
+    // Score delta backfill stores improvements as negative deltas, and eventually has nothing left to fix.
+    assert!(Changelog::backfill_score_deltas(&pool, 10000, "0").await.is_err());
+    while Changelog::backfill_score_deltas(&pool, 10000, "76561198040982247").await.unwrap() > 0 {}
+    assert_eq!(Changelog::backfill_score_deltas(&pool, 10000, "76561198040982247").await.unwrap(), 0);
 
     let query_params = ChangelogQueryParams {
         limit: Some(500),
@@ -313,6 +714,8 @@ async fn test_db_changelog() {
         yt: None,
         first: None,
         last: None,
+        proof_level: None,
+        pbs_only: None,
     };
 
     // ChangelogPage
@@ -330,10 +733,185 @@ async fn test_db_changelog() {
         yt: None,
         first: None,
         last: None,
+        proof_level: None,
+        pbs_only: None,
     };
     let filtered_cl_page = ChangelogPage::get_changelog_page(&pool, filter).await.unwrap().unwrap();
     assert_eq!(filtered_cl_page.len(), 1);
     assert_eq!(filtered_cl_page[0].id, 127825);
+    // Pagination total ignores the cursor and limit, but respects the filters
+    let total = ChangelogPage::count_filtered_changelog(&pool, ChangelogQueryParams::default()).await.unwrap();
+    assert!(total >= DEFAULT_PAGE_SIZE as i64);
+    let count_filter = ChangelogQueryParams { nick_name: Some("Daniel".to_string()), chamber: Some("47763".to_string()), sp: Some(true), coop: Some(true), wr_gain: Some(true), has_demo: Some(true), limit: Some(1), last: Some(1), ..Default::default() };
+    assert_eq!(ChangelogPage::count_filtered_changelog(&pool, count_filter).await.unwrap(), 1);
+    // Filter values are bound, not spliced into the query.
+    let injected = ChangelogQueryParams { chamber: Some("47763' OR '1'='1".to_string()), ..Default::default() };
+    assert_eq!(ChangelogPage::count_filtered_changelog(&pool, injected).await.unwrap(), 0);
+    // Proof level filters
+    for proof_level in [ProofLevel::None, ProofLevel::DemoOnly, ProofLevel::VideoOnly, ProofLevel::Both, ProofLevel::Any] {
+        let params = ChangelogQueryParams { limit: Some(100), proof_level: Some(proof_level), ..Default::default() };
+        let page = ChangelogPage::get_changelog_page(&pool, params).await.unwrap().unwrap();
+        assert!(page.iter().all(|cl| match proof_level {
+            ProofLevel::None => cl.demo_id.is_none() && cl.youtube_id.is_none(),
+            ProofLevel::DemoOnly => cl.demo_id.is_some() && cl.youtube_id.is_none(),
+            ProofLevel::VideoOnly => cl.demo_id.is_none() && cl.youtube_id.is_some(),
+            ProofLevel::Both => cl.demo_id.is_some() && cl.youtube_id.is_some(),
+            ProofLevel::Any => cl.demo_id.is_some() || cl.youtube_id.is_some(),
+        }));
+    }
+    // PBs only, newest first, so every entry is slower than the one before it on the same board.
+    let params = ChangelogQueryParams { profile_number: Some("76561198040982247".to_string()), chamber: Some("47763".to_string()), pbs_only: Some(true), ..Default::default() };
+    let pbs = ChangelogPage::get_changelog_page(&pool, params).await.unwrap().unwrap();
+    assert!(!pbs.is_empty());
+    let unbanned: Vec<&ChangelogPage> = pbs.iter().filter(|cl| !cl.banned).collect();
+    assert!(unbanned.windows(2).filter(|w| w[0].category_id == w[1].category_id).all(|w| w[0].score < w[1].score));
+    let all_params = ChangelogQueryParams { profile_number: Some("76561198040982247".to_string()), chamber: Some("47763".to_string()), ..Default::default() };
+    assert!(ChangelogPage::count_filtered_changelog(&pool, all_params).await.unwrap() >= pbs.len() as i64);
+
+    // Category feed
+    let feed = Changelog::get_category_feed(&pool, 19, Some(50), None).await.unwrap();
+    assert!(!feed.is_empty() && feed.len() <= 50);
+    assert!(feed.iter().all(|entry| entry.category_id == 19 && !entry.banned));
+    let older = Changelog::get_category_feed(&pool, 19, Some(50), Some(feed[0].id)).await.unwrap();
+    assert!(older.iter().all(|entry| entry.id < feed[0].id));
+    // Incremental sync, the entries directly after the cursor in ascending order, so paging never skips any.
+    let since = Changelog::get_since_id(&pool, older[0].id, 20).await.unwrap();
+    assert_eq!(since.len(), 20);
+    assert!(since[0].id > older[0].id && since.windows(2).all(|w| w[0].id < w[1].id));
+    let next = Changelog::get_since_id(&pool, since[9].id, 10).await.unwrap();
+    assert_eq!(next.iter().map(|e| e.id).collect::<Vec<_>>(), since[10..].iter().map(|e| e.id).collect::<Vec<_>>());
+    // A player's full changelog, across every map, with banned runs only hidden on request.
+    let activity = Changelog::get_all_for_profile(&pool, "76561198040982247".to_string(), Some(50), None, false).await.unwrap();
+    assert!(!activity.is_empty() && activity.len() <= 50);
+    assert!(activity.iter().all(|entry| entry.profile_number == "76561198040982247"));
+    let unbanned = Changelog::get_all_for_profile(&pool, "76561198040982247".to_string(), Some(50), Some(activity[0].id), true).await.unwrap();
+    assert!(unbanned.iter().all(|entry| !entry.banned && entry.id < activity[0].id));
+
+    // Large jumps, every entry improved on its previous run by at least the ratio.
+    let jumps = Changelog::get_large_jumps(&pool, 0.1, Some(20), None).await.unwrap();
+    for entry in jumps.iter() {
+        let previous = Changelog::get_changelog(&pool, entry.previous_id.unwrap()).await.unwrap().unwrap();
+        assert!(!entry.banned && -entry.score_delta.unwrap() as f64 / previous.score as f64 >= 0.1);
+    }
+    assert!(Changelog::get_large_jumps(&pool, 0.5, Some(20), None).await.unwrap().len() <= jumps.len());
+
+    // Top improvers
+    let since = NaiveDateTime::parse_from_str("2020-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let improvers = Changelog::get_top_improvers(&pool, since, 10).await.unwrap();
+    assert!(!improvers.is_empty() && improvers.len() <= 10);
+    assert!(improvers.iter().all(|improver| improver.improvement > 0 && improver.improvements > 0));
+    assert!(improvers.windows(2).all(|w| w[0].improvement >= w[1].improvement));
+    // Daily summary, a day with scores always has at least one active player.
+    let summary = Changelog::get_daily_summary(&pool, chrono::NaiveDate::from_ymd_opt(2021, 9, 1).unwrap()).await.unwrap();
+    assert!(summary.new_wrs <= summary.submissions && summary.active_players <= summary.submissions);
+    assert_eq!(summary.submissions > 0, summary.active_players > 0);
+    assert!(summary.top_improvement.iter().all(|top| top.improvement > 0));
+    // The notable run is stable within a day.
+    let day = chrono::NaiveDate::from_ymd_opt(2022, 2, 20).unwrap();
+    let notable = Changelog::get_random_notable(&pool, day).await.unwrap();
+    assert!(!notable.banned && notable.verified == Some(true));
+    assert_eq!(Changelog::get_random_notable(&pool, day).await.unwrap().id, notable.id);
+    // WR counts, every default category WR is also counted when all categories are.
+    let default_wrs = Changelog::get_wr_counts_for_all(&pool, CategoryScope::Default).await.unwrap();
+    let all_wrs = Changelog::get_wr_counts_for_all(&pool, CategoryScope::All).await.unwrap();
+    assert!(!default_wrs.is_empty() && default_wrs.values().all(|count| *count > 0));
+    assert!(default_wrs.iter().all(|(profile_number, count)| all_wrs[profile_number] >= *count));
+    // Daily activity, zero-filled
+    let counts = Changelog::get_daily_counts(&pool, "47802".to_string(), 7).await.unwrap();
+    assert_eq!(counts.len(), 7);
+    assert!(counts.windows(2).all(|w| w[1].date - w[0].date == chrono::Duration::days(1)));
+    // Hourly activity, zero-filled, a single player's submissions are a subset of everyone's.
+    let hourly = Changelog::get_hourly_distribution(&pool, None).await.unwrap();
+    assert_eq!(hourly.iter().map(|h| h.hour).collect::<Vec<_>>(), (0..24).collect::<Vec<_>>());
+    let player_hourly = Changelog::get_hourly_distribution(&pool, Some("76561198040982247".to_string())).await.unwrap();
+    assert_eq!(player_hourly.len(), 24);
+    assert!(player_hourly.iter().zip(hourly.iter()).all(|(p, h)| p.count <= h.count));
+    assert!(player_hourly.iter().any(|h| h.count > 0));
+    // Entries with a category belonging to another map
+    let other_cat_id = Categories::get_category_ids(&pool, "47802".to_string()).await.unwrap()[0];
+    let invalid_id = Changelog::insert_changelog(&pool, ChangelogInsert { category_id: other_cat_id, ..clinsert.clone() }).await.unwrap();
+    let invalid = Changelog::get_invalid_category_entries(&pool).await.unwrap();
+    assert!(invalid.iter().any(|entry| entry.id == invalid_id));
+    assert!(invalid.iter().all(|entry| entry.id != 127825));
+    assert!(Changelog::delete_changelog(&pool, invalid_id, "76561198040982247").await.unwrap());
+    // Invalid coop bundles, each reported problem holds for the bundle's halves.
+    let anomalies = Changelog::find_invalid_coop(&pool).await.unwrap();
+    assert!(anomalies.windows(2).all(|w| (w[0].coop_id, &w[0].reason) < (w[1].coop_id, &w[1].reason)));
+    for anomaly in anomalies.iter().take(20) {
+        let c1 = Changelog::get_changelog(&pool, anomaly.cl_id1).await.unwrap();
+        let c2 = match anomaly.cl_id2 {
+            Some(id) => Changelog::get_changelog(&pool, id).await.unwrap(),
+            None => None,
+        };
+        match (anomaly.reason.as_str(), &c1, &c2) {
+            ("missing_half", _, _) => assert!(c1.is_none() || (anomaly.p_id2.is_some() && c2.is_none())),
+            ("self_partner", _, _) => assert!(anomaly.p_id2.as_ref() == Some(&anomaly.p_id1) || c1.as_ref().map(|c| &c.profile_number) == c2.as_ref().map(|c| &c.profile_number)),
+            ("mismatched_map", Some(c1), Some(c2)) => assert_ne!(c1.map_id, c2.map_id),
+            ("mismatched_category", Some(c1), Some(c2)) => assert_ne!(c1.category_id, c2.category_id),
+            ("mismatched_score", Some(c1), Some(c2)) => assert_ne!(c1.score, c2.score),
+            (reason, _, _) => panic!("Unexpected coop anomaly {}", reason),
+        }
+    }
+    // Admin note search is case-insensitive, and matches anywhere in the note.
+    let admin = "76561198040982247";
+    let noted_id = Changelog::insert_changelog(&pool, ChangelogInsert { timestamp: None, admin_note: Some("Looks Segmented? check demo".to_string()), ..clinsert.clone() }).await.unwrap();
+    let noted = Changelog::search_admin_notes(&pool, "segmented?".to_string(), 100, admin).await.unwrap();
+    assert!(noted.iter().any(|entry| entry.id == noted_id));
+    assert!(noted.iter().all(|entry| entry.admin_note.as_ref().unwrap().to_lowercase().contains("segmented?")));
+    // Wildcards are matched literally, results are limited, and only admins can search.
+    let wildcard = Changelog::search_admin_notes(&pool, "seg%demo".to_string(), 100, admin).await.unwrap();
+    assert!(wildcard.iter().all(|entry| entry.admin_note.as_ref().unwrap().contains('%')));
+    assert!(Changelog::search_admin_notes(&pool, "".to_string(), 1, admin).await.unwrap().len() <= 1);
+    assert!(Changelog::search_admin_notes(&pool, "segmented?".to_string(), 100, "0").await.is_err());
+    assert!(Changelog::delete_changelog(&pool, noted_id, "76561198040982247").await.unwrap());
+}
+
+#[actix_web::test]
+async fn test_db_changelog_tie_break() {
+    use crate::models::models::*;
+    use chrono::NaiveDateTime;
+    use std::collections::HashMap;
+    let (_, pool) = get_config().await.expect("Error getting config and DB pool");
+    // Several entries sharing the exact same timestamp, as happens with bulk imports.
+    let clinsert = ChangelogInsert {
+        timestamp: Some(NaiveDateTime::parse_from_str("2099-01-01 00:00:00", "%Y-%m-%d %H:%M:%S").unwrap()),
+        profile_number: "76561198040982247".to_string(),
+        score: 1698,
+        map_id: "47763".to_string(),
+        category_id: 19,
+        verified: Some(true),
+        ..Default::default()
+    };
+    let mut inserted = Vec::new();
+    for i in 0..5 {
+        inserted.push(Changelog::insert_changelog(&pool, ChangelogInsert { score: clinsert.score + i, ..clinsert.clone() }).await.unwrap());
+    }
+    // Page through 2 at a time using the `last` cursor, every entry should be seen exactly once.
+    let mut seen: HashMap<i64, i32> = HashMap::new();
+    let mut last: Option<i64> = None;
+    loop {
+        let params = ChangelogQueryParams {
+            limit: Some(2),
+            chamber: Some("47763".to_string()),
+            sp: None,
+            coop: None,
+            last,
+            ..Default::default()
+        };
+        let page = ChangelogPage::get_changelog_page(&pool, params).await.unwrap().unwrap();
+        let page: Vec<&ChangelogPage> = page.iter().filter(|cl| inserted.contains(&cl.id)).collect();
+        if page.is_empty() {
+            break;
+        }
+        for cl in page.iter() {
+            *seen.entry(cl.id).or_insert(0) += 1;
+        }
+        last = Some(page[page.len() - 1].id);
+    }
+    for id in inserted.iter() {
+        assert_eq!(seen.get(id), Some(&1));
+        assert!(Changelog::delete_changelog(&pool, *id, "76561198040982247").await.unwrap());
+    }
 }
 
 #[actix_web::test]
@@ -346,6 +924,54 @@ async fn test_db_pages() {
     let coop_map_id = "52642".to_string();
     let smp = SpMap::get_sp_map_page(&pool, sp_map_id.clone(), DEFAULT_PAGE_SIZE as i32, 19).await.unwrap();
     assert_ne!(smp.len(), 0);
+    // Comparing a subset of players should keep their rank relative to the full board.
+    let compare_ids = vec![smp[0].profile_number.clone(), smp[smp.len() - 1].profile_number.clone()];
+    let best = Changelog::get_best_scores(&pool, sp_map_id.clone(), 19, &compare_ids).await.unwrap();
+    assert_eq!(best.len(), 2);
+    assert_eq!(best[0].rank, 1);
+    assert_eq!(best[1].score, smp[smp.len() - 1].score);
+    // Score context, scores at the top/bottom of the board still get a full window.
+    for (score, expected_index) in [(1, 0), (999999, 4)] {
+        let clinsert = ChangelogInsert { profile_number: "76561198040982247".to_string(), score, map_id: sp_map_id.clone(), category_id: 19, verified: Some(true), ..Default::default() };
+        let id = Changelog::insert_changelog(&pool, clinsert).await.unwrap();
+        let context = Changelog::get_score_with_neighbors(&pool, id, 2).await.unwrap();
+        assert_eq!(context.len(), 5);
+        assert_eq!(context[expected_index].score, score);
+        assert!(context.windows(2).all(|w| w[0].score <= w[1].score));
+        Changelog::delete_changelog(&pool, id, "76561198040982247").await.unwrap();
+    }
+    assert!(Changelog::get_score_with_neighbors(&pool, -1, 2).await.unwrap().is_empty());
+    // Two players sharing a new best score should show up as the first tie on the board.
+    let mut tie_ids = Vec::new();
+    for profile_number in [smp[0].profile_number.clone(), smp[1].profile_number.clone()] {
+        let clinsert = ChangelogInsert { profile_number, score: 1, map_id: sp_map_id.clone(), category_id: 19, verified: Some(true), ..Default::default() };
+        tie_ids.push(Changelog::insert_changelog(&pool, clinsert).await.unwrap());
+    }
+    let ties = Changelog::get_tied_scores(&pool, sp_map_id.clone(), 19).await.unwrap();
+    assert_eq!(ties[0].len(), 2);
+    assert!(ties[0].iter().all(|e| e.score == 1 && e.rank == 1));
+    assert!(ties.iter().all(|group| group.len() > 1 && group.iter().all(|e| e.score == group[0].score)));
+    assert!(ties.windows(2).all(|w| w[0][0].score < w[1][0].score));
+    for id in tie_ids {
+        Changelog::delete_changelog(&pool, id, "76561198040982247").await.unwrap();
+    }
+    let longest_wr = Changelog::get_longest_standing_wr(&pool, sp_map_id.clone(), 19).await.unwrap().unwrap();
+    assert!(longest_wr.duration_secs > 0);
+    assert!(longest_wr.score >= smp[0].score);
+    let wr_history = Changelog::get_wr_history(&pool, sp_map_id.clone(), 19).await.unwrap();
+    let reigns = Maps::get_wr_reign_history(&pool, sp_map_id.clone(), 19).await.unwrap();
+    assert!(!reigns.is_empty() && reigns.len() <= wr_history.len());
+    assert!(reigns.windows(2).all(|w| w[0].profile_number != w[1].profile_number && w[0].end == w[1].start && w[0].score > w[1].score));
+    assert!(reigns.last().unwrap().current && reigns.iter().filter(|r| r.current).count() == 1);
+    assert_eq!(reigns.last().unwrap().score, wr_history.last().unwrap().score);
+    // The world record at a point in time, nothing before the first record and the current record now.
+    let first_wr = wr_history[0].timestamp;
+    assert_eq!(Changelog::get_wr_at_time(&pool, sp_map_id.clone(), 19, first_wr - chrono::Duration::seconds(1)).await.unwrap(), None);
+    for wr in wr_history.iter() {
+        assert!(Changelog::get_wr_at_time(&pool, sp_map_id.clone(), 19, wr.timestamp).await.unwrap().unwrap() <= wr.score);
+    }
+    let now = chrono::Utc::now().naive_utc();
+    assert_eq!(Changelog::get_wr_at_time(&pool, sp_map_id.clone(), 19, now).await.unwrap(), wr_history.last().map(|wr| wr.score));
     let cmp = CoopMap::get_coop_map_page(&pool, coop_map_id.clone(), DEFAULT_PAGE_SIZE as i32, 81).await.unwrap();
     assert_ne!(cmp.len(), 0);
     let coop_entries_filtered = filter_coop_entries(cmp, config.proof.results as usize).await;
@@ -387,10 +1013,166 @@ async fn test_db_admins() {
         yt: None,
         first: None,
         last: None,
+        proof_level: None,
+        pbs_only: None,
     };
     let ban_page = Admin::get_admin_page(&pool, query_params).await.unwrap().unwrap();
     assert!(ban_page.len() == 5);
 
     let ban_stats = Admin::get_user_banned_time_stats(&pool).await.unwrap().unwrap();
     assert!(ban_stats.len() != 0);
+
+    // Bulk banning
+    let clinsert = ChangelogInsert {
+        profile_number: "76561198040982247".to_string(),
+        score: 1698,
+        map_id: "47763".to_string(),
+        category_id: 19,
+        verified: Some(true),
+        ..Default::default()
+    };
+    // Identical inserts are deduplicated, so each run needs its own score.
+    let ids = [
+        Changelog::insert_changelog(&pool, clinsert.clone()).await.unwrap(),
+        Changelog::insert_changelog(&pool, ChangelogInsert { score: 1699, ..clinsert.clone() }).await.unwrap(),
+    ];
+    // Non-admins can't ban scores.
+    assert!(Changelog::set_banned_bulk(&pool, &ids, true, "0".to_string()).await.is_err());
+    let affected = Changelog::set_banned_bulk(&pool, &ids, true, "76561198040982247".to_string()).await.unwrap();
+    assert_eq!(affected, vec![MapCategory { map_id: "47763".to_string(), category_id: 19 }]);
+    // Ranks on the affected board are recomputed along with the ban.
+    for id in ids.iter() {
+        let banned = Changelog::get_changelog(&pool, *id).await.unwrap().unwrap();
+        assert!(banned.banned && banned.post_rank.is_some());
+        Changelog::delete_changelog(&pool, *id, "76561198040982247").await.unwrap();
+    }
+
+    // Bulk verification
+    let pending = ChangelogInsert { verified: Some(false), ..clinsert.clone() };
+    let ids = [
+        Changelog::insert_changelog(&pool, pending.clone()).await.unwrap(),
+        Changelog::insert_changelog(&pool, ChangelogInsert { score: 1699, ..pending.clone() }).await.unwrap(),
+    ];
+    assert!(Changelog::verify_pending_for_map(&pool, "47763".to_string(), 19, "0".to_string()).await.is_err());
+    let verified = Changelog::verify_pending_for_map(&pool, "47763".to_string(), 19, "76561198040982247".to_string()).await.unwrap();
+    for id in ids.iter() {
+        assert!(verified.contains(id));
+        assert_eq!(Changelog::get_changelog(&pool, *id).await.unwrap().unwrap().verified, Some(true));
+        Changelog::delete_changelog(&pool, *id, "76561198040982247").await.unwrap();
+    }
+    // History compaction, on a category with no other entries for the map so only these runs are affected.
+    let cat_id = Categories::get_category_ids(&pool, "47802".to_string()).await.unwrap()[0];
+    let mut compact_ids = Vec::new();
+    for (i, (score, youtube_id)) in [(2000, None), (2100, None), (1900, None), (1950, Some("-InZK6yZb08?start=0")), (1980, None)].into_iter().enumerate() {
+        let run = ChangelogInsert {
+            timestamp: Some(chrono::NaiveDateTime::parse_from_str(&format!("2099-01-0{} 00:00:00", i + 1), "%Y-%m-%d %H:%M:%S").unwrap()),
+            score,
+            youtube_id: youtube_id.map(|yt| yt.to_string()),
+            category_id: cat_id,
+            ..clinsert.clone()
+        };
+        compact_ids.push(Changelog::insert_changelog(&pool, run).await.unwrap());
+    }
+    assert!(Changelog::compact_history(&pool, "76561198040982247".to_string(), "47763".to_string(), cat_id, true, "0".to_string()).await.is_err());
+    // Runs with proof are kept unless only PBs should be kept.
+    let archived = Changelog::compact_history(&pool, "76561198040982247".to_string(), "47763".to_string(), cat_id, false, "76561198040982247".to_string()).await.unwrap();
+    assert_eq!(archived, 1);
+    assert!(Changelog::get_changelog(&pool, compact_ids[1]).await.unwrap().unwrap().archived);
+    let archived = Changelog::compact_history(&pool, "76561198040982247".to_string(), "47763".to_string(), cat_id, true, "76561198040982247".to_string()).await.unwrap();
+    assert_eq!(archived, 1);
+    assert!(Changelog::get_changelog(&pool, compact_ids[3]).await.unwrap().unwrap().archived);
+    for id in [compact_ids[0], compact_ids[2], compact_ids[4]] {
+        assert!(!Changelog::get_changelog(&pool, id).await.unwrap().unwrap().archived);
+    }
+    for id in compact_ids.iter() {
+        Changelog::delete_changelog(&pool, *id, "76561198040982247").await.unwrap();
+    }
+    // Merging accounts, the removed account has the better score so the kept account's run is archived.
+    let keep = Users { profile_number: "76561190000000001".to_string(), board_name: Some("MergeKeep".to_string()), steam_name: None, banned: false, registered: 0, avatar: None, twitch: None, youtube: None, title: None, admin: 0, donation_amount: None, discord_id: None, merged_into: None, ban_reason: None };
+    let remove = Users { profile_number: "76561190000000002".to_string(), board_name: Some("MergeRemove".to_string()), twitch: Some("mergetwitch".to_string()), ..keep.clone() };
+    assert!(Users::insert_new_users(&pool, keep.clone(), None).await.unwrap());
+    assert!(Users::insert_new_users(&pool, remove.clone(), None).await.unwrap());
+    let keep_id = Changelog::insert_changelog(&pool, ChangelogInsert { profile_number: "76561190000000001".to_string(), score: 2000, category_id: cat_id, ..clinsert.clone() }).await.unwrap();
+    let remove_id = Changelog::insert_changelog(&pool, ChangelogInsert { profile_number: "76561190000000002".to_string(), score: 1900, category_id: cat_id, ..clinsert.clone() }).await.unwrap();
+    assert!(Users::merge_accounts(&pool, "76561190000000001".to_string(), "76561190000000002".to_string(), "0".to_string()).await.is_err());
+    assert!(Users::merge_accounts(&pool, "76561190000000001".to_string(), "76561190000000001".to_string(), "76561198040982247".to_string()).await.is_err());
+    let summary = Users::merge_accounts(&pool, "76561190000000001".to_string(), "76561190000000002".to_string(), "76561198040982247".to_string()).await.unwrap();
+    assert_eq!((summary.scores_transferred, summary.scores_archived, summary.coop_runs_transferred), (1, 1, 0));
+    let kept_run = Changelog::get_changelog(&pool, keep_id).await.unwrap().unwrap();
+    let moved_run = Changelog::get_changelog(&pool, remove_id).await.unwrap().unwrap();
+    assert!(kept_run.archived && !moved_run.archived);
+    assert_eq!(moved_run.profile_number, "76561190000000001");
+    assert_eq!(Users::get_user(&pool, "76561190000000001".to_string()).await.unwrap().unwrap().twitch, Some("mergetwitch".to_string()));
+    let removed = Users::get_user(&pool, "76561190000000002".to_string()).await.unwrap().unwrap();
+    assert_eq!((removed.merged_into, removed.twitch), (Some("76561190000000001".to_string()), None));
+    assert!(Users::merge_accounts(&pool, "76561190000000001".to_string(), "76561190000000002".to_string(), "76561198040982247".to_string()).await.is_err());
+    for id in [keep_id, remove_id] {
+        Changelog::delete_changelog(&pool, id, "76561198040982247").await.unwrap();
+    }
+    // Banning with a public reason, internal notes only go to the audit log.
+    assert!(Users::set_ban(&pool, "76561190000000001".to_string(), true, Some("Cheated runs".to_string()), Some("Internal".to_string()), false, "0".to_string()).await.is_err());
+    assert_eq!(Users::set_ban(&pool, "76561190000000001".to_string(), true, Some("Cheated runs".to_string()), Some("Internal".to_string()), false, "76561198040982247".to_string()).await.unwrap(), Some(0));
+    let wall = Users::get_banned_with_reasons(&pool, "").await.unwrap();
+    assert_eq!(wall.iter().find(|user| user.profile_number == "76561190000000001").unwrap().reason, Some("Cheated runs".to_string()));
+    assert_eq!(Users::set_ban(&pool, "76561190000000001".to_string(), false, None, None, false, "76561198040982247".to_string()).await.unwrap(), Some(0));
+    assert!(Users::get_banned_with_reasons(&pool, "").await.unwrap().iter().all(|user| user.profile_number != "76561190000000001"));
+    assert_eq!(Users::set_ban(&pool, "-1".to_string(), true, None, None, false, "76561198040982247".to_string()).await.unwrap(), None);
+    // Cascading bans the user's runs along with them, and only counts runs that changed.
+    let ban_ids = [
+        Changelog::insert_changelog(&pool, ChangelogInsert { profile_number: "76561190000000001".to_string(), score: 2000, category_id: cat_id, ..clinsert.clone() }).await.unwrap(),
+        Changelog::insert_changelog(&pool, ChangelogInsert { profile_number: "76561190000000001".to_string(), score: 1950, category_id: cat_id, banned: true, ..clinsert.clone() }).await.unwrap(),
+    ];
+    assert_eq!(Users::set_ban(&pool, "76561190000000001".to_string(), true, None, None, true, "76561198040982247".to_string()).await.unwrap(), Some(1));
+    // Unbanning only restores the runs the cascade banned, the individually banned run stays banned.
+    assert_eq!(Users::set_ban(&pool, "76561190000000001".to_string(), false, None, None, true, "76561198040982247".to_string()).await.unwrap(), Some(1));
+    assert!(!Changelog::get_changelog(&pool, ban_ids[0]).await.unwrap().unwrap().banned);
+    assert!(Changelog::get_changelog(&pool, ban_ids[1]).await.unwrap().unwrap().banned);
+    for id in ban_ids {
+        assert!(Changelog::get_changelog(&pool, id).await.unwrap().unwrap().post_rank.is_some());
+        Changelog::delete_changelog(&pool, id, "76561198040982247").await.unwrap();
+    }
+    assert!(Users::delete_user(&pool, "76561190000000001".to_string()).await.unwrap());
+    assert!(Users::delete_user(&pool, "76561190000000002".to_string()).await.unwrap());
+    // Featured maps, a new feature supersedes the current one.
+    let until = chrono::Utc::now().naive_utc() + chrono::Duration::days(1);
+    assert!(Maps::set_featured(&pool, "47763".to_string(), until, "0".to_string()).await.is_err());
+    assert!(Maps::set_featured(&pool, "47763".to_string(), chrono::Utc::now().naive_utc(), "76561198040982247".to_string()).await.is_err());
+    Maps::set_featured(&pool, "47763".to_string(), until, "76561198040982247".to_string()).await.unwrap();
+    let featured = Maps::get_featured(&pool).await.unwrap().unwrap();
+    // Postgres keeps microseconds, so compare to the second.
+    assert_eq!((featured.steam_id.as_str(), (featured.until - until).num_seconds()), ("47763", 0));
+    assert!(featured.players > 0 && featured.wr.is_some() && featured.wr_holder.is_some());
+    Maps::set_featured(&pool, "47802".to_string(), until, "76561198040982247".to_string()).await.unwrap();
+    assert_eq!(Maps::get_featured(&pool).await.unwrap().unwrap().steam_id, "47802");
+    // Difficulty tiers, out of range tiers are rejected and clearing removes the map from its tier.
+    assert!(Maps::set_tier(&pool, "47763".to_string(), Some(0), "76561198040982247".to_string()).await.is_err());
+    assert!(Maps::set_tier(&pool, "47763".to_string(), Some(3), "0".to_string()).await.is_err());
+    assert!(!Maps::set_tier(&pool, "-1".to_string(), Some(3), "76561198040982247".to_string()).await.unwrap());
+    assert!(Maps::set_tier(&pool, "47763".to_string(), Some(3), "76561198040982247".to_string()).await.unwrap());
+    assert!(Maps::get_by_tier(&pool, 3).await.unwrap().iter().any(|map| map.steam_id == "47763"));
+    assert!(Maps::set_tier(&pool, "47763".to_string(), None, "76561198040982247".to_string()).await.unwrap());
+    assert!(Maps::get_by_tier(&pool, 3).await.unwrap().iter().all(|map| map.steam_id != "47763"));
+    // Submission windows, a closed map or one outside of its window rejects submissions until it's reopened.
+    let admin = "76561198040982247".to_string();
+    assert!(Maps::is_submission_open(&pool, "47763".to_string()).await.unwrap());
+    assert!(!Maps::is_submission_open(&pool, "-1".to_string()).await.unwrap());
+    let now = chrono::Utc::now().naive_utc();
+    let (earlier, later) = (now - chrono::Duration::days(1), now + chrono::Duration::days(1));
+    assert!(Maps::set_submission_window(&pool, "47763".to_string(), true, Some(later), Some(earlier), admin.clone()).await.is_err());
+    assert!(Maps::set_submission_window(&pool, "47763".to_string(), false, None, None, "0".to_string()).await.is_err());
+    assert!(!Maps::set_submission_window(&pool, "-1".to_string(), false, None, None, admin.clone()).await.unwrap());
+    assert!(Maps::set_submission_window(&pool, "47763".to_string(), false, None, None, admin.clone()).await.unwrap());
+    assert!(!Maps::is_submission_open(&pool, "47763".to_string()).await.unwrap());
+    assert!(Maps::set_submission_window(&pool, "47763".to_string(), true, Some(later), None, admin.clone()).await.unwrap());
+    assert!(!Maps::is_submission_open(&pool, "47763".to_string()).await.unwrap());
+    assert!(Maps::set_submission_window(&pool, "47763".to_string(), true, Some(earlier), Some(later), admin.clone()).await.unwrap());
+    assert!(Maps::is_submission_open(&pool, "47763".to_string()).await.unwrap());
+    assert!(Maps::set_submission_window(&pool, "47763".to_string(), true, None, None, admin).await.unwrap());
+    // Both bulk actions should be recorded in the audit log, most recent first.
+    let audit = Admin::get_audit_log(&pool, 50, 0).await.unwrap();
+    assert!(audit.iter().any(|entry| entry.action == "verify" && entry.target == Some(ids[0].to_string())));
+    assert!(audit.iter().any(|entry| entry.action == "ban"));
+    assert!(audit.iter().any(|entry| entry.action == "merge_accounts" && entry.target == Some("76561190000000002".to_string())));
+    assert!(audit.iter().any(|entry| entry.action == "ban_user" && entry.details == Some("Internal".to_string())));
+    assert!(audit.windows(2).all(|w| w[0].id > w[1].id));
 }
\ No newline at end of file
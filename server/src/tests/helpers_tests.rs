@@ -0,0 +1,253 @@
+#[test]
+/// Demo file names should always be valid file names, and usable with `playdemo`.
+fn test_demo_file_name() {
+    use crate::tools::helpers::demo_file_name;
+
+    let name = demo_file_name("Portal Gun", Some("Zypeh"), "76561198040982247", 1763);
+    assert_eq!(name, "PortalGun_Zypeh_1763.dem");
+    // Illegal characters are stripped.
+    let name = demo_file_name(
+        "Portal Gun",
+        Some("a/b\\c:d*?\"<>|"),
+        "76561198040982247",
+        1763,
+    );
+    assert_eq!(name, "PortalGun_abcd_1763.dem");
+    // Fully unicode names fall back to the profile number.
+    let name = demo_file_name("Portal Gun", Some("ゼット"), "76561198040982247", 1763);
+    assert_eq!(name, "PortalGun_76561198040982247_1763.dem");
+    let name = demo_file_name("Portal Gun", None, "76561198040982247", 1763);
+    assert_eq!(name, "PortalGun_76561198040982247_1763.dem");
+}
+
+#[test]
+/// Youtube links in any of the common formats should normalize to `{id}?start={n}`.
+fn test_normalize_youtube_id() {
+    use crate::tools::helpers::normalize_youtube_id;
+
+    assert_eq!(
+        normalize_youtube_id("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+        Some("dQw4w9WgXcQ?start=0".to_string())
+    );
+    assert_eq!(
+        normalize_youtube_id("https://youtu.be/dQw4w9WgXcQ?t=42"),
+        Some("dQw4w9WgXcQ?start=42".to_string())
+    );
+    assert_eq!(
+        normalize_youtube_id("dQw4w9WgXcQ?start=10"),
+        Some("dQw4w9WgXcQ?start=10".to_string())
+    );
+    assert_eq!(normalize_youtube_id("https://example.com/video"), None);
+    assert_eq!(normalize_youtube_id("not a link"), None);
+}
+
+#[test]
+/// Common timestamp formats should all parse to the same UTC time, and unknown formats should error.
+fn test_parse_timestamp() {
+    use crate::tools::helpers::parse_timestamp;
+    use chrono::NaiveDateTime;
+
+    let expected =
+        NaiveDateTime::parse_from_str("2020-08-18 20:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    for input in [
+        "2020-08-18 20:30:00",
+        "2020-08-18T20:30:00",
+        "2020-08-18T20:30:00.000",
+        "2020-08-18T20:30:00Z",
+        "2020-08-18T22:30:00+02:00",
+        "2020-08-18 15:30:00-0500",
+        "1597782600",
+    ] {
+        assert_eq!(parse_timestamp(input).unwrap(), Some(expected), "{}", input);
+    }
+    assert_eq!(parse_timestamp("").unwrap(), None);
+    assert_eq!(parse_timestamp("  ").unwrap(), None);
+    assert!(parse_timestamp("18/08/2020").is_err());
+    assert!(parse_timestamp("yesterday").is_err());
+}
+
+#[test]
+/// Only 17 digit SteamID64s for individual accounts are valid profile numbers.
+fn test_validate_steam_id64() {
+    use crate::models::models::{ValidationError, ValidationErrors};
+    use crate::tools::helpers::validate_steam_id64;
+
+    assert!(validate_steam_id64("76561198040982247").is_ok());
+    for invalid in [
+        "",
+        "0",
+        "7656119804098224",
+        "765611980409822470",
+        "76561198O40982247",
+        "12345678901234567",
+    ] {
+        let e = validate_steam_id64(invalid).unwrap_err();
+        assert_eq!(
+            e.downcast_ref::<ValidationErrors>().unwrap().0,
+            vec![ValidationError::InvalidProfileNumber(invalid.to_string())]
+        );
+    }
+}
+
+#[test]
+/// Filters render to a parenthesized `WHERE`/`AND` clause, with placeholders numbered in the order they were added.
+fn test_query_filters() {
+    use crate::tools::helpers::QueryFilters;
+
+    assert_eq!(QueryFilters::new().where_clause(), "");
+    let mut filters = QueryFilters::new();
+    filters
+        .push("cl.demo_id IS NOT NULL")
+        .push_bind("cl.map_id = {}", "47763");
+    let mut additional = QueryFilters::new();
+    additional
+        .push("cl.banned = True OR u.banned = True")
+        .push_bind("cl.category_id = {}", 19);
+    filters.append(additional);
+    assert_eq!(
+        filters.where_clause(),
+        "\n WHERE (cl.demo_id IS NOT NULL)\n AND (cl.map_id = $1)\n AND (cl.banned = True OR u.banned = True)\n AND (cl.category_id = $2)\n"
+    );
+    // Values are never rendered into the clause itself.
+    let mut filters = QueryFilters::new();
+    filters.push_bind(
+        "cl.profile_number = ANY({})",
+        vec!["1' OR '1'='1".to_string()],
+    );
+    assert_eq!(
+        filters.where_clause(),
+        "\n WHERE (cl.profile_number = ANY($1))\n"
+    );
+}
+
+#[test]
+/// Changelog filters parse from raw query pairs, every malformed value is reported at once.
+fn test_changelog_params_from_query_map() {
+    use crate::models::models::{
+        ChangelogQueryParams, ProofLevel, ValidationError, ValidationErrors,
+    };
+    use std::collections::HashMap;
+
+    let query = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+    let params = ChangelogQueryParams::from_query_map(&query(&[
+        ("limit", "50"),
+        ("chamber", "47763"),
+        ("sp", "true"),
+        ("coop", "false"),
+        ("first", "157804"),
+        ("proof_level", "demo_only"),
+        ("unknown", "ignored"),
+    ]))
+    .unwrap();
+    assert_eq!(params.limit, Some(50));
+    assert_eq!(params.chamber, Some("47763".to_string()));
+    assert_eq!((params.sp, params.coop), (Some(true), Some(false)));
+    assert_eq!(params.first, Some(157804));
+    assert_eq!(params.proof_level, Some(ProofLevel::DemoOnly));
+    assert!(params.wr_gain.is_none() && params.nick_name.is_none());
+    let e = ChangelogQueryParams::from_query_map(&query(&[
+        ("limit", "-1"),
+        ("yt", "yes"),
+        ("proof_level", "video"),
+    ]))
+    .unwrap_err();
+    assert_eq!(
+        e.downcast_ref::<ValidationErrors>().unwrap().0,
+        vec![
+            ValidationError::InvalidParameter("limit".to_string(), "-1".to_string()),
+            ValidationError::InvalidParameter("yt".to_string(), "yes".to_string()),
+            ValidationError::InvalidParameter("proof_level".to_string(), "video".to_string()),
+        ]
+    );
+}
+
+#[test]
+/// LIKE wildcards and the escape character in user input should be matched literally.
+fn test_escape_like() {
+    use crate::tools::helpers::escape_like;
+
+    assert_eq!(escape_like("segmented?"), "segmented?");
+    assert_eq!(escape_like("100%"), "100\\%");
+    assert_eq!(escape_like("no_sla"), "no\\_sla");
+    assert_eq!(escape_like("a\\b"), "a\\\\b");
+}
+
+#[test]
+/// SAR versions compare component-wise as numbers, with pre-releases before their release.
+fn test_compare_sar_versions() {
+    use crate::tools::helpers::compare_sar_versions;
+    use std::cmp::Ordering;
+
+    assert_eq!(
+        compare_sar_versions("1.12.10", "1.12.9"),
+        Some(Ordering::Greater)
+    );
+    assert_eq!(
+        compare_sar_versions("1.12", "1.12.0"),
+        Some(Ordering::Equal)
+    );
+    assert_eq!(
+        compare_sar_versions("v1.12.7", "1.12.7"),
+        Some(Ordering::Equal)
+    );
+    assert_eq!(
+        compare_sar_versions("1.12.8-pre3", "1.12.8"),
+        Some(Ordering::Less)
+    );
+    assert_eq!(
+        compare_sar_versions("1.12.8-pre10", "1.12.8-pre9"),
+        Some(Ordering::Greater)
+    );
+    assert_eq!(
+        compare_sar_versions("1.12.8-pre3", "1.12.7"),
+        Some(Ordering::Greater)
+    );
+    assert_eq!(
+        compare_sar_versions("12.7.2-pre", "1.12.8"),
+        Some(Ordering::Greater)
+    );
+    assert_eq!(compare_sar_versions("unknown", "1.12.8"), None);
+}
+
+#[test]
+/// Controller errors should map to a status by the marker they carry, without leaking internal messages.
+fn test_boards_error_response() {
+    use crate::models::models::{ValidationError, ValidationErrors};
+    use crate::tools::backblaze::StorageUnavailable;
+    use crate::tools::errors::BoardsError;
+    use actix_web::{http::StatusCode, ResponseError};
+
+    let validation = BoardsError::from(anyhow::Error::from(ValidationErrors(vec![
+        ValidationError::NotAnImprovement,
+    ])));
+    assert_eq!(validation.status_code(), StatusCode::BAD_REQUEST);
+    assert_eq!(validation.body().error, "validation");
+    assert_eq!(
+        validation.body().detail,
+        "The user already has the same, or a better score"
+    );
+    // Markers are still found under added context.
+    let unavailable =
+        BoardsError::from(anyhow::Error::new(StorageUnavailable).context("timed out"));
+    assert_eq!(unavailable.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(unavailable.body().error, "unavailable");
+    let internal = BoardsError::from(anyhow::anyhow!(
+        "relation \"p2boards.secret\" does not exist"
+    ));
+    assert_eq!(internal.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(!internal.body().detail.contains("secret"));
+    // Missing rows are a 404, not an internal error.
+    let not_found = BoardsError::from(
+        anyhow::Error::new(sqlx::Error::RowNotFound).context("Error finding map"),
+    );
+    assert_eq!(not_found.status_code(), StatusCode::NOT_FOUND);
+    assert_eq!(not_found.body().error, "not_found");
+    let conflict = BoardsError::Conflict("Map 47763 is already featured".to_string());
+    assert_eq!(conflict.status_code(), StatusCode::CONFLICT);
+    assert_eq!(conflict.body().detail, "Map 47763 is already featured");
+}
@@ -1 +1,3 @@
-pub mod db_tests;
\ No newline at end of file
+pub mod db_tests;
+#[cfg(test)]
+pub mod helpers_tests;
@@ -0,0 +1,294 @@
+use crate::tools::config::BackBlazeConfig;
+use anyhow::{bail, Result};
+use raze::api::*;
+use raze::utils::*;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncRead;
+use tokio::sync::Mutex;
+
+/// BackBlaze auth tokens are valid for 24 hours, we refresh an hour early so we never use a token as it expires.
+const AUTH_LIFETIME: Duration = Duration::from_secs(23 * 60 * 60);
+/// After BackBlaze fails to authorize or can't be reached, requests fail fast for this long before we try again.
+const UNAVAILABLE_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Returned when BackBlaze can't be reached, so demo endpoints can respond with a 503 rather than a generic error.
+///
+/// Errors from [BackBlazeClient] are `anyhow` errors, check for this with `downcast_ref::<StorageUnavailable>()`.
+#[derive(Debug)]
+pub struct StorageUnavailable;
+
+impl fmt::Display for StorageUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Demo storage is currently unavailable")
+    }
+}
+
+impl std::error::Error for StorageUnavailable {}
+
+/// Returned when BackBlaze rejects a request for exceeding its rate limit, the request can be retried after a short wait.
+///
+/// Unlike [StorageUnavailable], this doesn't start the backoff period for other requests.
+#[derive(Debug)]
+pub struct RateLimited;
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Demo storage is rate limiting requests")
+    }
+}
+
+impl std::error::Error for RateLimited {}
+
+/// A BackBlaze client that caches the account authorization, shared across all demo uploads/deletes.
+///
+/// Authorization happens lazily on first use, and again once the token has expired or is rejected by BackBlaze.
+///
+/// The client also tracks BackBlaze's health, if authorizing fails or BackBlaze can't be reached,
+/// every request fails with [StorageUnavailable] until [UNAVAILABLE_BACKOFF] has passed.
+#[derive(Clone)]
+pub struct BackBlazeClient {
+    client: reqwest::Client,
+    config: BackBlazeConfig,
+    auth: Arc<Mutex<Option<(B2Auth, Instant)>>>,
+    unavailable_until: Arc<Mutex<Option<Instant>>>,
+}
+
+impl BackBlazeClient {
+    pub fn new(config: BackBlazeConfig) -> Result<Self> {
+        Ok(BackBlazeClient {
+            client: reqwest::ClientBuilder::new().build()?,
+            config,
+            auth: Arc::new(Mutex::new(None)),
+            unavailable_until: Arc::new(Mutex::new(None)),
+        })
+    }
+    /// `false` while BackBlaze is in its backoff period after a failure.
+    pub async fn is_available(&self) -> bool {
+        match *self.unavailable_until.lock().await {
+            Some(until) => Instant::now() >= until,
+            None => true,
+        }
+    }
+    /// Starts the backoff period, returns the [StorageUnavailable] error with the reason as context.
+    async fn mark_unavailable(&self, reason: String) -> anyhow::Error {
+        *self.unavailable_until.lock().await = Some(Instant::now() + UNAVAILABLE_BACKOFF);
+        eprintln!("BackBlaze marked as unavailable -> {}", reason);
+        anyhow::Error::new(StorageUnavailable).context(reason)
+    }
+    async fn mark_available(&self) {
+        *self.unavailable_until.lock().await = None;
+    }
+    /// Returns the cached authorization, re-authorizing if there is none or it has expired.
+    pub async fn auth(&self) -> Result<B2Auth> {
+        if !self.is_available().await {
+            return Err(StorageUnavailable.into());
+        }
+        // Hold the lock while authorizing so concurrent callers don't all re-authorize at once.
+        let mut cached = self.auth.lock().await;
+        if let Some((auth, authorized_at)) = cached.as_ref() {
+            if authorized_at.elapsed() < AUTH_LIFETIME {
+                return Ok(auth.clone());
+            }
+        }
+        let auth = match b2_authorize_account(
+            &self.client,
+            format!("{}:{}", self.config.keyid, self.config.key),
+        )
+        .await
+        {
+            Ok(auth) => auth,
+            Err(e) => {
+                return Err(self
+                    .mark_unavailable(format!("Failed to authorize with BackBlaze -> {:?}", e))
+                    .await)
+            }
+        };
+        self.mark_available().await;
+        *cached = Some((auth.clone(), Instant::now()));
+        Ok(auth)
+    }
+    /// Drops the cached authorization, the next call will re-authorize.
+    pub async fn invalidate(&self) {
+        *self.auth.lock().await = None;
+    }
+    /// Runs a request with the cached authorization, if BackBlaze rejects the token we re-authorize and retry once.
+    async fn with_auth<T, F, Fut>(&self, request: F) -> Result<T>
+    where
+        F: Fn(reqwest::Client, B2Auth) -> Fut,
+        Fut: Future<Output = Result<T, raze::Error>>,
+    {
+        let auth = self.auth().await?;
+        let res = match request(self.client.clone(), auth).await {
+            Err(e) if is_auth_error(&e) => {
+                self.invalidate().await;
+                let auth = self.auth().await?;
+                request(self.client.clone(), auth).await
+            }
+            res => res,
+        };
+        match res {
+            Ok(res) => Ok(res),
+            Err(e) if is_unavailable_error(&e) => Err(self
+                .mark_unavailable(format!("BackBlaze request failed -> {:?}", e))
+                .await),
+            Err(e) if is_rate_limited_error(&e) => Err(anyhow::Error::new(RateLimited)
+                .context(format!("BackBlaze request failed -> {:?}", e))),
+            Err(e) => bail!("BackBlaze request failed -> {:?}", e),
+        }
+    }
+    /// Returns the file information (name, size, etc) for a given file_id.
+    pub async fn get_file_info(&self, file_id: &str) -> Result<B2FileInfo> {
+        self.with_auth(
+            |client, auth| async move { b2_get_file_info(&client, &auth, file_id).await },
+        )
+        .await
+    }
+    /// Returns the file information for a given file_id, `None` if BackBlaze no longer has the file.
+    ///
+    /// Any other failure is an error, so a missing file can be told apart from BackBlaze being unreachable.
+    pub async fn find_file_info(&self, file_id: &str) -> Result<Option<B2FileInfo>> {
+        self.with_auth(|client, auth| async move {
+            match b2_get_file_info(&client, &auth, file_id).await {
+                Ok(info) => Ok(Some(info)),
+                Err(e) if is_not_found_error(&e) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+        .await
+    }
+    /// `false` if BackBlaze no longer has a file with the given file_id.
+    pub async fn file_exists(&self, file_id: &str) -> Result<bool> {
+        Ok(self.find_file_info(file_id).await?.is_some())
+    }
+    /// Deletes a specific version of a file from the bucket.
+    pub async fn delete_file_version(&self, file_name: &str, file_id: &str) -> Result<B2FileInfo> {
+        self.with_auth(|client, auth| async move {
+            b2_delete_file_version(&client, &auth, file_name, file_id).await
+        })
+        .await
+    }
+    /// Starts a download of a file by its file_id, the body of the response can be streamed back to the client.
+    pub async fn download(&self, file_id: &str) -> Result<reqwest::Response> {
+        let mut retried = false;
+        loop {
+            let auth = self.auth().await?;
+            let resp = match self
+                .client
+                .get(format!(
+                    "{}/b2api/v2/b2_download_file_by_id",
+                    auth.download_url
+                ))
+                .query(&[("fileId", file_id)])
+                .header("Authorization", auth.authorization_token)
+                .send()
+                .await
+            {
+                Ok(resp) => resp,
+                Err(e) => {
+                    return Err(self
+                        .mark_unavailable(format!("Failed to reach BackBlaze -> {}", e))
+                        .await)
+                }
+            };
+            if resp.status() == reqwest::StatusCode::UNAUTHORIZED && !retried {
+                self.invalidate().await;
+                retried = true;
+                continue;
+            }
+            if resp.status().is_server_error() {
+                return Err(self
+                    .mark_unavailable(format!("BackBlaze responded with {}", resp.status()))
+                    .await);
+            }
+            return Ok(resp.error_for_status()?);
+        }
+    }
+    /// Streams the contents of `reader` to the bucket in chunks. `size` must be the exact length of the data.
+    ///
+    /// The stream can't be replayed, so an upload rejected for authorization isn't retried,
+    /// but the cached authorization is dropped so the next upload re-authorizes.
+    pub async fn upload_stream<R>(
+        &self,
+        reader: R,
+        size: u64,
+        file_name: &str,
+    ) -> Result<B2FileInfo>
+    where
+        R: AsyncRead + Send + Sync + Unpin + 'static,
+    {
+        let bucket = self.config.bucket.clone();
+        let upload_auth = self
+            .with_auth(|client, auth| {
+                let bucket = bucket.clone();
+                async move { b2_get_upload_url(&client, &auth, bucket).await }
+            })
+            .await?;
+        let modf = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+        let param = FileParameters {
+            file_path: file_name,
+            file_size: size,
+            content_type: None,
+            content_sha1: Sha1Variant::HexAtEnd,
+            last_modified_millis: modf,
+        };
+
+        let stream = reader_to_stream(reader);
+        let stream = BytesStreamHashAtEnd::wrap(stream);
+        let stream = BytesStreamThrottled::wrap(stream, 500000000);
+
+        let body = reqwest::Body::wrap_stream(stream);
+        match b2_upload_file(&self.client, &upload_auth, body, param).await {
+            Ok(file_info) => Ok(file_info),
+            Err(e) => {
+                if is_auth_error(&e) {
+                    self.invalidate().await;
+                }
+                if is_unavailable_error(&e) {
+                    return Err(self
+                        .mark_unavailable(format!("Failed to upload file -> {:?}", e))
+                        .await);
+                }
+                bail!("Failed to upload file -> {:?}", e)
+            }
+        }
+    }
+}
+
+/// BackBlaze responds with a 401 and an `*_auth_token` code when a token is expired or invalid.
+fn is_auth_error(e: &raze::Error) -> bool {
+    matches!(
+        b2_error(e),
+        Some((401, _)) | Some((_, "bad_auth_token" | "expired_auth_token"))
+    )
+}
+
+/// Connection failures, and 503 `service_unavailable` responses from BackBlaze, mean the service itself is unavailable.
+fn is_unavailable_error(e: &raze::Error) -> bool {
+    matches!(e, raze::Error::ReqwestError(_))
+        || matches!(
+            b2_error(e),
+            Some((503, _)) | Some((_, "service_unavailable"))
+        )
+}
+
+/// The HTTP status and error code of an error response from BackBlaze, `None` if the request failed before we got one.
+fn b2_error(e: &raze::Error) -> Option<(u16, &str)> {
+    match e {
+        raze::Error::B2Error(err) => Some((err.status, err.code.as_str())),
+        _ => None,
+    }
+}
+
+/// BackBlaze responds with a 404 when the file_id doesn't resolve to a file in the bucket,
+/// or a `file_not_present` code when deleting a file version that's already gone.
+fn is_not_found_error(e: &raze::Error) -> bool {
+    matches!(b2_error(e), Some((404, _)) | Some((_, "file_not_present")))
+}
+
+/// BackBlaze responds with a 429 and a `too_many_requests` code when we exceed its rate limit.
+fn is_rate_limited_error(e: &raze::Error) -> bool {
+    matches!(b2_error(e), Some((429, _)) | Some((_, "too_many_requests")))
+}
@@ -1,23 +1,77 @@
-use crate::models::models::Points;
-use anyhow::Error;
+use crate::models::models::{MapLeaderboardEntry, Maps, Points, ProfileData, Users};
+use anyhow::{Error, Result};
 use serde::Serialize;
+use sqlx::PgPool;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+/// How long a map id without a default category is remembered, so repeated lookups for it don't each hit the database.
+const DEFAULT_CAT_MISS_TTL: Duration = Duration::from_secs(60);
+
+/// Leaderboards keyed by (map_id, cat_id), alongside when they were computed.
+type CachedLeaderboards = HashMap<(String, i32), (Instant, Vec<MapLeaderboardEntry>)>;
+
+/// Computed map leaderboards keyed by (map_id, cat_id), entries older than the TTL are treated as a miss.
+#[derive(Debug, Clone)]
+pub struct LeaderboardCache {
+    ttl: Duration,
+    entries: Arc<RwLock<CachedLeaderboards>>,
+}
+impl LeaderboardCache {
+    pub fn new(ttl: Duration) -> Self {
+        LeaderboardCache {
+            ttl,
+            entries: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+    /// Returns the cached leaderboard, `None` if it was never computed or has expired.
+    pub async fn get(&self, map_id: &str, cat_id: i32) -> Option<Vec<MapLeaderboardEntry>> {
+        self.entries
+            .read()
+            .await
+            .get(&(map_id.to_string(), cat_id))
+            .filter(|(cached_at, _)| cached_at.elapsed() < self.ttl)
+            .map(|(_, entries)| entries.clone())
+    }
+    pub async fn insert(&self, map_id: &str, cat_id: i32, entries: Vec<MapLeaderboardEntry>) {
+        self.entries
+            .write()
+            .await
+            .insert((map_id.to_string(), cat_id), (Instant::now(), entries));
+    }
+    /// Drops every cached category for a map.
+    pub async fn invalidate(&self, map_id: &str) {
+        self.entries
+            .write()
+            .await
+            .retain(|(cached, _), _| cached != map_id);
+    }
+    pub async fn clear(&self) {
+        self.entries.write().await.clear();
+    }
+}
 
 /// Holds a thread-sharable hashmap that we use to control cache invalidation.
 #[derive(Debug, Clone)]
 pub struct CacheState {
     pub current_state: Arc<Mutex<HashMap<&'static str, bool>>>,
-    pub default_cat_ids: HashMap<String, i32>,
+    pub default_cat_ids: Arc<Mutex<HashMap<String, i32>>>,
+    /// Map ids that had no default category when last looked up, and when that was.
+    pub default_cat_misses: Arc<Mutex<HashMap<String, Instant>>>,
     pub points: Arc<Mutex<HashMap<&'static str, HashMap<String, Points>>>>,
+    pub profiles: Arc<Mutex<HashMap<(String, bool), ProfileData>>>,
+    pub leaderboards: LeaderboardCache,
 }
 impl CacheState {
     /// Constructs a new hashmap for the cache state with static str's to represent all the values we want to cache
-    pub fn new(default_cat_ids: HashMap<String, i32>) -> Self {
+    ///
+    /// Map leaderboards are kept for `leaderboard_ttl` before being recomputed.
+    pub fn new(default_cat_ids: HashMap<String, i32>, leaderboard_ttl: Duration) -> Self {
         let mut hm = HashMap::new();
         let mut points = HashMap::new();
         let cached_endpoints: Vec<&'static str> = vec![
@@ -52,9 +106,117 @@ impl CacheState {
         // TODO: Dump the cache for in-memory, then load the most recent cache on startup
         CacheState {
             current_state: Arc::new(Mutex::new(hm)),
-            default_cat_ids,
+            default_cat_ids: Arc::new(Mutex::new(default_cat_ids)),
+            default_cat_misses: Arc::new(Mutex::new(HashMap::new())),
             points: Arc::new(Mutex::new(points)),
+            profiles: Arc::new(Mutex::new(HashMap::new())),
+            leaderboards: LeaderboardCache::new(leaderboard_ttl),
+        }
+    }
+    /// Marks the preview caches as stale, used whenever a change can affect ranks on a map.
+    ///
+    /// Cached profiles and leaderboards are dropped as well, as admin changes can affect which runs are valid for any number of users.
+    pub async fn invalidate_previews(&self) {
+        let mut state_data = self.current_state.lock().await;
+        for preview in ["sp_previews", "coop_previews"] {
+            if let Some(is_cached) = state_data.get_mut(preview) {
+                *is_cached = false;
+            }
+        }
+        self.profiles.lock().await.clear();
+        self.leaderboards.clear().await;
+    }
+    /// Returns the leaderboard for a map & category, only running [Maps::get_map_leaderboard] on a miss or once the TTL expires.
+    pub async fn get_map_leaderboard(
+        &self,
+        pool: &PgPool,
+        map_id: &str,
+        cat_id: i32,
+    ) -> Result<Vec<MapLeaderboardEntry>> {
+        if let Some(entries) = self.leaderboards.get(map_id, cat_id).await {
+            return Ok(entries);
+        }
+        let entries = Maps::get_map_leaderboard(pool, map_id.to_string(), cat_id).await?;
+        self.leaderboards
+            .insert(map_id, cat_id, entries.clone())
+            .await;
+        Ok(entries)
+    }
+    /// Drops the cached leaderboards for a map, used after a new or edited run so fresh records show immediately.
+    pub async fn invalidate_leaderboard(&self, map_id: &str) {
+        self.leaderboards.invalidate(map_id).await;
+    }
+    /// Returns the profile stats for a user, only running the expensive [Users::get_profile] queries on a cache miss.
+    ///
+    /// The SP maps' default categories come from [CacheState::get_default_cats].
+    pub async fn get_profile(
+        &self,
+        pool: &PgPool,
+        profile_number: &String,
+        pb_only: bool,
+    ) -> Result<Option<ProfileData>> {
+        let key = (profile_number.clone(), pb_only);
+        if let Some(profile) = self.profiles.lock().await.get(&key) {
+            return Ok(Some(profile.clone()));
+        }
+        let sp_maps = Maps::get_steam_ids(pool, false).await?;
+        let sp_default_cats = self.get_default_cats(pool, &sp_maps).await?;
+        let res = Users::get_profile(pool, profile_number, pb_only, &sp_default_cats).await?;
+        if let Some(profile) = &res {
+            self.profiles.lock().await.insert(key, profile.clone());
+        }
+        Ok(res)
+    }
+    /// Drops the cached profile stats for a user, used whenever they have a new or edited score.
+    pub async fn invalidate_profile(&self, profile_number: &str) {
+        self.profiles
+            .lock()
+            .await
+            .retain(|(cached, _), _| cached != profile_number);
+    }
+    /// Returns the default category for a map from the cache, see [CacheState::get_default_cats].
+    pub async fn get_default_cat(&self, pool: &PgPool, map_id: &str) -> Result<Option<i32>> {
+        let map_ids = [map_id.to_string()];
+        Ok(self.get_default_cats(pool, &map_ids).await?.remove(map_id))
+    }
+    /// Returns the default categories for a batch of maps, looking up only the map ids missing from the cache (added after startup).
+    ///
+    /// Map ids without a default category are left out of the result, and aren't looked up again for [DEFAULT_CAT_MISS_TTL].
+    pub async fn get_default_cats(
+        &self,
+        pool: &PgPool,
+        map_ids: &[String],
+    ) -> Result<HashMap<String, i32>> {
+        let mut found = HashMap::new();
+        let mut missing = Vec::new();
+        {
+            let default_cat_ids = self.default_cat_ids.lock().await;
+            let mut misses = self.default_cat_misses.lock().await;
+            misses.retain(|_, missed_at| missed_at.elapsed() < DEFAULT_CAT_MISS_TTL);
+            for id in map_ids.iter() {
+                match default_cat_ids.get(id) {
+                    Some(cat_id) => {
+                        found.insert(id.clone(), *cat_id);
+                    }
+                    None if !misses.contains_key(id) => missing.push(id.clone()),
+                    None => (),
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(found);
+        }
+        // Queried without holding the locks, so other lookups aren't blocked behind the database.
+        let fetched = Maps::get_default_cats(pool, &missing).await?;
+        self.default_cat_ids.lock().await.extend(fetched.clone());
+        let mut misses = self.default_cat_misses.lock().await;
+        for id in missing.into_iter() {
+            if !fetched.contains_key(&id) {
+                misses.insert(id, Instant::now());
+            }
         }
+        found.extend(fetched);
+        Ok(found)
     }
 }
 
@@ -21,12 +21,103 @@ pub struct BackBlazeConfig {
     pub bucket: String,
 }
 
+/// Defaults used when displaying users.
+#[derive(Deserialize, Debug, Clone)]
+pub struct UsersConfig {
+    pub default_avatar: String,
+}
+
+/// Webhooks posted to when the `notifications` feature is enabled, an empty/missing url disables the webhook.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct NotificationsConfig {
+    pub new_user_webhook: Option<String>,
+    pub daily_summary_webhook: Option<String>,
+}
+impl NotificationsConfig {
+    /// The new user webhook, if one is configured.
+    pub fn new_user_webhook(&self) -> Option<&str> {
+        self.new_user_webhook
+            .as_deref()
+            .filter(|url| !url.trim().is_empty())
+    }
+    /// The daily recap webhook, if one is configured.
+    #[allow(dead_code)]
+    pub fn daily_summary_webhook(&self) -> Option<&str> {
+        self.daily_summary_webhook
+            .as_deref()
+            .filter(|url| !url.trim().is_empty())
+    }
+}
+
+/// Limits on uploaded demo files.
+#[derive(Deserialize, Debug, Clone)]
+pub struct DemoConfig {
+    /// Largest demo accepted, in bytes. Uploads over this are rejected before reaching storage.
+    pub max_upload_bytes: u64,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+}
+impl Default for DemoConfig {
+    fn default() -> Self {
+        DemoConfig {
+            max_upload_bytes: 100 * 1024 * 1024,
+            retention: RetentionConfig::default(),
+        }
+    }
+}
+
+/// How long demos are kept in storage, enforced by [crate::tools::maintenance::enforce_retention].
+///
+/// Records, and runs that needed a demo under the proof standards, always keep theirs.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RetentionConfig {
+    /// Demos for runs currently ranked within this many places on their board are kept indefinitely.
+    /// Treated as at least 1, so the current record always keeps its demo.
+    pub keep_top_n: i32,
+    /// Demos uploaded more than this many days ago can be removed.
+    pub expire_after_days: Option<i32>,
+    /// Demos uploaded more than this many calendar months ago can be removed.
+    /// When both periods are set the shorter one applies, when neither is set every demo is kept.
+    pub expire_after_months: Option<i32>,
+}
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            keep_top_n: 10,
+            expire_after_days: None,
+            expire_after_months: None,
+        }
+    }
+}
+
+/// In-memory cache settings.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CacheConfig {
+    /// Seconds a computed map leaderboard is served from memory before being recomputed.
+    pub leaderboard_ttl_secs: u64,
+}
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            leaderboard_ttl_secs: 60,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     pub database_url: String,
     pub server: ServerConfig,
     pub proof: ProofConfig,
     pub backblaze: BackBlazeConfig,
+    pub users: UsersConfig,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    #[serde(default)]
+    pub demo: DemoConfig,
 }
 // Extracts the environment variables from .env
 impl Config {
@@ -0,0 +1,123 @@
+use crate::models::models::ValidationErrors;
+use crate::tools::backblaze::{RateLimited, StorageUnavailable};
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+
+/// Postgres error code for a unique constraint violation.
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// The JSON body of a [BoardsError] response.
+///
+/// `error` is a short, stable name for the kind of failure (`not_found`, `validation`, `conflict`, `unavailable` or `internal`),
+/// `detail` is a message that is safe to show to the user.
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct ErrorBody {
+    pub error: &'static str,
+    pub detail: String,
+}
+
+/// A handler error, so handlers can return `Result<HttpResponse, BoardsError>` and use `?` on controller calls.
+///
+/// Each variant maps to a status:
+/// - [BoardsError::NotFound] -> `404`
+/// - [BoardsError::Validation] -> `400`
+/// - [BoardsError::Conflict] -> `409`
+/// - [BoardsError::Unavailable] -> `503`
+/// - [BoardsError::Internal] -> `500`, the underlying message is only logged, never sent to the client.
+///
+/// Controllers return `anyhow` errors, converting one picks the variant from the error it carries, see the [From] impl.
+#[derive(Debug)]
+pub enum BoardsError {
+    NotFound(String),
+    Validation(ValidationErrors),
+    Conflict(String),
+    Unavailable(String),
+    Internal(anyhow::Error),
+}
+
+impl BoardsError {
+    /// The JSON body sent to the client for this error.
+    pub fn body(&self) -> ErrorBody {
+        match self {
+            BoardsError::NotFound(detail) => ErrorBody {
+                error: "not_found",
+                detail: detail.clone(),
+            },
+            BoardsError::Validation(errors) => ErrorBody {
+                error: "validation",
+                detail: errors.to_string(),
+            },
+            BoardsError::Conflict(detail) => ErrorBody {
+                error: "conflict",
+                detail: detail.clone(),
+            },
+            BoardsError::Unavailable(detail) => ErrorBody {
+                error: "unavailable",
+                detail: detail.clone(),
+            },
+            BoardsError::Internal(_) => ErrorBody {
+                error: "internal",
+                detail: "An unexpected error occurred.".to_string(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for BoardsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardsError::Validation(errors) => write!(f, "{}", errors),
+            BoardsError::Internal(e) => write!(f, "{:#}", e),
+            BoardsError::NotFound(detail)
+            | BoardsError::Conflict(detail)
+            | BoardsError::Unavailable(detail) => write!(f, "{}", detail),
+        }
+    }
+}
+
+/// Picks the variant from the marker type the error carries, even under added context:
+/// - [ValidationErrors] -> [BoardsError::Validation]
+/// - [StorageUnavailable] & [RateLimited] -> [BoardsError::Unavailable]
+/// - [sqlx::Error::RowNotFound] -> [BoardsError::NotFound]
+/// - A unique constraint violation -> [BoardsError::Conflict]
+/// - Anything else -> [BoardsError::Internal]
+impl From<anyhow::Error> for BoardsError {
+    fn from(e: anyhow::Error) -> Self {
+        if let Some(errors) = e.downcast_ref::<ValidationErrors>() {
+            return BoardsError::Validation(errors.clone());
+        }
+        if let Some(unavailable) = e.downcast_ref::<StorageUnavailable>() {
+            return BoardsError::Unavailable(unavailable.to_string());
+        }
+        if let Some(limited) = e.downcast_ref::<RateLimited>() {
+            return BoardsError::Unavailable(limited.to_string());
+        }
+        match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => {
+                BoardsError::NotFound("The requested resource does not exist.".to_string())
+            }
+            Some(sqlx::Error::Database(db)) if db.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+                BoardsError::Conflict("The resource already exists.".to_string())
+            }
+            _ => BoardsError::Internal(e),
+        }
+    }
+}
+
+impl ResponseError for BoardsError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            BoardsError::NotFound(_) => StatusCode::NOT_FOUND,
+            BoardsError::Validation(_) => StatusCode::BAD_REQUEST,
+            BoardsError::Conflict(_) => StatusCode::CONFLICT,
+            BoardsError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            BoardsError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+    fn error_response(&self) -> HttpResponse {
+        eprintln!("Request failed -> {}", self);
+        HttpResponse::build(self.status_code()).json(self.body())
+    }
+}
@@ -1,7 +1,15 @@
-use crate::models::models::{CalcValues, Changelog, CoopMap, CoopRanked, Maps, SpMap, Users};
+use crate::models::models::{
+    CalcValues, Changelog, CoopMap, CoopRanked, Maps, SpMap, Users, ValidationError,
+    ValidationErrors,
+};
 use anyhow::{bail, Result};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use futures::future::BoxFuture;
 use num::pow;
-use sqlx::PgPool;
+use sqlx::postgres::PgArguments;
+use sqlx::query::{Query, QueryAs};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
 /// Calcultes the score using the pre-existing iVerb point formula.
@@ -19,6 +27,217 @@ pub fn score(i: i32) -> f32 {
     }
 }
 
+/// Builds a playback-friendly demo file name, `{map_name}_{player}_{score}.dem`.
+///
+/// Only ASCII letters, digits, `-` and `_` are kept, so the name is valid on any filesystem and can be passed to `playdemo`.
+/// Falls back to the `profile_number` if nothing is left of the player's name.
+pub fn demo_file_name(
+    map_name: &str,
+    player: Option<&str>,
+    profile_number: &str,
+    score: i32,
+) -> String {
+    let clean = |s: &str| -> String {
+        s.chars()
+            .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+            .take(32)
+            .collect()
+    };
+    let player = match player.map(clean) {
+        Some(player) if !player.is_empty() => player,
+        _ => clean(profile_number),
+    };
+    sanitize_filename::sanitize(format!("{}_{}_{}.dem", clean(map_name), player, score))
+}
+
+/// Normalizes a youtube link (or a bare video id) to the `{id}?start={seconds}` format stored in the changelog.
+///
+/// Accepts `youtube.com/watch?v=`, `youtu.be/`, `/embed/` and `/shorts/` links, with an optional `t`/`start` timestamp.
+/// Returns `None` if no valid video id can be found.
+pub fn normalize_youtube_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let (path, query) = match input.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (input, ""),
+    };
+    let params: HashMap<&str, &str> = query
+        .split(['&', '#'])
+        .filter_map(|param| param.split_once('='))
+        .collect();
+    let id = if let Some(v) = params.get("v") {
+        *v
+    } else if let Some((_, id)) = ["youtu.be/", "/embed/", "/shorts/", "/v/"]
+        .iter()
+        .find_map(|prefix| path.split_once(prefix))
+    {
+        id.trim_end_matches('/')
+    } else if !path.contains('/') && !path.contains('.') {
+        path
+    } else {
+        return None;
+    };
+    if id.len() != 11
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    let start = params
+        .get("start")
+        .or_else(|| params.get("t"))
+        .and_then(|t| t.trim_end_matches('s').parse::<u32>().ok())
+        .unwrap_or(0);
+    Some(format!("{}?start={}", id, start))
+}
+
+/// Formats accepted for submission timestamps without a timezone, these are assumed to be UTC.
+const NAIVE_TIMESTAMP_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+/// Formats accepted for submission timestamps with a timezone offset, these are converted to UTC.
+const OFFSET_TIMESTAMP_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f%#z", "%Y-%m-%d %H:%M:%S%.f %#z"];
+
+/// Parses a submission timestamp into a UTC [NaiveDateTime].
+///
+/// Accepts RFC3339, `%Y-%m-%d %H:%M:%S` (with or without fractional seconds, a `T` separator or a timezone offset)
+/// and unix epoch seconds. Empty timestamps are `Ok(None)`, anything else that can't be parsed is an error.
+pub fn parse_timestamp(input: &str) -> Result<Option<NaiveDateTime>> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    if input.chars().all(|c| c.is_ascii_digit()) {
+        if let Some(timestamp) = input
+            .parse::<i64>()
+            .ok()
+            .and_then(|secs| Utc.timestamp_opt(secs, 0).single())
+        {
+            return Ok(Some(timestamp.naive_utc()));
+        }
+    }
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(input) {
+        return Ok(Some(timestamp.naive_utc()));
+    }
+    if let Some(timestamp) = OFFSET_TIMESTAMP_FORMATS
+        .iter()
+        .find_map(|format| DateTime::parse_from_str(input, format).ok())
+    {
+        return Ok(Some(timestamp.naive_utc()));
+    }
+    match NAIVE_TIMESTAMP_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+    {
+        Some(timestamp) => Ok(Some(timestamp)),
+        None => bail!("Unrecognized timestamp format {}", input),
+    }
+}
+
+/// Checks that a profile number is a SteamID64 for an individual account, 17 digits starting with `7656119`.
+///
+/// On failure, the error is a [ValidationErrors] holding [ValidationError::InvalidProfileNumber].
+pub fn validate_steam_id64(s: &str) -> Result<()> {
+    if s.len() == 17 && s.starts_with("7656119") && s.chars().all(|c| c.is_ascii_digit()) {
+        Ok(())
+    } else {
+        Err(ValidationErrors(vec![ValidationError::InvalidProfileNumber(s.to_string())]).into())
+    }
+}
+
+/// Escapes `\`, `%` and `_` in user input, so it's matched literally inside a `LIKE` pattern.
+pub fn escape_like(input: &str) -> String {
+    input
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Compares two SAR version strings, e.g. `1.12.7` or `1.12.8-pre3`, `None` if either isn't a recognised version.
+///
+/// Numeric components are compared as numbers (`1.12.10` is newer than `1.12.9`, missing components count as 0),
+/// and a pre-release is older than the release it precedes. Pre-release tags are compared by their embedded numbers.
+pub fn compare_sar_versions(a: &str, b: &str) -> Option<Ordering> {
+    fn parse(version: &str) -> Option<(Vec<u64>, Option<&str>)> {
+        let version = version.trim().trim_start_matches('v');
+        let version = version.split('+').next().unwrap_or(version);
+        let (core, pre) = match version.split_once('-') {
+            Some((core, pre)) => (core, Some(pre)),
+            None => (version, None),
+        };
+        let parts = core
+            .split('.')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        Some((parts, pre))
+    }
+    // Splits a pre-release tag into runs of digits and non-digits, so `pre10` sorts after `pre9`.
+    fn chunks(tag: &str) -> Vec<String> {
+        let mut chunks: Vec<String> = vec![];
+        for c in tag.chars() {
+            match chunks.last_mut() {
+                Some(chunk)
+                    if chunk.starts_with(|d: char| d.is_ascii_digit()) == c.is_ascii_digit() =>
+                {
+                    chunk.push(c)
+                }
+                _ => chunks.push(c.to_string()),
+            }
+        }
+        chunks
+    }
+    let (a_parts, a_pre) = parse(a)?;
+    let (b_parts, b_pre) = parse(b)?;
+    for i in 0..a_parts.len().max(b_parts.len()) {
+        let ord = a_parts
+            .get(i)
+            .unwrap_or(&0)
+            .cmp(b_parts.get(i).unwrap_or(&0));
+        if ord != Ordering::Equal {
+            return Some(ord);
+        }
+    }
+    Some(match (a_pre, b_pre) {
+        (None, None) => Ordering::Equal,
+        (None, Some(_)) => Ordering::Greater,
+        (Some(_), None) => Ordering::Less,
+        (Some(a_pre), Some(b_pre)) => {
+            let (a_chunks, b_chunks) = (chunks(a_pre), chunks(b_pre));
+            a_chunks
+                .iter()
+                .zip(b_chunks.iter())
+                .map(|(a, b)| match (a.parse::<u64>(), b.parse::<u64>()) {
+                    (Ok(a_num), Ok(b_num)) => a_num.cmp(&b_num),
+                    _ => a.cmp(b),
+                })
+                .find(|ord| *ord != Ordering::Equal)
+                .unwrap_or_else(|| a_chunks.len().cmp(&b_chunks.len()))
+        }
+    })
+}
+
+/// Runs `operation` in a single transaction, committing if it succeeds and rolling everything back if it returns an error.
+///
+/// Compose the `*_tx` variants of the controllers inside `operation` so multi-step changes are all or nothing, e.g.
+/// `with_transaction(pool, |tx| Box::pin(async move { Changelog::insert_changelog_tx(tx, cl).await })).await`.
+#[allow(dead_code)]
+pub async fn with_transaction<T, F>(pool: &PgPool, operation: F) -> Result<T>
+where
+    F: for<'c> FnOnce(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T>>,
+{
+    let mut tx = pool.begin().await?;
+    match operation(&mut tx).await {
+        Ok(res) => {
+            tx.commit().await?;
+            Ok(res)
+        }
+        Err(e) => {
+            if let Err(rollback) = tx.rollback().await {
+                eprintln!("Error rolling back transaction -> {}", rollback);
+            }
+            Err(e)
+        }
+    }
+}
+
 /// Grabs the default category IDs for all maps as a HashMap.
 pub async fn get_default_cat_ids(pool: &PgPool) -> HashMap<String, i32> {
     Maps::get_all_default_cats(pool).await.unwrap()
@@ -70,6 +289,12 @@ pub async fn filter_coop_entries(coop_entries: Vec<CoopMap>, limit: usize) -> Ve
 }
 
 /// Checks if a score is valid, if it is, returns post_rank, pre_rank, score_delta, previous_id
+///
+/// The score is compared against the user's best non-banned run, including runs still pending verification,
+/// so a slower run can't be submitted while a faster one awaits review.
+/// `score_delta` is `score - pb.score`, so improvements are negative, matching the backend importer.
+///
+/// Unknown users and scores that don't beat the current PB are a [ValidationErrors], any other error is from the database.
 pub async fn check_for_valid_score(
     pool: &PgPool,
     profile_number: String,
@@ -79,38 +304,34 @@ pub async fn check_for_valid_score(
     cat_id: i32,
 ) -> Result<CalcValues> {
     let mut values = CalcValues::default();
-    match Users::check_banned(pool, profile_number.clone()).await {
-        Ok(b) => {
-            if b {
-                values.banned = true;
-                return Ok(values);
-            }
-        }
-        Err(e) => {
+    let banned = match Users::check_banned(pool, profile_number.clone()).await {
+        Ok(banned) => banned,
+        Err(e) => match e.downcast_ref::<sqlx::Error>() {
             // Assuming someone is manually/automatically submitting a demo, a user account should be created for them on the boards.
             // TODO: Maybe this changes when AUTH changes?
-            eprintln!("User assumed not found -> {:?}", e);
-            bail!("User does not exist");
-        }
+            Some(sqlx::Error::RowNotFound) => {
+                let errors = vec![ValidationError::UnknownUser(profile_number)];
+                return Err(ValidationErrors(errors).into());
+            }
+            _ => return Err(e),
+        },
+    };
+    if banned {
+        values.banned = true;
+        return Ok(values);
     }
-    let cl = Changelog::get_sp_pb_history(pool, profile_number.clone(), map_id.clone()).await;
-    let cl = match cl {
-        Ok(x) => x,
-        Err(e) => {
-            eprintln!("Error with sp pb history -> {:?}", e);
-            eprintln!("Assume there is not sp_pb_histroy for the player.");
-            return Ok(values);
-        }
+    let pb = Changelog::get_best_entry(pool, profile_number.clone(), map_id.clone(), cat_id, false);
+    let pb = match pb.await? {
+        Some(x) => x,
+        None => return Ok(values),
     };
-    if cl[0].score <= score {
-        bail!("Current score is the same, or better.")
+    if pb.score <= score {
+        return Err(ValidationErrors(vec![ValidationError::NotAnImprovement]).into());
     }
-    values.score_delta = Some(cl[0].score - score);
-    values.previous_id = Some(cl[0].id);
+    values.score_delta = Some(score - pb.score);
+    values.previous_id = Some(pb.id);
     // Assuming there is a PB History, there must be other scores, this should return a valid list of ranked maps.
-    let cl_ranked = SpMap::get_sp_map_page(pool, map_id, limit, cat_id)
-        .await
-        .unwrap();
+    let cl_ranked = SpMap::get_sp_map_page(pool, map_id, limit, cat_id).await?;
     for (i, entry) in cl_ranked.iter().enumerate() {
         if entry.score >= score {
             values.post_rank = Some(i as i32 + 1);
@@ -121,3 +342,145 @@ pub async fn check_for_valid_score(
     }
     Ok(values)
 }
+
+/// A value bound to a placeholder in [QueryFilters].
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    Text(String),
+    TextList(Vec<String>),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+}
+
+impl From<String> for FilterValue {
+    fn from(value: String) -> Self {
+        FilterValue::Text(value)
+    }
+}
+
+impl From<&str> for FilterValue {
+    fn from(value: &str) -> Self {
+        FilterValue::Text(value.to_string())
+    }
+}
+
+impl From<Vec<String>> for FilterValue {
+    fn from(value: Vec<String>) -> Self {
+        FilterValue::TextList(value)
+    }
+}
+
+impl From<i64> for FilterValue {
+    fn from(value: i64) -> Self {
+        FilterValue::Int(value)
+    }
+}
+
+impl From<i32> for FilterValue {
+    fn from(value: i32) -> Self {
+        FilterValue::Int(value as i64)
+    }
+}
+
+impl From<f64> for FilterValue {
+    fn from(value: f64) -> Self {
+        FilterValue::Float(value)
+    }
+}
+
+impl From<bool> for FilterValue {
+    fn from(value: bool) -> Self {
+        FilterValue::Bool(value)
+    }
+}
+
+/// Accumulates the conditions of a `WHERE` clause alongside the values bound to them, so user input never ends up in the SQL itself.
+///
+/// Conditions use `{}` as the placeholder for their bound value. Placeholders are only numbered (`$1`, `$2`, ...) when the clause is rendered,
+/// so builders can be combined with [QueryFilters::append] in any order.
+#[derive(Debug, Clone, Default)]
+pub struct QueryFilters {
+    conditions: Vec<String>,
+    binds: Vec<FilterValue>,
+}
+
+impl QueryFilters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Adds a condition without any bound values.
+    pub fn push(&mut self, condition: impl Into<String>) -> &mut Self {
+        self.conditions.push(condition.into());
+        self
+    }
+    /// Adds a condition with a single `{}` placeholder, bound to `value`.
+    pub fn push_bind(&mut self, condition: &str, value: impl Into<FilterValue>) -> &mut Self {
+        debug_assert_eq!(condition.matches("{}").count(), 1);
+        self.conditions.push(condition.to_string());
+        self.binds.push(value.into());
+        self
+    }
+    /// Moves all of `other`'s conditions (and their values) after the ones already added.
+    pub fn append(&mut self, other: QueryFilters) -> &mut Self {
+        self.conditions.extend(other.conditions);
+        self.binds.extend(other.binds);
+        self
+    }
+    /// Renders the conditions as a `WHERE` clause, each condition is parenthesized and joined with `AND`.
+    ///
+    /// Returns an empty string if there are no conditions.
+    pub fn where_clause(&self) -> String {
+        let mut placeholder = 0;
+        let conditions: Vec<String> = self
+            .conditions
+            .iter()
+            .map(|condition| {
+                let mut parts = condition.split("{}");
+                let mut rendered = parts.next().unwrap_or_default().to_string();
+                for part in parts {
+                    placeholder += 1;
+                    rendered.push_str(&format!("${}{}", placeholder, part));
+                }
+                format!("({})", rendered.trim())
+            })
+            .collect();
+        if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("\n WHERE {}\n", conditions.join("\n AND "))
+        }
+    }
+    /// Binds the values of the conditions to a query built from [QueryFilters::where_clause].
+    pub fn bind_query<'q>(
+        &self,
+        mut query: Query<'q, Postgres, PgArguments>,
+    ) -> Query<'q, Postgres, PgArguments> {
+        for value in self.binds.iter().cloned() {
+            query = match value {
+                FilterValue::Text(value) => query.bind(value),
+                FilterValue::TextList(value) => query.bind(value),
+                FilterValue::Int(value) => query.bind(value),
+                FilterValue::Float(value) => query.bind(value),
+                FilterValue::Bool(value) => query.bind(value),
+            };
+        }
+        query
+    }
+    /// Binds the values of the conditions to a typed query built from [QueryFilters::where_clause].
+    pub fn bind_query_as<'q, O>(
+        &self,
+        mut query: QueryAs<'q, Postgres, O, PgArguments>,
+    ) -> QueryAs<'q, Postgres, O, PgArguments> {
+        for value in self.binds.iter().cloned() {
+            query = match value {
+                FilterValue::Text(value) => query.bind(value),
+                FilterValue::TextList(value) => query.bind(value),
+                FilterValue::Int(value) => query.bind(value),
+                FilterValue::Float(value) => query.bind(value),
+                FilterValue::Bool(value) => query.bind(value),
+            };
+        }
+        query
+    }
+}
@@ -0,0 +1,147 @@
+use crate::models::models::{Admin, Changelog, Demos, GcReport, RetentionReport};
+use crate::tools::backblaze::BackBlazeClient;
+use crate::tools::config::Config;
+use anyhow::Result;
+use sqlx::PgPool;
+
+/// Finds all orphaned demos, and unless `dry_run` is set, removes them from BackBlaze and the database.
+///
+/// A dry run makes no changes, and reports what would be removed (including the bytes that would be reclaimed).
+/// Errors if `admin_profile_number` isn't an admin, a run that removes demos is recorded in the admin audit log.
+pub async fn run_demo_gc(
+    pool: &PgPool,
+    b2: &BackBlazeClient,
+    dry_run: bool,
+    admin_profile_number: &str,
+) -> Result<GcReport> {
+    Admin::check_admin(pool, admin_profile_number).await?;
+    let orphaned = Demos::get_orphaned(pool).await?;
+    let mut report = GcReport {
+        dry_run,
+        orphaned: orphaned.iter().map(|demo| demo.id).collect(),
+        ..Default::default()
+    };
+    let removed = remove_demos(pool, b2, orphaned, dry_run).await?;
+    report.deleted_db = removed.deleted_db;
+    report.deleted_files = removed.deleted_files;
+    report.reclaimed_bytes = removed.reclaimed_bytes;
+    if !dry_run {
+        Admin::log_admin_action(
+            pool,
+            admin_profile_number,
+            "demo_gc",
+            None,
+            Some(format!(
+                "Removed {} orphaned demos, {} files ({} bytes)",
+                report.deleted_db, report.deleted_files, report.reclaimed_bytes
+            )),
+        )
+        .await?;
+    }
+    Ok(report)
+}
+
+/// Finds every demo past the configured retention period, and unless `dry_run` is set, removes them from BackBlaze
+/// and nulls out their changelog references. See [Demos::get_expired] for which demos are exempt.
+///
+/// The ids of the demos that were actually removed are returned in [RetentionReport::deleted].
+/// Nothing expires when neither `demo.retention.expire_after_days` nor `expire_after_months` is configured.
+/// Errors if `admin_profile_number` isn't an admin, a run that removes demos is recorded in the admin audit log.
+pub async fn enforce_retention(
+    pool: &PgPool,
+    b2: &BackBlazeClient,
+    config: &Config,
+    dry_run: bool,
+    admin_profile_number: &str,
+) -> Result<RetentionReport> {
+    Admin::check_admin(pool, admin_profile_number).await?;
+    let retention = &config.demo.retention;
+    let expired = match (retention.expire_after_days, retention.expire_after_months) {
+        (None, None) => vec![],
+        (days, months) => {
+            Demos::get_expired_after(pool, days, months, retention.keep_top_n, config.proof.demo)
+                .await?
+        }
+    };
+    let mut report = RetentionReport {
+        dry_run,
+        expired: expired.iter().map(|demo| demo.id).collect(),
+        ..Default::default()
+    };
+    let removed = remove_demos(pool, b2, expired, dry_run).await?;
+    report.deleted = removed.ids;
+    report.deleted_db = removed.deleted_db;
+    report.deleted_files = removed.deleted_files;
+    report.reclaimed_bytes = removed.reclaimed_bytes;
+    if !dry_run {
+        Admin::log_admin_action(
+            pool,
+            admin_profile_number,
+            "demo_retention",
+            None,
+            Some(format!(
+                "Removed {} expired demos, {} files ({} bytes)",
+                report.deleted_db, report.deleted_files, report.reclaimed_bytes
+            )),
+        )
+        .await?;
+    }
+    Ok(report)
+}
+
+/// Totals for [remove_demos].
+#[derive(Default)]
+struct RemovedDemos {
+    ids: Vec<i64>,
+    deleted_db: i64,
+    deleted_files: i64,
+    reclaimed_bytes: u64,
+}
+
+/// Removes each demo's file from BackBlaze, then any changelog references to it and the demo itself, in one transaction.
+///
+/// A dry run only totals the bytes that would be reclaimed. A file that's already missing from BackBlaze only has its database rows removed,
+/// but if BackBlaze can't be checked or the delete fails, the demo is kept so it can be retried.
+async fn remove_demos(
+    pool: &PgPool,
+    b2: &BackBlazeClient,
+    demos: Vec<Demos>,
+    dry_run: bool,
+) -> Result<RemovedDemos> {
+    let mut removed = RemovedDemos::default();
+    for demo in demos.into_iter() {
+        let file_info = match b2.find_file_info(&demo.file_id).await {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!(
+                    "Could not check file {} on BackBlaze, keeping demo {} for a retry -> {:?}",
+                    demo.file_id, demo.id, e
+                );
+                continue;
+            }
+        };
+        if let Some(info) = &file_info {
+            removed.reclaimed_bytes += info.content_length;
+        }
+        if dry_run {
+            continue;
+        }
+        if let Some(info) = file_info {
+            match b2.delete_file_version(&info.file_name, &demo.file_id).await {
+                Ok(_) => removed.deleted_files += 1,
+                Err(e) => {
+                    eprintln!("Failed to delete file {} -> {:?}", demo.file_id, e);
+                    continue;
+                }
+            }
+        }
+        let mut tx = pool.begin().await?;
+        Changelog::delete_references_to_demo(&mut tx, demo.id).await?;
+        if Demos::delete_demo(&mut tx, demo.id).await? {
+            tx.commit().await?;
+            removed.ids.push(demo.id);
+            removed.deleted_db += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -1,6 +1,17 @@
+/// Shared BackBlaze client used for all demo storage.
+pub mod backblaze;
 /// Caching for endpoints
 pub mod cache;
 /// Configuration module that handles extracting information from the environment for setup.
 pub mod config;
+/// JSON error responses for failed controller calls.
+pub mod errors;
 /// Arithmatic calculation functions for the board.
 pub mod helpers;
+/// Best-effort webhook notifications for community events.
+#[cfg(feature = "notifications")]
+pub mod notifications;
+/// Maintenance tasks that keep the database and demo storage consistent.
+pub mod maintenance;
+/// Validation pipeline for new score submissions.
+pub mod submissions;
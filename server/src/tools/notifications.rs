@@ -0,0 +1,85 @@
+use crate::models::models::{Changelog, DailySummary, UsersDisplay};
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+
+/// Posts a welcome message for a newly registered user to a webhook.
+///
+/// The payload is Discord-compatible, with the user's Steam profile link and avatar in an embed.
+pub async fn notify_new_user(webhook_url: &str, user: &UsersDisplay) -> Result<()> {
+    let profile_url = format!(
+        "https://steamcommunity.com/profiles/{}",
+        user.profile_number
+    );
+    let payload = json!({
+        "content": format!("Welcome {} to the boards!", user.user_name),
+        "embeds": [{
+            "title": user.user_name,
+            "url": profile_url,
+            "thumbnail": { "url": user.avatar },
+        }],
+    });
+    reqwest::Client::new()
+        .post(webhook_url)
+        .json(&payload)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Posts a recap of a day on the boards to a webhook, see [Changelog::get_daily_summary].
+pub async fn notify_daily_summary(webhook_url: &str, summary: &DailySummary) -> Result<()> {
+    let mut content = format!(
+        "**Recap for {}**: {} new scores from {} players, {} new world records.",
+        summary.date, summary.submissions, summary.active_players, summary.new_wrs
+    );
+    if let Some(top) = &summary.top_improvement {
+        content.push_str(&format!(
+            "\nBiggest improvement: {} on {}, {} faster.",
+            top.user_name.as_deref().unwrap_or(&top.profile_number),
+            top.map_name,
+            format_score(top.improvement)
+        ));
+    }
+    reqwest::Client::new()
+        .post(webhook_url)
+        .json(&json!({ "content": content }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Posts the previous day's recap to a webhook shortly after every UTC midnight, runs until the server stops.
+///
+/// Failures are logged, and the next day's recap is still attempted.
+pub async fn run_daily_recap(pool: PgPool, webhook_url: String) {
+    loop {
+        let now = Utc::now().naive_utc();
+        let next_midnight = (now.date() + Duration::days(1))
+            .and_hms_opt(0, 5, 0)
+            .unwrap();
+        let wait = (next_midnight - now).to_std().unwrap_or_default();
+        tokio::time::sleep(wait).await;
+        let yesterday = Utc::now().naive_utc().date() - Duration::days(1);
+        let res = match Changelog::get_daily_summary(&pool, yesterday).await {
+            Ok(summary) => notify_daily_summary(&webhook_url, &summary).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            eprintln!("Error posting daily recap for {} -> {}", yesterday, e);
+        }
+    }
+}
+
+/// Formats a score in centiseconds as `m:ss.cc` (or `s.cc` under a minute).
+fn format_score(score: i32) -> String {
+    let (minutes, seconds, centis) = (score / 6000, (score / 100) % 60, score % 100);
+    if minutes > 0 {
+        format!("{}:{:02}.{:02}", minutes, seconds, centis)
+    } else {
+        format!("{}.{:02}", seconds, centis)
+    }
+}
@@ -0,0 +1,211 @@
+use crate::models::models::{
+    Categories, ChangelogInsert, Maps, SpMap, SubmissionChangelog, Users, ValidatedSubmission,
+    ValidationError, ValidationErrors,
+};
+use crate::tools::cache::CacheState;
+use crate::tools::config::Config;
+use crate::tools::helpers::{check_for_valid_score, normalize_youtube_id, parse_timestamp};
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::fmt;
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::UnknownMap(map_id) => write!(f, "Map {} does not exist", map_id),
+            ValidationError::InvalidCategory(cat_id) => {
+                write!(f, "Category {} is not valid for this map", cat_id)
+            }
+            ValidationError::InvalidTimestamp(timestamp) => write!(
+                f,
+                "Timestamp {} is not in a recognized format, or is in the future",
+                timestamp
+            ),
+            ValidationError::InvalidScore(score) => write!(f, "Score {} is not valid", score),
+            ValidationError::InvalidYoutube(youtube_id) => {
+                write!(f, "{} is not a valid youtube link", youtube_id)
+            }
+            ValidationError::UnknownUser(profile_number) => {
+                write!(f, "User {} does not exist", profile_number)
+            }
+            ValidationError::InvalidProfileNumber(profile_number) => {
+                write!(f, "{} is not a valid SteamID64", profile_number)
+            }
+            ValidationError::UserBanned(profile_number) => {
+                write!(f, "User {} is banned", profile_number)
+            }
+            ValidationError::NotAnImprovement => {
+                write!(f, "The user already has the same, or a better score")
+            }
+            ValidationError::SubmissionsClosed(map_id) => {
+                write!(f, "Submissions are currently closed for map {}", map_id)
+            }
+            ValidationError::NoDefaultCategory(map_id) => {
+                write!(
+                    f,
+                    "Map {} has no default category, a category is required",
+                    map_id
+                )
+            }
+            ValidationError::InvalidParameter(param, value) => {
+                write!(f, "{} is not a valid value for {}", value, param)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors: Vec<String> = self.0.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", errors.join(", "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl ValidatedSubmission {
+    /// Whether the submission meets the proof standards once a demo is attached.
+    pub fn verified_with_demo(&self) -> bool {
+        !self.requires_video || self.insert.youtube_id.is_some()
+    }
+}
+
+/// Runs every check for a new submission in order, and builds a ready-to-insert [ChangelogInsert].
+///
+/// Checks: map, category (the map's default from the [CacheState] if none is given), submission window, timestamp (see [parse_timestamp] for the accepted formats), score, youtube link, user/ban status, improvement over the user's PB.
+/// Later checks that depend on earlier ones (e.g. ranks need a valid map and category) are skipped if those failed.
+///
+/// On failure, the error is a [ValidationErrors] containing every problem found, it can be recovered with `downcast_ref`.
+/// The verified decision is based on the rank the score would place at, and the proof standards in [Config].
+pub async fn validate(
+    pool: &PgPool,
+    submission: &SubmissionChangelog,
+    config: &Config,
+    cache: &CacheState,
+) -> Result<ValidatedSubmission> {
+    let mut errors = Vec::new();
+    // Map & category
+    let map = Maps::get_map(pool, submission.map_id.clone()).await?;
+    let category_id = match &map {
+        Some(map) => {
+            // Maps without a default of their own fall back to their game's default category.
+            let category_id = match submission.category_id {
+                Some(category_id) => Some(category_id),
+                None => cache.get_default_cat(pool, &map.steam_id).await?,
+            };
+            match category_id {
+                Some(category_id) => {
+                    let valid_ids =
+                        Categories::get_category_ids(pool, map.steam_id.clone()).await?;
+                    if !valid_ids.contains(&category_id) {
+                        errors.push(ValidationError::InvalidCategory(category_id));
+                    }
+                }
+                None => errors.push(ValidationError::NoDefaultCategory(map.steam_id.clone())),
+            }
+            if !Maps::is_submission_open(pool, map.steam_id.clone()).await? {
+                errors.push(ValidationError::SubmissionsClosed(map.steam_id.clone()));
+            }
+            category_id.unwrap_or_default()
+        }
+        None => {
+            errors.push(ValidationError::UnknownMap(submission.map_id.clone()));
+            submission.category_id.unwrap_or_default()
+        }
+    };
+    // Timestamp
+    let timestamp = match parse_timestamp(&submission.timestamp) {
+        Ok(Some(timestamp)) if timestamp <= Utc::now().naive_utc() => Some(timestamp),
+        Ok(None) => None,
+        _ => {
+            errors.push(ValidationError::InvalidTimestamp(
+                submission.timestamp.clone(),
+            ));
+            None
+        }
+    };
+    // Score
+    if submission.score <= 0 {
+        errors.push(ValidationError::InvalidScore(submission.score));
+    }
+    // Youtube
+    let youtube_id = match &submission.youtube_id {
+        Some(link) if !link.trim().is_empty() => match normalize_youtube_id(link) {
+            Some(youtube_id) => Some(youtube_id),
+            None => {
+                errors.push(ValidationError::InvalidYoutube(link.clone()));
+                None
+            }
+        },
+        _ => None,
+    };
+    // User
+    match Users::get_user(pool, submission.profile_number.clone()).await {
+        Ok(Some(user)) if user.banned => errors.push(ValidationError::UserBanned(
+            submission.profile_number.clone(),
+        )),
+        Ok(Some(_)) => (),
+        Ok(None) => errors.push(ValidationError::UnknownUser(
+            submission.profile_number.clone(),
+        )),
+        // `get_user` reports a missing user as `RowNotFound`, any other error is from the database.
+        Err(e) => match e.downcast_ref::<sqlx::Error>() {
+            Some(sqlx::Error::RowNotFound) => errors.push(ValidationError::UnknownUser(
+                submission.profile_number.clone(),
+            )),
+            _ => return Err(e),
+        },
+    }
+    let mut insert = ChangelogInsert {
+        timestamp,
+        profile_number: submission.profile_number.clone(),
+        score: submission.score,
+        map_id: submission.map_id.clone(),
+        youtube_id,
+        note: submission.note.clone(),
+        category_id,
+        submission: true,
+        ..Default::default()
+    };
+    if !errors.is_empty() {
+        return Err(ValidationErrors(errors).into());
+    }
+    // Improvement & ranks, only a score that doesn't beat the user's PB is a validation error, anything else is passed on.
+    let details = check_for_valid_score(
+        pool,
+        insert.profile_number.clone(),
+        insert.score,
+        insert.map_id.clone(),
+        config.proof.results,
+        category_id,
+    )
+    .await?;
+    insert.previous_id = details.previous_id;
+    insert.post_rank = details.post_rank;
+    insert.pre_rank = details.pre_rank;
+    insert.score_delta = details.score_delta;
+    // Proof, based on where the score would place on the board (excluding the user's current PB).
+    let board = SpMap::get_sp_map_page(
+        pool,
+        insert.map_id.clone(),
+        config.proof.results,
+        category_id,
+    )
+    .await?;
+    let rank = board
+        .iter()
+        .filter(|entry| entry.score < insert.score && entry.profile_number != insert.profile_number)
+        .count() as i32
+        + 1;
+    let requires_demo = rank <= config.proof.demo;
+    let requires_video = rank <= config.proof.video;
+    let verified = !requires_demo && (!requires_video || insert.youtube_id.is_some());
+    insert.verified = Some(verified);
+    Ok(ValidatedSubmission {
+        insert,
+        verified,
+        requires_demo,
+        requires_video,
+    })
+}